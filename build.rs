@@ -0,0 +1,66 @@
+// Collects the doc comments of the library functions in src/functions so that
+// `razen doc` and the generated API reference can show them at runtime.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+fn main() {
+    println!("cargo:rerun-if-changed=src/functions.rs");
+    println!("cargo:rerun-if-changed=src/functions");
+
+    // `pub mod arrlib { pub use super::array::*; }` exposes array.rs as arrlib
+    let modules = fs::read_to_string("src/functions.rs").expect("failed to read src/functions.rs");
+    let mut aliases = Vec::new();
+    let mut lines = modules.lines();
+    while let Some(line) = lines.next() {
+        let Some(alias) = line.trim().strip_prefix("pub mod ").and_then(|rest| rest.strip_suffix(" {")) else { continue };
+        let source = lines.next()
+            .and_then(|line| line.trim().strip_prefix("pub use super::"))
+            .and_then(|rest| rest.strip_suffix("::*;"));
+        if let Some(source) = source {
+            aliases.push((alias.to_string(), source.to_string()));
+        }
+    }
+
+    let mut docs = BTreeMap::new();
+    for (alias, source) in &aliases {
+        let path = format!("src/functions/{}.rs", source);
+        let Ok(code) = fs::read_to_string(&path) else { continue };
+        for (function, doc) in function_docs(&code) {
+            docs.insert((alias.clone(), function), doc);
+        }
+    }
+
+    let mut generated = String::from("pub fn function_doc(module: &str, function: &str) -> &'static str {\n    match (module, function) {\n");
+    for ((module, function), doc) in &docs {
+        generated.push_str(&format!("        ({:?}, {:?}) => {:?},\n", module, function, doc));
+    }
+    generated.push_str("        _ => \"\",\n    }\n}\n");
+
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("function_docs.rs");
+    fs::write(out, generated).expect("failed to write function docs");
+}
+
+// Doc comments of the top-level `pub fn`s in a source file
+fn function_docs(code: &str) -> Vec<(String, String)> {
+    let mut result = Vec::new();
+    let mut doc: Vec<&str> = Vec::new();
+    for line in code.lines() {
+        if let Some(text) = line.strip_prefix("///") {
+            doc.push(text.strip_prefix(' ').unwrap_or(text));
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix("pub fn ") {
+            let name: String = rest.chars().take_while(|c| c.is_alphanumeric() || *c == '_').collect();
+            if !doc.is_empty() {
+                result.push((name, doc.join("\n").trim().to_string()));
+            }
+        }
+        if !line.trim().starts_with("#[") {
+            doc.clear();
+        }
+    }
+    result
+}
@@ -0,0 +1,25 @@
+// Example plugin: cargo build --example greeter
+// then in a script: lib "razen-plugin/target/debug/examples/libgreeter.so";
+use razen_plugin::{export, Value};
+
+fn greet(args: Vec<Value>) -> Result<Value, String> {
+    let name = args[0].as_str().map(str::to_string).unwrap_or_else(|| args[0].to_string());
+    let times = args.get(1).and_then(Value::as_i64).unwrap_or(1);
+    if times < 1 {
+        return Err("Greeter.greet times must be at least 1".to_string());
+    }
+    Ok(Value::from(vec![format!("Hello, {}!", name); times as usize].join(" ")))
+}
+
+fn sum(args: Vec<Value>) -> Result<Value, String> {
+    Ok(Value::from(args.iter().filter_map(Value::as_f64).sum::<f64>()))
+}
+
+export! {
+    name: "greeter",
+    version: "0.1.0",
+    functions: {
+        "greet(string, int?) -> string" => greet,
+        "sum(number...) -> number" => sum,
+    }
+}
@@ -0,0 +1,35 @@
+/*
+ * Razen native plugin ABI, version 1.
+ *
+ * A plugin is a shared library that exports razen_plugin_info(). Arguments
+ * arrive as a JSON array; a function writes a JSON value (return 0) or an
+ * error message (any other status) to *result, which Razen releases with the
+ * plugin's free function.
+ */
+#ifndef RAZEN_PLUGIN_H
+#define RAZEN_PLUGIN_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#define RAZEN_PLUGIN_ABI_VERSION 1
+
+typedef int32_t (*razen_plugin_call)(const char *args_json, char **result);
+
+typedef struct {
+    const char *signature;      /* e.g. "add(int, int) -> int" */
+    razen_plugin_call call;
+} RazenPluginFunction;
+
+typedef struct {
+    uint32_t abi_version;       /* RAZEN_PLUGIN_ABI_VERSION */
+    const char *name;           /* library name used in scripts */
+    const char *version;        /* plugin version */
+    size_t function_count;
+    const RazenPluginFunction *functions;
+    void (*free)(char *);       /* releases strings written to *result */
+} RazenPluginInfo;
+
+const RazenPluginInfo *razen_plugin_info(void);
+
+#endif /* RAZEN_PLUGIN_H */
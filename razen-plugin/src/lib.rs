@@ -0,0 +1,137 @@
+//! Helpers for writing native Razen plugins in Rust.
+//!
+//! A plugin is a `cdylib` that lists its functions with [`export!`]:
+//!
+//! ```ignore
+//! use razen_plugin::{export, Value};
+//!
+//! fn greet(args: Vec<Value>) -> Result<Value, String> {
+//!     Ok(Value::from(format!("Hello, {}!", args[0].as_str().unwrap_or("world"))))
+//! }
+//!
+//! export! {
+//!     name: "greeter",
+//!     version: "0.1.0",
+//!     functions: {
+//!         "greet(string) -> string" => greet,
+//!     }
+//! }
+//! ```
+//!
+//! Scripts load it with `lib "path/to/libgreeter.so";` and call `Greeter[greet]("Ada")`.
+//! Razen checks argument counts and types against the signatures before calling.
+
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+
+pub use serde_json::Value;
+
+/// Plugin ABI version understood by this crate; Razen rejects plugins built for another version
+pub const ABI_VERSION: u32 = 1;
+
+/// Function called by Razen with the arguments as a JSON array
+pub type RazenPluginCall = extern "C" fn(args_json: *const c_char, result: *mut *mut c_char) -> i32;
+
+/// Signature of a function written for [`export!`]
+pub type PluginFunction = fn(Vec<Value>) -> Result<Value, String>;
+
+#[repr(C)]
+pub struct RazenPluginFunction {
+    pub signature: *const c_char,
+    pub call: RazenPluginCall,
+}
+
+#[repr(C)]
+pub struct RazenPluginInfo {
+    pub abi_version: u32,
+    pub name: *const c_char,
+    pub version: *const c_char,
+    pub function_count: usize,
+    pub functions: *const RazenPluginFunction,
+    pub free: unsafe extern "C" fn(*mut c_char),
+}
+
+/// Wrapper that lets [`export!`] keep plugin tables in statics
+#[repr(transparent)]
+pub struct Exported<T>(pub T);
+
+// SAFETY: exported tables only point at static strings and functions and are never mutated
+unsafe impl<T> Sync for Exported<T> {}
+
+/// Release a string returned to Razen
+///
+/// # Safety
+/// `ptr` must be null or a string written to `result` by [`dispatch`].
+pub unsafe extern "C" fn free_string(ptr: *mut c_char) {
+    if !ptr.is_null() {
+        drop(CString::from_raw(ptr));
+    }
+}
+
+/// Decode the arguments, call `function` and encode its result or error.
+/// Panics are caught and reported as errors instead of unwinding into Razen.
+///
+/// # Safety
+/// `args_json` must be null or a NUL-terminated string, and `result` null or valid for writes.
+pub unsafe fn dispatch(args_json: *const c_char, result: *mut *mut c_char, function: PluginFunction) -> i32 {
+    let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+        if args_json.is_null() {
+            return Err("Plugin called without arguments".to_string());
+        }
+        let text = CStr::from_ptr(args_json).to_string_lossy();
+        let args = match serde_json::from_str(&text) {
+            Ok(Value::Array(args)) => args,
+            _ => return Err(format!("Plugin arguments are not a JSON array: {}", text)),
+        };
+        function(args)
+    }));
+
+    let (status, text) = match outcome {
+        Ok(Ok(value)) => (0, value.to_string()),
+        Ok(Err(message)) => (1, message),
+        Err(_) => (2, "Plugin function panicked".to_string()),
+    };
+    if !result.is_null() {
+        let text = CString::new(text.replace('\0', "")).unwrap_or_default();
+        *result = text.into_raw();
+    }
+    status
+}
+
+/// Export a plugin's name, version and functions under the `razen_plugin_info` symbol.
+/// Each function is listed with its signature, for example `"add(int, int) -> int"`.
+/// Parameter types are int, float, number, string, bool, array, map and any;
+/// a trailing `?` marks an optional parameter and `...` a variadic last parameter.
+#[macro_export]
+macro_rules! export {
+    (
+        name: $name:expr,
+        version: $version:expr,
+        functions: { $($signature:expr => $function:path),* $(,)? } $(,)?
+    ) => {
+        #[no_mangle]
+        pub extern "C" fn razen_plugin_info() -> *const $crate::RazenPluginInfo {
+            const FUNCTIONS: &[$crate::RazenPluginFunction] = &[
+                $($crate::RazenPluginFunction {
+                    signature: concat!($signature, "\0").as_ptr() as *const ::std::ffi::c_char,
+                    call: {
+                        extern "C" fn call(args: *const ::std::ffi::c_char, result: *mut *mut ::std::ffi::c_char) -> i32 {
+                            // SAFETY: Razen passes a JSON string and a valid out pointer
+                            unsafe { $crate::dispatch(args, result, $function) }
+                        }
+                        call
+                    },
+                }),*
+            ];
+            static INFO: $crate::Exported<$crate::RazenPluginInfo> = $crate::Exported($crate::RazenPluginInfo {
+                abi_version: $crate::ABI_VERSION,
+                name: concat!($name, "\0").as_ptr() as *const ::std::ffi::c_char,
+                version: concat!($version, "\0").as_ptr() as *const ::std::ffi::c_char,
+                function_count: FUNCTIONS.len(),
+                functions: FUNCTIONS.as_ptr(),
+                free: $crate::free_string,
+            });
+            &INFO.0
+        }
+    };
+}
@@ -171,6 +171,11 @@ const razenKeywords = [
         description: 'Assertion',
         documentation: 'Used to assert that a condition is true.\n\n```razen\nassert(condition, "Error message");\n```'
     },
+    {
+        name: 'test',
+        description: 'Test declaration',
+        documentation: 'Declares a named test run by `razen-test`.\n\n```razen\ntest "addition works" {\n  assert_eq(1 + 2, 3);\n}\n```'
+    },
     {
         name: 'trace',
         description: 'Execution tracing',
@@ -292,7 +292,11 @@ const LIBRARY_RETURN_TYPES = {
     'info': 'object',         // Returns process info object
     'read_stdout': 'string',  // Returns process stdout
     'read_stderr': 'string',  // Returns process stderr
-    'write_stdin': 'boolean'  // Returns true/false if write was successful
+    'write_stdin': 'boolean', // Returns true/false if write was successful
+    'run': 'object',          // Returns {code, stdout, stderr}
+    'spawn': 'number',        // Returns process ID
+    'output': 'object',       // Returns {code, stdout, stderr} once the process exits
+    'pipeline': 'object'      // Returns {code, stdout, stderr} of the last stage
   },
   
   // Thread library return types
@@ -308,7 +312,44 @@ const LIBRARY_RETURN_TYPES = {
     'current': 'number',      // Returns current thread ID
     'cpu_count': 'number',    // Returns number of CPU cores
     'thread_id': 'number',    // Returns thread ID
-    'thread_count': 'number'  // Returns number of active threads
+    'thread_count': 'number', // Returns number of active threads
+    'channel': 'number',      // Returns channel ID
+    'send': 'boolean',        // Returns true once the value is queued
+    'recv': 'any',            // Returns the next value on the channel
+    'try_recv': 'any',        // Returns the next value or null if empty
+    'recv_timeout': 'any',    // Returns the next value or null on timeout
+    'close': 'boolean',       // Returns true once the channel is closed
+    'select': 'object',       // Returns {channel, value} or null on timeout
+    'channel_len': 'number'   // Returns number of queued values
+  },
+  
+  // Args library return types
+  'argslib': {
+    'parser': 'number',       // Returns parser ID
+    'flag': 'boolean',        // Returns true once the flag is declared
+    'option': 'boolean',      // Returns true once the option is declared
+    'positional': 'boolean',  // Returns true once the argument is declared
+    'require': 'boolean',     // Returns true once the option is required
+    'command': 'number',      // Returns the subcommand's parser ID
+    'usage': 'string',        // Returns generated help text
+    'parse': 'object'         // Returns map of parsed values
+  },
+  
+  // FFI library return types
+  'ffilib': {
+    'open': 'number',         // Returns library ID
+    'func': 'number',         // Returns function ID
+    'call': 'any',            // Returns the C function's result
+    'callback': 'number',     // Returns a C function pointer
+    'free_callback': 'boolean', // Returns true once released
+    'define_struct': 'number',  // Returns struct layout ID
+    'struct_size': 'number',  // Returns size in bytes
+    'struct_offset': 'number', // Returns field offset in bytes
+    'struct_alloc': 'number', // Returns address of zeroed memory
+    'struct_get': 'any',      // Returns the field value
+    'struct_set': 'boolean',  // Returns true once written
+    'read_string': 'string',  // Returns the C string at an address
+    'close': 'boolean'        // Returns true once closed
   },
   
   // Compiler library return types
@@ -529,6 +570,10 @@ const LIBRARY_RETURN_TYPES = {
   }
 };
 
+// Signatures and docs of the built-in libraries, generated with
+// `razen doc --format=json --output=src/server/libraries.json`
+const LIBRARY_DOCS = require('./libraries.json');
+
 // Library definitions
 const LIBRARIES = {
     "ArrLib": ["push", "pop", "join", "length", "unique"],
@@ -545,8 +590,10 @@ const LIBRARIES = {
     "BinaryLib": ["create", "open", "close", "write_bytes", "read_bytes", "seek", "tell", "bytes_to_string", "string_to_bytes", "stats"],
     "BitwiseLib": ["and", "or", "xor", "not", "left_shift", "right_shift", "unsigned_right_shift", "get_bit", "set_bit", "count_bits", "to_binary", "to_hex", "from_binary", "from_hex"],
     "SystemLib": ["getpid", "getcwd", "execute", "getenv", "setenv", "environ", "args", "path_exists", "realpath", "exit", "sleep", "hostname", "username", "current_time", "system_name"],
-    "ProcessLib": ["create", "wait", "is_running", "kill", "signal", "info", "read_stdout", "read_stderr", "write_stdin"],
-    "ThreadLib": ["create", "join", "is_running", "sleep", "mutex_create", "mutex_lock", "mutex_unlock", "mutex_destroy", "current", "cpu_count", "thread_id", "thread_count"],
+    "ProcessLib": ["create", "wait", "is_running", "kill", "signal", "info", "read_stdout", "read_stderr", "write_stdin", "run", "spawn", "output", "pipeline"],
+    "ArgsLib": ["parser", "flag", "option", "positional", "require", "command", "usage", "parse"],
+    "FFILib": ["open", "func", "call", "callback", "free_callback", "define_struct", "struct_size", "struct_offset", "struct_alloc", "struct_get", "struct_set", "read_string", "close"],
+    "ThreadLib": ["create", "join", "is_running", "sleep", "mutex_create", "mutex_lock", "mutex_unlock", "mutex_destroy", "current", "cpu_count", "thread_id", "thread_count", "channel", "send", "recv", "try_recv", "recv_timeout", "close", "select", "channel_len"],
     "CompilerLib": ["create_node", "add_child", "node_to_string", "create_symbol_table", "add_symbol", "lookup_symbol", "generate_ir", "optimize_ir", "generate_assembly", "parse", "tokenize", "compile"],
     "LexerLib": ["create_lexer", "tokenize", "define_token"],
     "ParserLib": ["create_parser", "parse", "define_rule", "create_grammar"],
@@ -1179,8 +1226,23 @@ connection.onCompletion((textDocumentPosition) => {
   if (libraryMatch) {
     const libraryName = libraryMatch[1].toLowerCase();
     const libraryFunctions = LIBRARIES[libraryName];
+    const libraryDocs = LIBRARY_DOCS[libraryName];
     
-    if (libraryFunctions) {
+    if (libraryDocs) {
+      // Generated docs give the full signature of each function
+      for (const func of libraryDocs) {
+        completionItems.push({
+          label: func.name,
+          kind: CompletionItemKind.Function,
+          detail: func.signature,
+          documentation: {
+            kind: MarkupKind.Markdown,
+            value: func.doc
+          },
+          insertText: `${func.name}](`
+        });
+      }
+    } else if (libraryFunctions) {
       // Add library functions as completion items
       for (const funcName of libraryFunctions) {
         const returnType = LIBRARY_RETURN_TYPES[libraryName] && 
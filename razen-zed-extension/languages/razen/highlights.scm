@@ -270,6 +270,7 @@
   "attribute"
   "const"
   "enum"
+  "struct"
   "inline"
   "final"
   "volatile"
@@ -1,16 +1,20 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::Path;
 use std::process::Command;
+use std::io::{self, Write};
 use std::io::Read;
 use std::fs::File;
 use std::path::PathBuf;
+use std::cell::RefCell;
+use std::rc::Rc;
 use std::{thread, time::Duration};
 
 use crate::ast::{Program, Statement, Expression};
 use crate::parser::Parser;
 use crate::value::Value as RazenValue;
 use crate::library;
+use crate::structs::{self, Kind, StructType};
 
 // Intermediate representation for code generation
 #[derive(Debug, Clone)]
@@ -79,6 +83,9 @@ pub enum IR {
     GetKey,
     SetKey,
 
+    // Struct operations
+    CreateStruct(String),  // struct name; field values are pushed in declaration order
+
     // Function definition
     DefineFunction(String, usize),  // function name, address
 
@@ -89,7 +96,7 @@ pub enum IR {
     Sleep,
 
     // Library call
-    LibraryCall(String, String, usize),  // library name, function name, arg count
+    LibraryCall(String, String, usize, Option<crate::library::FunctionHandle>, Vec<bool>),  // library name, function name, arg count, function resolved at compile time, arguments written as string literals
 }
 
 // Symbol table for variable and function tracking
@@ -160,6 +167,23 @@ impl FunctionTable {
     }
 }
 
+// A test discovered in the program: a `test "name" { ... }` block or a `test_` function
+#[derive(Debug, Clone)]
+pub struct TestCase {
+    pub name: String,      // Name shown in test reports
+    pub function: String,  // Function the test body was compiled into
+}
+
+// Variables the callbacks of one library call share
+type SharedVariables = Rc<RefCell<HashMap<String, String>>>;
+
+// How a run of the VM ended
+enum Completion {
+    Finished,
+    Exit(i32),
+    Returned(String), // Only when running a callback
+}
+
 // Compiler for translating AST to machine code
 pub struct Compiler {
     pub ir: Vec<IR>,
@@ -174,6 +198,13 @@ pub struct Compiler {
     errors: Vec<String>,            // Compilation errors
     variable_types: HashMap<String, String>, // Track variable types (name -> type)
     in_show_statement: bool,        // Flag to track if we're inside a show statement
+    pub tests: Vec<TestCase>,       // Tests discovered while compiling
+    source_path: Option<PathBuf>,   // The compiled file, for resolving plugins and namespacing storage
+    current_class: Option<String>,  // Class whose body is being compiled, for static methods
+    library_overrides: HashSet<String>, // Static methods declared `override static`
+    razen_libraries: HashSet<PathBuf>,  // Library files from properties/libs already compiled in
+    structs: HashMap<String, StructType>, // Declared structs, which the VM checks field values against
+    struct_errors: Vec<String>,         // Misused structs, which stop the program from compiling
 }
 
 impl Compiler {
@@ -191,6 +222,13 @@ impl Compiler {
             errors: Vec::new(),
             variable_types: HashMap::new(),
             in_show_statement: false,
+            tests: Vec::new(),
+            source_path: None,
+            current_class: None,
+            library_overrides: HashSet::new(),
+            razen_libraries: HashSet::new(),
+            structs: HashMap::new(),
+            struct_errors: Vec::new(),
         }
     }
 
@@ -284,6 +322,14 @@ impl Compiler {
     }
 
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        // Check for clean output flag in environment
+        let clean_output = std::env::args().take_while(|arg| arg != "--").any(|arg| arg == "--clean-output");
+        Self::from_file_with_clean_output(path, clean_output)
+    }
+
+    // Compile a file with clean output set before compilation starts
+    pub fn from_file_with_clean_output<P: AsRef<Path>>(path: P, clean_output: bool) -> Result<Self, String> {
+        let source_path = path.as_ref().to_path_buf();
         match Parser::from_file(path) {
             Ok(mut parser) => {
                 let program = parser.parse_program();
@@ -292,19 +338,24 @@ impl Compiler {
                 }
 
                 let mut compiler = Compiler::new();
-
-                // Check for clean output flag in environment
-                if std::env::args().any(|arg| arg == "--clean-output") {
-                    compiler.set_clean_output(true);
-                }
+                compiler.set_clean_output(clean_output);
+                compiler.source_path = Some(source_path);
 
                 compiler.compile_program(program);
+                if !compiler.struct_errors.is_empty() {
+                    return Err(format!("Compilation errors: {}", compiler.struct_errors.join("; ")));
+                }
                 Ok(compiler)
             },
             Err(e) => Err(e),
         }
     }
 
+    // Directory of the compiled file, for resolving plugins and libraries
+    fn source_dir(&self) -> Option<&Path> {
+        self.source_path.as_deref().and_then(Path::parent)
+    }
+
     fn generate_label(&mut self, prefix: &str) -> String {
         let label = format!("{}{}", prefix, self.label_counter);
         self.label_counter += 1;
@@ -375,6 +426,13 @@ impl Compiler {
         self.symbol_table.define("__assert");
         self.symbol_table.define("__assert_with_message");
         self.symbol_table.define("__trace");
+        self.symbol_table.define("__assert_fail");
+        self.symbol_table.define("__assert_error_contains");
+
+        // Test framework assertions
+        self.symbol_table.define("assert_eq");
+        self.symbol_table.define("assert_ne");
+        self.symbol_table.define("assert_throws");
 
         // Standard library functions
         self.symbol_table.define("floor");       // Math functions
@@ -445,6 +503,13 @@ impl Compiler {
 
         // Second pass: compile all statements
         for stmt in program.statements {
+            // Top-level `test_` functions without parameters are picked up by `razen test`
+            if let Statement::FunctionDeclaration { name, parameters, .. } = &stmt {
+                if name.starts_with("test_") && parameters.is_empty() {
+                    self.tests.push(TestCase { name: name.clone(), function: name.clone() });
+                }
+            }
+
             self.compile_statement(stmt);
         }
     }
@@ -468,6 +533,10 @@ impl Compiler {
             Statement::BlockStatement { statements } => {
                 self.compile_block_statement(statements);
             },
+            // The value is not matched yet, so the body runs like a plain block
+            Statement::WhenStatement { body, .. } => {
+                self.compile_block_statement(body);
+            },
             Statement::IfStatement { condition, consequence, alternative } => {
                 self.compile_if_statement(condition, consequence, alternative);
             },
@@ -498,8 +567,8 @@ impl Compiler {
             Statement::ReadStatement { name } => {
                 self.compile_read_statement(name);
             },
-            Statement::ExitStatement => {
-                self.compile_exit_statement();
+            Statement::ExitStatement { code } => {
+                self.compile_exit_statement(code);
             },
             Statement::DocumentTypeDeclaration { doc_type } => {
                 self.compile_document_type_declaration(doc_type);
@@ -521,6 +590,9 @@ impl Compiler {
             Statement::TraceStatement { value } => {
                 self.compile_trace_statement(value);
             },
+            Statement::TestDeclaration { name, body } => {
+                self.compile_test_declaration(name, body);
+            },
             // OOP (Section 12)
             Statement::ClassDeclaration { name, body } => {
                 self.compile_class_declaration(name, body);
@@ -528,6 +600,9 @@ impl Compiler {
             Statement::FinalClassDeclaration { name, body } => {
                 self.compile_final_class_declaration(name, body);
             },
+            Statement::StaticMethodDeclaration { name, parameters, body, override_native } => {
+                self.compile_static_method(name, parameters, body, override_native);
+            },
             // Performance and Type Safety
             Statement::ConstDeclaration { name, value } => {
                 self.compile_const_declaration(name, value);
@@ -535,6 +610,9 @@ impl Compiler {
             Statement::EnumDeclaration { name, variants } => {
                 self.compile_enum_declaration(name, variants);
             },
+            Statement::StructDeclaration { name, fields } => {
+                self.compile_struct_declaration(name, fields);
+            },
             Statement::InlineFunctionDeclaration { name, parameters, body } => {
                 self.compile_inline_function_declaration(name, parameters, body);
             },
@@ -560,6 +638,14 @@ impl Compiler {
             Statement::LibStatement { name } => {
                 self.compile_lib_statement(name);
             },
+            Statement::NativeLibStatement { path } => {
+                // Plugin paths are relative to the script, not the working directory
+                let path = match self.source_dir() {
+                    Some(dir) if Path::new(&path).is_relative() => dir.join(&path).to_string_lossy().into_owned(),
+                    _ => path,
+                };
+                self.compile_lib_statement(path);
+            },
             // Compiler Construction (Section 17)
             Statement::GrammarStatement { name, properties } => {
                 self.compile_grammar_statement(name, properties);
@@ -606,6 +692,8 @@ impl Compiler {
             Statement::AttributeStatement { name, values } => {
                 self.compile_attribute_statement(name, values);
             },
+            // Source trivia kept for the formatter generates no code
+            Statement::Comment { .. } | Statement::BlankLine | Statement::SourceDeclaration { .. } => {},
         }
     }
 
@@ -1046,8 +1134,14 @@ impl Compiler {
         self.emit(IR::StoreVar(name));
     }
 
-    fn compile_exit_statement(&mut self) {
-        // Emit the exit instruction to terminate the program
+    fn compile_exit_statement(&mut self, code: Option<Expression>) {
+        // The exit instruction pops the process exit status
+        match code {
+            Some(code) => self.compile_expression(code),
+            None => {
+                self.emit(IR::PushNumber(0.0));
+            },
+        }
         self.emit(IR::Exit);
     }
 
@@ -1079,6 +1173,14 @@ impl Compiler {
             Expression::PrefixExpression { operator, right } => {
                 self.compile_prefix_expression(operator, *right);
             },
+            // Property access reads a struct field or map key
+            Expression::InfixExpression { left, right, operator } if operator == "." => {
+                self.compile_expression(*left);
+                if let Expression::Identifier(property) = *right {
+                    self.emit(IR::PushString(property));
+                }
+                self.emit(IR::GetIndex);
+            },
             Expression::InfixExpression { left, right, operator, .. } => {
                 self.compile_infix_expression(*left, operator, *right);
             },
@@ -1103,6 +1205,9 @@ impl Compiler {
             Expression::NamespaceCall { namespace, function, arguments } => {
                 self.compile_namespace_call(namespace, function, arguments);
             },
+            Expression::StructLiteral { name, fields } => {
+                self.compile_struct_literal(name, fields);
+            },
         }
     }
 
@@ -1147,6 +1252,23 @@ impl Compiler {
     }
 
     fn compile_assignment_expression(&mut self, left: Expression, operator: String, right: Expression) {
+        // Assigning to a field builds the changed struct and stores it back
+        if let Expression::InfixExpression { left: target, operator: dot, right: field } = left.clone() {
+            if dot == "." {
+                let value = match operator.as_str() {
+                    "=" => right,
+                    _ => Expression::InfixExpression {
+                        left: Box::new(left),
+                        operator: operator.trim_end_matches('=').to_string(),
+                        right: Box::new(right),
+                    },
+                };
+                self.compile_expression(value);
+                self.compile_field_assignment(*target, *field);
+                return;
+            }
+        }
+
         // For simple assignment (=), just compile the right expression and store it
         if operator == "=" {
             // Compile the right expression
@@ -1193,22 +1315,32 @@ impl Compiler {
     }
 
     fn compile_call_expression(&mut self, function: Expression, arguments: Vec<Expression>) {
+        // assert_throws evaluates its first argument lazily, inside a try block
+        if matches!(&function, Expression::Identifier(name) if name == "assert_throws") && !arguments.is_empty() {
+            self.compile_assert_throws(arguments);
+            return;
+        }
+
+        // `Lib.function(...)` calls a library function or static method, like `Lib[function](...)`
+        if let Expression::InfixExpression { left, operator, right } = &function {
+            if operator == "." {
+                match (left.as_ref(), right.as_ref()) {
+                    (Expression::Identifier(_), Expression::Identifier(_)) => {
+                        self.compile_library_call(*left.clone(), *right.clone(), arguments);
+                    },
+                    _ => panic!("Dot expression must be identifiers on both sides"),
+                }
+                return;
+            }
+        }
+
         // Compile each argument
         for arg in &arguments {
             self.compile_expression(arg.clone());
         }
 
-        // Support function names like Identifier or InfixExpression (dot notation)
         let func_name = match function {
             Expression::Identifier(name) => name,
-            Expression::InfixExpression { left, operator, right } if operator == "." => {
-                // Only support left/right as Identifier for now
-                if let (Expression::Identifier(left_name), Expression::Identifier(right_name)) = (*left, *right) {
-                    format!("{}.{}", left_name, right_name)
-                } else {
-                    panic!("Dot expression must be identifiers on both sides");
-                }
-            },
             _ => panic!("Function call on non-identifier or unsupported expression"),
         };
 
@@ -1298,7 +1430,8 @@ impl Compiler {
         let full_func_name = format!("{}.{}", lib_name, func_name);
 
         // Call the library function with the given number of arguments
-        self.emit(IR::LibraryCall(lib_name, full_func_name, arguments.len()));
+        let handle = crate::library::resolve(&lib_name, &func_name);
+        self.emit(IR::LibraryCall(lib_name, full_func_name, arguments.len(), handle, string_literals(&arguments)));
 
         // For show statements, we need to handle the return value
         if self.in_show_statement {
@@ -1317,7 +1450,8 @@ impl Compiler {
         let full_func_name = format!("{}.{}", namespace, function);
 
         // Call the library function with the given number of arguments
-        self.emit(IR::LibraryCall(namespace, full_func_name, arguments.len()));
+        let handle = crate::library::resolve(&namespace, &function);
+        self.emit(IR::LibraryCall(namespace, full_func_name, arguments.len(), handle, string_literals(&arguments)));
 
         // For show statements, we need to handle the return value
         if self.in_show_statement {
@@ -1463,10 +1597,11 @@ impl Compiler {
                 IR::Label(_) => code.push(0x28),
                 IR::SetGlobal(_) => code.push(0x2B), // Global variable operations
                 IR::Sleep => code.push(0x2C),
-                IR::LibraryCall(_, _, _) => code.push(0x2D),
+                IR::LibraryCall(..) => code.push(0x2D),
                 IR::SetupTryCatch => code.push(0x2E),
                 IR::ClearTryCatch => code.push(0x2F),
                 IR::ThrowException => code.push(0x30),
+                IR::CreateStruct(_) => code.push(0x31),
             }
         }
 
@@ -1486,41 +1621,186 @@ impl Compiler {
         }
     }
 
-    // Execute the compiled code directly
-    // In compiler.rs
+    // Execute the compiled code directly, returning the exit status
+    pub fn execute(&self) -> Result<i32, String> {
+        self.execute_ir(&self.ir, &mut io::stdout())
+    }
+
+    // Execute the compiled code, sending program output to `out`
+    pub fn execute_with_output(&self, out: &mut dyn Write) -> Result<i32, String> {
+        self.execute_ir(&self.ir, out)
+    }
+
+    // Run the program's top-level code, then call a single test function
+    pub fn execute_test(&self, test: &TestCase, out: &mut dyn Write) -> Result<i32, String> {
+        let mut code = self.ir.clone();
+        code.push(IR::Call(test.function.clone(), 0));
+        code.push(IR::Pop);
+        self.execute_ir(&code, out)
+    }
+
+    // Assertion helpers used by `assert` and the test framework.
+    // Returns None when `name` is not one of them.
+    fn call_assertion(name: &str, args: &[String]) -> Option<Result<String, String>> {
+        fn values_equal(a: &str, b: &str) -> bool {
+            match (a.parse::<f64>(), b.parse::<f64>()) {
+                (Ok(a_num), Ok(b_num)) => a_num == b_num,
+                _ => a == b,
+            }
+        }
+
+        // Describe how two values differ, pointing at the first differing character
+        fn value_diff(actual: &str, expected: &str) -> String {
+            let mut diff = format!("\n    actual:   {}\n    expected: {}", actual, expected);
+            if actual.parse::<f64>().is_err() || expected.parse::<f64>().is_err() {
+                let position = actual.chars().zip(expected.chars())
+                    .position(|(a, e)| a != e)
+                    .unwrap_or_else(|| actual.chars().count().min(expected.chars().count()));
+                diff.push_str(&format!("\n              {}^ first difference at character {}", " ".repeat(position), position));
+            }
+            diff
+        }
 
-    // ... (keep the rest of the Compiler struct and its methods as they are) ...
+        let arg = |i: usize| args.get(i).map(String::as_str).unwrap_or("undefined");
+        let with_message = |text: String, i: usize| match args.get(i) {
+            Some(message) => format!("{}: {}", message, text),
+            None => text,
+        };
+        let truthy = |s: &str| !matches!(s, "false" | "0" | "" | "null" | "undefined" | "False");
 
-    // Execute the compiled code directly
-    pub fn execute(&self) -> Result<(), String> {
+        let result = match name {
+            "__assert" => {
+                if truthy(arg(0)) { Ok(()) } else { Err("Assertion failed".to_string()) }
+            },
+            "__assert_with_message" => {
+                if truthy(arg(0)) { Ok(()) } else { Err(format!("Assertion failed: {}", arg(1))) }
+            },
+            "assert_eq" => {
+                if values_equal(arg(0), arg(1)) {
+                    Ok(())
+                } else {
+                    Err(with_message(format!("assert_eq failed: values are not equal{}", value_diff(arg(0), arg(1))), 2))
+                }
+            },
+            "assert_ne" => {
+                if !values_equal(arg(0), arg(1)) {
+                    Ok(())
+                } else {
+                    Err(with_message(format!("assert_ne failed: both values are {}", arg(0)), 2))
+                }
+            },
+            "__assert_fail" => Err(arg(0).to_string()),
+            "__assert_error_contains" => {
+                if arg(0).contains(arg(1)) {
+                    Ok(())
+                } else {
+                    Err(format!("assert_throws failed: error message did not match{}", value_diff(arg(0), arg(1))))
+                }
+            },
+            _ => return None,
+        };
+
+        Some(result.map(|_| "null".to_string()))
+    }
+
+    // Returns the program's exit status
+    fn execute_ir(&self, code: &[IR], out: &mut dyn Write) -> Result<i32, String> {
         if !self.clean_output {
             println!("Executing Razen program...");
-            for (i, ir) in self.ir.iter().enumerate() {
+            for (i, ir) in code.iter().enumerate() {
                 println!("{}: {:?}", i, ir);
             }
         }
 
+        // Simplified pre-pass: Just register function addresses.
+        // Parameter binding is handled at call time.
+        let mut variables: HashMap<String, String> = HashMap::new();
+        for ir in code.iter() {
+            if let IR::DefineFunction(name, address) = ir {
+                variables.insert(name.clone(), address.to_string());
+            }
+        }
+
+        match self.run_ir(code, out, 0, &mut variables, false)? {
+            Completion::Exit(status) => Ok(status),
+            Completion::Finished | Completion::Returned(_) => {
+                if !self.clean_output {
+                    println!("Execution complete.");
+                }
+                Ok(0)
+            }
+        }
+    }
+
+    // Bind call arguments to a function's parameter names in a new scope
+    fn bind_parameters(&self, name: &str, args: &[String], scope: &mut HashMap<String, String>) {
+        if let Some(param_names) = self.function_param_names.get(name) {
+            for (i, param_name) in param_names.iter().enumerate() {
+                let value = args.get(i).cloned().unwrap_or_else(|| "undefined".to_string());
+                scope.insert(param_name.clone(), value);
+            }
+        }
+    }
+
+    // Lets library functions (e.g. FFI callbacks) call back into Razen functions.
+    // Each callback runs against the caller's globals, and what it assigns is kept for
+    // the next callback and written back to the caller when the library call returns.
+    fn script_runner(self: &Rc<Self>, code: &Rc<Vec<IR>>, globals: SharedVariables) -> crate::library::ScriptRunner {
+        let (compiler, code) = (Rc::clone(self), Rc::clone(code));
+        Rc::new(move |name: &str, args: Vec<String>| {
+            let mut scope = globals.borrow().clone();
+            let address = scope.get(name)
+                .filter(|_| compiler.function_param_names.contains_key(name))
+                .and_then(|address| address.parse::<usize>().ok())
+                .ok_or_else(|| format!("Callback function '{}' is not defined", name))?;
+            compiler.bind_parameters(name, &args, &mut scope);
+            let completion = compiler.run_ir(&code, &mut io::stdout(), address, &mut scope, true);
+
+            // Parameters and new variables belong to this call; only globals it assigned are kept
+            if let Some(param_names) = compiler.function_param_names.get(name) {
+                for param_name in param_names {
+                    scope.remove(param_name);
+                }
+            }
+            let mut globals = globals.borrow_mut();
+            for (variable, value) in scope {
+                if let Some(global) = globals.get_mut(&variable) {
+                    *global = value;
+                }
+            }
+            match completion? {
+                Completion::Returned(value) => Ok(value),
+                Completion::Finished => Ok("null".to_string()),
+                Completion::Exit(_) => Err(format!("exit() cannot be called from the callback {}", name)),
+            }
+        })
+    }
+
+    // Runs code from `start`; in a callback, a return from the entry function ends the run
+    fn run_ir(&self, code: &[IR], out: &mut dyn Write, start: usize, variables: &mut HashMap<String, String>, callback: bool) -> Result<Completion, String> {
         // Helper function for boolean logic
         fn is_truthy(s: &str) -> bool {
             !matches!(s, "false" | "0" | "" | "null" | "undefined" | "False")
         }
 
         let mut stack: Vec<String> = Vec::new();
-        let mut variables: HashMap<String, String> = HashMap::new();
-        let mut call_stack: Vec<(usize, HashMap<String, String>)> = Vec::new();
-        let mut exception_handlers: Vec<(String, usize)> = Vec::new();
-
-        // Simplified pre-pass: Just register function addresses.
-        // Parameter binding is handled at call time.
-        for ir in self.ir.iter() {
-            if let IR::DefineFunction(name, address) = ir {
-                variables.insert(name.clone(), address.to_string());
+        // Pop the call frames entered after an exception handler was installed
+        fn unwind_call_stack(call_stack: &mut Vec<(usize, HashMap<String, String>)>, variables: &mut HashMap<String, String>, depth: usize) {
+            while call_stack.len() > depth {
+                if let Some((_, caller_variables)) = call_stack.pop() {
+                    *variables = caller_variables;
+                }
             }
         }
 
-        let mut pc = 0;
-        while pc < self.ir.len() {
-            let ir = &self.ir[pc];
+        let mut call_stack: Vec<(usize, HashMap<String, String>)> = Vec::new();
+        let mut exception_handlers: Vec<(String, usize, usize)> = Vec::new(); // (label, handler pc, call depth)
+        // Built the first time a library function runs a callback, so it can re-enter this program
+        let callback_context: std::cell::OnceCell<(Rc<Compiler>, Rc<Vec<IR>>)> = std::cell::OnceCell::new();
+
+        let mut pc = start;
+        while pc < code.len() {
+            let ir = &code[pc];
             match ir {
                 IR::PushNumber(n) => stack.push(n.to_string()),
                 IR::PushString(s) => stack.push(s.clone()),
@@ -1701,10 +1981,12 @@ impl Compiler {
                 IR::Return => {
                     let return_value = stack.pop().unwrap_or_else(|| "null".to_string());
                     if let Some((return_addr, caller_variables)) = call_stack.pop() {
-                        variables = caller_variables;
+                        *variables = caller_variables;
                         stack.push(return_value);
                         pc = return_addr;
                         continue;
+                    } else if callback {
+                        return Ok(Completion::Returned(return_value));
                     } else {
                         stack.push(return_value);
                     }
@@ -1723,20 +2005,31 @@ impl Compiler {
                     if let Some(func_addr_str) = variables.get(name) {
                         if let Ok(func_addr) = func_addr_str.parse::<usize>() {
                             let mut func_variables = variables.clone(); // Inherit globals
-                            if let Some(param_names) = self.function_param_names.get(name) {
-                                for (i, param_name) in param_names.iter().enumerate() {
-                                    if i < args.len() {
-                                        func_variables.insert(param_name.clone(), args[i].clone());
-                                    } else {
-                                        func_variables.insert(param_name.clone(), "undefined".to_string());
-                                    }
-                                }
-                            }
+                            self.bind_parameters(name, &args, &mut func_variables);
                             call_stack.push((pc + 1, variables.clone()));
-                            variables = func_variables;
+                            *variables = func_variables;
                             pc = func_addr;
                             continue;
                         }
+                    } else if name == "__import_lib" {
+                        let library = args.first().map(String::as_str).unwrap_or_default();
+                        crate::library::import_library(library, self.source_dir())?;
+                        stack.push("true".to_string());
+                    } else if let Some(result) = Self::call_assertion(name, &args) {
+                        match result {
+                            Ok(value) => stack.push(value),
+                            Err(message) => {
+                                // Failed assertions are thrown like any other exception
+                                if let Some((_, handler_pc, depth)) = exception_handlers.pop() {
+                                    unwind_call_stack(&mut call_stack, variables, depth);
+                                    stack.push(message);
+                                    pc = handler_pc;
+                                    continue;
+                                } else {
+                                    return Err(message);
+                                }
+                            }
+                        }
                     } else {
                         if !self.clean_output { println!("Unknown function: {}", name); }
                         stack.push("undefined".to_string());
@@ -1745,30 +2038,57 @@ impl Compiler {
                 // **RESTORED**: The original, full-featured GetIndex logic
                 IR::GetIndex => {
                     if let (Some(index), Some(container)) = (stack.pop(), stack.pop()) {
-                        let mut found = false;
-                        // Check for enums/maps which are stored as "KEY:VALUE,KEY2:VALUE2"
-                        let enum_key_str = format!("{}:", index);
-                        if let Some(start_pos) = container.find(&enum_key_str) {
-                            let after_key = &container[start_pos + enum_key_str.len()..];
-                            let end_pos = after_key.find(',').unwrap_or(after_key.len());
-                            let value = &after_key[0..end_pos];
-                            stack.push(value.trim().to_string());
-                            found = true;
-                        }
-                        // Handle array indexing
-                        if !found && container.starts_with('[') && container.ends_with(']') {
-                            if let Ok(idx) = index.parse::<usize>() {
-                                let content = &container[1..container.len() - 1];
-                                 // This is a simplified split, a full parser would be needed for nested arrays.
-                                let elements: Vec<&str> = content.split(',').map(str::trim).collect();
-                                if idx < elements.len() {
-                                    stack.push(elements[idx].to_string());
-                                    found = true;
+                        let field = structs::type_name(&container)
+                            .and_then(|name| self.structs.get(name))
+                            .map(|struct_type| struct_type.get(&container, &index));
+                        match field {
+                            // Fields of struct instances
+                            Some(Ok(value)) => stack.push(value),
+                            Some(Err(e)) => {
+                                if let Some((_, handler_pc, depth)) = exception_handlers.pop() {
+                                    unwind_call_stack(&mut call_stack, variables, depth);
+                                    stack.push(e);
+                                    pc = handler_pc;
+                                    continue;
+                                } else {
+                                    return Err(e);
                                 }
-                            }
-                        }
-                        if !found {
-                            stack.push("undefined".to_string());
+                            },
+                            None => {
+                                let mut found = false;
+                                // Check for enums/maps which are stored as "KEY:VALUE,KEY2:VALUE2"
+                                // Maps returned by library calls print as "{KEY: VALUE, ...}"; nested
+                                // values keep their commas and quoted strings are decoded
+                                if !container.starts_with('[') {
+                                    let entries = container.strip_prefix('{')
+                                        .and_then(|inner| inner.strip_suffix('}'))
+                                        .unwrap_or(&container);
+                                    let value = split_top_level(entries).into_iter()
+                                        .filter_map(|entry| entry.split_once(':').map(|(key, value)| (key.trim().trim_matches('"').to_string(), value.trim().to_string())))
+                                        .find(|(key, _)| *key == index)
+                                        .map(|(_, value)| match value.starts_with('"') {
+                                            true => serde_json::from_str(&value).unwrap_or(value),
+                                            false => value,
+                                        });
+                                    if let Some(value) = value {
+                                        stack.push(value);
+                                        found = true;
+                                    }
+                                }
+                                // Handle array indexing; elements may be nested arrays or structs
+                                if !found && container.starts_with('[') && container.ends_with(']') {
+                                    if let Ok(idx) = index.parse::<usize>() {
+                                        let elements = split_top_level(&container[1..container.len() - 1]);
+                                        if idx < elements.len() {
+                                            stack.push(elements[idx].clone());
+                                            found = true;
+                                        }
+                                    }
+                                }
+                                if !found {
+                                    stack.push("undefined".to_string());
+                                }
+                            },
                         }
                     } else {
                         stack.push("undefined".to_string());
@@ -1777,9 +2097,23 @@ impl Compiler {
                 // **RESTORED**: The original, full-featured SetIndex logic
                 IR::SetIndex => {
                      if let (Some(value), Some(index_str), Some(container_str)) = (stack.pop(), stack.pop(), stack.pop()) {
-                        if container_str.starts_with('[') && container_str.ends_with(']') {
-                            let content = &container_str[1..container_str.len() - 1];
-                            let mut elements: Vec<String> = content.split(',').map(|s| s.trim().to_string()).collect();
+                        let struct_type = structs::type_name(&container_str).and_then(|name| self.structs.get(name));
+                        if let Some(struct_type) = struct_type {
+                            match struct_type.set(&container_str, &index_str, &value) {
+                                Ok(changed) => stack.push(changed),
+                                Err(e) => {
+                                    if let Some((_, handler_pc, depth)) = exception_handlers.pop() {
+                                        unwind_call_stack(&mut call_stack, variables, depth);
+                                        stack.push(e);
+                                        pc = handler_pc;
+                                        continue;
+                                    } else {
+                                        return Err(e);
+                                    }
+                                }
+                            }
+                        } else if container_str.starts_with('[') && container_str.ends_with(']') {
+                            let mut elements = split_top_level(&container_str[1..container_str.len() - 1]);
                             if let Ok(idx) = index_str.parse::<usize>() {
                                 while elements.len() <= idx {
                                     elements.push("null".to_string());
@@ -1793,46 +2127,111 @@ impl Compiler {
                         }
                      }
                 },
+                IR::CreateStruct(name) => {
+                    let struct_type = self.structs.get(name).ok_or_else(|| format!("Unknown struct: {}", name))?;
+                    let values = stack.split_off(stack.len().saturating_sub(struct_type.fields.len()));
+                    match struct_type.create(&values) {
+                        Ok(instance) => stack.push(instance),
+                        Err(e) => {
+                            if let Some((_, handler_pc, depth)) = exception_handlers.pop() {
+                                unwind_call_stack(&mut call_stack, variables, depth);
+                                stack.push(e);
+                                pc = handler_pc;
+                                continue;
+                            } else {
+                                return Err(e);
+                            }
+                        }
+                    }
+                },
                 // **RESTORED**: The original, full-featured LibraryCall logic
-                IR::LibraryCall(lib_name, func_name, arg_count) => {
+                IR::LibraryCall(lib_name, func_name, arg_count, handle, literals) => {
                     if !self.clean_output {
                         println!("Calling library function: {}.{} with {} arguments", lib_name, func_name, arg_count);
                     }
                     let function_name_only = func_name.split('.').last().unwrap_or(func_name);
-                    let mut args = Vec::new();
+                    let mut raw_args = Vec::new();
                     for _ in 0..*arg_count {
                         if let Some(arg) = stack.pop() {
-                            if let Ok(i) = arg.parse::<i64>() { args.push(crate::value::Value::Int(i)); }
-                            else if let Ok(f) = arg.parse::<f64>() { args.push(crate::value::Value::Float(f)); }
-                            else if arg == "true" { args.push(crate::value::Value::Bool(true)); }
-                            else if arg == "false" { args.push(crate::value::Value::Bool(false)); }
-                            else if arg == "null" || arg == "undefined" { args.push(crate::value::Value::Null); }
-                            else if arg.starts_with('[') && arg.ends_with(']') {
-                                let inner = &arg[1..arg.len()-1];
-                                let elements: Vec<crate::value::Value> = inner.split(',')
-                                    .map(|s| {
-                                        let s = s.trim();
-                                        if let Ok(i) = s.parse::<i64>() { crate::value::Value::Int(i) }
-                                        else if let Ok(f) = s.parse::<f64>() { crate::value::Value::Float(f) }
-                                        else { crate::value::Value::String(s.to_string()) }
-                                    }).collect();
-                                args.push(crate::value::Value::Array(elements));
-                            } else {
-                                 let mut final_arg_str = arg.clone();
-                                 if final_arg_str.starts_with('"') && final_arg_str.ends_with('"') && final_arg_str.len() >= 2 {
-                                     final_arg_str = final_arg_str[1..final_arg_str.len()-1].to_string();
-                                 }
-                                 args.push(crate::value::Value::String(final_arg_str));
+                            raw_args.push(arg);
+                        }
+                    }
+                    raw_args.reverse();
+
+                    let library = lib_name.to_lowercase();
+
+                    // Static methods written in Razen, unless a Rust function of the same name
+                    // exists and the method was not declared `override static`
+                    let method = format!("{}.{}", library, function_name_only);
+                    let method_address = variables.get(&method)
+                        .filter(|_| self.function_param_names.contains_key(&method))
+                        .and_then(|address| address.parse::<usize>().ok());
+                    if let Some(address) = method_address {
+                        let has_function = handle.is_some() || crate::library::has_function(&library, function_name_only);
+                        if self.library_overrides.contains(&method) || !has_function {
+                            let mut func_variables = variables.clone(); // Inherit globals
+                            self.bind_parameters(&method, &raw_args, &mut func_variables);
+                            call_stack.push((pc + 1, variables.clone()));
+                            *variables = func_variables;
+                            pc = address;
+                            continue;
+                        }
+                    }
+
+                    // Libraries imported at runtime (plugins) are not resolved at compile time
+                    let handle = handle.clone().or_else(|| crate::library::resolve(&library, function_name_only));
+                    // Values on the stack are text, so "42" and 42 look alike here. Parameters any
+                    // library declares as `string`, and string literals passed as `any`, keep their text
+                    let args: Vec<_> = raw_args.iter().enumerate().map(|(index, arg)| {
+                        let literal = literals.get(index).copied().unwrap_or(false);
+                        match handle.as_ref().and_then(|handle| handle.signature().parameter_type(index)) {
+                            Some("string") => library_text_arg(arg),
+                            Some("any") if literal => library_text_arg(arg),
+                            _ => library_arg(arg),
+                        }
+                    }).collect();
+                    // Callbacks see the variables as LoadVar would, and share one copy of them
+                    let callback_globals: std::cell::OnceCell<(HashMap<String, String>, SharedVariables)> = std::cell::OnceCell::new();
+                    let runner = || {
+                        let (compiler, shared_code) = callback_context.get_or_init(|| {
+                            (Rc::new(self.callback_compiler()), Rc::new(code.to_vec()))
+                        });
+                        let (_, globals) = callback_globals.get_or_init(|| {
+                            let mut visible = variables.clone();
+                            if let Some((_, func_vars)) = call_stack.last() {
+                                visible.extend(func_vars.clone());
+                            }
+                            (visible.clone(), Rc::new(RefCell::new(visible)))
+                        });
+                        compiler.script_runner(shared_code, Rc::clone(globals))
+                    };
+                    let context = crate::library::CallContext::new(&library)
+                        .with_runner(&runner)
+                        .with_script(self.source_path.as_deref());
+                    let result = match handle {
+                        Some(handle) => handle.call(&context, args),
+                        None => crate::library::call_library(&context, function_name_only, args),
+                    };
+
+                    // Keep what the callbacks assigned, stored the way StoreVar would
+                    if let Some((before, globals)) = callback_globals.into_inner() {
+                        let target = match call_stack.last_mut() {
+                            Some((_, func_vars)) => func_vars,
+                            None => &mut *variables,
+                        };
+                        for (name, value) in globals.take() {
+                            if before.get(&name) != Some(&value) {
+                                target.insert(name, value);
                             }
                         }
                     }
-                    args.reverse();
 
-                    match crate::library::call_library(&lib_name.to_lowercase(), function_name_only, args) {
+                    match result {
                         Ok(value) => stack.push(value.to_string()),
                         Err(e) => {
                             // Handle library errors by trying to throw an exception
-                            if let Some((_, handler_pc)) = exception_handlers.pop() {
+                            if let Some((_, handler_pc, depth)) = exception_handlers.pop() {
+                                unwind_call_stack(&mut call_stack, variables, depth);
                                 stack.push(e);
                                 pc = handler_pc;
                                 continue;
@@ -1865,9 +2264,9 @@ impl Compiler {
                 },
                 IR::Print => {
                     if let Some(value) = stack.pop() {
-                        use std::io::{self, Write};
-                        print!("{}", value);
-                        io::stdout().flush().unwrap();
+                        write!(out, "{}", value)
+                            .and_then(|_| out.flush())
+                            .map_err(|e| format!("Failed to write output: {}", e))?;
                     }
                 },
                 IR::ReadInput => {
@@ -1878,7 +2277,12 @@ impl Compiler {
                     if line.ends_with('\n') { line.pop(); if line.ends_with('\r') { line.pop(); } }
                     stack.push(line);
                 },
-                IR::Exit => { return Ok(()); },
+                IR::Exit => {
+                    let status = stack.pop().unwrap_or_else(|| "0".to_string());
+                    return status.parse::<f64>()
+                        .map(|status| Completion::Exit(status as i32))
+                        .map_err(|_| format!("Exit status must be a number, got {}", status));
+                },
                 IR::Sleep => {
                     if let Some(duration_str) = stack.pop() {
                         if let Ok(duration) = duration_str.parse::<f64>() {
@@ -1889,16 +2293,17 @@ impl Compiler {
                 // **RESTORED**: Exception Handling
                 IR::SetupTryCatch => {
                     if let Some(handler_label) = stack.pop() {
-                       let handler_pc = self.ir.iter().position(|ir| matches!(ir, IR::Label(l) if l == &handler_label));
+                       let handler_pc = code.iter().position(|ir| matches!(ir, IR::Label(l) if l == &handler_label));
                        if let Some(pc) = handler_pc {
-                           exception_handlers.push((handler_label, pc));
+                           exception_handlers.push((handler_label, pc, call_stack.len()));
                        }
                    }
                 },
                 IR::ClearTryCatch => { exception_handlers.pop(); },
                 IR::ThrowException => {
                     if let Some(error_message) = stack.pop() {
-                         if let Some((_, handler_pc)) = exception_handlers.pop() {
+                         if let Some((_, handler_pc, depth)) = exception_handlers.pop() {
+                            unwind_call_stack(&mut call_stack, variables, depth);
                             stack.push(error_message);
                             pc = handler_pc;
                             continue;
@@ -1917,10 +2322,18 @@ impl Compiler {
             pc += 1;
         }
 
-        if !self.clean_output {
-            println!("Execution complete.");
-        }
-        Ok(())
+        Ok(Completion::Finished)
+    }
+
+    // Just the state the VM reads at runtime, for running FFI callbacks
+    fn callback_compiler(&self) -> Compiler {
+        let mut compiler = Compiler::new();
+        compiler.function_param_names = self.function_param_names.clone();
+        compiler.clean_output = self.clean_output;
+        compiler.source_path = self.source_path.clone();
+        compiler.library_overrides = self.library_overrides.clone();
+        compiler.structs = self.structs.clone();
+        compiler
     }
 
     // Module System Methods
@@ -2051,6 +2464,51 @@ impl Compiler {
         } else {
             self.emit(IR::Call("__assert".to_string(), 1));
         }
+
+        // Discard the result of the assertion
+        self.emit(IR::Pop);
+    }
+
+    /// Compile assert_throws(expression, expected_message?)
+    fn compile_assert_throws(&mut self, mut arguments: Vec<Expression>) {
+        let catch_label = self.generate_label("assert_throws_catch");
+        let end_label = self.generate_label("assert_throws_end");
+        let expression = arguments.remove(0);
+        let description = format!("{}", crate::ast::Node::Expression(expression.clone()));
+
+        // Evaluate the expression with an exception handler installed
+        self.emit(IR::PushString(catch_label.clone()));
+        self.emit(IR::SetupTryCatch);
+        self.compile_expression(expression);
+        self.emit(IR::Pop);
+        self.emit(IR::ClearTryCatch);
+
+        // Reaching this point means nothing was thrown
+        self.emit(IR::PushString(format!("assert_throws failed: {} did not throw", description)));
+        self.emit(IR::Call("__assert_fail".to_string(), 1));
+        self.emit(IR::Pop);
+        let jump_to_end_pos = self.emit(IR::Jump(0));
+
+        // The exception message is on the stack here
+        self.emit_label(&catch_label);
+        if let Some(expected) = arguments.into_iter().next() {
+            self.compile_expression(expected);
+            self.emit(IR::Call("__assert_error_contains".to_string(), 2));
+        }
+        self.emit(IR::Pop);
+
+        let end_pos = self.emit_label(&end_label);
+        self.replace_instruction(jump_to_end_pos, IR::Jump(end_pos));
+
+        // Like every call expression, leave a value on the stack
+        self.emit(IR::PushNull);
+    }
+
+    /// Compile test block into a function that `razen test` can call
+    fn compile_test_declaration(&mut self, name: String, body: Vec<Statement>) {
+        let function = format!("__test_{}", self.tests.len());
+        self.tests.push(TestCase { name, function: function.clone() });
+        self.compile_function_declaration(function, Vec::new(), body);
     }
 
     /// Compile trace statement
@@ -2084,9 +2542,11 @@ impl Compiler {
         self.emit(IR::PushString(name.clone()));
 
         // Compile class body
+        let outer_class = self.current_class.replace(name.clone());
         for stmt in body {
             self.compile_statement(stmt);
         }
+        self.current_class = outer_class;
 
         // Leave class scope
         self.leave_scope();
@@ -2095,6 +2555,23 @@ impl Compiler {
         self.emit(IR::Call("__define_class".to_string(), 1));
     }
 
+    // Static methods compile to functions named "class.method" (lowercase class), which
+    // `Class[method](...)` calls when no Rust library function of that name exists
+    fn compile_static_method(&mut self, name: String, parameters: Vec<String>, body: Vec<Statement>, override_native: bool) {
+        let Some(class) = self.current_class.clone() else {
+            self.errors.push(format!("Static method '{}' must be declared inside a class", name));
+            return;
+        };
+
+        let function_name = format!("{}.{}", class.to_lowercase(), name);
+        if override_native {
+            self.library_overrides.insert(function_name.clone());
+        }
+        let outer_class = self.current_class.take();
+        self.compile_function_declaration(function_name, parameters, body);
+        self.current_class = outer_class;
+    }
+
     // API Integration Methods (Section 13)
 
     // Compile API declaration
@@ -2216,12 +2693,9 @@ impl Compiler {
         // Call library import function
         self.emit(IR::Call("__import_lib".to_string(), 1));
 
-        // Dynamically register all library functions for all libraries
-        let libs_path = PathBuf::from("properties/libs");
-        self.register_all_libraries(libs_path.to_str().unwrap_or("properties/libs"));
+        // Libraries written in Razen are compiled into the program
+        self.compile_razen_library(&name);
 
-        // No need to match on specific library names anymore
-        // Just register the specific library being imported
         if !self.clean_output {
             println!("[Compiler] Registered library: {}", name);
         }
@@ -2556,52 +3030,66 @@ impl Compiler {
 
     // Helper methods for library functions
 
-    /// Dynamically scan and register all library functions from properties/libs
-    pub fn register_all_libraries(&mut self, libs_dir: &str) {
-        let paths = match fs::read_dir(libs_dir) {
-            Ok(p) => p,
-            Err(_) => return,
-        };
+    // Find a library in properties/libs by file name or by the class it declares
+    fn find_razen_library(&self, name: &str) -> Option<PathBuf> {
+        let mut dirs = vec![PathBuf::from("properties/libs")];
+        if let Some(source_dir) = self.source_dir() {
+            dirs.insert(0, source_dir.join("properties/libs"));
+        }
 
-        // Simple pattern matching instead of regex
-        for entry in paths {
-            if let Ok(entry) = entry {
-                let path = entry.path();
-                if let Some(ext) = path.extension() {
-                    if ext == "rzn" {
-                        let lib_name = path.file_stem().unwrap().to_string_lossy().to_string();
-                        if let Ok(content) = fs::read_to_string(&path) {
-                            let mut current_class = None;
-                            for line in content.lines() {
-                                let line = line.trim();
-                                // Extract class name
-                                if line.starts_with("class ") {
-                                    let parts: Vec<&str> = line.split_whitespace().collect();
-                                    if parts.len() >= 2 {
-                                        current_class = Some(parts[1].trim_matches('{').to_string());
-                                    }
-                                }
-                                // Extract static method
-                                else if line.starts_with("static ") {
-                                    if let Some(class) = &current_class {
-                                        let parts: Vec<&str> = line.split_whitespace().collect();
-                                        if parts.len() >= 2 {
-                                            let fn_name = parts[1].split('(').next().unwrap_or(parts[1]);
-                                            let full_name = format!("{}.{}", class, fn_name);
-                                            self.function_table.define(&format!("{}.{}", lib_name, full_name), self.ir.len());
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
+        let name = name.to_lowercase();
+        for dir in dirs {
+            let Ok(entries) = fs::read_dir(&dir) else { continue };
+            let mut files: Vec<PathBuf> = entries.flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == "rzn"))
+                .collect();
+            files.sort();
+
+            if let Some(path) = files.iter().find(|path| path.file_stem().is_some_and(|stem| stem.to_string_lossy().to_lowercase() == name)) {
+                return Some(path.clone());
+            }
+            let declares_class = |path: &PathBuf| {
+                fs::read_to_string(path).is_ok_and(|content| content.lines().any(|line| {
+                    line.trim().strip_prefix("class ")
+                        .and_then(|rest| rest.split(|c: char| c.is_whitespace() || c == '{').next())
+                        .is_some_and(|class| class.to_lowercase() == name)
+                }))
+            };
+            if let Some(path) = files.iter().find(|path| declares_class(path)) {
+                return Some(path.clone());
             }
         }
+        None
     }
 
-    // All library functions are now dynamically registered from the .rzn files
-    // No need for individual define_*_lib_functions methods
+    // Parse and compile a library from properties/libs once, making its static methods callable
+    fn compile_razen_library(&mut self, name: &str) {
+        let Some(path) = self.find_razen_library(name) else { return };
+        if !self.razen_libraries.insert(path.clone()) {
+            return;
+        }
+
+        let source = match fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(e) => {
+                self.errors.push(format!("Failed to read library {}: {}", path.display(), e));
+                return;
+            }
+        };
+        let mut parser = Parser::new(crate::lexer::Lexer::new(source));
+        let program = parser.parse_program();
+        if !parser.get_errors().is_empty() {
+            for error in parser.get_errors() {
+                self.errors.push(format!("Error parsing library {}: {}", path.display(), error));
+            }
+            return;
+        }
+
+        for statement in program.statements {
+            self.compile_statement(statement);
+        }
+    }
 
     // Performance and Type Safety Compilation Functions
 
@@ -2664,6 +3152,65 @@ impl Compiler {
         self.emit(IR::StoreVar(name));
     }
 
+    // Compile struct declaration; it only defines a type, so no code is generated
+    fn compile_struct_declaration(&mut self, name: String, fields: Vec<(String, String)>) {
+        if !self.clean_output {
+            println!("[Compiler] Struct declaration: {}", name);
+        }
+
+        match StructType::declare(&name, &fields, &self.structs) {
+            Ok(struct_type) => { self.structs.insert(name, struct_type); },
+            Err(e) => self.struct_errors.push(e),
+        }
+    }
+
+    // Compile struct constructor: field values in declaration order, then CreateStruct
+    fn compile_struct_literal(&mut self, name: String, fields: Vec<(String, Expression)>) {
+        let ordered = match self.structs.get(&name) {
+            Some(struct_type) => struct_type.order(&fields, Kind::of_literal).map(|values| values.into_iter().cloned().collect::<Vec<_>>()),
+            None => Err(format!("Unknown struct: {}", name)),
+        };
+
+        match ordered {
+            Ok(values) => {
+                for value in values {
+                    self.compile_expression(value);
+                }
+                self.emit(IR::CreateStruct(name));
+            },
+            Err(e) => {
+                self.struct_errors.push(e);
+                self.emit(IR::PushNull);
+            },
+        }
+    }
+
+    // With the new value on the stack, set `target.field` and store the changed value back
+    fn compile_field_assignment(&mut self, target: Expression, field: Expression) {
+        let Expression::Identifier(field) = field else {
+            self.struct_errors.push("Expected a field name after '.'".to_string());
+            return;
+        };
+
+        // SetIndex takes the container, field and value in that order
+        self.compile_expression(target.clone());
+        self.emit(IR::Swap);
+        self.emit(IR::PushString(field));
+        self.emit(IR::Swap);
+        self.emit(IR::SetIndex);
+
+        match target {
+            Expression::Identifier(name) => { self.emit(IR::StoreVar(name)); },
+            Expression::InfixExpression { left, operator, right } if operator == "." => {
+                self.compile_field_assignment(*left, *right);
+            },
+            other => {
+                self.struct_errors.push(format!("Cannot assign to a field of {}", crate::ast::Node::Expression(other)));
+                self.emit(IR::Pop);
+            },
+        }
+    }
+
     // Compile inline function declaration
     fn compile_inline_function_declaration(&mut self, name: String, parameters: Vec<String>, body: Vec<Statement>) {
         if !self.clean_output {
@@ -2731,9 +3278,11 @@ impl Compiler {
         self.emit(IR::PushString(name.clone()));
 
         // Compile class body
+        let outer_class = self.current_class.replace(name.clone());
         for stmt in body {
             self.compile_statement(stmt);
         }
+        self.current_class = outer_class;
 
         // Leave class scope
         self.leave_scope();
@@ -2802,3 +3351,119 @@ impl Compiler {
         self.emit(IR::StoreVar(name));
     }
 }
+
+// Which library call arguments are written as string literals
+fn string_literals(arguments: &[Expression]) -> Vec<bool> {
+    arguments.iter().map(|arg| matches!(arg, Expression::StringLiteral(_))).collect()
+}
+
+// Text for a parameter declared as `string`, kept as written so "007" stays "007"
+fn library_text_arg(arg: &str) -> crate::value::Value {
+    let text = match arg.strip_prefix('"').and_then(|rest| rest.strip_suffix('"')) {
+        Some(inner) => inner,
+        None => arg,
+    };
+    crate::value::Value::String(text.to_string())
+}
+
+// Convert a VM stack string back into a library argument. Maps and arrays are
+// stored as "{key:value, ...}" and "[a, b]", so split them at top-level commas.
+fn library_arg(arg: &str) -> crate::value::Value {
+    use crate::value::Value;
+
+    if let Ok(i) = arg.parse::<i64>() { Value::Int(i) }
+    else if let Ok(f) = arg.parse::<f64>() { Value::Float(f) }
+    else if arg == "true" { Value::Bool(true) }
+    else if arg == "false" { Value::Bool(false) }
+    else if arg == "null" || arg == "undefined" { Value::Null }
+    else if arg.starts_with('[') && arg.ends_with(']') {
+        let elements = split_top_level(&arg[1..arg.len()-1]).iter()
+            .map(|element| library_element_arg(element))
+            .collect();
+        Value::Array(elements)
+    } else if arg.starts_with('{') && arg.ends_with('}') && arg.len() > 2 && arg.contains(':') {
+        let mut map = std::collections::HashMap::new();
+        for entry in split_top_level(&arg[1..arg.len()-1]) {
+            match entry.split_once(':') {
+                Some((key, value)) => { map.insert(key.trim().to_string(), library_arg(value.trim())); }
+                None => return Value::String(arg.to_string()),
+            }
+        }
+        Value::Map(map)
+    } else {
+        let mut final_arg_str = arg.to_string();
+        if final_arg_str.starts_with('"') && final_arg_str.ends_with('"') && final_arg_str.len() >= 2 {
+            final_arg_str = final_arg_str[1..final_arg_str.len()-1].to_string();
+        }
+        Value::String(final_arg_str)
+    }
+}
+
+// An element of an array argument. Quoted elements, as in text read from JSON or files,
+// are strings without their quotes, so ["007", "a, b"] passes "007" and "a, b" to every library.
+fn library_element_arg(element: &str) -> crate::value::Value {
+    use crate::value::Value;
+
+    if let Ok(i) = element.parse::<i64>() { Value::Int(i) }
+    else if let Ok(f) = element.parse::<f64>() { Value::Float(f) }
+    else if element.starts_with('[') || element.starts_with('{') { library_arg(element) }
+    else { library_text_arg(element) }
+}
+
+// Split a list of values on the commas that are not inside brackets, braces or strings
+pub(crate) fn split_top_level(text: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0i32;
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in text.chars() {
+        if in_string {
+            in_string = escaped || c != '"';
+            escaped = !escaped && c == '\\';
+        } else {
+            match c {
+                '"' => in_string = true,
+                '[' | '{' => depth += 1,
+                ']' | '}' => depth -= 1,
+                ',' if depth == 0 => {
+                    parts.push(current.trim().to_string());
+                    current.clear();
+                    continue;
+                },
+                _ => {},
+            }
+        }
+        current.push(c);
+    }
+    if !current.trim().is_empty() || !parts.is_empty() {
+        parts.push(current.trim().to_string());
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_top_level_skips_nested_and_quoted_commas() {
+        let parts = split_top_level(r#""a, b", [1, 2], {k: "v, w"}, "say \"x, y\"""#);
+        assert_eq!(parts, vec![r#""a, b""#, "[1, 2]", r#"{k: "v, w"}"#, r#""say \"x, y\"""#]);
+        assert!(split_top_level("").is_empty());
+    }
+
+    #[test]
+    fn test_array_arguments_keep_quoted_elements_as_text() {
+        use crate::value::Value;
+
+        let text = |value: &str| Value::String(value.to_string());
+        assert_eq!(library_arg(r#"["007", "a, b", 3, [1, "x"]]"#), Value::Array(vec![
+            text("007"),
+            text("a, b"),
+            Value::Int(3),
+            Value::Array(vec![Value::Int(1), text("x")]),
+        ]));
+        assert_eq!(library_arg("[007, plain]"), Value::Array(vec![Value::Int(7), text("plain")]));
+    }
+}
@@ -0,0 +1,174 @@
+// API documentation for the built-in libraries (`razen doc`), generated from
+// the signatures they are registered with and the doc comments in src/functions.
+
+use serde_json::json;
+
+use crate::library::{self, FunctionDoc};
+
+/// Output formats of `razen doc`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DocFormat {
+    Text,
+    Markdown,
+    Html,
+    Json,
+}
+
+impl DocFormat {
+    pub fn parse(name: &str) -> Result<DocFormat, String> {
+        match name {
+            "text" => Ok(DocFormat::Text),
+            "markdown" | "md" => Ok(DocFormat::Markdown),
+            "html" => Ok(DocFormat::Html),
+            "json" => Ok(DocFormat::Json),
+            _ => Err(format!("Unknown doc format '{}' (expected text, markdown, html or json)", name)),
+        }
+    }
+}
+
+/// Docs of the named libraries, or of every library when no names are given
+pub fn collect(names: &[String]) -> Result<Vec<(String, Vec<FunctionDoc>)>, String> {
+    let names: Vec<String> = if names.is_empty() {
+        let mut names = library::get_library_names();
+        names.sort();
+        names
+    } else {
+        names.iter().map(|name| name.to_lowercase()).collect()
+    };
+
+    names.into_iter()
+        .map(|name| library::get_library_docs(&name).map(|docs| (name, docs)))
+        .collect()
+}
+
+/// Render library docs; the text format without named libraries lists them instead
+pub fn render(libraries: &[(String, Vec<FunctionDoc>)], format: DocFormat, summary: bool) -> String {
+    match format {
+        DocFormat::Text if summary => render_summary(libraries),
+        DocFormat::Text => render_text(libraries),
+        DocFormat::Markdown => render_markdown(libraries),
+        DocFormat::Html => render_html(libraries),
+        DocFormat::Json => render_json(libraries),
+    }
+}
+
+fn render_summary(libraries: &[(String, Vec<FunctionDoc>)]) -> String {
+    let width = libraries.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    let mut out = String::from("Libraries (run `razen doc <library>` for details):\n\n");
+    for (name, functions) in libraries {
+        out.push_str(&format!("  {:width$}  {} functions\n", name, functions.len(), width = width));
+    }
+    out
+}
+
+fn render_text(libraries: &[(String, Vec<FunctionDoc>)]) -> String {
+    let mut out = String::new();
+    for (name, functions) in libraries {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(&format!("{}\n", name));
+        for function in functions {
+            out.push_str(&format!("\n  {}\n", function.signature));
+            for line in function.doc.lines() {
+                out.push_str(format!("      {}", line).trim_end());
+                out.push('\n');
+            }
+        }
+    }
+    out
+}
+
+// Doc comments are a description followed by "Example: ..." lines
+fn split_doc(doc: &str) -> (String, Vec<&str>) {
+    let mut description = Vec::new();
+    let mut examples = Vec::new();
+    for line in doc.lines() {
+        match line.trim().strip_prefix("Example:") {
+            Some(example) => examples.push(example.trim()),
+            None => description.push(line),
+        }
+    }
+    (description.join("\n").trim().to_string(), examples)
+}
+
+fn render_markdown(libraries: &[(String, Vec<FunctionDoc>)]) -> String {
+    let mut out = String::from("# Razen Library Reference\n\n");
+    out.push_str("Generated with `razen doc --format=markdown` from the signatures and doc comments of the built-in libraries. Edit the doc comments in `src/functions` rather than this file.\n\n");
+    out.push_str("Call a function with bracket notation after importing its library, for example `lib arrlib;` and `ArrLib[push](items, 4)`. Parameters marked `?` are optional and `...` accepts any number of arguments.\n\n");
+    for (name, functions) in libraries {
+        out.push_str(&format!("- [{}](#{}) ({} functions)\n", name, name, functions.len()));
+    }
+
+    for (name, functions) in libraries {
+        out.push_str(&format!("\n## {}\n", name));
+        for function in functions {
+            let (description, examples) = split_doc(&function.doc);
+            out.push_str(&format!("\n### `{}`\n", function.signature));
+            if !description.is_empty() {
+                out.push_str(&format!("\n{}\n", description));
+            }
+            if !examples.is_empty() {
+                out.push_str(&format!("\n```razen\n{}\n```\n", examples.join("\n")));
+            }
+        }
+    }
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn render_html(libraries: &[(String, Vec<FunctionDoc>)]) -> String {
+    let mut out = String::from("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>Razen Library Reference</title>\n");
+    out.push_str("<style>\nbody { font-family: sans-serif; max-width: 60em; margin: 2em auto; padding: 0 1em; }\ncode, pre { background: #f4f4f4; }\npre { padding: 0.5em; }\nh3 code { background: none; }\n</style>\n</head>\n<body>\n");
+    out.push_str("<h1>Razen Library Reference</h1>\n<p>Generated with <code>razen doc --format=html</code> from the signatures and doc comments of the built-in libraries.</p>\n<ul>\n");
+    for (name, functions) in libraries {
+        out.push_str(&format!("<li><a href=\"#{0}\">{0}</a> ({1} functions)</li>\n", escape_html(name), functions.len()));
+    }
+    out.push_str("</ul>\n");
+
+    for (name, functions) in libraries {
+        out.push_str(&format!("<h2 id=\"{0}\">{0}</h2>\n", escape_html(name)));
+        for function in functions {
+            let (description, examples) = split_doc(&function.doc);
+            out.push_str(&format!("<h3><code>{}</code></h3>\n", escape_html(&function.signature.to_string())));
+            if !description.is_empty() {
+                out.push_str(&format!("<pre>{}</pre>\n", escape_html(&description)));
+            }
+            if !examples.is_empty() {
+                out.push_str(&format!("<pre><code>{}</code></pre>\n", escape_html(&examples.join("\n"))));
+            }
+        }
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+fn render_json(libraries: &[(String, Vec<FunctionDoc>)]) -> String {
+    let mut result = serde_json::Map::new();
+    for (name, functions) in libraries {
+        let functions: Vec<serde_json::Value> = functions.iter().map(|function| {
+            let signature = &function.signature;
+            let required = signature.parameters.len() - signature.optional - usize::from(signature.variadic);
+            let parameters: Vec<serde_json::Value> = signature.parameters.iter().enumerate().map(|(index, kind)| json!({
+                "name": signature.names[index],
+                "type": kind,
+                "optional": index >= required,
+                "variadic": signature.variadic && index + 1 == signature.parameters.len(),
+            })).collect();
+            json!({
+                "name": signature.name,
+                "signature": signature.to_string(),
+                "parameters": parameters,
+                "returns": signature.returns,
+                "doc": function.doc,
+            })
+        }).collect();
+        result.insert(name.clone(), serde_json::Value::Array(functions));
+    }
+    let mut out = serde_json::to_string_pretty(&serde_json::Value::Object(result)).unwrap_or_default();
+    out.push('\n');
+    out
+}
@@ -0,0 +1,611 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::ast::{Expression, Program, Statement};
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::token::TokenType;
+
+const INDENT: &str = "    ";
+
+// Map and array literals longer than this are written one entry per line
+const MAX_INLINE_WIDTH: usize = 80;
+
+// Binding strength of printed expressions, mirroring the parser's precedence table
+const ASSIGNMENT: u8 = 1;
+const PREFIX: u8 = 9;
+const NAMESPACE: u8 = 10;
+const CALL: u8 = 11;
+const INDEX: u8 = 12;
+const ATOM: u8 = 13;
+
+/// Format Razen source code, keeping comments and single blank lines.
+/// Fails instead of changing the meaning of the program.
+pub fn format_source(source: &str) -> Result<String, String> {
+    let mut parser = Parser::new(Lexer::new(source.to_string()));
+    parser.set_preserve_trivia(true);
+    let program = parser.parse_program();
+    if !parser.get_errors().is_empty() {
+        return Err(format!("Cannot format a file with syntax errors:\n  {}", parser.get_errors().join("\n  ")));
+    }
+
+    let formatted = format_program(&program);
+
+    // Safety net: the formatted code must parse to the same program with the same comments
+    if parse_plain(source) != parse_plain(&formatted) {
+        return Err("Formatting would change the meaning of the program; file left unchanged".to_string());
+    }
+    if count_comments(source) != count_comments(&formatted) {
+        return Err("Formatting would lose comments; file left unchanged".to_string());
+    }
+
+    Ok(formatted)
+}
+
+/// Print a whole program
+pub fn format_program(program: &Program) -> String {
+    let mut printer = Printer::new();
+    printer.statements(&program.statements);
+    printer.finish()
+}
+
+/// Print a single statement without a trailing newline
+pub fn format_statement(statement: &Statement) -> String {
+    let mut printer = Printer::new();
+    printer.statement(statement);
+    printer.finish().trim_end_matches('\n').to_string()
+}
+
+/// Print an expression on a single line
+pub fn format_expression(expression: &Expression) -> String {
+    expression_text(expression)
+}
+
+/// Format a file in place, or only compare it when `check` is set.
+/// Returns whether the file was (or would be) changed.
+pub fn format_file(path: &Path, check: bool) -> Result<bool, String> {
+    let source = fs::read_to_string(path).map_err(|e| format!("Failed to read file: {}", e))?;
+    let formatted = format_source(&source)?;
+    if formatted == source {
+        return Ok(false);
+    }
+    if !check {
+        fs::write(path, formatted).map_err(|e| format!("Failed to write file: {}", e))?;
+    }
+    Ok(true)
+}
+
+/// Collect the .rzn files below a path, sorted for stable output
+pub fn collect_files(path: &Path) -> io::Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut files = Vec::new();
+    let mut entries: Vec<PathBuf> = fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<_>>()?;
+    entries.sort();
+
+    for entry in entries {
+        if entry.is_dir() {
+            files.extend(collect_files(&entry)?);
+        } else if entry.extension().is_some_and(|ext| ext == "rzn") {
+            files.push(entry);
+        }
+    }
+    Ok(files)
+}
+
+fn parse_plain(source: &str) -> Option<Program> {
+    let mut parser = Parser::new(Lexer::new(source.to_string()));
+    let program = parser.parse_program();
+    if parser.get_errors().is_empty() { Some(program) } else { None }
+}
+
+fn count_comments(source: &str) -> usize {
+    let mut lexer = Lexer::new(source.to_string());
+    let mut count = 0;
+    loop {
+        match lexer.next_token().token_type {
+            TokenType::Comment => count += 1,
+            TokenType::EOF => return count,
+            _ => {}
+        }
+    }
+}
+
+struct Printer {
+    out: String,
+    indent: usize,
+    trailing: Vec<(usize, usize)>,  // Line index and code length of lines ending in a comment
+}
+
+impl Printer {
+    fn new() -> Self {
+        Printer {
+            out: String::new(),
+            indent: 0,
+            trailing: Vec::new(),
+        }
+    }
+
+    // Align the comments of consecutive lines that end in one
+    fn finish(self) -> String {
+        let mut lines: Vec<String> = self.out.split('\n').map(str::to_string).collect();
+        let mut start = 0;
+        while start < self.trailing.len() {
+            let mut end = start + 1;
+            while end < self.trailing.len() && self.trailing[end].0 == self.trailing[end - 1].0 + 1 {
+                end += 1;
+            }
+            let run = &self.trailing[start..end];
+            let width = run.iter().map(|&(line, code)| lines[line][..code].chars().count()).max().unwrap_or(0);
+            for &(line, code) in run {
+                let (text, comment) = lines[line].split_at(code);
+                let padding = width - text.chars().count() + 1;
+                lines[line] = format!("{}{}{}", text, " ".repeat(padding), comment.trim_start());
+            }
+            start = end;
+        }
+        lines.join("\n")
+    }
+
+    fn line(&mut self, text: &str) {
+        for _ in 0..self.indent {
+            self.out.push_str(INDENT);
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    // Print `header {`, the indented body and the closing brace
+    fn block(&mut self, header: &str, body: &[Statement]) {
+        if body.is_empty() {
+            self.line(&format!("{} {{}}", header));
+            return;
+        }
+        self.line(&format!("{} {{", header));
+        self.body(body);
+    }
+
+    fn body(&mut self, body: &[Statement]) {
+        self.indent += 1;
+        self.statements(body);
+        self.indent -= 1;
+        self.line("}");
+    }
+
+    // Continue the block just printed, as in `} else {`
+    fn chain(&mut self, keyword: &str, body: &[Statement]) {
+        self.out.pop();
+        self.out.push(' ');
+        self.out.push_str(keyword);
+        if body.is_empty() {
+            self.out.push_str(" {}\n");
+        } else {
+            self.out.push_str(" {\n");
+            self.body(body);
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::VariableDeclaration { var_type, name, value } => {
+                self.declaration(&format!("{} {}", var_type, name), value.as_ref());
+            },
+            Statement::VolatileDeclaration { var_type, name, value } => {
+                self.declaration(&format!("volatile {} {}", var_type, name), value.as_ref());
+            },
+            Statement::ConstDeclaration { name, value } => {
+                self.declaration(&format!("const {}", name), Some(value));
+            },
+            Statement::FunctionDeclaration { name, parameters, body } => {
+                self.block(&format!("fun {}({})", name, parameters.join(", ")), body);
+            },
+            Statement::StaticMethodDeclaration { name, parameters, body, override_native } => {
+                let prefix = if *override_native { "override static" } else { "static" };
+                self.block(&format!("{} {}({})", prefix, name, parameters.join(", ")), body);
+            },
+            Statement::InlineFunctionDeclaration { name, parameters, body } => {
+                self.block(&format!("inline fun {}({})", name, parameters.join(", ")), body);
+            },
+            Statement::ReturnStatement { value } => match value {
+                Some(value) => self.value_statement("return ", value, ";"),
+                None => self.line("return;"),
+            },
+            Statement::ExpressionStatement { expression } => self.value_statement("", expression, ";"),
+            // Bare blocks have no syntax of their own, and `when true` runs one the same way
+            Statement::BlockStatement { statements } => self.block("when true", statements),
+            Statement::WhenStatement { value, body } => self.block(&format!("when {}", expression_text(value)), body),
+            Statement::IfStatement { condition, consequence, alternative } => {
+                self.block(&format!("if ({})", expression_text(condition)), consequence);
+                if let Some(alternative) = alternative {
+                    self.chain("else", alternative);
+                }
+            },
+            Statement::WhileStatement { condition, body } => {
+                self.block(&format!("while ({})", expression_text(condition)), body);
+            },
+            Statement::ForStatement { iterator, iterable, body } => {
+                self.block(&format!("for ({} in {})", iterator, expression_text(iterable)), body);
+            },
+            Statement::BreakStatement => self.line("break;"),
+            Statement::ContinueStatement => self.line("continue;"),
+            Statement::ShowStatement { value, color } => match color {
+                Some(color) => self.value_statement(&format!("show({}) ", color), value, ";"),
+                None => self.value_statement("show ", value, ";"),
+            },
+            Statement::TryStatement { try_block, catch_param, catch_block, finally_block } => {
+                self.block("try", try_block);
+                if let Some(catch_block) = catch_block {
+                    match catch_param {
+                        Some(param) => self.chain(&format!("catch ({})", param), catch_block),
+                        None => self.chain("catch", catch_block),
+                    }
+                }
+                if let Some(finally_block) = finally_block {
+                    self.chain("finally", finally_block);
+                }
+            },
+            Statement::ThrowStatement { value } => self.value_statement("throw ", value, ";"),
+            Statement::ReadStatement { name } => self.line(&format!("read {};", name)),
+            Statement::ExitStatement { code } => match code {
+                Some(code) => self.line(&format!("exit({});", expression_text(code))),
+                None => self.line("exit;"),
+            },
+            Statement::DocumentTypeDeclaration { doc_type } => self.line(&format!("type {};", doc_type)),
+            Statement::ModuleImport { names, alias, source } => match alias {
+                Some(alias) => self.line(&format!("use {} as {} from \"{}\";", names.join(", "), alias, source)),
+                None => self.line(&format!("use {} from \"{}\";", names.join(", "), source)),
+            },
+            Statement::ModuleExport { name } => self.line(&format!("export {};", name)),
+            Statement::DebugStatement { value } => self.value_statement("debug ", value, ";"),
+            Statement::AssertStatement { condition, message } => match message {
+                Some(message) => self.line(&format!("assert({}, {});", expression_text(condition), expression_text(message))),
+                None => self.line(&format!("assert({});", expression_text(condition))),
+            },
+            // The parser rejects a semicolon after a traced expression
+            Statement::TraceStatement { value } => self.value_statement("trace ", value, ""),
+            Statement::TestDeclaration { name, body } => self.block(&format!("test \"{}\"", name), body),
+            Statement::ClassDeclaration { name, body } => self.block(&format!("class {}", name), body),
+            Statement::FinalClassDeclaration { name, body } => self.block(&format!("final class {}", name), body),
+            Statement::ApiDeclaration { name, url } => self.line(&format!("api {} = from(\"{}\");", name, url)),
+            Statement::ApiCall { name, body } => self.block(&format!("call {}", name), body),
+            Statement::ConnectStatement { name, url, options } => {
+                let header = format!("connect {} = from(\"{}\")", name, url);
+                if options.is_empty() {
+                    self.line(&format!("{};", header));
+                } else {
+                    self.line(&format!("{} {{", header));
+                    self.indent += 1;
+                    for (option, value) in options {
+                        self.line(&format!("{} {};", option, expression_text(value)));
+                    }
+                    self.indent -= 1;
+                    self.line("}");
+                }
+            },
+            Statement::ImportStatement { imports, path } => {
+                self.line(&format!("import {{ {} }} from(\"{}\");", imports.join(", "), path));
+            },
+            Statement::LibStatement { name } => self.line(&format!("lib {};", name)),
+            Statement::NativeLibStatement { path } => self.line(&format!("lib \"{}\";", path)),
+            Statement::LoadStatement { cycles, block } => {
+                self.block(&format!("load({})", expression_text(cycles)), block);
+            },
+            Statement::EnumDeclaration { name, variants } => {
+                self.line(&format!("enum {} {{", name));
+                self.indent += 1;
+                for (variant, value) in variants {
+                    match value {
+                        Some(value) => self.line(&format!("{} = {},", variant, expression_text(value))),
+                        None => self.line(&format!("{},", variant)),
+                    }
+                }
+                self.indent -= 1;
+                self.line("}");
+            },
+            Statement::StructDeclaration { name, fields } => {
+                self.line(&format!("struct {} {{", name));
+                self.indent += 1;
+                for (field, type_name) in fields {
+                    self.line(&format!("{}: {},", field, type_name));
+                }
+                self.indent -= 1;
+                self.line("}");
+            },
+            // Typed compiler construction statements only print the entries they keep
+            Statement::GrammarStatement { name, properties } => self.config("grammar", name, properties.to_vec()),
+            Statement::LexerStatement { name, config } => self.config("lexer", name, config.to_vec()),
+            Statement::ParserStatement { name, config } => self.config("parser", name, config.to_vec()),
+            Statement::NodeStatement { name, properties } => self.config("node", name, properties.to_vec()),
+            Statement::TargetStatement { name, properties } => self.config("target", name, properties.to_vec()),
+            Statement::AttributeStatement { name, values } => self.config("attribute", name, values.to_vec()),
+            Statement::TokenStatement { name, pattern } => self.line(&format!("token {} = \"{}\";", name, pattern)),
+            Statement::RuleStatement { name, production, node_type } => match node_type {
+                Some(node_type) => {
+                    let mut pairs = Vec::new();
+                    push_string(&mut pairs, "production", production);
+                    pairs.push(("astNode".to_string(), Expression::Identifier(node_type.clone())));
+                    self.config("rule", name, pairs);
+                },
+                None => self.line(&format!("rule {} = \"{}\";", name, production)),
+            },
+            Statement::VisitorStatement { name, methods } => {
+                let mut pairs = Vec::new();
+                push_strings(&mut pairs, "methods", methods);
+                self.config("visitor", name, pairs);
+            },
+            Statement::SymbolStatement { name, attributes } => {
+                let mut pairs = Vec::new();
+                push_strings(&mut pairs, "attributes", attributes);
+                self.config("symbol", name, pairs);
+            },
+            Statement::ScopeStatement { name, parent } => {
+                let parent = match parent {
+                    Some(parent) => Expression::Identifier(parent.clone()),
+                    None => Expression::NullLiteral,
+                };
+                self.config("scope", name, vec![("parent".to_string(), parent)]);
+            },
+            Statement::TypeStatement { name, operations } => {
+                let mut pairs = Vec::new();
+                push_strings(&mut pairs, "operations", operations);
+                self.config("typesys", name, pairs);
+            },
+            Statement::IRStatement { name, opcode, operands } => {
+                let mut pairs = Vec::new();
+                push_string(&mut pairs, "opcode", opcode);
+                push_strings(&mut pairs, "operands", operands);
+                self.config("ir", name, pairs);
+            },
+            Statement::CodeGenStatement { name, target, instructions } => {
+                let mut pairs = Vec::new();
+                push_string(&mut pairs, "architecture", target);
+                pairs.extend(instructions.iter().cloned());
+                self.config("codegen", name, pairs);
+            },
+            Statement::OptimizeStatement { name, description, passes } => {
+                let mut pairs = Vec::new();
+                push_string(&mut pairs, "description", description);
+                push_strings(&mut pairs, "passes", passes);
+                self.config("optimize", name, pairs);
+            },
+            Statement::Comment { text, trailing } => {
+                let comment = format!("#{}", text.trim_end());
+                if *trailing && self.out.ends_with('\n') {
+                    self.out.pop();
+                    let line_start = self.out.rfind('\n').map_or(0, |i| i + 1);
+                    self.trailing.push((self.out.matches('\n').count(), self.out.len() - line_start));
+                    self.out.push(' ');
+                    self.out.push_str(&comment);
+                    self.out.push('\n');
+                } else {
+                    self.line(&comment);
+                }
+            },
+            Statement::BlankLine => self.out.push('\n'),
+            Statement::SourceDeclaration { keyword, name, value } => {
+                self.value_statement(&format!("{} {} = ", keyword, name), value, ";");
+            },
+        }
+    }
+
+    fn declaration(&mut self, head: &str, value: Option<&Expression>) {
+        match value {
+            Some(value) => self.value_statement(&format!("{} = ", head), value, ";"),
+            None => self.line(&format!("{};", head)),
+        }
+    }
+
+    fn config(&mut self, keyword: &str, name: &str, pairs: Vec<(String, Expression)>) {
+        let map = Expression::MapLiteral {
+            pairs: pairs.into_iter().map(|(key, value)| (Expression::StringLiteral(key), value)).collect(),
+        };
+        self.value_statement(&format!("{} {} = ", keyword, name), &map, ";");
+    }
+
+    // Print `prefix value suffix`, breaking long map and array literals over several lines
+    fn value_statement(&mut self, prefix: &str, value: &Expression, suffix: &str) {
+        let text = format!("{}{}{}", prefix, self.wrapped(value, prefix.len() + suffix.len()), suffix);
+        self.line(&text);
+    }
+
+    fn wrapped(&self, expression: &Expression, extra: usize) -> String {
+        let inline = expression_text(expression);
+        let width = self.indent * INDENT.len() + extra + inline.len();
+        if width <= MAX_INLINE_WIDTH || inline.contains('\n') {
+            return inline;
+        }
+
+        let nested = Printer { out: String::new(), indent: self.indent + 1, trailing: Vec::new() };
+        let padding = INDENT.repeat(self.indent + 1);
+        let closing = INDENT.repeat(self.indent);
+        match expression {
+            Expression::MapLiteral { pairs } if !pairs.is_empty() => {
+                let entries: Vec<String> = pairs.iter()
+                    .map(|(key, value)| {
+                        let key = expression_text(key);
+                        format!("{}{}: {}", padding, key, nested.wrapped(value, key.len() + 3))
+                    })
+                    .collect();
+                format!("{{\n{}\n{}}}", entries.join(",\n"), closing)
+            },
+            // Short values fill each line, as written in the examples
+            Expression::ArrayLiteral { elements } if !elements.is_empty()
+                && elements.iter().all(|element| !matches!(element, Expression::ArrayLiteral { .. } | Expression::MapLiteral { .. })) =>
+            {
+                let mut rows = vec![String::new()];
+                for element in elements {
+                    let text = format!("{},", expression_text(element));
+                    let row = rows.last_mut().unwrap();
+                    if !row.is_empty() && padding.len() + row.len() + 1 + text.len() > MAX_INLINE_WIDTH {
+                        rows.push(text);
+                    } else if row.is_empty() {
+                        row.push_str(&text);
+                    } else {
+                        row.push(' ');
+                        row.push_str(&text);
+                    }
+                }
+                // Arrays cannot end with a comma
+                rows.last_mut().unwrap().pop();
+                let rows: Vec<String> = rows.iter().map(|row| format!("{}{}", padding, row)).collect();
+                format!("[\n{}\n{}]", rows.join("\n"), closing)
+            },
+            Expression::ArrayLiteral { elements } if !elements.is_empty() => {
+                let entries: Vec<String> = elements.iter()
+                    .map(|element| format!("{}{}", padding, nested.wrapped(element, 1)))
+                    .collect();
+                format!("[\n{}\n{}]", entries.join(",\n"), closing)
+            },
+            _ => inline,
+        }
+    }
+}
+
+fn push_string(pairs: &mut Vec<(String, Expression)>, key: &str, value: &str) {
+    if !value.is_empty() {
+        pairs.push((key.to_string(), Expression::StringLiteral(value.to_string())));
+    }
+}
+
+fn push_strings(pairs: &mut Vec<(String, Expression)>, key: &str, values: &[String]) {
+    let elements = values.iter().map(|value| Expression::StringLiteral(value.clone())).collect();
+    pairs.push((key.to_string(), Expression::ArrayLiteral { elements }));
+}
+
+fn operator_precedence(operator: &str) -> u8 {
+    match operator {
+        "=" | "+=" | "-=" | "*=" | "/=" | "%=" => ASSIGNMENT,
+        "||" => 2,
+        "&&" => 3,
+        "==" | "!=" => 4,
+        "<" | "<=" | ">" | ">=" => 5,
+        "+" | "-" => 6,
+        "*" | "/" | "%" | "//" => 7,
+        "**" => 8,
+        "::" => NAMESPACE,
+        "." => CALL,
+        _ => 0,
+    }
+}
+
+fn precedence(expression: &Expression) -> u8 {
+    match expression {
+        Expression::AssignmentExpression { .. } => ASSIGNMENT,
+        Expression::InfixExpression { operator, .. } => operator_precedence(operator),
+        Expression::PrefixExpression { .. } => PREFIX,
+        Expression::CallExpression { .. } | Expression::NamespaceCall { .. } => CALL,
+        Expression::IndexExpression { .. } | Expression::LibraryCall { .. } => INDEX,
+        _ => ATOM,
+    }
+}
+
+fn parenthesized(expression: &Expression, needs_parens: bool) -> String {
+    if needs_parens {
+        format!("({})", expression_text(expression))
+    } else {
+        expression_text(expression)
+    }
+}
+
+// Left side of a call, index or property access
+fn postfix_target(expression: &Expression) -> String {
+    let needs_parens = precedence(expression) < NAMESPACE
+        || matches!(expression, Expression::NumberLiteral(_));
+    parenthesized(expression, needs_parens)
+}
+
+fn arguments_text(arguments: &[Expression]) -> String {
+    arguments.iter().map(expression_text).collect::<Vec<_>>().join(", ")
+}
+
+fn expression_text(expression: &Expression) -> String {
+    match expression {
+        Expression::Identifier(name) => name.clone(),
+        Expression::StringLiteral(value) => format!("\"{}\"", value),
+        Expression::NumberLiteral(value) => format!("{}", value),
+        Expression::BooleanLiteral(value) => format!("{}", value),
+        Expression::NullLiteral => "null".to_string(),
+        Expression::PrefixExpression { operator, right } => {
+            format!("{}{}", operator, parenthesized(right, precedence(right) < PREFIX))
+        },
+        Expression::InfixExpression { left, operator, right } if operator == "." || operator == "::" => {
+            format!("{}{}{}", postfix_target(left), operator, expression_text(right))
+        },
+        Expression::InfixExpression { left, operator, right } => {
+            let level = operator_precedence(operator);
+            format!(
+                "{} {} {}",
+                parenthesized(left, precedence(left) < level),
+                operator,
+                parenthesized(right, precedence(right) <= level)
+            )
+        },
+        Expression::AssignmentExpression { left, operator, right } => {
+            format!("{} {} {}", parenthesized(left, precedence(left) <= ASSIGNMENT), operator, expression_text(right))
+        },
+        Expression::CallExpression { function, arguments } => {
+            // `a[i](...)` would be read back as a library call
+            let function_text = match **function {
+                Expression::IndexExpression { .. } => parenthesized(function, true),
+                _ => postfix_target(function),
+            };
+            format!("{}({})", function_text, arguments_text(arguments))
+        },
+        Expression::ArrayLiteral { elements } => format!("[{}]", arguments_text(elements)),
+        Expression::IndexExpression { left, index } => {
+            format!("{}[{}]", postfix_target(left), expression_text(index))
+        },
+        Expression::MapLiteral { pairs } => {
+            let entries: Vec<String> = pairs.iter()
+                .map(|(key, value)| format!("{}: {}", expression_text(key), expression_text(value)))
+                .collect();
+            format!("{{{}}}", entries.join(", "))
+        },
+        Expression::LibraryCall { library, function, arguments } => {
+            format!("{}[{}]({})", postfix_target(library), expression_text(function), arguments_text(arguments))
+        },
+        Expression::NamespaceCall { namespace, function, arguments } => {
+            format!("{}::{}({})", namespace, function, arguments_text(arguments))
+        },
+        Expression::StructLiteral { name, fields } if fields.is_empty() => format!("{} {{}}", name),
+        Expression::StructLiteral { name, fields } => {
+            let entries: Vec<String> = fields.iter()
+                .map(|(field, value)| format!("{}: {}", field, expression_text(value)))
+                .collect();
+            format!("{} {{ {} }}", name, entries.join(", "))
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_keeps_comments_and_is_idempotent() {
+        let source = "# header\n\n\nnum x=1;   # one\nnum total = 20; # twenty\nfun add(a,b){\n# body\nreturn (a+b)*2;}\nif x>1 {show(green) \"big\";} else {}\nwhen x+1 {\n# matched\nshow x;}\n";
+        let formatted = format_source(source).unwrap();
+
+        assert_eq!(formatted, "# header\n\nnum x = 1;      # one\nnum total = 20; # twenty\nfun add(a, b) {\n    # body\n    return (a + b) * 2;\n}\nif (x > 1) {\n    show(green) \"big\";\n} else {}\nwhen x + 1 {\n    # matched\n    show x;\n}\n");
+        assert_eq!(format_source(&formatted).unwrap(), formatted);
+    }
+
+    #[test]
+    fn test_format_compiler_construction_statements() {
+        let source = "rule expr = {\"production\": \"term\", \"astNode\": BinaryNode, \"description\": \"kept\"};\ntoken NUM = \"[0-9]+\";\n";
+        let formatted = format_source(source).unwrap();
+
+        assert_eq!(formatted, "rule expr = {\n    \"production\": \"term\",\n    \"astNode\": BinaryNode,\n    \"description\": \"kept\"\n};\ntoken NUM = \"[0-9]+\";\n");
+    }
+}
@@ -24,6 +24,15 @@ mod image;
 mod date;
 mod filesystem;
 mod api;
+mod httpserver;
+mod httpfixtures;
+mod args;
+mod storage;
+mod typecheck;
+mod typeconvert;
+mod num;
+mod console;
+mod reference;
 
 // New modules for self-compilation
 mod memory;
@@ -31,6 +40,7 @@ mod binary;
 mod bitwise;
 mod syscall;
 mod process;
+mod ffi;
 mod thread;
 mod compiler;
 
@@ -104,6 +114,14 @@ pub mod apilib {
     pub use super::api::*;
 }
 
+pub mod httpserverlib {
+    pub use super::httpserver::*;
+}
+
+pub mod argslib {
+    pub use super::args::*;
+}
+
 pub mod uuidlib {
     pub use super::uuid::*;
 }
@@ -148,6 +166,30 @@ pub mod filesystemlib {
     pub use super::filesystem::*;
 }
 
+pub mod storagelib {
+    pub use super::storage::*;
+}
+
+pub mod typechecklib {
+    pub use super::typecheck::*;
+}
+
+pub mod typeconvertlib {
+    pub use super::typeconvert::*;
+}
+
+pub mod numlib {
+    pub use super::num::*;
+}
+
+pub mod iolib {
+    pub use super::console::*;
+}
+
+pub mod reflib {
+    pub use super::reference::*;
+}
+
 // New library modules for self-compilation
 pub mod memorylib {
     pub use super::memory::*;
@@ -169,6 +211,10 @@ pub mod processlib {
     pub use super::process::*;
 }
 
+pub mod ffilib {
+    pub use super::ffi::*;
+}
+
 pub mod threadlib {
     pub use super::thread::*;
 }
@@ -208,4 +254,18 @@ pub mod codegenlib {
 
 pub mod optimizelib {
     pub use super::optimize::*;
-}
\ No newline at end of file
+}
+
+// Write a script and run it on another thread; joining the handle gives what it printed
+#[cfg(test)]
+fn run_script_in_background(script: &std::path::Path, source: &str) -> std::thread::JoinHandle<String> {
+    std::fs::write(script, source).unwrap();
+    let script = script.to_path_buf();
+    std::thread::spawn(move || {
+        crate::library::initialize();
+        let compiler = crate::compiler::Compiler::from_file_with_clean_output(&script, true).unwrap();
+        let mut out = Vec::new();
+        compiler.execute_with_output(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    })
+}
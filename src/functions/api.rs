@@ -1,14 +1,76 @@
+use super::httpfixtures::{self, Reply, SendError};
+use crate::library::CallContext;
 use crate::value::Value;
 use std::collections::HashMap;
-use reqwest::blocking::{Client, Response};
+use reqwest::blocking::{multipart, Client, RequestBuilder};
+use reqwest::cookie::{CookieStore, Jar};
 use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
 use serde_json::{json, Value as JsonValue};
 use std::time::Duration;
-use url::form_urlencoded;
+use url::{form_urlencoded, Url};
 
 // Default timeout for API requests in seconds
 const DEFAULT_TIMEOUT: u64 = 30;
+// Seconds to wait before the first retry; the wait doubles after each attempt
+const DEFAULT_BACKOFF: f64 = 0.5;
+// Downloads are written to disk in chunks of this size
+const DOWNLOAD_CHUNK: usize = 64 * 1024;
+
+// create_api() opens a session: a client that keeps its connections, cookies and
+// default headers between calls. The returned configuration map refers to it by
+// its "session" id, the way BinaryLib and NetLib hand out integer handles.
+lazy_static::lazy_static! {
+    static ref SESSIONS: Mutex<HashMap<i64, Session>> = Mutex::new(HashMap::new());
+}
+static NEXT_SESSION: AtomicI64 = AtomicI64::new(1);
+
+struct Session {
+    client: Client,
+    cookies: Arc<Jar>,
+    headers: HashMap<String, Value>,
+    retry: RetryPolicy,
+}
+
+// How often a request is repeated after a 5xx response or a timeout
+#[derive(Clone, Copy)]
+struct RetryPolicy {
+    retries: u32,
+    backoff: Duration,
+    on_timeout: bool,
+}
+
+impl RetryPolicy {
+    const NONE: RetryPolicy = RetryPolicy { retries: 0, backoff: Duration::ZERO, on_timeout: false };
+}
+
+// What a request is sent with: a session's client, or a fresh one for one-off calls
+struct Connection {
+    client: Client,
+    cookies: Option<Arc<Jar>>,
+    headers: HashMap<String, Value>,
+    retry: RetryPolicy,
+}
+
+impl Connection {
+    fn fresh() -> Result<Connection, String> {
+        Ok(Connection { client: build_client(None)?, cookies: None, headers: HashMap::new(), retry: RetryPolicy::NONE })
+    }
+}
+
+// Clients have no overall timeout of their own; each request sets one
+fn build_client(cookies: Option<Arc<Jar>>) -> Result<Client, String> {
+    let mut builder = Client::builder().timeout(None::<Duration>);
+    if let Some(jar) = cookies {
+        builder = builder.cookie_provider(jar);
+    }
+    builder.build().map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
 
 // Common HTTP status codes
 const HTTP_OK: u16 = 200;
@@ -361,119 +423,154 @@ fn make_request(
     body: Option<Value>,
     timeout_seconds: Option<u64>
 ) -> Result<Value, String> {
-    // Create a new client with timeout
-    let timeout = Duration::from_secs(timeout_seconds.unwrap_or(DEFAULT_TIMEOUT));
-    let client = Client::builder()
-        .timeout(timeout)
-        .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
-    
-    // Build the request
-    let mut request_builder = match method.to_uppercase().as_str() {
-        "GET" => client.get(url),
-        "POST" => client.post(url),
-        "PUT" => client.put(url),
-        "DELETE" => client.delete(url),
-        "PATCH" => client.patch(url),
-        "HEAD" => client.head(url),
-        "OPTIONS" => client.request(reqwest::Method::OPTIONS, url),
-        _ => return Err(format!("Unsupported HTTP method: {}", method)),
-    };
+    let connection = Connection::fresh()?;
+    let timeout = Some(timeout_seconds.unwrap_or(DEFAULT_TIMEOUT));
+    let response = send_request(&connection, method, url, &params, &headers, body.as_ref(), timeout)?;
     
-    // Add query parameters for GET requests
-    if method.to_uppercase() == "GET" && params != Value::Null {
-        if let Value::Map(params_map) = params {
-            for (key, value) in params_map {
-                let value_str = match value {
-                    Value::String(s) => s,
-                    Value::Int(i) => i.to_string(),
-                    Value::Float(f) => f.to_string(),
-                    Value::Bool(b) => b.to_string(),
-                    _ => value.to_string(),
-                };
-                request_builder = request_builder.query(&[(key, value_str)]);
-            }
-        }
+    // Process the response
+    process_response(response)
+}
+
+/// Send a request over a connection, retrying it as the connection's policy allows
+fn send_request(
+    connection: &Connection,
+    method: &str,
+    url: &str,
+    params: &Value,
+    headers: &Value,
+    body: Option<&Value>,
+    timeout_seconds: Option<u64>
+) -> Result<Reply, String> {
+    // Unknown methods are refused before anything is sent
+    let method = method.to_uppercase();
+    if !["GET", "POST", "PUT", "DELETE", "PATCH", "HEAD", "OPTIONS"].contains(&method.as_str()) {
+        return Err(format!("Unsupported HTTP method: {}", method));
     }
+    let json_body = match body {
+        Some(body_value) if method == "POST" || method == "PUT" || method == "PATCH" => Some(value_to_json(body_value.clone())?),
+        _ => None,
+    };
     
-    // Add headers
-    if let Value::Map(headers_map) = headers {
-        let mut header_map = HeaderMap::new();
-        for (key, value) in headers_map {
-            if let Ok(header_name) = HeaderName::from_str(&key) {
-                let value_str = match value {
-                    Value::String(s) => s,
-                    Value::Int(i) => i.to_string(),
-                    Value::Float(f) => f.to_string(),
-                    Value::Bool(b) => b.to_string(),
-                    _ => value.to_string(),
-                };
-                if let Ok(header_value) = HeaderValue::from_str(&value_str) {
-                    header_map.insert(header_name, header_value);
-                } else {
-                    return Err(format!("Invalid header value for '{}': {}", key, value_str));
+    send_with_retries(connection, timeout_seconds, || {
+        let mut request_builder = new_request(connection, &method, url, headers, timeout_seconds)?;
+        
+        // Add query parameters for GET requests
+        if method == "GET" {
+            if let Value::Map(params_map) = params {
+                for (key, value) in params_map {
+                    request_builder = request_builder.query(&[(key, header_text(value))]);
                 }
-            } else {
-                return Err(format!("Invalid header name: {}", key));
             }
         }
-        request_builder = request_builder.headers(header_map);
-    }
-    
-    // Add body for POST, PUT, and PATCH requests
-    if let Some(body_value) = body {
-        if method.to_uppercase() == "POST" || method.to_uppercase() == "PUT" || method.to_uppercase() == "PATCH" {
-            // Convert body to JSON
-            let json_body = value_to_json(body_value)?;
-            request_builder = request_builder.json(&json_body);
+        
+        // Add body for POST, PUT, and PATCH requests
+        if let Some(json_body) = &json_body {
+            request_builder = request_builder.json(json_body);
         }
+        Ok(request_builder)
+    })
+}
+
+/// Start a request with the connection's default headers, overridden by the given ones
+fn new_request(
+    connection: &Connection,
+    method: &str,
+    url: &str,
+    headers: &Value,
+    timeout_seconds: Option<u64>
+) -> Result<RequestBuilder, String> {
+    let method = reqwest::Method::from_str(method)
+        .map_err(|_| format!("Unsupported HTTP method: {}", method))?;
+    let mut request_builder = connection.client.request(method, url);
+    if let Some(seconds) = timeout_seconds {
+        request_builder = request_builder.timeout(Duration::from_secs(seconds));
     }
     
-    // Send the request with error handling
-    let response = match request_builder.send() {
-        Ok(resp) => resp,
-        Err(e) => {
-            if e.is_timeout() {
-                return Err(format!("API request timed out after {} seconds", timeout_seconds.unwrap_or(DEFAULT_TIMEOUT)));
-            } else if e.is_connect() {
-                return Err(format!("Failed to connect to API server: {}", e));
-            } else if e.is_request() {
-                return Err(format!("Invalid request: {}", e));
+    let mut header_map = HeaderMap::new();
+    let request_headers = match headers {
+        Value::Map(headers_map) => Some(headers_map),
+        _ => None,
+    };
+    for (key, value) in connection.headers.iter().chain(request_headers.into_iter().flatten()) {
+        if let Ok(header_name) = HeaderName::from_str(key) {
+            let value_str = header_text(value);
+            if let Ok(header_value) = HeaderValue::from_str(&value_str) {
+                header_map.insert(header_name, header_value);
             } else {
-                return Err(format!("API request failed: {}", e));
+                return Err(format!("Invalid header value for '{}': {}", key, value_str));
             }
+        } else {
+            return Err(format!("Invalid header name: {}", key));
         }
-    };
-    
-    // Process the response
-    process_response(response)
+    }
+    Ok(request_builder.headers(header_map))
+}
+
+fn header_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Int(i) => i.to_string(),
+        Value::Float(f) => f.to_string(),
+        Value::Bool(b) => b.to_string(),
+        _ => value.to_string(),
+    }
+}
+
+/// Send the request `build` makes, building it again for every retry.
+/// After the last attempt a 5xx response is returned like any other
+fn send_with_retries(
+    connection: &Connection,
+    timeout_seconds: Option<u64>,
+    build: impl Fn() -> Result<RequestBuilder, String>
+) -> Result<Reply, String> {
+    let retry = connection.retry;
+    let mut attempt = 0;
+    loop {
+        let can_retry = attempt < retry.retries;
+        let request = build()?.build().map_err(|e| request_error(e, timeout_seconds))?;
+        // Recorded and replayed exchanges pass through here too
+        match httpfixtures::execute(&connection.client, request, connection.cookies.as_deref()) {
+            Ok(reply) if reply.status.is_server_error() && can_retry => {},
+            Ok(reply) => return Ok(reply),
+            Err(SendError::Http(e)) if e.is_timeout() && retry.on_timeout && can_retry => {},
+            Err(SendError::Http(e)) => return Err(request_error(e, timeout_seconds)),
+            Err(SendError::Fixture(message)) => return Err(message),
+        }
+        std::thread::sleep(retry.backoff * 2u32.saturating_pow(attempt.min(16)));
+        attempt += 1;
+    }
+}
+
+fn request_error(e: reqwest::Error, timeout_seconds: Option<u64>) -> String {
+    if e.is_timeout() {
+        format!("API request timed out after {} seconds", timeout_seconds.unwrap_or(DEFAULT_TIMEOUT))
+    } else if e.is_connect() {
+        format!("Failed to connect to API server: {}", e)
+    } else if e.is_request() {
+        format!("Invalid request: {}", e)
+    } else {
+        format!("API request failed: {}", e)
+    }
 }
 
 /// Process an API response and convert it to a Razen Value
-fn process_response(response: Response) -> Result<Value, String> {
+fn process_response(response: Reply) -> Result<Value, String> {
     // Get status code
-    let status = response.status().as_u16();
+    let status = response.status.as_u16();
     
     // Get headers before consuming the response
     let mut headers_map = HashMap::new();
-    for (name, value) in response.headers().iter() {
-        if let Ok(value_str) = value.to_str() {
-            headers_map.insert(name.as_str().to_string(), Value::String(value_str.to_string()));
-        }
+    for (name, value) in response.headers.iter() {
+        headers_map.insert(name.clone(), Value::String(value.clone()));
     }
     
     // Get content type for better handling and clone it before consuming the response
-    let content_type = response.headers()
-        .get("content-type")
-        .and_then(|v| v.to_str().ok())
+    let content_type = response.header("content-type")
         .unwrap_or("text/plain")
         .to_string(); // Clone the string to avoid borrowing issues
     
     // Try to get the response as text (this consumes the response)
-    let response_text = match response.text() {
-        Ok(text) => text,
-        Err(e) => return Err(format!("Failed to read response body: {}", e)),
-    };
+    let response_text = response.text()?;
     
     // Create the response data structure
     let mut result_map = HashMap::new();
@@ -732,13 +829,20 @@ pub fn to_json(args: Vec<Value>) -> Result<Value, String> {
     }
 }
 
-/// Create an API configuration with authentication
+/// Create an API configuration with authentication. It opens a session that keeps
+/// cookies and connections between calls, until close_api() is called
 /// Example: create_api("https://api.example.com", "your-api-key", "bearer", 30) => api_config
+/// Example: create_api("https://api.example.com", "", "none", 30, {"retries": 3, "backoff": 0.5, "headers": {"Accept": "application/json"}}) => api_config
 /// Arguments:
 ///   - url: Base URL for the API
 ///   - api_key: (Optional) API key for authentication
 ///   - auth_type: (Optional) Authentication type ("bearer", "basic", "apikey", default: "bearer")
 ///   - timeout: (Optional) Default timeout in seconds
+///   - options: (Optional) Session options
+///     - headers: Headers sent with every request
+///     - retries: How often to retry after a 5xx response or a timeout (default: 0)
+///     - backoff: Seconds to wait before the first retry, doubling after each one (default: 0.5)
+///     - retry_timeouts: Whether timeouts are retried (default: true)
 pub fn create_api(args: Vec<Value>) -> Result<Value, String> {
     if args.len() < 1 {
         return Err("API.create_api: Expected at least 1 argument (url)".to_string());
@@ -771,16 +875,140 @@ pub fn create_api(args: Vec<Value>) -> Result<Value, String> {
         DEFAULT_TIMEOUT
     };
     
+    // Get session options if provided
+    let mut headers = HashMap::new();
+    let mut retry = RetryPolicy {
+        retries: 0,
+        backoff: Duration::from_secs_f64(DEFAULT_BACKOFF),
+        on_timeout: true,
+    };
+    match args.get(4) {
+        Some(Value::Map(options)) => {
+            match options.get("headers") {
+                Some(Value::Map(h)) => headers = h.clone(),
+                Some(Value::Null) | None => {},
+                Some(_) => return Err("API.create_api: headers must be a map".to_string()),
+            }
+            if let Some(retries) = options.get("retries") {
+                retry.retries = retries.as_int()?.max(0) as u32;
+            }
+            if let Some(backoff) = options.get("backoff") {
+                let seconds = backoff.as_float()?;
+                if !seconds.is_finite() || seconds < 0.0 {
+                    return Err(format!("API.create_api: Invalid backoff {}", seconds));
+                }
+                retry.backoff = Duration::from_secs_f64(seconds);
+            }
+            if let Some(on_timeout) = options.get("retry_timeouts") {
+                retry.on_timeout = on_timeout.as_bool()?;
+            }
+        },
+        Some(Value::Null) | None => {},
+        Some(_) => return Err("API.create_api: options must be a map".to_string()),
+    }
+    
+    let cookies = Arc::new(Jar::default());
+    let session = Session { client: build_client(Some(cookies.clone()))?, cookies, headers, retry };
+    let id = NEXT_SESSION.fetch_add(1, Ordering::SeqCst);
+    SESSIONS.lock().unwrap().insert(id, session);
+    
     // Create API configuration
     let mut api_config = HashMap::new();
     api_config.insert("url".to_string(), Value::String(url));
     api_config.insert("api_key".to_string(), Value::String(api_key));
     api_config.insert("auth_type".to_string(), Value::String(auth_type));
     api_config.insert("timeout".to_string(), Value::Int(timeout as i64));
+    api_config.insert("session".to_string(), Value::Int(id));
     
     Ok(Value::Map(api_config))
 }
 
+// The URL, authentication headers, timeout and connection for a call through an API configuration
+struct ApiCall {
+    url: String,
+    headers: HashMap<String, Value>,
+    timeout: Option<u64>,
+    connection: Connection,
+}
+
+fn api_call(function: &str, api_config: &Value, endpoint: &str) -> Result<ApiCall, String> {
+    let config_map = match api_config {
+        Value::Map(config_map) => config_map,
+        _ => return Err("API configuration must be a map".to_string()),
+    };
+    let url = if let Some(Value::String(url)) = config_map.get("url") {
+        url.clone()
+    } else {
+        return Err("API configuration missing 'url'".to_string());
+    };
+    
+    let api_key = match config_map.get("api_key") {
+        Some(Value::String(key)) => key.clone(),
+        Some(Value::Int(key)) => key.to_string(),
+        _ => "".to_string(), // Default to empty API key if not provided
+    };
+    
+    let auth_type = if let Some(Value::String(auth)) = config_map.get("auth_type") {
+        auth.clone()
+    } else {
+        "none".to_string() // Default to no auth if not specified
+    };
+    
+    // Get default timeout from config
+    let timeout = if let Some(Value::Int(t)) = config_map.get("timeout") {
+        Some(*t as u64)
+    } else {
+        Some(DEFAULT_TIMEOUT)
+    };
+    
+    // Combine URL and endpoint
+    let url = if endpoint.starts_with("http") {
+        endpoint.to_string()
+    } else {
+        format!("{}{}", url.trim_end_matches('/'), endpoint)
+    };
+    
+    // Create headers with authentication
+    let mut headers = HashMap::new();
+    match auth_type.to_lowercase().as_str() {
+        "bearer" => {
+            if !api_key.is_empty() {
+                headers.insert("Authorization".to_string(), Value::String(format!("Bearer {}", api_key)));
+            }
+        },
+        "basic" => {
+            if !api_key.is_empty() {
+                headers.insert("Authorization".to_string(), Value::String(format!("Basic {}", api_key)));
+            }
+        },
+        "apikey" => {
+            if !api_key.is_empty() {
+                headers.insert("X-API-Key".to_string(), Value::String(api_key));
+            }
+        },
+        "none" => {
+            // No authentication headers needed
+        },
+        _ => {
+            return Err(format!("Unsupported auth type: {}", auth_type));
+        }
+    }
+    
+    // Configurations made before sessions existed still work, one request at a time
+    let connection = match config_map.get("session") {
+        Some(session) => {
+            let id = session.as_int()?;
+            let sessions = SESSIONS.lock().unwrap();
+            let session = sessions.get(&id)
+                .ok_or_else(|| format!("API.{}: API session {} is closed", function, id))?;
+            Connection { client: session.client.clone(), cookies: Some(session.cookies.clone()), headers: session.headers.clone(), retry: session.retry }
+        },
+        None => Connection::fresh()?,
+    };
+    
+    Ok(ApiCall { url, headers, timeout, connection })
+}
+
 /// Execute an API call with the given API configuration
 /// Example: execute_api(api_config, "GET", "/endpoint", {"param": "value"}, 30) => response
 /// Arguments:
@@ -794,115 +1022,275 @@ pub fn execute_api(args: Vec<Value>) -> Result<Value, String> {
         return Err("API.execute_api: Expected at least 2 arguments (api_config, method)".to_string());
     }
     
-    let api_config = args[0].clone();
     let method = args[1].as_string()?;
     
-    // Extract API configuration
-    if let Value::Map(config_map) = api_config {
-        let url = if let Some(Value::String(url)) = config_map.get("url") {
-            url.clone()
-        } else {
-            return Err("API configuration missing 'url'".to_string());
-        };
-        
-        let api_key = if let Some(Value::String(key)) = config_map.get("api_key") {
-            key.clone()
-        } else {
-            "".to_string() // Default to empty API key if not provided
-        };
-        
-        let auth_type = if let Some(Value::String(auth)) = config_map.get("auth_type") {
-            auth.clone()
-        } else {
-            "none".to_string() // Default to no auth if not specified
-        };
-        
-        // Get default timeout from config
-        let default_timeout = if let Some(Value::Int(t)) = config_map.get("timeout") {
-            Some(*t as u64)
-        } else {
-            Some(DEFAULT_TIMEOUT)
-        };
-        
-        // Get endpoint if provided
-        let endpoint = if args.len() > 2 {
-            args[2].as_string()?
-        } else {
-            "".to_string()
-        };
-        
-        // Combine URL and endpoint
-        let full_url = if endpoint.starts_with("http") {
-            endpoint
-        } else {
-            format!("{}{}", url.trim_end_matches('/'), endpoint)
-        };
-        
-        // Get params/data if provided
-        let params_or_data = if args.len() > 3 {
-            args[3].clone()
-        } else {
-            Value::Map(HashMap::new())
+    // Get endpoint if provided
+    let endpoint = if args.len() > 2 {
+        args[2].as_string()?
+    } else {
+        "".to_string()
+    };
+    let ApiCall { url: full_url, headers: mut headers_map, timeout: default_timeout, connection } = api_call("execute_api", &args[0], &endpoint)?;
+    
+    // Get params/data if provided
+    let params_or_data = if args.len() > 3 {
+        args[3].clone()
+    } else {
+        Value::Map(HashMap::new())
+    };
+    
+    // Get timeout if provided (overrides the one in config)
+    let timeout = if args.len() > 4 {
+        match &args[4] {
+            Value::Int(t) => Some(*t as u64),
+            Value::Float(t) => Some(*t as u64),
+            _ => default_timeout,
+        }
+    } else {
+        default_timeout
+    };
+    
+    // Add content type for POST/PUT/PATCH
+    if method.to_uppercase() == "POST" || method.to_uppercase() == "PUT" || method.to_uppercase() == "PATCH" {
+        headers_map.insert("Content-Type".to_string(), Value::String("application/json".to_string()));
+    }
+    
+    let headers = Value::Map(headers_map);
+    let no_params = Value::Map(HashMap::new());
+    
+    // Make the request based on the method
+    let response = match method.to_uppercase().as_str() {
+        "GET" | "HEAD" | "OPTIONS" => send_request(&connection, &method, &full_url, &params_or_data, &headers, None, timeout)?,
+        "POST" | "PUT" | "PATCH" => send_request(&connection, &method, &full_url, &no_params, &headers, Some(&params_or_data), timeout)?,
+        "DELETE" => send_request(&connection, &method, &full_url, &no_params, &headers, None, timeout)?,
+        _ => return Err(format!("Unsupported method: {}", method)),
+    };
+    process_response(response)
+}
+
+/// Upload files as a multipart/form-data POST. File paths are relative to the script
+/// Example: upload(api_config, "/photos", {"photo": "cat.jpg"}, {"title": "My cat"}) => response
+/// Arguments:
+///   - api_config: API configuration created with create_api
+///   - endpoint: API endpoint to append to the base URL
+///   - files: Map of form field names to file paths
+///   - fields: (Optional) Map of other form fields
+///   - timeout: (Optional) Timeout in seconds (overrides the one in api_config)
+pub fn upload(context: &CallContext, args: Vec<Value>) -> Result<Value, String> {
+    let endpoint = args[1].as_string()?;
+    let call = api_call("upload", &args[0], &endpoint)?;
+    
+    let mut files = Vec::new();
+    match &args[2] {
+        Value::Map(map) => {
+            for (field, path) in map {
+                let path = script_path(context, &path.as_string()?);
+                if !path.is_file() {
+                    return Err(format!("API.upload: Cannot read {}: no such file", path.display()));
+                }
+                files.push((field.clone(), path));
+            }
+        },
+        _ => return Err("API.upload: files must be a map of field names to paths".to_string()),
+    }
+    let fields = match args.get(3) {
+        Some(Value::Map(map)) => map.iter().map(|(k, v)| (k.clone(), header_text(v))).collect(),
+        Some(Value::Null) | None => Vec::new(),
+        Some(_) => return Err("API.upload: fields must be a map".to_string()),
+    };
+    let timeout = match args.get(4) {
+        Some(Value::Null) | None => call.timeout,
+        Some(t) => Some(t.as_float()? as u64),
+    };
+    let headers = Value::Map(call.headers);
+    
+    // A form can only be sent once, so every attempt reads the files again
+    let response = send_with_retries(&call.connection, timeout, || {
+        let mut form = multipart::Form::new();
+        for (name, value) in &fields {
+            form = form.text(name.clone(), value.clone());
+        }
+        for (name, path) in &files {
+            form = form.file(name.clone(), path)
+                .map_err(|e| format!("API.upload: Cannot read {}: {}", path.display(), e))?;
+        }
+        Ok(new_request(&call.connection, "POST", &call.url, &headers, timeout)?.multipart(form))
+    })?;
+    process_response(response)
+}
+
+/// Download a response body straight to a file (relative to the script) without holding it in memory.
+/// The progress function is called with the bytes received so far and the total size (0 when the server does not say).
+/// Downloads have no time limit unless a timeout is given
+/// Example: download(api_config, "/files/report.pdf", "report.pdf") => {"path": "report.pdf", "bytes": 52340, "status": 200}
+/// Example: download(api_config, "/files/big.zip", "big.zip", "show_progress") => {"path": "big.zip", "bytes": 1048576, "status": 200}
+/// Arguments:
+///   - api_config: API configuration created with create_api
+///   - endpoint: API endpoint to append to the base URL
+///   - path: File to write
+///   - progress: (Optional) Name of a function to call as chunks arrive
+///   - timeout: (Optional) Timeout in seconds for the whole download
+pub fn download(context: &CallContext, args: Vec<Value>) -> Result<Value, String> {
+    let endpoint = args[1].as_string()?;
+    let call = api_call("download", &args[0], &endpoint)?;
+    let display_path = args[2].as_string()?;
+    let path = script_path(context, &display_path);
+    let progress = match args.get(3) {
+        Some(Value::Null) | None => None,
+        Some(name) => {
+            let runner = context.script_runner()
+                .ok_or_else(|| "API.download: progress callbacks can only be used from a Razen script".to_string())?;
+            Some((name.as_string()?, runner))
+        },
+    };
+    let timeout = match args.get(4) {
+        Some(Value::Null) | None => None,
+        Some(t) => Some(t.as_float()? as u64),
+    };
+    
+    let headers = Value::Map(call.headers);
+    let mut response = send_with_retries(&call.connection, timeout, || {
+        new_request(&call.connection, "GET", &call.url, &headers, timeout)
+    })?;
+    let status = response.status.as_u16();
+    if !response.status.is_success() {
+        return Err(format!("API.download: {} answered with status {}", call.url, status));
+    }
+    let total = response.content_length().unwrap_or(0);
+    
+    // Write next to the target and move it into place once complete, so a failed
+    // download never leaves a truncated file behind under the real name
+    let mut partial = path.clone().into_os_string();
+    partial.push(".part");
+    let partial = PathBuf::from(partial);
+    let mut file = File::create(&partial)
+        .map_err(|e| format!("API.download: Cannot write {}: {}", partial.display(), e))?;
+    let mut received: u64 = 0;
+    let mut buffer = vec![0u8; DOWNLOAD_CHUNK];
+    let result = loop {
+        let count = match response.read(&mut buffer) {
+            Ok(0) => break Ok(()),
+            Ok(count) => count,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => break Err(format!("API.download: Failed to read response body: {}", e)),
         };
-        
-        // Get timeout if provided (overrides the one in config)
-        let timeout = if args.len() > 4 {
-            match &args[4] {
-                Value::Int(t) => Some(*t as u64),
-                Value::Float(t) => Some(*t as u64),
-                _ => default_timeout,
+        if let Err(e) = file.write_all(&buffer[..count]) {
+            break Err(format!("API.download: Cannot write {}: {}", partial.display(), e));
+        }
+        received += count as u64;
+        if let Some((name, runner)) = &progress {
+            if let Err(e) = runner(name, vec![received.to_string(), total.to_string()]) {
+                break Err(e);
             }
+        }
+    };
+    let result = result.and_then(|_| file.flush().map_err(|e| format!("API.download: Cannot write {}: {}", partial.display(), e)));
+    drop(file);
+    if let Err(e) = result.and_then(|_| fs::rename(&partial, &path).map_err(|e| format!("API.download: Cannot write {}: {}", path.display(), e))) {
+        let _ = fs::remove_file(&partial);
+        return Err(e);
+    }
+    
+    let mut result_map = HashMap::new();
+    result_map.insert("path".to_string(), Value::String(display_path));
+    result_map.insert("bytes".to_string(), Value::Int(received as i64));
+    result_map.insert("status".to_string(), Value::Int(status as i64));
+    Ok(Value::Map(result_map))
+}
+
+fn script_path(context: &CallContext, path: &str) -> PathBuf {
+    let path = PathBuf::from(path);
+    match context.script().and_then(Path::parent) {
+        Some(script_dir) if path.is_relative() => script_dir.join(path),
+        _ => path,
+    }
+}
+
+// Run `f` on the session an API configuration refers to
+fn with_session<T>(function: &str, api_config: &Value, f: impl FnOnce(&mut Session, Url) -> Result<T, String>) -> Result<T, String> {
+    let (url, id) = match api_config {
+        Value::Map(config_map) => match (config_map.get("url"), config_map.get("session")) {
+            (Some(url), Some(id)) => (url.as_string()?, id.as_int()?),
+            _ => return Err(format!("API.{}: Expected an API configuration from create_api", function)),
+        },
+        _ => return Err("API configuration must be a map".to_string()),
+    };
+    let url = Url::parse(&url).map_err(|e| format!("API.{}: Invalid URL {}: {}", function, url, e))?;
+    let mut sessions = SESSIONS.lock().unwrap();
+    let session = sessions.get_mut(&id)
+        .ok_or_else(|| format!("API.{}: API session {} is closed", function, id))?;
+    f(session, url)
+}
+
+/// Set a header sent with every request of an API session; a null value removes it
+/// Example: set_header(api_config, "Accept-Language", "en") => true
+pub fn set_header(args: Vec<Value>) -> Result<Value, String> {
+    let name = args[1].as_string()?;
+    if HeaderName::from_str(&name).is_err() {
+        return Err(format!("Invalid header name: {}", name));
+    }
+    with_session("set_header", &args[0], |session, _| {
+        // Header names are case-insensitive
+        session.headers.retain(|key, _| !key.eq_ignore_ascii_case(&name));
+        match args.get(2) {
+            Some(Value::Null) | None => {},
+            Some(value) => { session.headers.insert(name, value.clone()); },
+        }
+        Ok(Value::Bool(true))
+    })
+}
+
+/// Get the cookies an API session would send to its base URL, or to an endpoint under it
+/// Example: cookies(api_config) => {"session_id": "abc123"}
+/// Example: cookies(api_config, "/account") => {"session_id": "abc123", "theme": "dark"}
+pub fn cookies(args: Vec<Value>) -> Result<Value, String> {
+    let endpoint = match args.get(1) {
+        Some(Value::Null) | None => String::new(),
+        Some(endpoint) => endpoint.as_string()?,
+    };
+    with_session("cookies", &args[0], |session, base| {
+        let url = if endpoint.starts_with("http") {
+            Url::parse(&endpoint).map_err(|e| format!("API.cookies: Invalid URL {}: {}", endpoint, e))?
         } else {
-            default_timeout
+            base.join(&endpoint).map_err(|e| format!("API.cookies: Invalid endpoint {}: {}", endpoint, e))?
         };
-        
-        // Create headers with authentication
-        let mut headers_map = HashMap::new();
-        match auth_type.to_lowercase().as_str() {
-            "bearer" => {
-                if !api_key.is_empty() {
-                    headers_map.insert("Authorization".to_string(), Value::String(format!("Bearer {}", api_key)));
-                }
-            },
-            "basic" => {
-                if !api_key.is_empty() {
-                    headers_map.insert("Authorization".to_string(), Value::String(format!("Basic {}", api_key)));
+        let mut cookie_map = HashMap::new();
+        if let Some(header) = session.cookies.cookies(&url) {
+            for pair in header.to_str().unwrap_or_default().split("; ") {
+                if let Some((name, value)) = pair.split_once('=') {
+                    cookie_map.insert(name.to_string(), Value::String(value.to_string()));
                 }
-            },
-            "apikey" => {
-                if !api_key.is_empty() {
-                    headers_map.insert("X-API-Key".to_string(), Value::String(api_key));
-                }
-            },
-            "none" => {
-                // No authentication headers needed
-            },
-            _ => {
-                return Err(format!("Unsupported auth type: {}", auth_type));
             }
         }
-        
-        // Add content type for POST/PUT/PATCH
-        if method.to_uppercase() == "POST" || method.to_uppercase() == "PUT" || method.to_uppercase() == "PATCH" {
-            headers_map.insert("Content-Type".to_string(), Value::String("application/json".to_string()));
-        }
-        
-        let headers = Value::Map(headers_map);
-        
-        // Make the request based on the method
-        match method.to_uppercase().as_str() {
-            "GET" => make_request("GET", &full_url, params_or_data, headers, None, timeout),
-            "POST" => make_request("POST", &full_url, Value::Map(HashMap::new()), headers, Some(params_or_data), timeout),
-            "PUT" => make_request("PUT", &full_url, Value::Map(HashMap::new()), headers, Some(params_or_data), timeout),
-            "DELETE" => make_request("DELETE", &full_url, Value::Map(HashMap::new()), headers, None, timeout),
-            "PATCH" => make_request("PATCH", &full_url, Value::Map(HashMap::new()), headers, Some(params_or_data), timeout),
-            "HEAD" => make_request("HEAD", &full_url, params_or_data, headers, None, timeout),
-            "OPTIONS" => make_request("OPTIONS", &full_url, params_or_data, headers, None, timeout),
-            _ => Err(format!("Unsupported method: {}", method)),
-        }
-    } else {
-        Err("API configuration must be a map".to_string())
+        Ok(Value::Map(cookie_map))
+    })
+}
+
+/// Add a cookie to an API session for its base URL's host
+/// Example: set_cookie(api_config, "theme", "dark") => true
+pub fn set_cookie(args: Vec<Value>) -> Result<Value, String> {
+    let name = args[1].as_string()?;
+    let value = args[2].as_string()?;
+    if name.is_empty() || name.contains(['=', ';']) || value.contains(';') {
+        return Err(format!("API.set_cookie: Invalid cookie {}={}", name, value));
     }
+    with_session("set_cookie", &args[0], |session, url| {
+        session.cookies.add_cookie_str(&format!("{}={}; Path=/", name, value), &url);
+        Ok(Value::Bool(true))
+    })
+}
+
+/// Close an API session, releasing its connections and cookies
+/// Example: close_api(api_config) => true
+pub fn close_api(args: Vec<Value>) -> Result<Value, String> {
+    let id = match &args[0] {
+        Value::Map(config_map) => match config_map.get("session") {
+            Some(id) => id.as_int()?,
+            None => return Ok(Value::Bool(false)),
+        },
+        _ => return Err("API configuration must be a map".to_string()),
+    };
+    Ok(Value::Bool(SESSIONS.lock().unwrap().remove(&id).is_some()))
 }
 
 /// URL encode a string
@@ -1138,3 +1526,205 @@ pub fn is_server_error(args: Vec<Value>) -> Result<Value, String> {
     
     Ok(Value::Bool(status_code >= 500 && status_code < 600))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread::JoinHandle;
+
+    // Answer one canned response per connection and hand back the raw requests
+    fn serve(responses: Vec<&'static str>) -> (String, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = Vec::new();
+                let mut buffer = [0u8; 4096];
+                loop {
+                    let count = stream.read(&mut buffer).unwrap();
+                    request.extend_from_slice(&buffer[..count]);
+                    let text = String::from_utf8_lossy(&request).to_lowercase();
+                    if let Some(end) = text.find("\r\n\r\n") {
+                        let length = text.lines()
+                            .find_map(|line| line.strip_prefix("content-length: "))
+                            .and_then(|length| length.trim().parse::<usize>().ok());
+                        let complete = match length {
+                            Some(length) => request.len() >= end + 4 + length,
+                            None => !text.contains("transfer-encoding: chunked") || text.ends_with("0\r\n\r\n"),
+                        };
+                        if complete || count == 0 {
+                            break;
+                        }
+                    }
+                }
+                requests.push(String::from_utf8_lossy(&request).into_owned());
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+            requests
+        });
+        (url, server)
+    }
+
+    #[test]
+    fn test_session_retries_and_keeps_cookies() {
+        let (url, server) = serve(vec![
+            "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nSet-Cookie: token=abc; Path=/\r\nContent-Type: application/json\r\nContent-Length: 11\r\nConnection: close\r\n\r\n{\"ok\":true}",
+            "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nhi",
+            "HTTP/1.1 500 Internal Server Error\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            "HTTP/1.1 500 Internal Server Error\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        ]);
+        let options = Value::Map(HashMap::from([
+            ("retries".to_string(), Value::Int(1)),
+            ("backoff".to_string(), Value::Float(0.01)),
+            ("headers".to_string(), Value::Map(HashMap::from([("X-Client".to_string(), Value::String("razen".to_string()))]))),
+        ]));
+        let api = create_api(vec![Value::String(url), Value::String("key".to_string()), Value::String("bearer".to_string()), Value::Int(5), options]).unwrap();
+
+        let response = execute_api(vec![api.clone(), Value::String("GET".to_string()), Value::String("/login".to_string())]).unwrap();
+        let Value::Map(response) = response else { panic!("expected a map") };
+        assert_eq!(response["status"], Value::Int(200));
+        assert_eq!(response["ok"], Value::Bool(true));
+        let Value::Map(cookie_map) = cookies(vec![api.clone()]).unwrap() else { panic!("expected a map") };
+        assert_eq!(cookie_map["token"], Value::String("abc".to_string()));
+
+        set_header(vec![api.clone(), Value::String("x-client".to_string()), Value::Null]).unwrap();
+        execute_api(vec![api.clone(), Value::String("GET".to_string()), Value::String("/me".to_string())]).unwrap();
+        // Retries run out and the last 5xx response is returned
+        let failed = execute_api(vec![api.clone(), Value::String("GET".to_string()), Value::String("/broken".to_string())]).unwrap();
+        let Value::Map(failed) = failed else { panic!("expected a map") };
+        assert_eq!(failed["status"], Value::Int(500));
+
+        assert_eq!(close_api(vec![api.clone()]).unwrap(), Value::Bool(true));
+        let closed = execute_api(vec![api, Value::String("GET".to_string())]).unwrap_err();
+        assert!(closed.contains("is closed"), "{}", closed);
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests.len(), 5);
+        assert!(requests[0].starts_with("GET /login ") && requests[1].starts_with("GET /login "));
+        assert!(requests[1].to_lowercase().contains("\r\nx-client: razen\r\n"));
+        assert!(requests[1].to_lowercase().contains("\r\nauthorization: bearer key\r\n"));
+        assert!(requests[2].to_lowercase().contains("\r\ncookie: token=abc\r\n"), "{}", requests[2]);
+        assert!(!requests[2].to_lowercase().contains("x-client"));
+    }
+
+    #[test]
+    fn test_upload_and_download_from_a_script() {
+        let dir = std::env::temp_dir().join(format!("razen_api_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("notes.txt"), "uploaded notes").unwrap();
+        let body = "x".repeat(DOWNLOAD_CHUNK + 10);
+        let download_response: &'static str = Box::leak(format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body).into_boxed_str());
+        let (url, server) = serve(vec![
+            "HTTP/1.1 201 Created\r\nContent-Type: application/json\r\nContent-Length: 15\r\nConnection: close\r\n\r\n{\"stored\":true}",
+            download_response,
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        ]);
+        let progress = dir.join("progress.txt");
+        let script = dir.join("client.rzn");
+        let run = crate::functions::run_script_in_background(&script, &format!(r#"lib apilib;
+lib file;
+
+fun on_progress(received, total) {{
+    File[append]("{progress}", received + "/" + total + ";");
+}}
+
+var client = APILib[create_api]("{url}");
+var uploaded = APILib[upload](client, "/files", {{"file": "notes.txt"}}, {{"title": "Notes"}});
+show uploaded.status;
+var saved = APILib[download](client, "/data.bin", "data.bin", "on_progress");
+show saved.bytes;
+show APILib[close_api](client);
+"#, progress = progress.display()));
+        let output = run.join().unwrap();
+        assert_eq!(output.replace('\r', ""), format!("201\n{}\ntrue\n", body.len()));
+        assert_eq!(fs::read_to_string(dir.join("data.bin")).unwrap(), body);
+        let total = body.len();
+        let calls = fs::read_to_string(&progress).unwrap();
+        assert!(calls.ends_with(&format!("{}/{};", total, total)), "{}", calls);
+
+        // A failed download leaves nothing behind
+        crate::library::initialize();
+        let api = create_api(vec![Value::String(url)]).unwrap();
+        let context = CallContext::new("apilib").with_script(Some(&script));
+        let missing = download(&context, vec![api, Value::String("/missing".to_string()), Value::String("missing.bin".to_string())]).unwrap_err();
+        assert!(missing.contains("status 404"), "{}", missing);
+        assert!(!dir.join("missing.bin").exists() && !dir.join("missing.bin.part").exists());
+
+        let requests = server.join().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(requests[0].starts_with("POST /files "));
+        assert!(requests[0].contains("multipart/form-data; boundary="));
+        assert!(requests[0].contains("name=\"file\"; filename=\"notes.txt\""));
+        assert!(requests[0].contains("uploaded notes") && requests[0].contains("name=\"title\"\r\n\r\nNotes"));
+    }
+
+    #[test]
+    fn test_recorded_exchanges_replay_without_the_network() {
+        let dir = std::env::temp_dir().join(format!("razen_api_fixtures_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let (url, server) = serve(vec![
+            "HTTP/1.1 200 OK\r\nSet-Cookie: seen=1; Path=/\r\nContent-Type: application/json\r\nContent-Length: 12\r\nConnection: close\r\n\r\n{\"page\":\"1\"}",
+            "HTTP/1.1 201 Created\r\nContent-Type: application/json\r\nContent-Length: 11\r\nConnection: close\r\n\r\n{\"id\":\"x\"}\n",
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 12\r\nConnection: close\r\n\r\n{\"page\":\"2\"}",
+            "HTTP/1.1 200 OK\r\nContent-Length: 4\r\nConnection: close\r\n\r\npong",
+        ]);
+        let params = || Value::Map(HashMap::from([
+            ("b".to_string(), Value::Int(2)),
+            ("a".to_string(), Value::Int(1)),
+        ]));
+        let item = || Value::Map(HashMap::from([("n".to_string(), Value::Int(1))]));
+        let page = |response: Value| match response {
+            Value::Map(map) => map["page"].clone(),
+            other => panic!("expected a map, got {}", other),
+        };
+        let run = |api: &Value| {
+            let first = execute_api(vec![api.clone(), Value::String("GET".to_string()), Value::String("/users".to_string()), params()]).unwrap();
+            let created = execute_api(vec![api.clone(), Value::String("POST".to_string()), Value::String("/items".to_string()), item()]).unwrap();
+            let second = execute_api(vec![api.clone(), Value::String("GET".to_string()), Value::String("/users".to_string()), params()]).unwrap();
+            let Value::Map(created) = created else { panic!("expected a map") };
+            assert_eq!(created["status"], Value::Int(201));
+            (page(first), page(second))
+        };
+
+        httpfixtures::with_mode(Some(&dir), None, || {
+            let api = create_api(vec![Value::String(url.clone()), Value::String("secret".to_string()), Value::String("bearer".to_string())]).unwrap();
+            assert_eq!(run(&api), (Value::String("1".to_string()), Value::String("2".to_string())));
+            assert_eq!(super::super::net::get(vec![Value::String(format!("{}/ping", url))]).unwrap(), Value::String("pong".to_string()));
+        });
+        server.join().unwrap();
+        let files: Vec<PathBuf> = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().path()).collect();
+        assert_eq!(files.len(), 4);
+        let texts: Vec<String> = files.iter().map(|file| fs::read_to_string(file).unwrap()).collect();
+        assert!(texts.iter().all(|text| !text.contains("secret")));
+        assert!(texts.iter().any(|text| text.contains("\"authorization\": \"[redacted]\"")));
+        // Require the POST body to match as well
+        let post = files.iter().find(|file| file.file_name().unwrap().to_string_lossy().starts_with("POST-")).unwrap();
+        let mut recording: JsonValue = serde_json::from_str(&fs::read_to_string(post).unwrap()).unwrap();
+        recording["match"] = json!(["body"]);
+        fs::write(post, recording.to_string()).unwrap();
+
+        // The server is gone, so every answer comes from the recordings
+        httpfixtures::with_mode(None, Some(&dir), || {
+            let api = create_api(vec![Value::String(url.clone()), Value::String("other".to_string()), Value::String("bearer".to_string())]).unwrap();
+            assert_eq!(run(&api), (Value::String("1".to_string()), Value::String("2".to_string())));
+            let Value::Map(cookie_map) = cookies(vec![api.clone()]).unwrap() else { panic!("expected a map") };
+            assert_eq!(cookie_map["seen"], Value::String("1".to_string()));
+            // The last recording repeats
+            let again = execute_api(vec![api.clone(), Value::String("GET".to_string()), Value::String("/users".to_string()), params()]).unwrap();
+            assert_eq!(page(again), Value::String("2".to_string()));
+            assert_eq!(super::super::net::get(vec![Value::String(format!("{}/ping", url))]).unwrap(), Value::String("pong".to_string()));
+
+            let other_item = Value::Map(HashMap::from([("n".to_string(), Value::Int(2))]));
+            let changed = execute_api(vec![api.clone(), Value::String("POST".to_string()), Value::String("/items".to_string()), other_item]).unwrap_err();
+            assert!(changed.contains("No recording in") && changed.contains("has a different body"), "{}", changed);
+            let unknown = execute_api(vec![api, Value::String("GET".to_string()), Value::String("/unknown".to_string())]).unwrap_err();
+            assert!(unknown.contains("matches GET http://") && unknown.contains("none for this URL"), "{}", unknown);
+        });
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,566 @@
+use crate::value::Value;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+lazy_static::lazy_static! {
+    // Parsers and subcommands declared by the script, indexed by ID - 1
+    static ref PARSERS: Mutex<Vec<ArgParser>> = Mutex::new(Vec::new());
+}
+
+// Declarative description of a command line
+struct ArgParser {
+    program: String,              // Name shown in usage, including parent commands
+    description: String,
+    flags: Vec<ArgSpec>,
+    options: Vec<ArgSpec>,
+    positionals: Vec<ArgSpec>,
+    commands: Vec<(String, String, usize)>, // name, help, parser ID
+}
+
+struct ArgSpec {
+    name: String,
+    short: Option<char>,
+    help: String,
+    value_type: String,           // string, int, float or bool
+    default: Value,
+    required: bool,
+}
+
+impl ArgParser {
+    fn new(program: String, description: String) -> Self {
+        ArgParser {
+            program,
+            description,
+            flags: Vec::new(),
+            options: Vec::new(),
+            positionals: Vec::new(),
+            commands: Vec::new(),
+        }
+    }
+
+    fn has_name(&self, name: &str) -> bool {
+        self.flags.iter().chain(&self.options).chain(&self.positionals).any(|spec| spec.name == name)
+            || self.commands.iter().any(|(command, _, _)| command == name)
+    }
+
+    fn has_short(&self, short: char) -> bool {
+        self.flags.iter().chain(&self.options).any(|spec| spec.short == Some(short))
+    }
+
+    fn find_long(&self, name: &str) -> Option<(&ArgSpec, bool)> {
+        self.flags.iter().find(|spec| spec.name == name).map(|spec| (spec, true))
+            .or_else(|| self.options.iter().find(|spec| spec.name == name).map(|spec| (spec, false)))
+    }
+
+    fn find_short(&self, short: char) -> Option<(&ArgSpec, bool)> {
+        self.flags.iter().find(|spec| spec.short == Some(short)).map(|spec| (spec, true))
+            .or_else(|| self.options.iter().find(|spec| spec.short == Some(short)).map(|spec| (spec, false)))
+    }
+
+    fn usage(&self) -> String {
+        let mut usage = format!("Usage: {}", self.program);
+        if !self.flags.is_empty() || !self.options.is_empty() {
+            usage.push_str(" [options]");
+        }
+        for spec in &self.positionals {
+            if spec.required {
+                usage.push_str(&format!(" <{}>", spec.name));
+            } else {
+                usage.push_str(&format!(" [{}]", spec.name));
+            }
+        }
+        if !self.commands.is_empty() {
+            usage.push_str(" <command>");
+        }
+        usage.push('\n');
+
+        if !self.description.is_empty() {
+            usage.push_str(&format!("\n{}\n", self.description));
+        }
+
+        if !self.commands.is_empty() {
+            let rows: Vec<(String, String)> = self.commands.iter()
+                .map(|(name, help, _)| (name.clone(), help.clone()))
+                .collect();
+            usage.push_str(&help_section("Commands", &rows));
+        }
+
+        if !self.positionals.is_empty() {
+            let rows: Vec<(String, String)> = self.positionals.iter()
+                .map(|spec| (format!("<{}>", spec.name), with_default(spec)))
+                .collect();
+            usage.push_str(&help_section("Arguments", &rows));
+        }
+
+        let mut rows: Vec<(String, String)> = Vec::new();
+        for spec in &self.flags {
+            rows.push((switch_names(spec), spec.help.clone()));
+        }
+        for spec in &self.options {
+            rows.push((format!("{} <{}>", switch_names(spec), spec.value_type), with_default(spec)));
+        }
+        if !self.has_name("help") {
+            let short = if self.has_short('h') { "    --help" } else { "-h, --help" };
+            rows.push((short.to_string(), "Show this help".to_string()));
+        }
+        usage.push_str(&help_section("Options", &rows));
+        usage
+    }
+}
+
+fn switch_names(spec: &ArgSpec) -> String {
+    match spec.short {
+        Some(short) => format!("-{}, --{}", short, spec.name),
+        None => format!("    --{}", spec.name),
+    }
+}
+
+fn with_default(spec: &ArgSpec) -> String {
+    let mut help = spec.help.clone();
+    if spec.required {
+        help.push_str(" (required)");
+    } else if spec.default != Value::Null {
+        help.push_str(&format!(" (default: {})", spec.default));
+    }
+    help.trim().to_string()
+}
+
+fn help_section(title: &str, rows: &[(String, String)]) -> String {
+    let width = rows.iter().map(|(left, _)| left.len()).max().unwrap_or(0);
+    let mut section = format!("\n{}:\n", title);
+    for (left, right) in rows {
+        section.push_str(format!("  {:width$}  {}", left, right, width = width).trim_end());
+        section.push('\n');
+    }
+    section
+}
+
+// Convert a command line string to the declared type
+fn convert(value_type: &str, name: &str, text: &str) -> Result<Value, String> {
+    match value_type {
+        "string" => Ok(Value::String(text.to_string())),
+        "int" => text.parse::<i64>()
+            .map(Value::Int)
+            .map_err(|_| format!("Invalid value '{}' for {}: expected an integer", text, name)),
+        "float" => text.parse::<f64>()
+            .map(Value::Float)
+            .map_err(|_| format!("Invalid value '{}' for {}: expected a number", text, name)),
+        "bool" => Value::String(text.to_string()).as_bool()
+            .map(Value::Bool)
+            .map_err(|_| format!("Invalid value '{}' for {}: expected true or false", text, name)),
+        _ => unreachable!("argument types are checked when declared"),
+    }
+}
+
+// Optional text argument; empty strings and null count as missing
+fn optional_text(args: &[Value], index: usize) -> Result<String, String> {
+    match args.get(index) {
+        None | Some(Value::Null) => Ok(String::new()),
+        Some(value) => value.as_string(),
+    }
+}
+
+fn short_name(args: &[Value], index: usize) -> Result<Option<char>, String> {
+    let short = optional_text(args, index)?;
+    let mut chars = short.trim_start_matches('-').chars();
+    match (chars.next(), chars.next()) {
+        (None, _) => Ok(None),
+        (Some(c), None) if c.is_ascii_alphanumeric() => Ok(Some(c)),
+        _ => Err(format!("Short name '{}' must be a single letter or digit", short)),
+    }
+}
+
+fn long_name(args: &[Value], index: usize) -> Result<String, String> {
+    let name = optional_text(args, index)?;
+    let name = name.trim_start_matches('-').to_string();
+    if name.is_empty() || name.contains(char::is_whitespace) || name.contains('=') {
+        return Err(format!("Invalid argument name '{}'", name));
+    }
+    Ok(name)
+}
+
+fn parser_id(value: &Value) -> Result<usize, String> {
+    let id = value.as_int()?;
+    let parsers = PARSERS.lock().unwrap();
+    if id < 1 || id as usize > parsers.len() {
+        return Err(format!("Invalid parser ID: {}", id));
+    }
+    Ok(id as usize)
+}
+
+// Register a new argument on a parser after checking its names are unused
+fn add_spec(id: usize, spec: ArgSpec, kind: &str) -> Result<Value, String> {
+    let mut parsers = PARSERS.lock().unwrap();
+    let parser = &mut parsers[id - 1];
+    if parser.has_name(&spec.name) {
+        return Err(format!("Argument '{}' is already defined", spec.name));
+    }
+    if let Some(short) = spec.short {
+        if parser.has_short(short) {
+            return Err(format!("Short name '-{}' is already defined", short));
+        }
+    }
+    match kind {
+        "flag" => parser.flags.push(spec),
+        "option" => parser.options.push(spec),
+        _ => {
+            if !parser.commands.is_empty() {
+                return Err("A parser cannot have both positional arguments and commands".to_string());
+            }
+            if spec.required && parser.positionals.iter().any(|p| !p.required) {
+                return Err(format!("Required argument '{}' cannot follow an optional one", spec.name));
+            }
+            parser.positionals.push(spec);
+        }
+    }
+    Ok(Value::Bool(true))
+}
+
+/// Create a parser for the script's command line
+/// Example: parser("deploy", "Deploy the site") => 1
+pub fn parser(args: Vec<Value>) -> Result<Value, String> {
+    if args.is_empty() || args.len() > 2 {
+        return Err("ArgsLib.parser requires 1 or 2 arguments: program_name, [description]".to_string());
+    }
+
+    let program = args[0].as_string()?;
+    let description = optional_text(&args, 1)?;
+
+    let mut parsers = PARSERS.lock().unwrap();
+    parsers.push(ArgParser::new(program, description));
+
+    Ok(Value::Int(parsers.len() as i64))
+}
+
+/// Declare a boolean flag such as `-v` / `--verbose`, false unless given
+/// Example: flag(1, "verbose", "v", "Print every step") => true
+pub fn flag(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() < 2 || args.len() > 4 {
+        return Err("ArgsLib.flag requires 2 to 4 arguments: parser_id, name, [short], [help]".to_string());
+    }
+
+    let spec = ArgSpec {
+        name: long_name(&args, 1)?,
+        short: short_name(&args, 2)?,
+        help: optional_text(&args, 3)?,
+        value_type: "bool".to_string(),
+        default: Value::Bool(false),
+        required: false,
+    };
+
+    add_spec(parser_id(&args[0])?, spec, "flag")
+}
+
+/// Declare an option that takes a value (`--out file`, `--out=file`, `-o file`).
+/// The type is one of string, int, float or bool. Without a default the option is null when omitted.
+/// Example: option(1, "port", "p", "Port to listen on", "int", 8080) => true
+pub fn option(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() < 2 || args.len() > 6 {
+        return Err("ArgsLib.option requires 2 to 6 arguments: parser_id, name, [short], [help], [type], [default]".to_string());
+    }
+
+    let name = long_name(&args, 1)?;
+    let value_type = typed(&args, 4)?;
+    let default = default_value(&args, 5, &value_type, &name)?;
+    let spec = ArgSpec {
+        short: short_name(&args, 2)?,
+        help: optional_text(&args, 3)?,
+        name,
+        value_type,
+        default,
+        required: false,
+    };
+
+    add_spec(parser_id(&args[0])?, spec, "option")
+}
+
+/// Declare a positional argument. It is required unless a default is given.
+/// Example: positional(1, "target", "Environment to deploy to") => true
+pub fn positional(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() < 2 || args.len() > 5 {
+        return Err("ArgsLib.positional requires 2 to 5 arguments: parser_id, name, [help], [type], [default]".to_string());
+    }
+
+    let name = long_name(&args, 1)?;
+    let value_type = typed(&args, 3)?;
+    let default = default_value(&args, 4, &value_type, &name)?;
+    let spec = ArgSpec {
+        help: optional_text(&args, 2)?,
+        required: args.len() < 5,
+        name,
+        short: None,
+        value_type,
+        default,
+    };
+
+    add_spec(parser_id(&args[0])?, spec, "positional")
+}
+
+/// Make a previously declared option required
+/// Example: require(1, "token") => true
+pub fn require(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() != 2 {
+        return Err("ArgsLib.require requires exactly 2 arguments: parser_id, name".to_string());
+    }
+
+    let id = parser_id(&args[0])?;
+    let name = long_name(&args, 1)?;
+    let mut parsers = PARSERS.lock().unwrap();
+    let spec = parsers[id - 1].options.iter_mut()
+        .find(|spec| spec.name == name)
+        .ok_or_else(|| format!("No option named '{}' to require", name))?;
+    spec.required = true;
+
+    Ok(Value::Bool(true))
+}
+
+/// Declare a subcommand and return the parser for its own arguments.
+/// Parsing sets `command` to the chosen name and merges its values into the result.
+/// Example: command(1, "build", "Build the project") => 2
+pub fn command(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() < 2 || args.len() > 3 {
+        return Err("ArgsLib.command requires 2 or 3 arguments: parser_id, name, [help]".to_string());
+    }
+
+    let id = parser_id(&args[0])?;
+    let name = long_name(&args, 1)?;
+    let help = optional_text(&args, 2)?;
+
+    let mut parsers = PARSERS.lock().unwrap();
+    if parsers[id - 1].has_name(&name) {
+        return Err(format!("Argument '{}' is already defined", name));
+    }
+    if !parsers[id - 1].positionals.is_empty() {
+        return Err("A parser cannot have both positional arguments and commands".to_string());
+    }
+    let program = format!("{} {}", parsers[id - 1].program, name);
+    parsers.push(ArgParser::new(program, help.clone()));
+    let command_id = parsers.len();
+    parsers[id - 1].commands.push((name, help, command_id));
+
+    Ok(Value::Int(command_id as i64))
+}
+
+/// Get the generated usage and help text
+/// Example: usage(1) => "Usage: deploy [options] <target>..."
+pub fn usage(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() != 1 {
+        return Err("ArgsLib.usage requires exactly 1 argument: parser_id".to_string());
+    }
+
+    let id = parser_id(&args[0])?;
+    let parsers = PARSERS.lock().unwrap();
+
+    Ok(Value::String(parsers[id - 1].usage()))
+}
+
+/// Parse the script arguments, or the given array, into a map of values.
+/// `--help` prints the usage text and exits; invalid input raises an error.
+/// Example: parse(1) => {verbose: true, port: 8080, target: "staging"}
+pub fn parse(args: Vec<Value>) -> Result<Value, String> {
+    if args.is_empty() || args.len() > 2 {
+        return Err("ArgsLib.parse requires 1 or 2 arguments: parser_id, [args]".to_string());
+    }
+
+    let id = parser_id(&args[0])?;
+    let argv = match args.get(1) {
+        Some(list) => list.as_array()?,
+        None => super::syscall::args(Vec::new())?.as_array()?,
+    };
+    let argv: Vec<String> = argv.iter().map(|arg| arg.to_string()).collect();
+
+    let parsers = PARSERS.lock().unwrap();
+    let mut result = HashMap::new();
+    match parse_into(&parsers, id, &argv, &mut result)? {
+        Some(help) => {
+            print!("{}", help);
+            std::process::exit(0);
+        }
+        None => Ok(Value::Map(result)),
+    }
+}
+
+// Parse `argv` with parser `id`, returning the help text if it was asked for
+fn parse_into(parsers: &[ArgParser], id: usize, argv: &[String], result: &mut HashMap<String, Value>) -> Result<Option<String>, String> {
+    let parser = &parsers[id - 1];
+    let mut positionals: Vec<&String> = Vec::new();
+    let mut seen: Vec<&str> = Vec::new();
+    let mut only_positionals = false;
+    let mut i = 0;
+
+    for spec in parser.flags.iter().chain(&parser.options) {
+        result.insert(spec.name.clone(), spec.default.clone());
+    }
+
+    while i < argv.len() {
+        let arg = &argv[i];
+        i += 1;
+
+        if only_positionals || arg == "-" || !arg.starts_with('-') {
+            if positionals.is_empty() && !parser.commands.is_empty() {
+                let (name, _, command_id) = parser.commands.iter()
+                    .find(|(name, _, _)| name == arg)
+                    .ok_or_else(|| error(parser, &format!("Unknown command '{}'", arg)))?;
+                check_required(parser, &seen)?;
+                result.insert("command".to_string(), Value::String(name.clone()));
+                return parse_into(parsers, *command_id, &argv[i..], result);
+            }
+            positionals.push(arg);
+            continue;
+        }
+
+        if arg == "--" {
+            only_positionals = true;
+            continue;
+        }
+
+        if let Some(long) = arg.strip_prefix("--") {
+            let (name, inline) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (long, None),
+            };
+            if name == "help" && parser.find_long("help").is_none() {
+                return Ok(Some(parser.usage()));
+            }
+            let (spec, is_flag) = parser.find_long(name)
+                .ok_or_else(|| error(parser, &format!("Unknown option '--{}'", name)))?;
+            let value = if is_flag {
+                match inline {
+                    Some(text) => convert("bool", &format!("--{}", name), &text)?,
+                    None => Value::Bool(true),
+                }
+            } else {
+                let text = match inline {
+                    Some(text) => text,
+                    None => take_value(argv, &mut i, &format!("--{}", name), parser)?,
+                };
+                convert(&spec.value_type, &format!("--{}", name), &text)?
+            };
+            seen.push(&spec.name);
+            result.insert(spec.name.clone(), value);
+            continue;
+        }
+
+        // One or more short switches: -v, -vq, -o file, -ofile
+        let shorts: Vec<char> = arg[1..].chars().collect();
+        for (pos, short) in shorts.iter().enumerate() {
+            if *short == 'h' && !parser.has_short('h') {
+                return Ok(Some(parser.usage()));
+            }
+            let (spec, is_flag) = parser.find_short(*short)
+                .ok_or_else(|| error(parser, &format!("Unknown option '-{}'", short)))?;
+            seen.push(&spec.name);
+            if is_flag {
+                result.insert(spec.name.clone(), Value::Bool(true));
+                continue;
+            }
+            let rest: String = shorts[pos + 1..].iter().collect();
+            let text = if rest.is_empty() {
+                take_value(argv, &mut i, &format!("-{}", short), parser)?
+            } else {
+                rest
+            };
+            result.insert(spec.name.clone(), convert(&spec.value_type, &format!("-{}", short), &text)?);
+            break;
+        }
+    }
+
+    if !parser.commands.is_empty() {
+        return Err(error(parser, "Missing command"));
+    }
+    check_required(parser, &seen)?;
+
+    if positionals.len() > parser.positionals.len() {
+        return Err(error(parser, &format!("Unexpected argument '{}'", positionals[parser.positionals.len()])));
+    }
+    for (index, spec) in parser.positionals.iter().enumerate() {
+        let value = match positionals.get(index) {
+            Some(text) => convert(&spec.value_type, &spec.name, text)?,
+            None if spec.required => return Err(error(parser, &format!("Missing required argument <{}>", spec.name))),
+            None => spec.default.clone(),
+        };
+        result.insert(spec.name.clone(), value);
+    }
+
+    Ok(None)
+}
+
+fn take_value(argv: &[String], i: &mut usize, name: &str, parser: &ArgParser) -> Result<String, String> {
+    match argv.get(*i) {
+        Some(value) => {
+            *i += 1;
+            Ok(value.clone())
+        }
+        None => Err(error(parser, &format!("Option '{}' requires a value", name))),
+    }
+}
+
+fn check_required(parser: &ArgParser, seen: &[&str]) -> Result<(), String> {
+    match parser.options.iter().find(|spec| spec.required && !seen.contains(&spec.name.as_str())) {
+        Some(spec) => Err(error(parser, &format!("Missing required option '--{}'", spec.name))),
+        None => Ok(()),
+    }
+}
+
+fn error(parser: &ArgParser, message: &str) -> String {
+    format!("{} (run '{} --help' for usage)", message, parser.program)
+}
+
+fn typed(args: &[Value], index: usize) -> Result<String, String> {
+    let value_type = optional_text(args, index)?.to_lowercase();
+    match value_type.as_str() {
+        "" => Ok("string".to_string()),
+        "string" | "int" | "float" | "bool" => Ok(value_type),
+        _ => Err(format!("Unknown argument type '{}' (expected string, int, float or bool)", value_type)),
+    }
+}
+
+fn default_value(args: &[Value], index: usize, value_type: &str, name: &str) -> Result<Value, String> {
+    match args.get(index) {
+        None | Some(Value::Null) => Ok(Value::Null),
+        Some(value) => convert(value_type, &format!("the default of '{}'", name), &value.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Value {
+        Value::Array(args.iter().map(|arg| Value::String(arg.to_string())).collect())
+    }
+
+    #[test]
+    fn test_parse_flags_options_and_positionals() {
+        let id = parser(vec![Value::String("tool".to_string())]).unwrap();
+        flag(vec![id.clone(), Value::String("verbose".to_string()), Value::String("v".to_string())]).unwrap();
+        option(vec![id.clone(), Value::String("port".to_string()), Value::String("p".to_string()),
+                    Value::Null, Value::String("int".to_string()), Value::Int(80)]).unwrap();
+        positional(vec![id.clone(), Value::String("target".to_string())]).unwrap();
+
+        let parsed = parse(vec![id.clone(), strings(&["-vp", "8080", "prod"])]).unwrap().as_map().unwrap();
+        assert_eq!(parsed["verbose"], Value::Bool(true));
+        assert_eq!(parsed["port"], Value::Int(8080));
+        assert_eq!(parsed["target"], Value::String("prod".to_string()));
+
+        let parsed = parse(vec![id.clone(), strings(&["prod"])]).unwrap().as_map().unwrap();
+        assert_eq!(parsed["verbose"], Value::Bool(false));
+        assert_eq!(parsed["port"], Value::Int(80));
+
+        assert!(parse(vec![id.clone(), strings(&["--port=abc", "prod"])]).unwrap_err().contains("expected an integer"));
+        assert!(parse(vec![id.clone(), strings(&[])]).unwrap_err().contains("Missing required argument <target>"));
+        assert!(parse(vec![id, strings(&["--nope", "prod"])]).unwrap_err().contains("Unknown option '--nope'"));
+    }
+
+    #[test]
+    fn test_parse_subcommands() {
+        let id = parser(vec![Value::String("git".to_string())]).unwrap();
+        let commit = command(vec![id.clone(), Value::String("commit".to_string())]).unwrap();
+        option(vec![commit, Value::String("message".to_string()), Value::String("m".to_string())]).unwrap();
+
+        let parsed = parse(vec![id.clone(), strings(&["commit", "-m", "fix"])]).unwrap().as_map().unwrap();
+        assert_eq!(parsed["command"], Value::String("commit".to_string()));
+        assert_eq!(parsed["message"], Value::String("fix".to_string()));
+        assert!(parse(vec![id, strings(&["push"])]).unwrap_err().contains("Unknown command 'push'"));
+    }
+}
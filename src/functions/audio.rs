@@ -1,57 +1,482 @@
 use crate::value::Value;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::collections::HashMap;
+use std::f64::consts::PI;
 
-/// Plays an audio file
-pub fn play(args: Vec<Value>) -> Result<Value, String> {
+// Sounds are plain maps holding interleaved "samples" between -1.0 and 1.0 together
+// with their "sample_rate" and "channels", so scripts can read and build them like
+// any other array. Every function returns a new sound and leaves its input alone.
+
+const DEFAULT_SAMPLE_RATE: i64 = 44100;
+const DEFAULT_AMPLITUDE: f64 = 0.5;
+const DEFAULT_BITS: i64 = 16;
+
+struct Sound {
+    samples: Vec<f64>,
+    sample_rate: u32,
+    channels: u16,
+}
+
+impl Sound {
+    fn frames(&self) -> usize {
+        self.samples.len() / self.channels as usize
+    }
+
+    fn seconds_to_frames(&self, seconds: f64) -> usize {
+        (seconds.max(0.0) * self.sample_rate as f64).round() as usize
+    }
+}
+
+fn sound(value: &Value) -> Result<Sound, String> {
+    let map = match value {
+        Value::Map(map) => map,
+        other => return Err(format!("Expected a sound from Audio.load or Audio.sine, got {}", other)),
+    };
+    let field = |name: &str| map.get(name).ok_or_else(|| format!("Sound is missing its \"{}\"", name));
+
+    let sample_rate = field("sample_rate")?.as_int()?;
+    if sample_rate <= 0 || sample_rate > u32::MAX as i64 {
+        return Err(format!("Sample rate must be a positive number, got {}", sample_rate));
+    }
+    let channels = field("channels")?.as_int()?;
+    if channels <= 0 || channels > u16::MAX as i64 {
+        return Err(format!("Channel count must be a positive number, got {}", channels));
+    }
+    let samples = field("samples")?.as_array()?.iter()
+        .map(|sample| sample.as_float())
+        .collect::<Result<Vec<f64>, String>>()?;
+    if samples.len() % channels as usize != 0 {
+        return Err(format!("{} samples cannot be split evenly into {} channels", samples.len(), channels));
+    }
+    Ok(Sound { samples, sample_rate: sample_rate as u32, channels: channels as u16 })
+}
+
+// Describe a sound to the script
+fn to_value(sound: Sound, extra: Vec<(&str, Value)>) -> Value {
+    let frames = sound.frames();
+    let mut result = HashMap::new();
+    result.insert("sample_rate".to_string(), Value::Int(sound.sample_rate as i64));
+    result.insert("channels".to_string(), Value::Int(sound.channels as i64));
+    result.insert("frames".to_string(), Value::Int(frames as i64));
+    result.insert("duration".to_string(), Value::Float(frames as f64 / sound.sample_rate as f64));
+    result.insert("samples".to_string(), Value::Array(sound.samples.into_iter().map(Value::Float).collect()));
+    for (key, value) in extra {
+        result.insert(key.to_string(), value);
+    }
+    Value::Map(result)
+}
+
+fn seconds(value: &Value, name: &str) -> Result<f64, String> {
+    let seconds = value.as_float()?;
+    if !seconds.is_finite() || seconds < 0.0 {
+        return Err(format!("{} must not be negative, got {}", name, seconds));
+    }
+    Ok(seconds)
+}
+
+fn sample_rate_arg(value: Option<&Value>) -> Result<u32, String> {
+    let rate = match value {
+        Some(Value::Null) | None => DEFAULT_SAMPLE_RATE,
+        Some(rate) => rate.as_int()?,
+    };
+    if rate <= 0 || rate > u32::MAX as i64 {
+        return Err(format!("Sample rate must be a positive number, got {}", rate));
+    }
+    Ok(rate as u32)
+}
+
+fn amplitude_arg(value: Option<&Value>) -> Result<f64, String> {
+    match value {
+        Some(Value::Null) | None => Ok(DEFAULT_AMPLITUDE),
+        Some(amplitude) => Ok(amplitude.as_float()?.clamp(0.0, 1.0)),
+    }
+}
+
+/// Loads a WAV file into a sound with samples between -1.0 and 1.0
+/// Example: load("voice.wav") => {"samples": [...], "sample_rate": 44100, "channels": 1, "frames": 88200, "duration": 2.0, "bits": 16, "path": "voice.wav"}
+pub fn load(args: Vec<Value>) -> Result<Value, String> {
     if args.len() != 1 {
-        return Err("play() requires exactly 1 argument: path to audio file".to_string());
+        return Err("load() requires exactly 1 argument: path".to_string());
     }
 
     let path = match &args[0] {
         Value::String(s) => s,
-        _ => return Err("Argument must be a string path to an audio file".to_string()),
+        _ => return Err("Path must be a string".to_string()),
+    };
+
+    let mut reader = hound::WavReader::open(path).map_err(|e| format!("Cannot open WAV file {}: {}", path, e))?;
+    let spec = reader.spec();
+    let samples = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>()
+            .map(|sample| sample.map(|s| s as f64))
+            .collect::<Result<Vec<f64>, _>>(),
+        hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f64;
+            reader.samples::<i32>()
+                .map(|sample| sample.map(|s| s as f64 / scale))
+                .collect::<Result<Vec<f64>, _>>()
+        }
+    }.map_err(|e| format!("Cannot decode WAV file {}: {}", path, e))?;
+
+    let sound = Sound { samples, sample_rate: spec.sample_rate, channels: spec.channels };
+    Ok(to_value(sound, vec![
+        ("bits", Value::Int(spec.bits_per_sample as i64)),
+        ("path", Value::String(path.clone())),
+    ]))
+}
+
+/// Saves a sound as a WAV file with 8, 16 (default) or 24 bit integer samples, or 32 bit float samples.
+/// Samples outside -1.0 to 1.0 are clipped
+/// Example: save(sound, "out.wav") => {"success": true, "path": "out.wav", "message": "Sound saved to out.wav"}
+/// Example: save(sound, "out.wav", 24) => {"success": true, "path": "out.wav", "message": "Sound saved to out.wav"}
+pub fn save(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() < 2 {
+        return Err("save() requires at least 2 arguments: sound and path".to_string());
+    }
+
+    let sound = sound(&args[0])?;
+    let path = match &args[1] {
+        Value::String(s) => s,
+        _ => return Err("Second argument must be a string path".to_string()),
+    };
+    let bits = match args.get(2) {
+        Some(Value::Null) | None => DEFAULT_BITS,
+        Some(bits) => bits.as_int()?,
+    };
+    let sample_format = match bits {
+        8 | 16 | 24 => hound::SampleFormat::Int,
+        32 => hound::SampleFormat::Float,
+        other => return Err(format!("Unsupported sample size: {} bits (expected 8, 16, 24 or 32)", other)),
+    };
+
+    let spec = hound::WavSpec {
+        channels: sound.channels,
+        sample_rate: sound.sample_rate,
+        bits_per_sample: bits as u16,
+        sample_format,
+    };
+    let write = || -> Result<(), hound::Error> {
+        let mut writer = hound::WavWriter::create(path, spec)?;
+        let scale = ((1i64 << (bits - 1)) - 1) as f64;
+        for sample in &sound.samples {
+            let sample = sample.clamp(-1.0, 1.0);
+            match bits {
+                8 => writer.write_sample((sample * scale).round() as i8)?,
+                16 => writer.write_sample((sample * scale).round() as i16)?,
+                24 => writer.write_sample((sample * scale).round() as i32)?,
+                _ => writer.write_sample(sample as f32)?,
+            }
+        }
+        writer.finalize()
+    };
+    write().map_err(|e| format!("Cannot save sound to {}: {}", path, e))?;
+
+    // Create a result object with success information
+    let mut result = HashMap::new();
+    result.insert("success".to_string(), Value::Bool(true));
+    result.insert("path".to_string(), Value::String(path.clone()));
+    result.insert("message".to_string(), Value::String(format!("Sound saved to {}", path)));
+
+    Ok(Value::Map(result))
+}
+
+// Build a mono sound one sample at a time from the time in seconds
+fn generate(duration: &Value, sample_rate: Option<&Value>, wave: impl FnMut(f64) -> f64) -> Result<Value, String> {
+    let duration = seconds(duration, "Duration")?;
+    let sample_rate = sample_rate_arg(sample_rate)?;
+    let mut wave = wave;
+    let frames = (duration * sample_rate as f64).round() as usize;
+    let samples = (0..frames).map(|frame| wave(frame as f64 / sample_rate as f64)).collect();
+    Ok(to_value(Sound { samples, sample_rate, channels: 1 }, Vec::new()))
+}
+
+/// Generates a mono sine tone at a frequency in hertz, lasting a number of seconds
+/// Example: sine(440, 1.5) => {"samples": [...], "sample_rate": 44100, "channels": 1, "frames": 66150, "duration": 1.5}
+/// Example: sine(440, 1, 8000, 0.25) => {"samples": [...], "sample_rate": 8000, "channels": 1, "frames": 8000, "duration": 1.0}
+pub fn sine(args: Vec<Value>) -> Result<Value, String> {
+    let frequency = args[0].as_float()?;
+    let amplitude = amplitude_arg(args.get(3))?;
+    generate(&args[1], args.get(2), |time| amplitude * (2.0 * PI * frequency * time).sin())
+}
+
+/// Generates a mono square wave at a frequency in hertz, lasting a number of seconds
+/// Example: square(220, 0.5) => {"samples": [...], "sample_rate": 44100, "channels": 1, "frames": 22050, "duration": 0.5}
+pub fn square(args: Vec<Value>) -> Result<Value, String> {
+    let frequency = args[0].as_float()?;
+    let amplitude = amplitude_arg(args.get(3))?;
+    generate(&args[1], args.get(2), |time| {
+        if (frequency * time).fract() < 0.5 { amplitude } else { -amplitude }
+    })
+}
+
+/// Generates mono white noise lasting a number of seconds. The same seed always gives the same noise
+/// Example: noise(2) => {"samples": [...], "sample_rate": 44100, "channels": 1, "frames": 88200, "duration": 2.0}
+/// Example: noise(1, 8000, 0.1, 42) => {"samples": [...], "sample_rate": 8000, "channels": 1, "frames": 8000, "duration": 1.0}
+pub fn noise(args: Vec<Value>) -> Result<Value, String> {
+    let amplitude = amplitude_arg(args.get(2))?;
+    let mut rng = match args.get(3) {
+        Some(Value::Null) | None => ChaCha8Rng::from_entropy(),
+        Some(seed) => ChaCha8Rng::seed_from_u64(seed.as_int()? as u64),
+    };
+    generate(&args[0], args.get(1), |_| amplitude * rng.gen_range(-1.0..=1.0))
+}
+
+/// Multiplies every sample by a factor; 2 doubles the volume and 0.5 halves it
+/// Example: gain(sound, 0.5) => {"samples": [...], "sample_rate": 44100, "channels": 1, "frames": 44100, "duration": 1.0}
+pub fn gain(args: Vec<Value>) -> Result<Value, String> {
+    let mut sound = sound(&args[0])?;
+    let factor = args[1].as_float()?;
+    sound.samples.iter_mut().for_each(|sample| *sample *= factor);
+    Ok(to_value(sound, Vec::new()))
+}
+
+/// Adds two sounds together, sample by sample. They must share a sample rate and channel count;
+/// the result is as long as the longer one
+/// Example: mix(voice, music) => {"samples": [...], "sample_rate": 44100, "channels": 2, "frames": 132300, "duration": 3.0}
+pub fn mix(args: Vec<Value>) -> Result<Value, String> {
+    let mut first = sound(&args[0])?;
+    let second = sound(&args[1])?;
+    if first.sample_rate != second.sample_rate {
+        return Err(format!("Cannot mix sounds at {} Hz and {} Hz: resample one of them first", first.sample_rate, second.sample_rate));
+    }
+    if first.channels != second.channels {
+        return Err(format!("Cannot mix sounds with {} and {} channels", first.channels, second.channels));
+    }
+
+    if second.samples.len() > first.samples.len() {
+        first.samples.resize(second.samples.len(), 0.0);
+    }
+    for (sample, other) in first.samples.iter_mut().zip(&second.samples) {
+        *sample += other;
+    }
+    Ok(to_value(first, Vec::new()))
+}
+
+/// Converts a sound to another sample rate, interpolating between neighbouring samples
+/// Example: resample(sound, 22050) => {"samples": [...], "sample_rate": 22050, "channels": 1, "frames": 22050, "duration": 1.0}
+pub fn resample(args: Vec<Value>) -> Result<Value, String> {
+    let sound = sound(&args[0])?;
+    let sample_rate = sample_rate_arg(args.get(1))?;
+    let channels = sound.channels as usize;
+    let frames = sound.frames();
+    let new_frames = (frames as f64 * sample_rate as f64 / sound.sample_rate as f64).round() as usize;
+
+    let step = sound.sample_rate as f64 / sample_rate as f64;
+    let mut samples = Vec::with_capacity(new_frames * channels);
+    for frame in 0..new_frames {
+        let position = frame as f64 * step;
+        let before = (position.floor() as usize).min(frames - 1);
+        let after = (before + 1).min(frames - 1);
+        let weight = position - before as f64;
+        for channel in 0..channels {
+            let (a, b) = (sound.samples[before * channels + channel], sound.samples[after * channels + channel]);
+            samples.push(a + (b - a) * weight);
+        }
+    }
+    Ok(to_value(Sound { samples, sample_rate, channels: sound.channels }, Vec::new()))
+}
+
+/// Keeps the part of a sound between two times in seconds, or from a time to the end
+/// Example: trim(sound, 0.5, 1.5) => {"samples": [...], "sample_rate": 44100, "channels": 1, "frames": 44100, "duration": 1.0}
+/// Example: trim(sound, 2) => {"samples": [...], "sample_rate": 44100, "channels": 1, "frames": 44100, "duration": 1.0}
+pub fn trim(args: Vec<Value>) -> Result<Value, String> {
+    let mut sound = sound(&args[0])?;
+    let frames = sound.frames();
+    let start = sound.seconds_to_frames(seconds(&args[1], "Start")?);
+    let end = match args.get(2) {
+        Some(Value::Null) | None => frames,
+        Some(end) => sound.seconds_to_frames(seconds(end, "End")?),
+    };
+    if start > end || end > frames {
+        let rate = sound.sample_rate as f64;
+        return Err(format!("Cannot trim {}s to {}s from a sound lasting {}s", start as f64 / rate, end as f64 / rate, frames as f64 / rate));
+    }
+
+    let channels = sound.channels as usize;
+    sound.samples = sound.samples[start * channels..end * channels].to_vec();
+    Ok(to_value(sound, Vec::new()))
+}
+
+/// Fades a sound in from silence over its first seconds, and optionally out to silence over its last
+/// Example: fade(sound, 0.1) => {"samples": [...], "sample_rate": 44100, "channels": 1, "frames": 44100, "duration": 1.0}
+/// Example: fade(sound, 0, 0.5) => {"samples": [...], "sample_rate": 44100, "channels": 1, "frames": 44100, "duration": 1.0}
+pub fn fade(args: Vec<Value>) -> Result<Value, String> {
+    let mut sound = sound(&args[0])?;
+    let frames = sound.frames();
+    let fade_in = sound.seconds_to_frames(seconds(&args[1], "Fade in")?).min(frames);
+    let fade_out = match args.get(2) {
+        Some(Value::Null) | None => 0,
+        Some(fade_out) => sound.seconds_to_frames(seconds(fade_out, "Fade out")?).min(frames),
     };
 
-    // In a real implementation, this would use an audio library like rodio
-    // For now, we'll just simulate audio playback
-    println!("Playing audio file: {}", path);
-    
-    // Return success
+    let channels = sound.channels as usize;
+    for (frame, samples) in sound.samples.chunks_mut(channels).enumerate() {
+        let mut level = 1.0;
+        if frame < fade_in {
+            level *= frame as f64 / fade_in as f64;
+        }
+        if frame >= frames - fade_out {
+            level *= (frames - 1 - frame) as f64 / fade_out as f64;
+        }
+        samples.iter_mut().for_each(|sample| *sample *= level);
+    }
+    Ok(to_value(sound, Vec::new()))
+}
+
+/// Measures the root mean square level of a sound, from 0.0 for silence up to 1.0
+/// Example: rms(Audio[sine](440, 1, 44100, 1.0)) => 0.7071
+pub fn rms(args: Vec<Value>) -> Result<Value, String> {
+    let sound = sound(&args[0])?;
+    if sound.samples.is_empty() {
+        return Ok(Value::Float(0.0));
+    }
+    let sum: f64 = sound.samples.iter().map(|sample| sample * sample).sum();
+    Ok(Value::Float((sum / sound.samples.len() as f64).sqrt()))
+}
+
+/// Finds the largest absolute sample value in a sound
+/// Example: peak(Audio[sine](440, 1, 44100, 0.8)) => 0.8
+pub fn peak(args: Vec<Value>) -> Result<Value, String> {
+    let sound = sound(&args[0])?;
+    Ok(Value::Float(sound.samples.iter().fold(0.0, |peak: f64, sample| peak.max(sample.abs()))))
+}
+
+// Playback needs a sound device and the system audio libraries, so it is only built with
+// `--features playback`. Everything else in this library works on files and samples alone.
+
+#[cfg(feature = "playback")]
+mod playback {
+    use super::Sound;
+    use rodio::buffer::SamplesBuffer;
+    use rodio::{OutputStream, OutputStreamHandle, Sink};
+    use std::sync::{Mutex, OnceLock};
+
+    static DEVICE: OnceLock<Result<OutputStreamHandle, String>> = OnceLock::new();
+    static SINK: Mutex<Option<Sink>> = Mutex::new(None);
+
+    fn device() -> Result<&'static OutputStreamHandle, String> {
+        DEVICE.get_or_init(|| {
+            let (stream, handle) = OutputStream::try_default().map_err(|e| format!("Cannot open the audio device: {}", e))?;
+            // The stream stops playing when dropped, so keep it open for the rest of the run
+            std::mem::forget(stream);
+            Ok(handle)
+        }).as_ref().map_err(|e| e.clone())
+    }
+
+    pub fn play(sound: Sound) -> Result<(), String> {
+        let sink = Sink::try_new(device()?).map_err(|e| format!("Cannot play sound: {}", e))?;
+        let samples = sound.samples.iter().map(|sample| *sample as f32).collect::<Vec<f32>>();
+        sink.append(SamplesBuffer::new(sound.channels, sound.sample_rate, samples));
+        if let Some(previous) = SINK.lock().unwrap().replace(sink) {
+            previous.stop();
+        }
+        Ok(())
+    }
+
+    pub fn pause() -> bool {
+        SINK.lock().unwrap().as_ref().map(|sink| sink.pause()).is_some()
+    }
+
+    pub fn stop() -> bool {
+        SINK.lock().unwrap().take().map(|sink| sink.stop()).is_some()
+    }
+}
+
+#[cfg(not(feature = "playback"))]
+mod playback {
+    use super::Sound;
+
+    const UNAVAILABLE: &str = "Audio playback is not available: build razen with --features playback";
+
+    pub fn play(_sound: Sound) -> Result<(), String> {
+        Err(UNAVAILABLE.to_string())
+    }
+
+    pub fn pause() -> bool {
+        false
+    }
+
+    pub fn stop() -> bool {
+        false
+    }
+}
+
+/// Plays a sound, or a WAV file, on the system audio device without waiting for it to finish.
+/// Only available when razen is built with --features playback
+/// Example: play("chime.wav") => true
+/// Example: play(Audio[sine](440, 1)) => true
+pub fn play(args: Vec<Value>) -> Result<Value, String> {
+    let sound = match &args[0] {
+        Value::String(_) => sound(&load(args)?)?,
+        other => sound(other)?,
+    };
+    playback::play(sound)?;
     Ok(Value::Bool(true))
 }
 
-/// Pauses the current audio playback
+/// Pauses the sound that is playing; false when nothing is playing
+/// Example: pause() => true
 pub fn pause(_args: Vec<Value>) -> Result<Value, String> {
-    // In a real implementation, this would pause the current audio playback
-    println!("Pausing audio playback");
-    
-    // Return success
-    Ok(Value::Bool(true))
+    Ok(Value::Bool(playback::pause()))
 }
 
-/// Stops the current audio playback
+/// Stops the sound that is playing; false when nothing is playing
+/// Example: stop() => true
 pub fn stop(_args: Vec<Value>) -> Result<Value, String> {
-    // In a real implementation, this would stop the current audio playback
-    println!("Stopping audio playback");
-    
-    // Return success
-    Ok(Value::Bool(true))
+    Ok(Value::Bool(playback::stop()))
 }
 
-/// Starts recording audio
-pub fn record(args: Vec<Value>) -> Result<Value, String> {
-    if args.len() != 1 {
-        return Err("record() requires exactly 1 argument: path to output file".to_string());
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(sound: &Value, name: &str) -> Value {
+        sound.as_map().unwrap()[name].clone()
     }
 
-    let path = match &args[0] {
-        Value::String(s) => s,
-        _ => return Err("Argument must be a string path to an output file".to_string()),
-    };
+    #[test]
+    fn test_sounds_survive_a_round_trip_through_each_sample_size() {
+        let dir = std::env::temp_dir().join(format!("razen_audio_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut stereo = HashMap::new();
+        stereo.insert("sample_rate".to_string(), Value::Int(8000));
+        stereo.insert("channels".to_string(), Value::Int(2));
+        stereo.insert("samples".to_string(), Value::Array([0.0, 0.5, -0.5, 1.0, 0.25, -1.0].into_iter().map(Value::Float).collect()));
+        let stereo = Value::Map(stereo);
 
-    // In a real implementation, this would use an audio library to record
-    // For now, we'll just simulate audio recording
-    println!("Recording audio to file: {}", path);
-    
-    // Return success
-    Ok(Value::Bool(true))
+        for (bits, tolerance) in [(8, 1.0 / 64.0), (16, 1e-4), (24, 1e-6), (32, 0.0)] {
+            let path = dir.join(format!("{}.wav", bits)).display().to_string();
+            save(vec![stereo.clone(), Value::String(path.clone()), Value::Int(bits)]).unwrap();
+            let loaded = load(vec![Value::String(path)]).unwrap();
+            assert_eq!(field(&loaded, "bits"), Value::Int(bits));
+            assert_eq!(field(&loaded, "channels"), Value::Int(2));
+            assert_eq!(field(&loaded, "frames"), Value::Int(3));
+            let expected = sound(&stereo).unwrap().samples;
+            let actual = sound(&loaded).unwrap().samples;
+            for (a, b) in expected.iter().zip(&actual) {
+                assert!((a - b).abs() <= tolerance, "{} bits: {} became {}", bits, a, b);
+            }
+        }
+
+        let bad = save(vec![stereo, Value::String(dir.join("bad.wav").display().to_string()), Value::Int(12)]).unwrap_err();
+        assert!(bad.starts_with("Unsupported sample size"), "{}", bad);
+        std::fs::write(dir.join("noise.wav"), "not a wav file").unwrap();
+        assert!(load(vec![Value::String(dir.join("noise.wav").display().to_string())]).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_resample_keeps_the_duration_and_shape() {
+        let tone = sine(vec![Value::Int(10), Value::Int(1), Value::Int(1000), Value::Float(1.0)]).unwrap();
+        let halved = resample(vec![tone.clone(), Value::Int(500)]).unwrap();
+        assert_eq!(field(&halved, "frames"), Value::Int(500));
+        assert_eq!(field(&halved, "duration"), Value::Float(1.0));
+        let (before, after) = (sound(&tone).unwrap().samples, sound(&halved).unwrap().samples);
+        for (frame, sample) in after.iter().enumerate() {
+            assert!((sample - before[frame * 2]).abs() < 1e-9);
+        }
+        let rms = rms(vec![halved]).unwrap().as_float().unwrap();
+        assert!((rms - 0.5f64.sqrt()).abs() < 1e-3, "{}", rms);
+    }
 }
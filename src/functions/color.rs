@@ -56,39 +56,33 @@ pub fn get_color_code(color_name: &str) -> String {
     }
 }
 
-/// Converts a hex color string to RGB array
+/// Converts a hex color string to RGB array. "#rgb" shorthand is accepted, and
+/// "#rrggbbaa" gives an RGBA array
 /// Example: hex_to_rgb("#ff0000") => [255, 0, 0]
+/// Example: hex_to_rgb("#ff000080") => [255, 0, 0, 128]
 pub fn hex_to_rgb(args: Vec<Value>) -> Result<Value, String> {
     if args.len() != 1 {
         return Err("Color.hex_to_rgb requires exactly 1 argument: hex".to_string());
     }
     
     let hex = args[0].as_string()?;
-    let hex = hex.trim_start_matches('#');
-    
-    if hex.len() != 6 {
-        return Err("Invalid hex color format. Expected format: #RRGGBB".to_string());
-    }
-    
-    // Parse the hex values
-    let r = u8::from_str_radix(&hex[0..2], 16)
-        .map_err(|_| "Invalid hex color format for red component".to_string())?;
-    let g = u8::from_str_radix(&hex[2..4], 16)
-        .map_err(|_| "Invalid hex color format for green component".to_string())?;
-    let b = u8::from_str_radix(&hex[4..6], 16)
-        .map_err(|_| "Invalid hex color format for blue component".to_string())?;
+    let [r, g, b, a] = parse_hex(&hex)?;
     
     // Create the RGB array
     let mut rgb = Vec::new();
     rgb.push(Value::Int(r as i64));
     rgb.push(Value::Int(g as i64));
     rgb.push(Value::Int(b as i64));
+    if hex.trim_start_matches('#').len() == 8 {
+        rgb.push(Value::Int(a as i64));
+    }
     
     Ok(Value::Array(rgb))
 }
 
-/// Converts an RGB array to a hex color string
+/// Converts an RGB (or RGBA) array to a hex color string
 /// Example: rgb_to_hex([255, 0, 0]) => "#ff0000"
+/// Example: rgb_to_hex([255, 0, 0, 128]) => "#ff000080"
 pub fn rgb_to_hex(args: Vec<Value>) -> Result<Value, String> {
     if args.len() != 1 {
         return Err("Color.rgb_to_hex requires exactly 1 argument: rgb array".to_string());
@@ -99,45 +93,76 @@ pub fn rgb_to_hex(args: Vec<Value>) -> Result<Value, String> {
         _ => return Err("Color.rgb_to_hex requires an array argument".to_string()),
     };
     
-    if rgb.len() != 3 {
-        return Err("RGB array must contain exactly 3 values: [r, g, b]".to_string());
+    if rgb.len() != 3 && rgb.len() != 4 {
+        return Err("RGB array must contain exactly 3 values: [r, g, b] (or 4 with alpha)".to_string());
     }
     
-    // Extract the RGB components
-    let r = match rgb[0] {
-        Value::Int(val) => {
-            if val < 0 || val > 255 {
-                return Err("Red component must be between 0 and 255".to_string());
-            }
-            val as u8
-        },
-        _ => return Err("RGB components must be integers".to_string()),
-    };
-    
-    let g = match rgb[1] {
-        Value::Int(val) => {
-            if val < 0 || val > 255 {
-                return Err("Green component must be between 0 and 255".to_string());
-            }
-            val as u8
-        },
-        _ => return Err("RGB components must be integers".to_string()),
-    };
-    
-    let b = match rgb[2] {
-        Value::Int(val) => {
-            if val < 0 || val > 255 {
-                return Err("Blue component must be between 0 and 255".to_string());
-            }
-            val as u8
-        },
-        _ => return Err("RGB components must be integers".to_string()),
-    };
-    
     // Convert to hex
-    let hex = format!("#{:02x}{:02x}{:02x}", r, g, b);
+    let [r, g, b, a] = parse_rgb(rgb)?;
+    if rgb.len() == 4 {
+        Ok(Value::String(format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)))
+    } else {
+        Ok(Value::String(format!("#{:02x}{:02x}{:02x}", r, g, b)))
+    }
+}
+
+/// Read a color given as a hex string or an [r, g, b] / [r, g, b, a] array, for libraries that draw
+pub fn parse_color(value: &Value) -> Result<[u8; 4], String> {
+    match value {
+        Value::Array(rgb) if rgb.len() == 3 || rgb.len() == 4 => parse_rgb(rgb),
+        Value::Array(_) => Err("RGB array must contain exactly 3 values: [r, g, b] (or 4 with alpha)".to_string()),
+        Value::String(hex) => parse_hex(hex),
+        other => Err(format!("Invalid color {}: expected a hex string or an [r, g, b] array", other)),
+    }
+}
+
+/// Hex string for a color, with the alpha only when it is not opaque
+pub fn to_hex([r, g, b, a]: [u8; 4]) -> String {
+    if a == 255 {
+        format!("#{:02x}{:02x}{:02x}", r, g, b)
+    } else {
+        format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+    }
+}
+
+fn parse_hex(hex: &str) -> Result<[u8; 4], String> {
+    let digits = hex.trim().trim_start_matches('#');
+    // "#f80" is short for "#ff8800"
+    let digits = if digits.len() == 3 {
+        digits.chars().flat_map(|c| [c, c]).collect()
+    } else {
+        digits.to_string()
+    };
+    if (digits.len() != 6 && digits.len() != 8) || !digits.is_ascii() {
+        return Err("Invalid hex color format. Expected format: #RRGGBB".to_string());
+    }
     
-    Ok(Value::String(hex))
+    // Parse the hex values
+    let names = ["red", "green", "blue", "alpha"];
+    let mut rgba = [255u8; 4];
+    for (i, component) in rgba.iter_mut().enumerate().take(digits.len() / 2) {
+        *component = u8::from_str_radix(&digits[i * 2..i * 2 + 2], 16)
+            .map_err(|_| format!("Invalid hex color format for {} component", names[i]))?;
+    }
+    Ok(rgba)
+}
+
+fn parse_rgb(rgb: &[Value]) -> Result<[u8; 4], String> {
+    // Extract the RGB components
+    let names = ["Red", "Green", "Blue", "Alpha"];
+    let mut rgba = [255u8; 4];
+    for (i, value) in rgb.iter().enumerate() {
+        rgba[i] = match value {
+            Value::Int(val) => {
+                if !(0..=255).contains(val) {
+                    return Err(format!("{} component must be between 0 and 255", names[i]));
+                }
+                *val as u8
+            },
+            _ => return Err("RGB components must be integers".to_string()),
+        };
+    }
+    Ok(rgba)
 }
 
 /// Lightens a hex color by a percentage
@@ -0,0 +1,92 @@
+use crate::value::Value;
+use std::io::{self, BufRead, Write};
+
+fn joined(args: &[Value]) -> String {
+    args.iter().map(Value::to_string).collect::<Vec<_>>().join(" ")
+}
+
+fn read_line(input: &mut impl BufRead) -> Result<Option<String>, String> {
+    let mut line = String::new();
+    let read = input.read_line(&mut line).map_err(|e| format!("Failed to read input: {}", e))?;
+    if read == 0 {
+        return Ok(None);
+    }
+    if line.ends_with('\n') {
+        line.pop();
+        if line.ends_with('\r') {
+            line.pop();
+        }
+    }
+    Ok(Some(line))
+}
+
+/// Print values separated by spaces, followed by a newline
+/// Example: say("Hello", "World") => prints "Hello World"
+pub fn say(args: Vec<Value>) -> Result<Value, String> {
+    println!("{}", joined(&args));
+    Ok(Value::Null)
+}
+
+/// Print values separated by spaces, without a newline
+/// Example: write("Loading...") => prints "Loading..."
+pub fn write(args: Vec<Value>) -> Result<Value, String> {
+    print!("{}", joined(&args));
+    io::stdout().flush().map_err(|e| format!("Failed to write output: {}", e))?;
+    Ok(Value::Null)
+}
+
+/// Print values separated by spaces to standard error
+/// Example: error("Something went wrong") => prints to stderr
+pub fn error(args: Vec<Value>) -> Result<Value, String> {
+    eprintln!("{}", joined(&args));
+    Ok(Value::Null)
+}
+
+/// Show a prompt and read a line of input; null at the end of input
+/// Example: ask("Name? ") => "Ada"
+pub fn ask(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() > 1 {
+        return Err("IO.ask takes at most 1 argument: question".to_string());
+    }
+    if let Some(question) = args.first() {
+        print!("{}", question.to_string());
+        io::stdout().flush().map_err(|e| format!("Failed to write output: {}", e))?;
+    }
+    Ok(read_line(&mut io::stdin().lock())?.map(Value::String).unwrap_or(Value::Null))
+}
+
+/// Read a line of input without a prompt; null at the end of input
+/// Example: read() => "some input"
+pub fn read(args: Vec<Value>) -> Result<Value, String> {
+    if !args.is_empty() {
+        return Err("IO.read takes no arguments".to_string());
+    }
+    Ok(read_line(&mut io::stdin().lock())?.map(Value::String).unwrap_or(Value::Null))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_values_are_joined_with_spaces() {
+        let values = vec![Value::String("total".to_string()), Value::Int(3), Value::Bool(true), Value::Null];
+        assert_eq!(joined(&values), "total 3 true null");
+        assert_eq!(joined(&[]), "");
+        assert_eq!(say(vec![Value::String("ok".to_string())]).unwrap(), Value::Null);
+    }
+
+    #[test]
+    fn test_lines_are_read_without_line_endings() {
+        let mut input = Cursor::new("first\r\nsecond\n\nlast");
+        assert_eq!(read_line(&mut input).unwrap(), Some("first".to_string()));
+        assert_eq!(read_line(&mut input).unwrap(), Some("second".to_string()));
+        assert_eq!(read_line(&mut input).unwrap(), Some(String::new()));
+        assert_eq!(read_line(&mut input).unwrap(), Some("last".to_string()));
+        assert_eq!(read_line(&mut input).unwrap(), None);
+
+        assert_eq!(ask(vec![Value::Int(1), Value::Int(2)]).unwrap_err(), "IO.ask takes at most 1 argument: question");
+        assert_eq!(read(vec![Value::Int(1)]).unwrap_err(), "IO.read takes no arguments");
+    }
+}
@@ -1,13 +1,79 @@
 use crate::value::Value;
-use sha2::{Sha256, Digest};
+use sha2::{Sha256, Sha512, Digest};
 use base64::{engine::general_purpose, Engine as _};
 use aes_gcm::{
     aead::{Aead, KeyInit, OsRng},
     Aes256Gcm, Nonce
 };
 use aes_gcm::AeadCore;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
 use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
 use sha2::Sha256 as HkdfSha256;
+use std::collections::HashMap;
+use subtle::ConstantTimeEq;
+
+// Every value this library returns has a fixed text format, so hashes, signatures
+// and encrypted strings saved by one version of Razen keep working in the next:
+//
+//   digests, HMACs, keys and signatures   lowercase hex
+//   hash_password                         $pbkdf2-sha256$i=<iterations>,l=32$<salt>$<hash>
+//                                         (PHC string format, base64 without padding)
+//   encrypt                               v2:<base64 of salt (16) + nonce (12) + ciphertext>
+//
+// encrypt derives its AES-256 key with PBKDF2-SHA256 from the key string and a random
+// salt. decrypt also reads the unprefixed base64 strings of earlier versions, whose key
+// came from unsalted HKDF.
+
+const PASSWORD_ITERATIONS: u32 = 600_000;
+const PASSWORD_SALT_LEN: usize = 16;
+const PASSWORD_HASH_LEN: usize = 32;
+// Limits on stored hashes, so a crafted one cannot make verification run for hours
+const MAX_PASSWORD_ITERATIONS: u32 = 10_000_000;
+const MAX_PASSWORD_HASH_LEN: usize = 64;
+const ENCRYPTION_PREFIX: &str = "v2:";
+const ENCRYPTION_ITERATIONS: u32 = 100_000;
+const NONCE_LEN: usize = 12;
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(text: &str, what: &str) -> Result<Vec<u8>, String> {
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return Err(format!("Invalid {}: expected an even number of hex digits", what));
+    }
+    (0..text.len()).step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).map_err(|_| format!("Invalid {}: {} is not a hex byte", what, &text[i..i + 2])))
+        .collect()
+}
+
+// Keys and signatures are hex strings of a fixed length
+fn hex_array<const N: usize>(value: &Value, what: &str) -> Result<[u8; N], String> {
+    let bytes = from_hex(&value.as_string()?, what)?;
+    let length = bytes.len();
+    bytes.try_into().map_err(|_| format!("Invalid {}: expected {} bytes, got {}", what, N, length))
+}
+
+fn random(count: usize) -> Vec<u8> {
+    let mut bytes = vec![0u8; count];
+    OsRng.fill_bytes(&mut bytes);
+    bytes
+}
+
+fn utf8(bytes: Vec<u8>) -> Result<Value, String> {
+    String::from_utf8(bytes)
+        .map(Value::String)
+        .map_err(|_| "Decoded data is not valid UTF-8 text".to_string())
+}
+
+fn digest<D: Digest>(name: &str, args: &[Value]) -> Result<Value, String> {
+    if args.len() != 1 {
+        return Err(format!("Crypto.{} requires exactly 1 argument: string", name));
+    }
+    Ok(Value::String(to_hex(&D::digest(args[0].as_string()?.as_bytes()))))
+}
 
 /// Hashes a string using SHA-256
 /// Example: hash("abc") => "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
@@ -15,101 +81,375 @@ pub fn hash(args: Vec<Value>) -> Result<Value, String> {
     if args.len() != 1 {
         return Err("Crypto.hash requires exactly 1 argument: string".to_string());
     }
-    
+
     let input = args[0].as_string()?;
-    
+
     // Create a SHA-256 hasher
     let mut hasher = Sha256::new();
-    
+
     // Update the hasher with the input
     hasher.update(input.as_bytes());
-    
+
     // Get the hash result
     let result = hasher.finalize();
-    
+
     // Convert to hex string
     let hex_string = format!("{:x}", result);
-    
+
     Ok(Value::String(hex_string))
 }
 
-/// Encrypts a string with a key using AES-256-GCM
-/// Example: encrypt("message", "key") => "encrypted_data"
+/// Hashes a string using SHA-512
+/// Example: sha512("abc") => "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f"
+pub fn sha512(args: Vec<Value>) -> Result<Value, String> {
+    digest::<Sha512>("sha512", &args)
+}
+
+/// Hashes a string using SHA-1. Only for checksums of legacy formats, not for security
+/// Example: sha1("abc") => "a9993e364706816aba3e25717850c26c9cd0d89d"
+pub fn sha1(args: Vec<Value>) -> Result<Value, String> {
+    digest::<sha1::Sha1>("sha1", &args)
+}
+
+/// Hashes a string using MD5. Only for checksums of legacy formats, not for security
+/// Example: md5("abc") => "900150983cd24fb0d6963f7d28e17f72"
+pub fn md5(args: Vec<Value>) -> Result<Value, String> {
+    digest::<md5::Md5>("md5", &args)
+}
+
+/// Computes the HMAC-SHA256 of a message with a key, as hex
+/// Example: hmac_sha256("key", "The quick brown fox jumps over the lazy dog") => "f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+pub fn hmac_sha256(args: Vec<Value>) -> Result<Value, String> {
+    let key = args[0].as_string()?;
+    let message = args[1].as_string()?;
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key.as_bytes()).map_err(|e| format!("Invalid HMAC key: {}", e))?;
+    mac.update(message.as_bytes());
+    Ok(Value::String(to_hex(&mac.finalize().into_bytes())))
+}
+
+/// Compares two strings in constant time, so the time taken does not reveal where they differ.
+/// Use it to check HMACs and tokens
+/// Example: constant_time_equals(expected_mac, received_mac) => true
+pub fn constant_time_equals(args: Vec<Value>) -> Result<Value, String> {
+    let a = args[0].as_string()?;
+    let b = args[1].as_string()?;
+    Ok(Value::Bool(bool::from(a.as_bytes().ct_eq(b.as_bytes()))))
+}
+
+/// Generates cryptographically secure random bytes from the operating system, encoded as
+/// "hex" (default), "base64" or "base64url"
+/// Example: random_bytes(16) => "9f86d081884c7d659a2feaa0c55ad015"
+/// Example: random_bytes(12, "base64url") => "q3U7BXJpY2hhcmQt"
+pub fn random_bytes(args: Vec<Value>) -> Result<Value, String> {
+    let count = args[0].as_int()?;
+    if !(0..=1 << 20).contains(&count) {
+        return Err(format!("Byte count must be between 0 and 1048576, got {}", count));
+    }
+    let bytes = random(count as usize);
+    let encoding = match args.get(1) {
+        Some(Value::Null) | None => "hex".to_string(),
+        Some(encoding) => encoding.as_string()?.to_lowercase(),
+    };
+    match encoding.as_str() {
+        "hex" => Ok(Value::String(to_hex(&bytes))),
+        "base64" => Ok(Value::String(general_purpose::STANDARD.encode(&bytes))),
+        "base64url" => Ok(Value::String(general_purpose::URL_SAFE_NO_PAD.encode(&bytes))),
+        other => Err(format!("Unknown encoding: {} (expected hex, base64 or base64url)", other)),
+    }
+}
+
+fn pbkdf2_sha256(password: &[u8], salt: &[u8], iterations: u32, output: &mut [u8]) {
+    pbkdf2::pbkdf2_hmac::<Sha256>(password, salt, iterations, output);
+}
+
+/// Hashes a password with PBKDF2-SHA256 and a random salt, for storing and later checking
+/// with verify_password. The result is a PHC string that records the iteration count
+/// (default 600000, at most 10000000), so hashes keep verifying if the default changes
+/// Example: hash_password("hunter2") => "$pbkdf2-sha256$i=600000,l=32$6x3Yb8NlrRzNvC1oXWkW5g$tkSNs0VmiyJBr1x3ZxDFT5jfNT55m1CNTVNZr3aaUm8"
+pub fn hash_password(args: Vec<Value>) -> Result<Value, String> {
+    let password = args[0].as_string()?;
+    let iterations = match args.get(1) {
+        Some(Value::Null) | None => PASSWORD_ITERATIONS,
+        Some(iterations) => match iterations.as_int()? {
+            n if (1..=MAX_PASSWORD_ITERATIONS as i64).contains(&n) => n as u32,
+            n => return Err(format!("Iterations must be between 1 and {}, got {}", MAX_PASSWORD_ITERATIONS, n)),
+        },
+    };
+
+    let salt = random(PASSWORD_SALT_LEN);
+    let mut hash = [0u8; PASSWORD_HASH_LEN];
+    pbkdf2_sha256(password.as_bytes(), &salt, iterations, &mut hash);
+    Ok(Value::String(format!(
+        "$pbkdf2-sha256$i={},l={}${}${}",
+        iterations,
+        PASSWORD_HASH_LEN,
+        general_purpose::STANDARD_NO_PAD.encode(salt),
+        general_purpose::STANDARD_NO_PAD.encode(hash),
+    )))
+}
+
+/// Checks a password against a hash from hash_password
+/// Example: verify_password("hunter2", stored_hash) => true
+pub fn verify_password(args: Vec<Value>) -> Result<Value, String> {
+    let password = args[0].as_string()?;
+    let stored = args[1].as_string()?;
+    let invalid = || "Invalid password hash: expected $pbkdf2-sha256$i=<iterations>,l=<length>$<salt>$<hash>".to_string();
+
+    let parts: Vec<&str> = stored.split('$').collect();
+    let [_, "pbkdf2-sha256", params, salt, hash] = parts.as_slice() else { return Err(invalid()) };
+    let (mut iterations, mut length) = (None, None);
+    for param in params.split(',') {
+        match param.split_once('=') {
+            Some(("i", value)) => iterations = Some(value.parse::<u32>().map_err(|_| invalid())?),
+            Some(("l", value)) => length = Some(value.parse::<usize>().map_err(|_| invalid())?),
+            _ => return Err(invalid()),
+        }
+    }
+    let iterations = iterations.filter(|i| (1..=MAX_PASSWORD_ITERATIONS).contains(i)).ok_or_else(invalid)?;
+    let length = length.filter(|l| (1..=MAX_PASSWORD_HASH_LEN).contains(l)).ok_or_else(invalid)?;
+    let salt = general_purpose::STANDARD_NO_PAD.decode(salt).map_err(|_| invalid())?;
+    let expected = general_purpose::STANDARD_NO_PAD.decode(hash).map_err(|_| invalid())?;
+    if expected.len() != length {
+        return Err(invalid());
+    }
+
+    let mut actual = vec![0u8; expected.len()];
+    pbkdf2_sha256(password.as_bytes(), &salt, iterations, &mut actual);
+    Ok(Value::Bool(bool::from(actual.ct_eq(&expected))))
+}
+
+/// Creates a new Ed25519 key pair. Both keys are hex; keep the secret key private
+/// Example: ed25519_keypair() => {"public_key": "d75a9801...511a", "secret_key": "9d61b19d...7f60"}
+pub fn ed25519_keypair(_args: Vec<Value>) -> Result<Value, String> {
+    let seed: [u8; 32] = random(32).try_into().expect("32 random bytes");
+    let signing_key = SigningKey::from_bytes(&seed);
+
+    let mut keys = HashMap::new();
+    keys.insert("public_key".to_string(), Value::String(to_hex(signing_key.verifying_key().as_bytes())));
+    keys.insert("secret_key".to_string(), Value::String(to_hex(&seed)));
+    Ok(Value::Map(keys))
+}
+
+/// Signs a message with an Ed25519 secret key, returning the 64 byte signature as hex
+/// Example: ed25519_sign("hello", keys.secret_key) => "e5564300c360ac72...7a100b"
+pub fn ed25519_sign(args: Vec<Value>) -> Result<Value, String> {
+    let message = args[0].as_string()?;
+    let signing_key = SigningKey::from_bytes(&hex_array(&args[1], "Ed25519 secret key")?);
+    Ok(Value::String(to_hex(&signing_key.sign(message.as_bytes()).to_bytes())))
+}
+
+/// Checks an Ed25519 signature of a message against a public key
+/// Example: ed25519_verify("hello", signature, keys.public_key) => true
+pub fn ed25519_verify(args: Vec<Value>) -> Result<Value, String> {
+    let message = args[0].as_string()?;
+    let signature = Signature::from_bytes(&hex_array(&args[1], "Ed25519 signature")?);
+    let public_key = VerifyingKey::from_bytes(&hex_array(&args[2], "Ed25519 public key")?)
+        .map_err(|_| "Invalid Ed25519 public key".to_string())?;
+    Ok(Value::Bool(public_key.verify(message.as_bytes(), &signature).is_ok()))
+}
+
+/// Encodes the UTF-8 bytes of a string as lowercase hex
+/// Example: hex_encode("Hi!") => "486921"
+pub fn hex_encode(args: Vec<Value>) -> Result<Value, String> {
+    Ok(Value::String(to_hex(args[0].as_string()?.as_bytes())))
+}
+
+/// Decodes hex (either case) back into a string
+/// Example: hex_decode("486921") => "Hi!"
+pub fn hex_decode(args: Vec<Value>) -> Result<Value, String> {
+    utf8(from_hex(&args[0].as_string()?, "hex")?)
+}
+
+/// Encodes a string as standard base64 with padding
+/// Example: base64_encode("Hi?") => "SGk/"
+pub fn base64_encode(args: Vec<Value>) -> Result<Value, String> {
+    Ok(Value::String(general_purpose::STANDARD.encode(args[0].as_string()?)))
+}
+
+/// Decodes standard base64 with padding back into a string
+/// Example: base64_decode("SGk/") => "Hi?"
+pub fn base64_decode(args: Vec<Value>) -> Result<Value, String> {
+    let decoded = general_purpose::STANDARD.decode(args[0].as_string()?)
+        .map_err(|e| format!("Invalid base64: {}", e))?;
+    utf8(decoded)
+}
+
+/// Encodes a string as URL-safe base64 without padding, for URLs, file names and tokens
+/// Example: base64url_encode("Hi?") => "SGk_"
+pub fn base64url_encode(args: Vec<Value>) -> Result<Value, String> {
+    Ok(Value::String(general_purpose::URL_SAFE_NO_PAD.encode(args[0].as_string()?)))
+}
+
+/// Decodes URL-safe base64, with or without padding, back into a string
+/// Example: base64url_decode("SGk_") => "Hi?"
+pub fn base64url_decode(args: Vec<Value>) -> Result<Value, String> {
+    let text = args[0].as_string()?;
+    let decoded = general_purpose::URL_SAFE_NO_PAD.decode(text.trim_end_matches('='))
+        .map_err(|e| format!("Invalid base64url: {}", e))?;
+    utf8(decoded)
+}
+
+fn cipher(key: &[u8; 32]) -> Result<Aes256Gcm, String> {
+    Aes256Gcm::new_from_slice(key).map_err(|_| "Failed to create cipher".to_string())
+}
+
+/// Encrypts a string with a key using AES-256-GCM. The result is "v2:" followed by the
+/// base64 of a random salt, nonce and the ciphertext
+/// Example: encrypt("message", "key") => "v2:3q2+7wAAAAAAAAAAAAAAAA..."
 pub fn encrypt(args: Vec<Value>) -> Result<Value, String> {
     if args.len() != 2 {
         return Err("Crypto.encrypt requires exactly 2 arguments: string, key".to_string());
     }
-    
+
     let plaintext = args[0].as_string()?;
     let key_str = args[1].as_string()?;
-    
-    // Derive a proper length key using HKDF
-    let mut derived_key = [0u8; 32]; // AES-256 needs a 32-byte key
-    let hkdf = Hkdf::<HkdfSha256>::new(None, key_str.as_bytes());
-    hkdf.expand(b"aes-256-gcm", &mut derived_key)
-        .map_err(|_| "Key derivation failed".to_string())?;
-    
-    // Create cipher instance
-    let cipher = Aes256Gcm::new_from_slice(&derived_key)
-        .map_err(|_| "Failed to create cipher".to_string())?;
-    
+
+    // Derive a 32-byte AES-256 key from the key string and a fresh salt
+    let salt = random(PASSWORD_SALT_LEN);
+    let mut derived_key = [0u8; 32];
+    pbkdf2_sha256(key_str.as_bytes(), &salt, ENCRYPTION_ITERATIONS, &mut derived_key);
+    let cipher = cipher(&derived_key)?;
+
     // Generate a random 96-bit nonce
     let nonce_bytes = Aes256Gcm::generate_nonce(&mut OsRng);
     let nonce = Nonce::from_slice(nonce_bytes.as_slice());
-    
+
     // Encrypt the plaintext
     let ciphertext = cipher.encrypt(nonce, plaintext.as_bytes())
         .map_err(|_| "Encryption failed".to_string())?;
-    
-    // Combine nonce and ciphertext and encode as base64
-    let mut combined = nonce_bytes.to_vec();
+
+    // Combine salt, nonce and ciphertext and encode as base64
+    let mut combined = salt;
+    combined.extend_from_slice(&nonce_bytes);
     combined.extend_from_slice(&ciphertext);
     let encoded = general_purpose::STANDARD.encode(&combined);
-    
-    Ok(Value::String(encoded))
+
+    Ok(Value::String(format!("{}{}", ENCRYPTION_PREFIX, encoded)))
 }
 
-/// Decrypts a string with a key using AES-256-GCM
-/// Example: decrypt("encrypted_data", "key") => "message"
+/// Decrypts a string from encrypt with the same key
+/// Example: decrypt("v2:3q2+7wAAAAAAAAAAAAAAAA...", "key") => "message"
 pub fn decrypt(args: Vec<Value>) -> Result<Value, String> {
     if args.len() != 2 {
         return Err("Crypto.decrypt requires exactly 2 arguments: encrypted_string, key".to_string());
     }
-    
+
     let encrypted = args[0].as_string()?;
     let key_str = args[1].as_string()?;
-    
+
+    // Strings without the version prefix come from earlier versions
+    let (current, encoded) = match encrypted.strip_prefix(ENCRYPTION_PREFIX) {
+        Some(encoded) => (true, encoded),
+        None => (false, encrypted.as_str()),
+    };
+
     // Decode the base64 input
-    let combined = general_purpose::STANDARD.decode(encrypted.as_bytes())
+    let combined = general_purpose::STANDARD.decode(encoded.as_bytes())
         .map_err(|_| "Invalid base64 encoding".to_string())?;
-    
-    // Extract nonce (first 12 bytes) and ciphertext
-    if combined.len() <= 12 {
-        return Err("Invalid encrypted data".to_string());
-    }
-    let nonce_bytes = &combined[..12];
-    let ciphertext = &combined[12..];
-    
-    // Derive a proper length key using HKDF
+
     let mut derived_key = [0u8; 32]; // AES-256 needs a 32-byte key
-    let hkdf = Hkdf::<HkdfSha256>::new(None, key_str.as_bytes());
-    hkdf.expand(b"aes-256-gcm", &mut derived_key)
-        .map_err(|_| "Key derivation failed".to_string())?;
-    
-    // Create cipher instance
-    let cipher = Aes256Gcm::new_from_slice(&derived_key)
-        .map_err(|_| "Failed to create cipher".to_string())?;
-    
-    // Create nonce
+    let rest = if current {
+        if combined.len() <= PASSWORD_SALT_LEN + NONCE_LEN {
+            return Err("Invalid encrypted data".to_string());
+        }
+        let (salt, rest) = combined.split_at(PASSWORD_SALT_LEN);
+        pbkdf2_sha256(key_str.as_bytes(), salt, ENCRYPTION_ITERATIONS, &mut derived_key);
+        rest
+    } else {
+        if combined.len() <= NONCE_LEN {
+            return Err("Invalid encrypted data".to_string());
+        }
+        let hkdf = Hkdf::<HkdfSha256>::new(None, key_str.as_bytes());
+        hkdf.expand(b"aes-256-gcm", &mut derived_key)
+            .map_err(|_| "Key derivation failed".to_string())?;
+        &combined[..]
+    };
+
+    // Extract nonce (first 12 bytes) and ciphertext
+    let (nonce_bytes, ciphertext) = rest.split_at(NONCE_LEN);
+    let cipher = cipher(&derived_key)?;
     let nonce = Nonce::from_slice(nonce_bytes);
-    
+
     // Decrypt the ciphertext
     let plaintext = cipher.decrypt(nonce, ciphertext)
         .map_err(|_| "Decryption failed. The key may be incorrect.".to_string())?;
-    
+
     // Convert plaintext bytes to string
     let plaintext_str = String::from_utf8(plaintext)
         .map_err(|_| "Decrypted data is not valid UTF-8".to_string())?;
-    
+
     Ok(Value::String(plaintext_str))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(function: fn(Vec<Value>) -> Result<Value, String>, args: &[&str]) -> Value {
+        function(args.iter().map(|arg| Value::String(arg.to_string())).collect()).unwrap()
+    }
+
+    fn text(value: &str) -> Value {
+        Value::String(value.to_string())
+    }
+
+    #[test]
+    fn test_digests_and_signatures_match_published_vectors() {
+        assert_eq!(call(md5, &["abc"]), text("900150983cd24fb0d6963f7d28e17f72"));
+        assert_eq!(call(sha1, &["abc"]), text("a9993e364706816aba3e25717850c26c9cd0d89d"));
+        assert_eq!(call(sha512, &["abc"]), text("ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f"));
+        // RFC 4231, test case 2
+        assert_eq!(call(hmac_sha256, &["Jefe", "what do ya want for nothing?"]), text("5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"));
+
+        // RFC 8032, test 1
+        let secret = "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60";
+        let public = "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a";
+        let signature = "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e065224901555fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b";
+        assert_eq!(call(ed25519_sign, &["", secret]), text(signature));
+        assert_eq!(call(ed25519_verify, &["", signature, public]), Value::Bool(true));
+        assert_eq!(call(ed25519_verify, &["tampered", signature, public]), Value::Bool(false));
+
+        let Value::Map(keys) = ed25519_keypair(Vec::new()).unwrap() else { panic!("expected a map") };
+        let signature = call(ed25519_sign, &["hello", &keys["secret_key"].to_string()]).to_string();
+        assert_eq!(call(ed25519_verify, &["hello", &signature, &keys["public_key"].to_string()]), Value::Bool(true));
+    }
+
+    #[test]
+    fn test_password_hashes_record_their_parameters() {
+        let stored = hash_password(vec![text("hunter2"), Value::Int(1000)]).unwrap().to_string();
+        assert!(stored.starts_with("$pbkdf2-sha256$i=1000,l=32$"), "{}", stored);
+        assert_eq!(call(verify_password, &["hunter2", &stored]), Value::Bool(true));
+        assert_eq!(call(verify_password, &["hunter3", &stored]), Value::Bool(false));
+
+        // RFC 7914, section 11: PBKDF2-HMAC-SHA256 of "passwd" with salt "salt" and 1 iteration
+        let known = format!(
+            "$pbkdf2-sha256$i=1,l=32${}${}",
+            general_purpose::STANDARD_NO_PAD.encode("salt"),
+            general_purpose::STANDARD_NO_PAD.encode(from_hex("55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc", "hash").unwrap()),
+        );
+        assert_eq!(call(verify_password, &["passwd", &known]), Value::Bool(true));
+        assert!(verify_password(vec![text("passwd"), text("$argon2id$v=19$m=19456,t=2,p=1$c2FsdA$aGFzaA")]).is_err());
+
+        // Crafted hashes: too many iterations, a length that does not match, or a huge hash
+        let (salt, hash) = (general_purpose::STANDARD_NO_PAD.encode("salt"), general_purpose::STANDARD_NO_PAD.encode([0u8; 32]));
+        for params in ["i=4294967295,l=32", "i=1000,l=16", "i=1000", "i=0,l=32"] {
+            let crafted = format!("$pbkdf2-sha256${}${}${}", params, salt, hash);
+            assert!(verify_password(vec![text("x"), text(&crafted)]).unwrap_err().starts_with("Invalid password hash"), "{}", params);
+        }
+        let huge = format!("$pbkdf2-sha256$i=1,l=65536${}${}", salt, general_purpose::STANDARD_NO_PAD.encode(vec![0u8; 65536]));
+        assert!(verify_password(vec![text("x"), text(&huge)]).is_err());
+        assert!(hash_password(vec![text("x"), Value::Int(MAX_PASSWORD_ITERATIONS as i64 + 1)]).is_err());
+    }
+
+    #[test]
+    fn test_encrypted_strings_from_earlier_versions_still_decrypt() {
+        // Made by encrypt before salted keys, without the "v2:" prefix
+        let legacy = "AAECAwQFBgcICQoL4XQw0S1EGkpoNipxfNwoTwDCgThQwKF4rvEGpW8=";
+        assert_eq!(call(decrypt, &[legacy, "secret_key"]), text("Hello, Razen!"));
+
+        let encrypted = call(encrypt, &["Hello, Razen!", "secret_key"]).to_string();
+        assert!(encrypted.starts_with("v2:"), "{}", encrypted);
+        assert_eq!(call(decrypt, &[&encrypted, "secret_key"]), text("Hello, Razen!"));
+        assert!(decrypt(vec![text(&encrypted), text("wrong_key")]).is_err());
+    }
+}
@@ -0,0 +1,703 @@
+use crate::library::{CallContext, ScriptRunner};
+use crate::value::Value;
+use libffi::low::ffi_cif;
+use libffi::middle::{arg, Arg, Cif, Closure, CodePtr, Type};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::{c_void, CStr, CString};
+use std::os::raw::{c_long, c_ulong};
+use std::sync::{Arc, Mutex};
+
+lazy_static::lazy_static! {
+    // Libraries, functions, callbacks and struct layouts handed out to scripts by ID
+    static ref FFI_STATE: Mutex<FfiState> = Mutex::new(FfiState::new());
+}
+
+thread_local! {
+    // Taken from the call context around FFI calls so callbacks can re-enter the interpreter
+    static CALLBACK_RUNNER: RefCell<Option<ScriptRunner>> = const { RefCell::new(None) };
+    // First error raised inside a callback, reported once the foreign call returns
+    static CALLBACK_ERROR: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+// C types understood by declarations; buffer and callback are argument-only
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CType {
+    Void,
+    Char,
+    Short,
+    Int,
+    UInt,
+    Long,
+    ULong,
+    Size,
+    Float,
+    Double,
+    Pointer,
+    String,
+    Buffer,
+    Callback,
+}
+
+impl CType {
+    pub fn parse(name: &str) -> Result<CType, String> {
+        match name.trim() {
+            "void" => Ok(CType::Void),
+            "char" => Ok(CType::Char),
+            "short" => Ok(CType::Short),
+            "int" => Ok(CType::Int),
+            "uint" => Ok(CType::UInt),
+            "long" => Ok(CType::Long),
+            "ulong" => Ok(CType::ULong),
+            "size_t" => Ok(CType::Size),
+            "float" => Ok(CType::Float),
+            "double" => Ok(CType::Double),
+            "pointer" => Ok(CType::Pointer),
+            "string" => Ok(CType::String),
+            "buffer" => Ok(CType::Buffer),
+            "callback" => Ok(CType::Callback),
+            other => Err(format!(
+                "Unknown C type '{}' (expected void, char, short, int, uint, long, ulong, size_t, float, double, pointer, string, buffer or callback)",
+                other
+            )),
+        }
+    }
+
+    fn ffi_type(self) -> Type {
+        match self {
+            CType::Void => Type::void(),
+            CType::Char => Type::i8(),
+            CType::Short => Type::i16(),
+            CType::Int => Type::i32(),
+            CType::UInt => Type::u32(),
+            CType::Long => Type::c_long(),
+            CType::ULong => Type::c_ulong(),
+            CType::Size => Type::usize(),
+            CType::Float => Type::f32(),
+            CType::Double => Type::f64(),
+            CType::Pointer | CType::String | CType::Buffer | CType::Callback => Type::pointer(),
+        }
+    }
+
+    // Size and alignment inside a struct
+    fn layout(self) -> Result<usize, String> {
+        match self {
+            CType::Char => Ok(1),
+            CType::Short => Ok(2),
+            CType::Int | CType::UInt | CType::Float => Ok(4),
+            CType::Long | CType::ULong => Ok(std::mem::size_of::<c_long>()),
+            CType::Size => Ok(std::mem::size_of::<usize>()),
+            CType::Double => Ok(8),
+            CType::Pointer | CType::String => Ok(std::mem::size_of::<*const c_void>()),
+            other => Err(format!("{:?} cannot be used as a struct field", other)),
+        }
+    }
+}
+
+// A symbol with its declared signature
+struct FfiFunction {
+    symbol: String,
+    code: CodePtr,
+    cif: Cif,
+    params: Vec<CType>,
+    returns: CType,
+    // Keeps the library loaded while the function exists
+    _library: Arc<libloading::Library>,
+}
+
+// A Razen function exposed to C as a function pointer
+struct Callback {
+    _closure: Closure<'static>,
+    data: *mut CallbackData,
+}
+
+struct CallbackData {
+    function: String,
+    params: Vec<CType>,
+    returns: CType,
+}
+
+struct StructLayout {
+    fields: Vec<(String, CType, usize)>, // name, type, offset
+    size: usize,
+}
+
+struct FfiState {
+    libraries: HashMap<usize, Arc<libloading::Library>>,
+    functions: HashMap<usize, Arc<FfiFunction>>,
+    callbacks: HashMap<usize, Callback>, // by code address
+    structs: HashMap<usize, Arc<StructLayout>>,
+    next_id: usize,
+}
+
+// SAFETY: the raw pointers in CIFs, closures and code pointers are never mutated after
+// creation, and libffi allows them to be used from any thread
+unsafe impl Send for FfiState {}
+unsafe impl Send for FfiFunction {}
+unsafe impl Sync for FfiFunction {}
+
+impl FfiState {
+    fn new() -> Self {
+        FfiState {
+            libraries: HashMap::new(),
+            functions: HashMap::new(),
+            callbacks: HashMap::new(),
+            structs: HashMap::new(),
+            next_id: 1,
+        }
+    }
+
+    fn next_id(&mut self) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+}
+
+fn type_list(value: &Value) -> Result<Vec<CType>, String> {
+    value.as_array()?.iter().map(|t| CType::parse(&t.to_string())).collect()
+}
+
+fn function_arg(value: &Value) -> Result<Arc<FfiFunction>, String> {
+    let id = value.as_int()? as usize;
+    FFI_STATE.lock().unwrap().functions.get(&id)
+        .cloned()
+        .ok_or_else(|| format!("Invalid FFI function ID: {}", id))
+}
+
+fn struct_arg(value: &Value) -> Result<Arc<StructLayout>, String> {
+    let id = value.as_int()? as usize;
+    FFI_STATE.lock().unwrap().structs.get(&id)
+        .cloned()
+        .ok_or_else(|| format!("Invalid FFI struct ID: {}", id))
+}
+
+fn pointer_arg(value: &Value) -> Result<usize, String> {
+    match value {
+        Value::Null => Ok(0),
+        other => Ok(other.as_int()? as usize),
+    }
+}
+
+/// Open a shared library; with no path the running program's own symbols are used
+/// Example: open("libm.so.6") => 1
+pub fn open(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() > 1 {
+        return Err("FFI.open takes at most 1 argument: path".to_string());
+    }
+
+    let path = match args.first() {
+        None | Some(Value::Null) => String::new(),
+        Some(path) => path.as_string()?,
+    };
+    let library = if path.is_empty() {
+        libloading::os::unix::Library::this().into()
+    } else {
+        // SAFETY: loading runs the library's initialisers, which scripts opt into by opening it
+        unsafe { libloading::Library::new(&path) }
+            .map_err(|e| format!("Failed to open library {}: {}", path, e))?
+    };
+
+    let mut state = FFI_STATE.lock().unwrap();
+    let id = state.next_id();
+    state.libraries.insert(id, Arc::new(library));
+
+    Ok(Value::Int(id as i64))
+}
+
+/// Declare a function from an opened library with its parameter and return types
+/// Example: func(1, "pow", ["double", "double"], "double") => 2
+pub fn func(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() != 4 {
+        return Err("FFI.func requires exactly 4 arguments: library_id, symbol, param_types, return_type".to_string());
+    }
+
+    let library_id = args[0].as_int()? as usize;
+    let symbol = args[1].as_string()?;
+    let params = type_list(&args[2])?;
+    let returns = CType::parse(&args[3].as_string()?)?;
+    if params.contains(&CType::Void) {
+        return Err("void can only be used as a return type".to_string());
+    }
+    if matches!(returns, CType::Buffer | CType::Callback) {
+        return Err(format!("{:?} can only be used as a parameter type", returns).to_lowercase());
+    }
+
+    let library = FFI_STATE.lock().unwrap().libraries.get(&library_id)
+        .cloned()
+        .ok_or_else(|| format!("Invalid FFI library ID: {}", library_id))?;
+    let code = {
+        let name = CString::new(symbol.clone()).map_err(|_| "Symbol names cannot contain NUL bytes".to_string())?;
+        // SAFETY: the symbol is only called through the CIF built from the declared signature
+        let address: libloading::Symbol<*mut c_void> = unsafe { library.get(name.as_bytes_with_nul()) }
+            .map_err(|e| format!("Symbol {} not found: {}", symbol, e))?;
+        CodePtr(*address)
+    };
+
+    let cif = Cif::new(params.iter().map(|t| t.ffi_type()), returns.ffi_type());
+    let function = FfiFunction { symbol, code, cif, params, returns, _library: library };
+
+    let mut state = FFI_STATE.lock().unwrap();
+    let id = state.next_id();
+    state.functions.insert(id, Arc::new(function));
+
+    Ok(Value::Int(id as i64))
+}
+
+// Argument converted to its C representation
+enum CArg {
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    U32(u32),
+    Long(c_long),
+    ULong(c_ulong),
+    Size(usize),
+    F32(f32),
+    F64(f64),
+    Ptr(*mut c_void),
+}
+
+impl CArg {
+    fn as_arg(&self) -> Arg {
+        match self {
+            CArg::I8(v) => arg(v),
+            CArg::I16(v) => arg(v),
+            CArg::I32(v) => arg(v),
+            CArg::U32(v) => arg(v),
+            CArg::Long(v) => arg(v),
+            CArg::ULong(v) => arg(v),
+            CArg::Size(v) => arg(v),
+            CArg::F32(v) => arg(v),
+            CArg::F64(v) => arg(v),
+            CArg::Ptr(v) => arg(v),
+        }
+    }
+}
+
+/// Call a declared function. Strings are passed as C strings and buffers from
+/// MemoryLib[create_buffer] as pointers to their bytes, which the function may modify.
+/// Example: call(2, 2, 10) => 1024
+pub fn call(context: &CallContext, args: Vec<Value>) -> Result<Value, String> {
+    // Nested calls (from inside a callback) restore the outer runner when they return
+    let previous = CALLBACK_RUNNER.with(|current| current.replace(context.script_runner()));
+    let result = call_foreign(args);
+    CALLBACK_RUNNER.with(|current| current.replace(previous));
+    result
+}
+
+fn call_foreign(args: Vec<Value>) -> Result<Value, String> {
+    if args.is_empty() {
+        return Err("FFI.call requires at least 1 argument: function_id".to_string());
+    }
+
+    let function = function_arg(&args[0])?;
+    let values = &args[1..];
+    if values.len() != function.params.len() {
+        return Err(format!("{} expects {} argument(s), got {}", function.symbol, function.params.len(), values.len()));
+    }
+
+    // Owned data the C arguments point into; filled before any pointer is taken
+    let mut strings: Vec<CString> = Vec::new();
+    let mut buffers: Vec<(usize, Vec<u8>)> = Vec::new();
+    for (value, ctype) in values.iter().zip(&function.params) {
+        match ctype {
+            CType::String if *value != Value::Null => {
+                strings.push(CString::new(value.as_string()?)
+                    .map_err(|_| format!("String argument to {} contains a NUL byte", function.symbol))?);
+            }
+            CType::Buffer => {
+                let id = value.as_int()? as usize;
+                buffers.push((id, super::memory::buffer_contents(id)?));
+            }
+            _ => {}
+        }
+    }
+
+    let mut c_args = Vec::with_capacity(values.len());
+    let (mut next_string, mut next_buffer) = (0, 0);
+    for (index, (value, ctype)) in values.iter().zip(&function.params).enumerate() {
+        let wrong = |e: String| format!("Argument {} to {}: {}", index + 1, function.symbol, e);
+        c_args.push(match ctype {
+            CType::Char => CArg::I8(value.as_int().map_err(wrong)? as i8),
+            CType::Short => CArg::I16(value.as_int().map_err(wrong)? as i16),
+            CType::Int => CArg::I32(value.as_int().map_err(wrong)? as i32),
+            CType::UInt => CArg::U32(value.as_int().map_err(wrong)? as u32),
+            CType::Long => CArg::Long(value.as_int().map_err(wrong)? as c_long),
+            CType::ULong => CArg::ULong(value.as_int().map_err(wrong)? as c_ulong),
+            CType::Size => CArg::Size(value.as_int().map_err(wrong)? as usize),
+            CType::Float => CArg::F32(value.as_float().map_err(wrong)? as f32),
+            CType::Double => CArg::F64(value.as_float().map_err(wrong)?),
+            CType::Pointer | CType::Callback => CArg::Ptr(pointer_arg(value).map_err(wrong)? as *mut c_void),
+            CType::String if *value == Value::Null => CArg::Ptr(std::ptr::null_mut()),
+            CType::String => {
+                next_string += 1;
+                CArg::Ptr(strings[next_string - 1].as_ptr() as *mut c_void)
+            }
+            CType::Buffer => {
+                next_buffer += 1;
+                CArg::Ptr(buffers[next_buffer - 1].1.as_mut_ptr() as *mut c_void)
+            }
+            CType::Void => unreachable!("void parameters are rejected when declared"),
+        });
+    }
+    let ffi_args: Vec<Arg> = c_args.iter().map(CArg::as_arg).collect();
+
+    CALLBACK_ERROR.with(|error| *error.borrow_mut() = None);
+    // SAFETY: the CIF matches the declared signature and every pointer argument stays
+    // alive until the call returns; the declaration itself is the script's promise
+    let result = unsafe {
+        let (cif, code) = (&function.cif, function.code);
+        match function.returns {
+            CType::Void => {
+                cif.call::<()>(code, &ffi_args);
+                Value::Null
+            }
+            // Small integers are returned widened to a full register
+            CType::Char => Value::Int(cif.call::<i64>(code, &ffi_args) as i8 as i64),
+            CType::Short => Value::Int(cif.call::<i64>(code, &ffi_args) as i16 as i64),
+            CType::Int => Value::Int(cif.call::<i64>(code, &ffi_args) as i32 as i64),
+            CType::UInt => Value::Int(cif.call::<u64>(code, &ffi_args) as u32 as i64),
+            CType::Long => Value::Int(cif.call::<i64>(code, &ffi_args) as c_long as _),
+            CType::ULong => Value::Int(cif.call::<u64>(code, &ffi_args) as c_ulong as i64),
+            CType::Size => Value::Int(cif.call::<usize>(code, &ffi_args) as i64),
+            CType::Float => Value::Float(cif.call::<f32>(code, &ffi_args) as f64),
+            CType::Double => Value::Float(cif.call::<f64>(code, &ffi_args)),
+            CType::Pointer => Value::Int(cif.call::<*mut c_void>(code, &ffi_args) as i64),
+            CType::String => {
+                let ptr = cif.call::<*const std::ffi::c_char>(code, &ffi_args);
+                if ptr.is_null() {
+                    Value::Null
+                } else {
+                    Value::String(CStr::from_ptr(ptr).to_string_lossy().into_owned())
+                }
+            }
+            CType::Buffer | CType::Callback => unreachable!("rejected when declared"),
+        }
+    };
+
+    for (id, bytes) in &buffers {
+        super::memory::set_buffer_contents(*id, bytes)?;
+    }
+    if let Some(error) = CALLBACK_ERROR.with(|error| error.borrow_mut().take()) {
+        return Err(error);
+    }
+
+    Ok(result)
+}
+
+// Entry point libffi calls for every Razen callback
+unsafe extern "C" fn run_callback(_cif: &ffi_cif, result: &mut u64, args: *const *const c_void, data: &CallbackData) {
+    let mut values = Vec::with_capacity(data.params.len());
+    for (index, ctype) in data.params.iter().enumerate() {
+        let ptr = *args.add(index);
+        values.push(match ctype {
+            CType::Char => (*(ptr as *const i8)).to_string(),
+            CType::Short => (*(ptr as *const i16)).to_string(),
+            CType::Int => (*(ptr as *const i32)).to_string(),
+            CType::UInt => (*(ptr as *const u32)).to_string(),
+            CType::Long => (*(ptr as *const c_long)).to_string(),
+            CType::ULong => (*(ptr as *const c_ulong)).to_string(),
+            CType::Size => (*(ptr as *const usize)).to_string(),
+            CType::Float => (*(ptr as *const f32)).to_string(),
+            CType::Double => (*(ptr as *const f64)).to_string(),
+            CType::String => {
+                let text = *(ptr as *const *const std::ffi::c_char);
+                if text.is_null() { "null".to_string() } else { CStr::from_ptr(text).to_string_lossy().into_owned() }
+            }
+            _ => (*(ptr as *const usize)).to_string(),
+        });
+    }
+
+    let runner = CALLBACK_RUNNER.with(|runner| runner.borrow().clone());
+    let outcome = match runner {
+        Some(runner) => runner(&data.function, values),
+        None => Err(format!("Callback {} was called outside of an FFI call", data.function)),
+    };
+
+    *result = 0;
+    match outcome {
+        Ok(value) => match data.returns {
+            CType::Void => {}
+            CType::Float => *(result as *mut u64 as *mut f32) = value.parse::<f32>().unwrap_or(0.0),
+            CType::Double => *(result as *mut u64 as *mut f64) = value.parse::<f64>().unwrap_or(0.0),
+            _ => *result = value.parse::<f64>().map(|v| v as i64).unwrap_or(0) as u64,
+        },
+        Err(error) => CALLBACK_ERROR.with(|pending| {
+            pending.borrow_mut().get_or_insert(error);
+        }),
+    }
+}
+
+/// Wrap a Razen function as a C function pointer with the given signature.
+/// The callback can be passed wherever a pointer or callback parameter is declared.
+/// Example: callback("compare", ["pointer", "pointer"], "int") => 94823412
+pub fn callback(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() != 3 {
+        return Err("FFI.callback requires exactly 3 arguments: function_name, param_types, return_type".to_string());
+    }
+
+    let function = args[0].as_string()?;
+    let params = type_list(&args[1])?;
+    let returns = CType::parse(&args[2].as_string()?)?;
+    if params.iter().chain([&returns]).any(|t| matches!(t, CType::Buffer | CType::Callback)) || params.contains(&CType::Void) {
+        return Err("Callbacks only support scalar, pointer and string types".to_string());
+    }
+
+    let cif = Cif::new(params.iter().map(|t| t.ffi_type()), returns.ffi_type());
+    let data = Box::into_raw(Box::new(CallbackData { function, params, returns }));
+    // SAFETY: the data is freed only after the closure that refers to it is dropped
+    let closure = Closure::new(cif, run_callback, unsafe { &*data });
+    let address = *closure.code_ptr() as usize;
+
+    FFI_STATE.lock().unwrap().callbacks.insert(address, Callback { _closure: closure, data });
+
+    Ok(Value::Int(address as i64))
+}
+
+/// Release a callback created with callback()
+/// Example: free_callback(94823412) => true
+pub fn free_callback(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() != 1 {
+        return Err("FFI.free_callback requires exactly 1 argument: callback".to_string());
+    }
+
+    let address = pointer_arg(&args[0])?;
+    let callback = FFI_STATE.lock().unwrap().callbacks.remove(&address)
+        .ok_or_else(|| format!("Invalid callback: {}", address))?;
+    let data = callback.data;
+    drop(callback);
+    // SAFETY: the closure using the data was dropped above
+    drop(unsafe { Box::from_raw(data) });
+
+    Ok(Value::Bool(true))
+}
+
+/// Describe a C struct as "name:type" fields in declaration order; offsets follow C alignment rules
+/// Example: define_struct(["x:int", "y:double"]) => 3
+pub fn define_struct(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() != 1 {
+        return Err("FFI.define_struct requires exactly 1 argument: fields".to_string());
+    }
+
+    let mut fields = Vec::new();
+    let (mut offset, mut align) = (0usize, 1usize);
+    for field in args[0].as_array()? {
+        let field = field.to_string();
+        let (name, ctype) = field.split_once(':')
+            .ok_or_else(|| format!("Struct field '{}' must be written as name:type", field))?;
+        let ctype = CType::parse(ctype)?;
+        let size = ctype.layout()?;
+        offset = offset.div_ceil(size) * size;
+        align = align.max(size);
+        fields.push((name.trim().to_string(), ctype, offset));
+        offset += size;
+    }
+    if fields.is_empty() {
+        return Err("A struct needs at least one field".to_string());
+    }
+    let layout = StructLayout { fields, size: offset.div_ceil(align) * align };
+
+    let mut state = FFI_STATE.lock().unwrap();
+    let id = state.next_id();
+    state.structs.insert(id, Arc::new(layout));
+
+    Ok(Value::Int(id as i64))
+}
+
+/// Get the size of a struct in bytes, including padding
+/// Example: struct_size(3) => 16
+pub fn struct_size(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() != 1 {
+        return Err("FFI.struct_size requires exactly 1 argument: struct_id".to_string());
+    }
+
+    Ok(Value::Int(struct_arg(&args[0])?.size as i64))
+}
+
+/// Get the byte offset of a struct field
+/// Example: struct_offset(3, "y") => 8
+pub fn struct_offset(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() != 2 {
+        return Err("FFI.struct_offset requires exactly 2 arguments: struct_id, field".to_string());
+    }
+
+    let layout = struct_arg(&args[0])?;
+    let (_, _, offset) = field(&layout, &args[1])?;
+    Ok(Value::Int(*offset as i64))
+}
+
+/// Allocate zeroed memory for a struct; release it with MemoryLib[free]
+/// Example: struct_alloc(3) => 140721254236160
+pub fn struct_alloc(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() != 1 {
+        return Err("FFI.struct_alloc requires exactly 1 argument: struct_id".to_string());
+    }
+
+    let layout = struct_arg(&args[0])?;
+    super::memory::alloc(vec![Value::Int(layout.size as i64)])
+}
+
+fn field<'a>(layout: &'a StructLayout, name: &Value) -> Result<&'a (String, CType, usize), String> {
+    let name = name.as_string()?;
+    layout.fields.iter()
+        .find(|(field, _, _)| *field == name)
+        .ok_or_else(|| format!("Struct has no field named '{}'", name))
+}
+
+/// Read a field of the struct at an address
+/// Example: struct_get(3, ptr, "y") => 2.5
+pub fn struct_get(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() != 3 {
+        return Err("FFI.struct_get requires exactly 3 arguments: struct_id, address, field".to_string());
+    }
+
+    let layout = struct_arg(&args[0])?;
+    let address = pointer_arg(&args[1])?;
+    if address == 0 {
+        return Err("Cannot read a field through a null pointer".to_string());
+    }
+    let (_, ctype, offset) = field(&layout, &args[2])?;
+    let ptr = (address + offset) as *const u8;
+
+    // SAFETY: the script vouches that the address points at a struct with this layout
+    let value = unsafe {
+        match ctype {
+            CType::Char => Value::Int(*(ptr as *const i8) as i64),
+            CType::Short => Value::Int((ptr as *const i16).read_unaligned() as i64),
+            CType::Int => Value::Int((ptr as *const i32).read_unaligned() as i64),
+            CType::UInt => Value::Int((ptr as *const u32).read_unaligned() as i64),
+            CType::Long => Value::Int((ptr as *const c_long).read_unaligned() as _),
+            CType::ULong => Value::Int((ptr as *const c_ulong).read_unaligned() as i64),
+            CType::Size => Value::Int((ptr as *const usize).read_unaligned() as i64),
+            CType::Float => Value::Float((ptr as *const f32).read_unaligned() as f64),
+            CType::Double => Value::Float((ptr as *const f64).read_unaligned()),
+            CType::Pointer => Value::Int((ptr as *const usize).read_unaligned() as i64),
+            CType::String => {
+                let text = (ptr as *const *const std::ffi::c_char).read_unaligned();
+                if text.is_null() { Value::Null } else { Value::String(CStr::from_ptr(text).to_string_lossy().into_owned()) }
+            }
+            _ => unreachable!("checked when the struct was defined"),
+        }
+    };
+    Ok(value)
+}
+
+/// Write a field of the struct at an address. String fields are read-only; store a pointer instead.
+/// Example: struct_set(3, ptr, "x", 7) => true
+pub fn struct_set(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() != 4 {
+        return Err("FFI.struct_set requires exactly 4 arguments: struct_id, address, field, value".to_string());
+    }
+
+    let layout = struct_arg(&args[0])?;
+    let address = pointer_arg(&args[1])?;
+    if address == 0 {
+        return Err("Cannot write a field through a null pointer".to_string());
+    }
+    let (name, ctype, offset) = field(&layout, &args[2])?;
+    let ptr = (address + offset) as *mut u8;
+    let value = &args[3];
+
+    // SAFETY: the script vouches that the address points at a struct with this layout
+    unsafe {
+        match ctype {
+            CType::Char => *(ptr as *mut i8) = value.as_int()? as i8,
+            CType::Short => (ptr as *mut i16).write_unaligned(value.as_int()? as i16),
+            CType::Int => (ptr as *mut i32).write_unaligned(value.as_int()? as i32),
+            CType::UInt => (ptr as *mut u32).write_unaligned(value.as_int()? as u32),
+            CType::Long => (ptr as *mut c_long).write_unaligned(value.as_int()? as c_long),
+            CType::ULong => (ptr as *mut c_ulong).write_unaligned(value.as_int()? as c_ulong),
+            CType::Size => (ptr as *mut usize).write_unaligned(value.as_int()? as usize),
+            CType::Float => (ptr as *mut f32).write_unaligned(value.as_float()? as f32),
+            CType::Double => (ptr as *mut f64).write_unaligned(value.as_float()?),
+            CType::Pointer => (ptr as *mut usize).write_unaligned(pointer_arg(value)?),
+            CType::String => return Err(format!("String field '{}' is read-only; declare it as a pointer to set it", name)),
+            _ => unreachable!("checked when the struct was defined"),
+        }
+    }
+    Ok(Value::Bool(true))
+}
+
+/// Read a NUL-terminated C string from an address
+/// Example: read_string(ptr) => "hello"
+pub fn read_string(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() != 1 {
+        return Err("FFI.read_string requires exactly 1 argument: address".to_string());
+    }
+
+    let address = pointer_arg(&args[0])?;
+    if address == 0 {
+        return Ok(Value::Null);
+    }
+    // SAFETY: the script vouches that the address points at a C string
+    let text = unsafe { CStr::from_ptr(address as *const std::ffi::c_char) };
+    Ok(Value::String(text.to_string_lossy().into_owned()))
+}
+
+/// Close a library opened with open(); functions declared from it stay usable
+/// Example: close(1) => true
+pub fn close(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() != 1 {
+        return Err("FFI.close requires exactly 1 argument: library_id".to_string());
+    }
+
+    let id = args[0].as_int()? as usize;
+    FFI_STATE.lock().unwrap().libraries.remove(&id)
+        .ok_or_else(|| format!("Invalid FFI library ID: {}", id))?;
+
+    Ok(Value::Bool(true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(args: Vec<Value>) -> Result<Value, String> {
+        super::call(&CallContext::new("ffilib"), args)
+    }
+
+    #[test]
+    fn test_call_libc_and_struct_layout() {
+        let libc = open(vec![]).unwrap();
+        let labs = func(vec![libc.clone(), Value::String("labs".to_string()),
+                             Value::Array(vec![Value::String("long".to_string())]), Value::String("long".to_string())]).unwrap();
+        assert_eq!(call(vec![labs.clone(), Value::Int(-42)]).unwrap(), Value::Int(42));
+        assert!(call(vec![labs]).unwrap_err().contains("expects 1 argument"));
+
+        let strlen = func(vec![libc, Value::String("strlen".to_string()),
+                               Value::Array(vec![Value::String("string".to_string())]), Value::String("size_t".to_string())]).unwrap();
+        assert_eq!(call(vec![strlen, Value::String("razen".to_string())]).unwrap(), Value::Int(5));
+
+        let point = define_struct(vec![Value::Array(vec![
+            Value::String("tag:char".to_string()),
+            Value::String("y:double".to_string()),
+            Value::String("x:int".to_string()),
+        ])]).unwrap();
+        assert_eq!(struct_size(vec![point.clone()]).unwrap(), Value::Int(24));
+        assert_eq!(struct_offset(vec![point, Value::String("y".to_string())]).unwrap(), Value::Int(8));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_long_follows_the_platform_c_long() {
+        let libc = open(vec![]).unwrap();
+        let labs = func(vec![libc.clone(), Value::String("labs".to_string()),
+                             Value::Array(vec![Value::String("long".to_string())]), Value::String("long".to_string())]).unwrap();
+        let big: i64 = c_long::MAX as _;
+        assert_eq!(call(vec![labs, Value::Int(-big)]).unwrap(), Value::Int(big));
+
+        let abs = func(vec![libc, Value::String("abs".to_string()),
+                            Value::Array(vec![Value::String("int".to_string())]), Value::String("int".to_string())]).unwrap();
+        assert_eq!(call(vec![abs, Value::Int(-7)]).unwrap(), Value::Int(7));
+
+        let pair = define_struct(vec![Value::Array(vec![
+            Value::String("count:long".to_string()),
+            Value::String("size:size_t".to_string()),
+        ])]).unwrap();
+        assert_eq!(struct_offset(vec![pair.clone(), Value::String("size".to_string())]).unwrap(),
+                   Value::Int(std::mem::size_of::<c_long>() as i64));
+        assert_eq!(struct_size(vec![pair]).unwrap(),
+                   Value::Int((std::mem::size_of::<c_long>() + std::mem::size_of::<usize>()) as i64));
+    }
+}
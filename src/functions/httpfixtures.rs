@@ -0,0 +1,431 @@
+use reqwest::blocking::{Client, Request};
+use reqwest::cookie::{CookieStore, Jar};
+use reqwest::header::HeaderValue;
+use reqwest::StatusCode;
+use base64::{engine::general_purpose, Engine as _};
+use serde_json::{json, Value as JsonValue};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+// Every HTTP request APILib and NetLib make goes through execute(), so test runs
+// can record real exchanges once and replay them later without the network:
+//
+//   RAZEN_HTTP_RECORD=dir  send requests and save each exchange as dir/<name>.json
+//   RAZEN_HTTP_REPLAY=dir  answer requests from the saved files, never touching the network
+//
+// A recording matches on method and URL (query parameters in any order). Its "match"
+// list can also require the same body ("body") or header values (header names);
+// RAZEN_HTTP_MATCH=body,authorization fills that list in while recording.
+// Identical requests are answered in the order they were recorded, the last
+// recording repeating once the others are used up.
+
+const RECORD_VAR: &str = "RAZEN_HTTP_RECORD";
+const REPLAY_VAR: &str = "RAZEN_HTTP_REPLAY";
+const MATCH_VAR: &str = "RAZEN_HTTP_MATCH";
+
+// Credentials are not written to recordings unless a match rule needs them
+const REDACTED_HEADERS: &[&str] = &["authorization", "proxy-authorization", "cookie", "x-api-key"];
+const REDACTED: &str = "[redacted]";
+
+lazy_static::lazy_static! {
+    // Recordings loaded from each replay directory, and which have been answered
+    static ref REPLAYS: Mutex<HashMap<PathBuf, Vec<Recording>>> = Mutex::new(HashMap::new());
+    // How often each file name has been recorded in this run
+    static ref RECORDED: Mutex<HashMap<String, usize>> = Mutex::new(HashMap::new());
+}
+
+#[cfg(test)]
+thread_local! {
+    // Tests run in parallel, so they set the mode per thread instead of through the environment
+    static TEST_MODE: std::cell::RefCell<Option<Mode>> = const { std::cell::RefCell::new(None) };
+}
+
+#[derive(Clone)]
+enum Mode {
+    Live,
+    Record(PathBuf),
+    Replay(PathBuf),
+}
+
+fn mode() -> Mode {
+    #[cfg(test)]
+    if let Some(mode) = TEST_MODE.with(|mode| mode.borrow().clone()) {
+        return mode;
+    }
+    let directory = |name| std::env::var_os(name).filter(|dir| !dir.is_empty()).map(PathBuf::from);
+    // Replaying wins, so a stray RAZEN_HTTP_RECORD never lets a test reach the network
+    if let Some(dir) = directory(REPLAY_VAR) {
+        Mode::Replay(dir)
+    } else if let Some(dir) = directory(RECORD_VAR) {
+        Mode::Record(dir)
+    } else {
+        Mode::Live
+    }
+}
+
+/// A response, either from the network or from a recording
+pub struct Reply {
+    pub status: StatusCode,
+    pub headers: Vec<(String, String)>,
+    body: Box<dyn Read + Send>,
+}
+
+impl Reply {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn content_length(&self) -> Option<u64> {
+        self.header("content-length").and_then(|length| length.trim().parse().ok())
+    }
+
+    pub fn text(mut self) -> Result<String, String> {
+        let mut body = Vec::new();
+        self.body.read_to_end(&mut body)
+            .map_err(|e| format!("Failed to read response body: {}", e))?;
+        Ok(String::from_utf8_lossy(&body).into_owned())
+    }
+}
+
+impl Read for Reply {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.body.read(buf)
+    }
+}
+
+pub enum SendError {
+    // The request failed on the network
+    Http(reqwest::Error),
+    // A recording could not be written, read or found
+    Fixture(String),
+}
+
+impl fmt::Display for SendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendError::Http(e) => write!(f, "{}", e),
+            SendError::Fixture(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+/// Send a request, or record or replay it as the environment asks. Replayed
+/// Set-Cookie headers go into `cookies`, as the client would have stored them
+pub fn execute(client: &Client, request: Request, cookies: Option<&Jar>) -> Result<Reply, SendError> {
+    match mode() {
+        Mode::Live => {
+            let response = client.execute(request).map_err(SendError::Http)?;
+            let status = response.status();
+            let headers = header_pairs(response.headers());
+            Ok(Reply { status, headers, body: Box::new(response) })
+        }
+        Mode::Record(dir) => {
+            let recorded = RecordedRequest::from_request(&request);
+            let mut response = client.execute(request).map_err(SendError::Http)?;
+            let status = response.status();
+            let headers = header_pairs(response.headers());
+            let mut body = Vec::new();
+            response.read_to_end(&mut body)
+                .map_err(|e| SendError::Fixture(format!("Failed to read response body: {}", e)))?;
+            record(&dir, &recorded, status, &headers, &body).map_err(SendError::Fixture)?;
+            Ok(Reply { status, headers, body: Box::new(Cursor::new(body)) })
+        }
+        Mode::Replay(dir) => {
+            let recorded = RecordedRequest::from_request(&request);
+            let reply = replay(&dir, &recorded).map_err(SendError::Fixture)?;
+            if let Some(jar) = cookies {
+                let set_cookies: Vec<HeaderValue> = reply.headers.iter()
+                    .filter(|(name, _)| name.eq_ignore_ascii_case("set-cookie"))
+                    .filter_map(|(_, value)| HeaderValue::from_str(value).ok())
+                    .collect();
+                jar.set_cookies(&mut set_cookies.iter(), request.url());
+            }
+            Ok(reply)
+        }
+    }
+}
+
+fn header_pairs(headers: &reqwest::header::HeaderMap) -> Vec<(String, String)> {
+    headers.iter()
+        .filter_map(|(name, value)| Some((name.as_str().to_string(), value.to_str().ok()?.to_string())))
+        .collect()
+}
+
+// The parts of a request recordings are matched on
+struct RecordedRequest {
+    method: String,
+    url: String,
+    headers: HashMap<String, String>,
+    body: Option<String>,
+}
+
+impl RecordedRequest {
+    fn from_request(request: &Request) -> Self {
+        let headers = request.headers().iter()
+            .filter_map(|(name, value)| Some((name.as_str().to_string(), value.to_str().ok()?.to_string())))
+            .collect();
+        // Streamed bodies such as file uploads are not recorded
+        let body = request.body()
+            .and_then(|body| body.as_bytes())
+            .map(|bytes| String::from_utf8_lossy(bytes).into_owned());
+        RecordedRequest { method: request.method().as_str().to_string(), url: normalize_url(request.url()), headers, body }
+    }
+
+    fn describe(&self) -> String {
+        format!("{} {}", self.method, self.url)
+    }
+}
+
+// Sort the query so that parameter order does not affect matching
+fn normalize_url(url: &Url) -> String {
+    let mut url = url.clone();
+    let mut pairs: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+    if pairs.is_empty() {
+        url.set_query(None);
+    } else {
+        pairs.sort();
+        url.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+    url.to_string()
+}
+
+struct Recording {
+    file: PathBuf,
+    request: RecordedRequest,
+    rules: Vec<String>,
+    status: StatusCode,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    answered: bool,
+}
+
+impl Recording {
+    // Why this recording does not answer a request, or None when it does
+    fn mismatch(&self, request: &RecordedRequest) -> Option<String> {
+        if !self.request.method.eq_ignore_ascii_case(&request.method) || self.request.url != request.url {
+            return Some(format!("{} is for {}", self.file.display(), self.request.describe()));
+        }
+        for rule in &self.rules {
+            if rule == "body" {
+                if self.request.body != request.body {
+                    return Some(format!("{} has a different body", self.file.display()));
+                }
+            } else if self.request.headers.get(rule) != request.headers.get(rule) {
+                return Some(format!("{} has a different {} header", self.file.display(), rule));
+            }
+        }
+        None
+    }
+}
+
+fn match_rules() -> Vec<String> {
+    std::env::var(MATCH_VAR).unwrap_or_default()
+        .split(',')
+        .map(|rule| rule.trim().to_lowercase())
+        .filter(|rule| !rule.is_empty())
+        .collect()
+}
+
+fn record(dir: &Path, request: &RecordedRequest, status: StatusCode, headers: &[(String, String)], body: &[u8]) -> Result<(), String> {
+    let rules = match_rules();
+    let request_headers: serde_json::Map<String, JsonValue> = request.headers.iter()
+        .map(|(name, value)| {
+            let value = if REDACTED_HEADERS.contains(&name.as_str()) && !rules.contains(name) { REDACTED } else { value };
+            (name.clone(), JsonValue::String(value.to_string()))
+        })
+        .collect();
+    let response_headers: Vec<JsonValue> = headers.iter().map(|(name, value)| json!([name, value])).collect();
+    let mut response = json!({ "status": status.as_u16(), "headers": response_headers });
+    match std::str::from_utf8(body) {
+        Ok(text) => response["body"] = JsonValue::String(text.to_string()),
+        Err(_) => response["body_base64"] = JsonValue::String(general_purpose::STANDARD.encode(body)),
+    }
+    let recording = json!({
+        "request": {
+            "method": request.method,
+            "url": request.url,
+            "headers": request_headers,
+            "body": request.body,
+        },
+        "match": rules,
+        "response": response,
+    });
+
+    let base = fixture_name(request);
+    let count = {
+        let mut recorded = RECORDED.lock().unwrap();
+        let count = recorded.entry(dir.join(&base).display().to_string()).or_insert(0);
+        *count += 1;
+        *count
+    };
+
+    fs::create_dir_all(dir)
+        .map_err(|e| format!("{}: Cannot create {}: {}", RECORD_VAR, dir.display(), e))?;
+    let file = dir.join(format!("{}-{}.json", base, count));
+    let text = serde_json::to_string_pretty(&recording).map_err(|e| e.to_string())?;
+    fs::write(&file, text + "\n")
+        .map_err(|e| format!("{}: Cannot write {}: {}", RECORD_VAR, file.display(), e))
+}
+
+// Same request, same name: recording again replaces the old files. The name ends with
+// the start of a SHA-256 digest so it stays the same across Rust releases and systems.
+fn fixture_name(request: &RecordedRequest) -> String {
+    let mut digest = Sha256::new();
+    for part in [Some(&request.method), Some(&request.url), request.body.as_ref()] {
+        match part {
+            Some(text) => {
+                digest.update([1]);
+                digest.update(text.as_bytes());
+            }
+            None => digest.update([0]),
+        }
+    }
+    let hash: String = digest.finalize()[..4].iter().map(|byte| format!("{:02x}", byte)).collect();
+
+    let slug: String = request.url.split_once("://").map_or(request.url.as_str(), |(_, rest)| rest)
+        .split(['?', '#']).next().unwrap_or_default()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    let slug: String = slug.trim_matches('-').chars().take(60).collect();
+    format!("{}-{}-{}", request.method, slug, hash)
+}
+
+fn replay(dir: &Path, request: &RecordedRequest) -> Result<Reply, String> {
+    let mut replays = REPLAYS.lock().unwrap();
+    if !replays.contains_key(dir) {
+        replays.insert(dir.to_path_buf(), load_recordings(dir)?);
+    }
+    let recordings = replays.get_mut(dir).unwrap();
+
+    let matching: Vec<usize> = (0..recordings.len())
+        .filter(|&i| recordings[i].mismatch(request).is_none())
+        .collect();
+    let chosen = matching.iter().copied().find(|&i| !recordings[i].answered).or(matching.last().copied());
+    let Some(index) = chosen else {
+        // Explain the near misses: recordings for the same URL
+        let near: Vec<String> = recordings.iter()
+            .filter(|recording| recording.request.url == request.url)
+            .filter_map(|recording| recording.mismatch(request))
+            .take(5)
+            .collect();
+        let mut message = format!("{}: No recording in {} matches {}", REPLAY_VAR, dir.display(), request.describe());
+        if near.is_empty() {
+            message.push_str(&format!(" ({} recordings, none for this URL)", recordings.len()));
+        } else {
+            message.push_str(&format!(" ({})", near.join("; ")));
+        }
+        return Err(message);
+    };
+
+    let recording = &mut recordings[index];
+    recording.answered = true;
+    Ok(Reply {
+        status: recording.status,
+        headers: recording.headers.clone(),
+        body: Box::new(Cursor::new(recording.body.clone())),
+    })
+}
+
+fn load_recordings(dir: &Path) -> Result<Vec<Recording>, String> {
+    let entries = fs::read_dir(dir)
+        .map_err(|e| format!("{}: Cannot read {}: {}", REPLAY_VAR, dir.display(), e))?;
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    // Repeats of a request are answered in the order of their numbered names
+    files.sort_by_key(|file| {
+        let stem = file.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+        match stem.rsplit_once('-').map(|(base, count)| (base.to_string(), count.parse::<usize>())) {
+            Some((base, Ok(count))) => (base, count),
+            _ => (stem, 0),
+        }
+    });
+    files.iter().map(|file| load_recording(file)).collect()
+}
+
+fn load_recording(file: &Path) -> Result<Recording, String> {
+    let invalid = |reason: &str| format!("{}: Invalid recording {}: {}", REPLAY_VAR, file.display(), reason);
+    let text = fs::read_to_string(file).map_err(|e| invalid(&e.to_string()))?;
+    let json: JsonValue = serde_json::from_str(&text).map_err(|e| invalid(&e.to_string()))?;
+    let (request, response) = (&json["request"], &json["response"]);
+
+    let method = request["method"].as_str().ok_or_else(|| invalid("request.method is missing"))?;
+    let url = request["url"].as_str().ok_or_else(|| invalid("request.url is missing"))?;
+    let url = Url::parse(url).map_err(|e| invalid(&format!("request.url: {}", e)))?;
+    let headers = request["headers"].as_object()
+        .map(|headers| headers.iter()
+            .filter_map(|(name, value)| Some((name.to_lowercase(), value.as_str()?.to_string())))
+            .collect())
+        .unwrap_or_default();
+    let rules = json["match"].as_array()
+        .map(|rules| rules.iter().filter_map(|rule| rule.as_str()).map(str::to_lowercase).collect())
+        .unwrap_or_default();
+
+    let status = response["status"].as_u64()
+        .and_then(|status| StatusCode::from_u16(status as u16).ok())
+        .ok_or_else(|| invalid("response.status is missing"))?;
+    let response_headers = response["headers"].as_array()
+        .map(|headers| headers.iter()
+            .filter_map(|pair| Some((pair[0].as_str()?.to_string(), pair[1].as_str()?.to_string())))
+            .collect())
+        .unwrap_or_default();
+    let body = match (response["body"].as_str(), response["body_base64"].as_str()) {
+        (_, Some(encoded)) => general_purpose::STANDARD.decode(encoded).map_err(|e| invalid(&format!("response.body_base64: {}", e)))?,
+        (Some(text), None) => text.as_bytes().to_vec(),
+        (None, None) => Vec::new(),
+    };
+
+    Ok(Recording {
+        file: file.to_path_buf(),
+        request: RecordedRequest {
+            method: method.to_uppercase(),
+            url: normalize_url(&url),
+            headers,
+            body: request["body"].as_str().map(str::to_string),
+        },
+        rules,
+        status,
+        headers: response_headers,
+        body,
+        answered: false,
+    })
+}
+
+#[cfg(test)]
+pub(crate) fn with_mode<T>(record: Option<&Path>, replay: Option<&Path>, f: impl FnOnce() -> T) -> T {
+    let mode = match (replay, record) {
+        (Some(dir), _) => Mode::Replay(dir.to_path_buf()),
+        (None, Some(dir)) => Mode::Record(dir.to_path_buf()),
+        (None, None) => Mode::Live,
+    };
+    TEST_MODE.with(|current| *current.borrow_mut() = Some(mode));
+    let result = f();
+    TEST_MODE.with(|current| *current.borrow_mut() = None);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, url: &str, body: Option<&str>) -> RecordedRequest {
+        RecordedRequest { method: method.to_string(), url: url.to_string(), headers: HashMap::new(), body: body.map(str::to_string) }
+    }
+
+    #[test]
+    fn test_fixture_names_are_stable() {
+        assert_eq!(fixture_name(&request("GET", "http://127.0.0.1:8080/users?page=2", None)), "GET-127-0-0-1-8080-users-724ce9f8");
+        assert_eq!(fixture_name(&request("POST", "http://127.0.0.1:8080/users", Some("{\"name\":\"Ada\"}"))), "POST-127-0-0-1-8080-users-2ca87eb6");
+        assert_eq!(fixture_name(&request("POST", "http://127.0.0.1:8080/users", Some(""))), "POST-127-0-0-1-8080-users-322d488f");
+    }
+}
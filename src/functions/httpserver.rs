@@ -0,0 +1,850 @@
+use crate::library::{CallContext, ScriptRunner};
+use crate::value::Value;
+use super::json::{json_to_razen_value, razen_value_to_json};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+// Requests are handled one at a time on the thread running the script, since route
+// handlers are Razen functions. serve() polls a non-blocking listener so that stop()
+// and Ctrl-C are noticed between requests; the request being handled always finishes.
+
+// Limits for a single request
+const MAX_HEADER_BYTES: usize = 64 * 1024;
+const MAX_BODY_BYTES: usize = 10 * 1024 * 1024;
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+// How long serve() sleeps when no connection is waiting
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+lazy_static::lazy_static! {
+    // Servers handed out to scripts by ID
+    static ref SERVERS: Mutex<Servers> = Mutex::new(Servers { next_id: 1, servers: HashMap::new() });
+}
+
+// Set by SIGINT/SIGTERM while serve() runs
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    // Response set by json(), response() or redirect() in the running handler;
+    // the outer Option is None outside of handlers
+    static CURRENT_RESPONSE: RefCell<Option<Option<Response>>> = const { RefCell::new(None) };
+}
+
+struct Servers {
+    next_id: i64,
+    servers: HashMap<i64, Server>,
+}
+
+struct Server {
+    listener: TcpListener,
+    routes: Vec<Route>,
+    mounts: Vec<(Vec<String>, PathBuf)>, // URL prefix segments and directory of static files
+    serving: bool,
+    stopping: bool,
+}
+
+struct Route {
+    method: String,
+    pattern: Vec<Segment>,
+    handler: String,
+}
+
+#[derive(Debug, PartialEq)]
+enum Segment {
+    Literal(String),
+    Param(String),
+    Rest(String),
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Response {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn new(status: u16, content_type: &str, body: impl Into<Vec<u8>>) -> Response {
+        Response { status, headers: vec![("Content-Type".to_string(), content_type.to_string())], body: body.into() }
+    }
+
+    fn text(status: u16, body: &str) -> Response {
+        Response::new(status, "text/plain; charset=utf-8", body)
+    }
+
+    fn empty(status: u16) -> Response {
+        Response { status, headers: Vec::new(), body: Vec::new() }
+    }
+
+    fn write_to(&self, stream: &mut TcpStream, head_only: bool) -> std::io::Result<()> {
+        let mut head = format!("HTTP/1.1 {} {}\r\n", self.status, reason(self.status));
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str(&format!("Content-Length: {}\r\nConnection: close\r\n\r\n", self.body.len()));
+        stream.write_all(head.as_bytes())?;
+        if !head_only {
+            stream.write_all(&self.body)?;
+        }
+        stream.flush()
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        303 => "See Other",
+        304 => "Not Modified",
+        307 => "Temporary Redirect",
+        308 => "Permanent Redirect",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        411 => "Length Required",
+        413 => "Payload Too Large",
+        422 => "Unprocessable Entity",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        503 => "Service Unavailable",
+        _ => "",
+    }
+}
+
+fn server_id(value: &Value) -> Result<i64, String> {
+    value.as_int().map_err(|_| format!("Invalid server: {}", value))
+}
+
+// Run `f` with a server, failing for unknown or stopped servers
+fn with_server<T>(id: i64, f: impl FnOnce(&mut Server) -> Result<T, String>) -> Result<T, String> {
+    let mut servers = SERVERS.lock().unwrap();
+    let server = servers.servers.get_mut(&id).ok_or_else(|| format!("Server {} does not exist or was stopped", id))?;
+    f(server)
+}
+
+/// Create a server listening on a port (0 picks a free one) of a host (127.0.0.1 by default)
+/// Example: create(8080) => 1
+/// Example: create(0, "0.0.0.0") => 2
+pub fn create(args: Vec<Value>) -> Result<Value, String> {
+    let port = match args.first() {
+        Some(port) => port.as_int()?,
+        None => 0,
+    };
+    let host = match args.get(1) {
+        Some(host) => host.as_string()?,
+        None => "127.0.0.1".to_string(),
+    };
+    if !(0..=65535).contains(&port) {
+        return Err(format!("Invalid port {}", port));
+    }
+
+    let listener = TcpListener::bind((host.as_str(), port as u16))
+        .map_err(|e| format!("Failed to listen on {}:{}: {}", host, port, e))?;
+    listener.set_nonblocking(true).map_err(|e| format!("Failed to set up server: {}", e))?;
+
+    let mut servers = SERVERS.lock().unwrap();
+    let id = servers.next_id;
+    servers.next_id += 1;
+    servers.servers.insert(id, Server { listener, routes: Vec::new(), mounts: Vec::new(), serving: false, stopping: false });
+    Ok(Value::Int(id))
+}
+
+/// Get the port a server listens on, e.g. the one picked for port 0
+/// Example: port(1) => 8080
+pub fn port(args: Vec<Value>) -> Result<Value, String> {
+    let id = server_id(&args[0])?;
+    with_server(id, |server| {
+        let address = server.listener.local_addr().map_err(|e| format!("Failed to read server address: {}", e))?;
+        Ok(Value::Int(address.port() as i64))
+    })
+}
+
+/// Handle requests for a method ("GET", "POST", ... or "*" for any) and path with a Razen function.
+/// ":name" segments match one path segment and "*name" the rest of the path; both end up in request.params.
+/// The function gets the request map: method, path, params, query, headers, body and data (the parsed JSON or form body)
+/// Example: route(1, "GET", "/users/:id", "get_user") => true
+pub fn route(args: Vec<Value>) -> Result<Value, String> {
+    let id = server_id(&args[0])?;
+    let method = args[1].as_string()?.to_uppercase();
+    let path = args[2].as_string()?;
+    let handler = args[3].as_string()?;
+    let pattern = parse_pattern(&path)?;
+
+    with_server(id, |server| {
+        server.routes.push(Route { method, pattern, handler });
+        Ok(Value::Bool(true))
+    })
+}
+
+/// Serve the files of a directory (relative to the script) under a URL prefix, answering GET and HEAD requests that no route handles
+/// Example: static_files(1, "/assets", "public") => true
+pub fn static_files(context: &CallContext, args: Vec<Value>) -> Result<Value, String> {
+    let id = server_id(&args[0])?;
+    let prefix = segments(&args[1].as_string()?);
+    let mut directory = PathBuf::from(args[2].as_string()?);
+    if let Some(script_dir) = context.script().and_then(Path::parent) {
+        if directory.is_relative() {
+            directory = script_dir.join(directory);
+        }
+    }
+    let directory = directory.canonicalize()
+        .map_err(|e| format!("Cannot serve files from {}: {}", directory.display(), e))?;
+    if !directory.is_dir() {
+        return Err(format!("Cannot serve files from {}: not a directory", directory.display()));
+    }
+
+    with_server(id, |server| {
+        server.mounts.push((prefix, directory));
+        Ok(Value::Bool(true))
+    })
+}
+
+/// Handle requests until stop() is called, the script is interrupted with Ctrl-C, or max_requests have been answered.
+/// Returns the number of requests answered
+/// Example: serve(1) => 12
+/// Example: serve(1, 1) => 1
+pub fn serve(context: &CallContext, args: Vec<Value>) -> Result<Value, String> {
+    let id = server_id(&args[0])?;
+    let limit = match args.get(1) {
+        Some(limit) => Some(limit.as_int()?.max(0) as usize),
+        None => None,
+    };
+    let runner = context.script_runner()
+        .ok_or_else(|| "HttpServer.serve can only be called from a Razen script".to_string())?;
+
+    with_server(id, |server| {
+        if server.serving {
+            return Err(format!("Server {} is already serving", id));
+        }
+        server.serving = true;
+        Ok(())
+    })?;
+    let previous = watch_interrupts();
+    let result = serve_requests(id, &runner, limit);
+    restore_interrupts(previous);
+
+    let mut servers = SERVERS.lock().unwrap();
+    let stopping = servers.servers.get(&id).is_some_and(|server| server.stopping);
+    // Stopping closes the listener; otherwise serve() can be called again
+    if stopping || INTERRUPTED.swap(false, Ordering::SeqCst) {
+        servers.servers.remove(&id);
+    } else if let Some(server) = servers.servers.get_mut(&id) {
+        server.serving = false;
+    }
+    result.map(|count| Value::Int(count as i64))
+}
+
+fn serve_requests(id: i64, runner: &ScriptRunner, limit: Option<usize>) -> Result<usize, String> {
+    let mut answered = 0;
+    while limit.is_none_or(|limit| answered < limit) && !INTERRUPTED.load(Ordering::SeqCst) {
+        let accepted = with_server(id, |server| {
+            if server.stopping {
+                return Ok(None);
+            }
+            match server.listener.accept() {
+                Ok((stream, _)) => Ok(Some(Some(stream))),
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => Ok(Some(None)),
+                Err(e) => Err(format!("Failed to accept connection: {}", e)),
+            }
+        })?;
+        match accepted {
+            None => break,
+            Some(Some(stream)) => {
+                handle_connection(id, stream, runner);
+                answered += 1;
+            }
+            Some(None) => std::thread::sleep(POLL_INTERVAL),
+        }
+    }
+    Ok(answered)
+}
+
+/// Stop a server once the request being handled is answered; serve() then returns and the port is released
+/// Example: stop(1) => true
+pub fn stop(args: Vec<Value>) -> Result<Value, String> {
+    let id = server_id(&args[0])?;
+    let mut servers = SERVERS.lock().unwrap();
+    let serving = match servers.servers.get_mut(&id) {
+        Some(server) => {
+            server.stopping = true;
+            server.serving
+        }
+        None => return Ok(Value::Bool(false)),
+    };
+    if !serving {
+        servers.servers.remove(&id);
+    }
+    Ok(Value::Bool(true))
+}
+
+/// Answer the current request with a value encoded as JSON (status 200 unless given)
+/// Example: json({"id": 7, "name": "Ada"}) => true
+/// Example: json({"error": "not found"}, 404) => true
+pub fn json(args: Vec<Value>) -> Result<Value, String> {
+    let status = status_arg(args.get(1), 200)?;
+    let body = serde_json::to_string(&razen_value_to_json(&args[0])?)
+        .map_err(|e| format!("Failed to encode JSON response: {}", e))?;
+    set_response(Response::new(status, "application/json", body))
+}
+
+/// Answer the current request with a body, status (200) and headers; the content type is text/plain unless a header sets it
+/// Example: response("<h1>Hi</h1>", 200, {"Content-Type": "text/html"}) => true
+pub fn response(args: Vec<Value>) -> Result<Value, String> {
+    let status = status_arg(args.get(1), 200)?;
+    let mut response = Response::text(status, &body_text(&args[0]));
+    if let Some(headers) = args.get(2) {
+        for (name, value) in headers.as_map()? {
+            response.headers.retain(|(existing, _)| !existing.eq_ignore_ascii_case(&name));
+            response.headers.push((name, value.as_string()?));
+        }
+    }
+    set_response(response)
+}
+
+/// Answer the current request with a redirect to another location (status 302 unless given)
+/// Example: redirect("/login") => true
+pub fn redirect(args: Vec<Value>) -> Result<Value, String> {
+    let status = status_arg(args.get(1), 302)?;
+    if !(300..400).contains(&status) {
+        return Err(format!("Redirects need a 3xx status, got {}", status));
+    }
+    let mut response = Response::empty(status);
+    response.headers.push(("Location".to_string(), args[0].as_string()?));
+    set_response(response)
+}
+
+fn status_arg(value: Option<&Value>, default: u16) -> Result<u16, String> {
+    match value {
+        None | Some(Value::Null) => Ok(default),
+        Some(value) => {
+            let status = value.as_int()?;
+            if (100..=599).contains(&status) {
+                Ok(status as u16)
+            } else {
+                Err(format!("Invalid HTTP status {}", status))
+            }
+        }
+    }
+}
+
+fn body_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        value => value.to_string(),
+    }
+}
+
+fn set_response(response: Response) -> Result<Value, String> {
+    CURRENT_RESPONSE.with(|current| match current.borrow_mut().as_mut() {
+        Some(slot) => {
+            *slot = Some(response);
+            Ok(Value::Bool(true))
+        }
+        None => Err("Responses can only be set inside a route handler".to_string()),
+    })
+}
+
+fn handle_connection(id: i64, mut stream: TcpStream, runner: &ScriptRunner) {
+    // The listener is non-blocking, but reading a request may wait for the client
+    let _ = stream.set_nonblocking(false);
+    let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+    let request = match read_request(&mut stream) {
+        Ok(request) => request,
+        Err(response) => {
+            let _ = response.write_to(&mut stream, false);
+            return;
+        }
+    };
+    let response = respond(id, &request, runner);
+    // A client that went away does not stop the server
+    let _ = response.write_to(&mut stream, request.method == "HEAD");
+}
+
+fn read_request(stream: &mut TcpStream) -> Result<Request, Response> {
+    let mut reader = BufReader::new(stream);
+    let mut head = Vec::new();
+    loop {
+        let mut line = Vec::new();
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) if head.is_empty() => return Err(Response::text(400, "Empty request")),
+            Ok(0) => return Err(Response::text(400, "Incomplete request")),
+            Ok(_) => {}
+            Err(e) => return Err(Response::text(400, &format!("Failed to read request: {}", e))),
+        }
+        let end = line == b"\r\n" || line == b"\n";
+        head.extend_from_slice(&line);
+        if head.len() > MAX_HEADER_BYTES {
+            return Err(Response::text(431, "Request headers too large"));
+        }
+        if end {
+            break;
+        }
+    }
+
+    let head = String::from_utf8_lossy(&head);
+    let mut lines = head.lines();
+    let request_line = lines.next().unwrap_or_default();
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target), Some(_version)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(Response::text(400, "Malformed request line"));
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    let mut headers = Vec::new();
+    for line in lines.take_while(|line| !line.is_empty()) {
+        let Some((name, value)) = line.split_once(':') else {
+            return Err(Response::text(400, "Malformed header"));
+        };
+        headers.push((name.trim().to_lowercase(), value.trim().to_string()));
+    }
+    let mut request = Request {
+        method: method.to_uppercase(),
+        path: path.to_string(),
+        query: query.to_string(),
+        headers,
+        body: Vec::new(),
+    };
+
+    if request.header("transfer-encoding").is_some() {
+        return Err(Response::text(411, "Chunked request bodies are not supported; send a Content-Length"));
+    }
+    let length = match request.header("content-length") {
+        Some(length) => length.parse::<usize>().map_err(|_| Response::text(400, "Invalid Content-Length"))?,
+        None => 0,
+    };
+    if length > MAX_BODY_BYTES {
+        return Err(Response::text(413, "Request body too large"));
+    }
+    request.body = vec![0; length];
+    reader.read_exact(&mut request.body)
+        .map_err(|e| Response::text(400, &format!("Failed to read request body: {}", e)))?;
+    Ok(request)
+}
+
+// What a route or static file gives for a request
+fn respond(id: i64, request: &Request, runner: &ScriptRunner) -> Response {
+    let path = segments(&request.path);
+    let found = with_server(id, |server| Ok(find_route(server, &request.method, &path)));
+    match found {
+        Ok(Found::Route(handler, params)) => run_handler(request, &handler, params, runner),
+        Ok(Found::File(file)) => serve_file(&file),
+        Ok(Found::NotAllowed(methods)) => {
+            let mut response = Response::text(405, "Method Not Allowed");
+            response.headers.push(("Allow".to_string(), methods.join(", ")));
+            response
+        }
+        Ok(Found::Nothing) => Response::text(404, "Not Found"),
+        Err(e) => Response::text(503, &e),
+    }
+}
+
+enum Found {
+    Route(String, HashMap<String, Value>),
+    File(PathBuf),
+    NotAllowed(Vec<String>),
+    Nothing,
+}
+
+fn find_route(server: &Server, method: &str, path: &[String]) -> Found {
+    let mut allowed = Vec::new();
+    for route in &server.routes {
+        if let Some(params) = match_pattern(&route.pattern, path) {
+            if route.method == "*" || route.method == method || (method == "HEAD" && route.method == "GET") {
+                return Found::Route(route.handler.clone(), params);
+            }
+            allowed.push(route.method.clone());
+        }
+    }
+
+    if method == "GET" || method == "HEAD" {
+        for (prefix, directory) in &server.mounts {
+            if let Some(file) = path.strip_prefix(prefix.as_slice()).and_then(|rest| resolve_file(directory, rest)) {
+                return Found::File(file);
+            }
+        }
+    }
+    match allowed.is_empty() {
+        true => Found::Nothing,
+        false => Found::NotAllowed(allowed),
+    }
+}
+
+fn run_handler(request: &Request, handler: &str, params: HashMap<String, Value>, runner: &ScriptRunner) -> Response {
+    let request_value = match request_value(request, params) {
+        Ok(value) => value,
+        Err(message) => return Response::text(400, &message),
+    };
+
+    let previous = CURRENT_RESPONSE.with(|current| current.replace(Some(None)));
+    let result = runner(handler, vec![script_text(&request_value)]);
+    let set = CURRENT_RESPONSE.with(|current| current.replace(previous)).flatten();
+
+    match (result, set) {
+        (Err(e), _) => {
+            eprintln!("Error in route handler {} for {} {}: {}", handler, request.method, request.path, e);
+            Response::text(500, "Internal Server Error")
+        }
+        (Ok(_), Some(response)) => response,
+        (Ok(value), None) if value == "null" || value == "undefined" => Response::empty(204),
+        (Ok(value), None) => Response::text(200, &value),
+    }
+}
+
+// The map route handlers receive
+fn request_value(request: &Request, params: HashMap<String, Value>) -> Result<Value, String> {
+    let body = String::from_utf8_lossy(&request.body).into_owned();
+    let content_type = request.header("content-type").unwrap_or_default();
+    let data = if content_type.starts_with("application/json") && !body.trim().is_empty() {
+        let parsed = serde_json::from_str(&body).map_err(|e| format!("Invalid JSON body: {}", e))?;
+        json_to_razen_value(parsed)?
+    } else if content_type.starts_with("application/x-www-form-urlencoded") {
+        form_map(&body)
+    } else {
+        Value::Null
+    };
+
+    let headers = request.headers.iter()
+        .map(|(name, value)| (name.clone(), Value::String(value.clone())))
+        .collect();
+    Ok(Value::Map(HashMap::from([
+        ("method".to_string(), Value::String(request.method.clone())),
+        ("path".to_string(), Value::String(request.path.clone())),
+        ("params".to_string(), Value::Map(params)),
+        ("query".to_string(), form_map(&request.query)),
+        ("headers".to_string(), Value::Map(headers)),
+        ("body".to_string(), Value::String(body)),
+        ("data".to_string(), data),
+    ])))
+}
+
+// Query strings and form bodies; a key given more than once maps to an array of its values
+fn form_map(text: &str) -> Value {
+    let mut map: HashMap<String, Value> = HashMap::new();
+    for (key, value) in form_urlencoded::parse(text.as_bytes()) {
+        let value = Value::String(value.into_owned());
+        match map.remove(key.as_ref()) {
+            Some(Value::Array(mut values)) => {
+                values.push(value);
+                map.insert(key.into_owned(), Value::Array(values));
+            }
+            Some(first) => {
+                map.insert(key.into_owned(), Value::Array(vec![first, value]));
+            }
+            None => {
+                map.insert(key.into_owned(), value);
+            }
+        }
+    }
+    Value::Map(map)
+}
+
+/// Text of a value as the interpreter stores it. Strings that would break up the
+/// surrounding map or array (commas, brackets, colons, quotes) are quoted.
+pub fn script_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => {
+            let plain = !text.is_empty()
+                && text.trim() == text
+                && !text.contains([',', ':', '"', '[', ']', '{', '}', '\n', '\r']);
+            match plain {
+                true => text.clone(),
+                false => serde_json::to_string(text).unwrap(),
+            }
+        }
+        Value::Array(items) => format!("[{}]", items.iter().map(script_text).collect::<Vec<_>>().join(", ")),
+        Value::Map(map) => {
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            let entries: Vec<String> = entries.into_iter()
+                .map(|(key, value)| format!("{}: {}", key, script_text(value)))
+                .collect();
+            format!("{{{}}}", entries.join(", "))
+        }
+        other => other.to_string(),
+    }
+}
+
+fn parse_pattern(path: &str) -> Result<Vec<Segment>, String> {
+    let parts = segments(path);
+    let mut pattern = Vec::with_capacity(parts.len());
+    for (index, part) in parts.iter().enumerate() {
+        pattern.push(if let Some(name) = part.strip_prefix(':') {
+            Segment::Param(name.to_string())
+        } else if let Some(name) = part.strip_prefix('*') {
+            if index + 1 != parts.len() {
+                return Err(format!("'*{}' must be the last segment of route {}", name, path));
+            }
+            Segment::Rest(if name.is_empty() { "rest".to_string() } else { name.to_string() })
+        } else {
+            Segment::Literal(part.clone())
+        });
+    }
+    Ok(pattern)
+}
+
+// Decoded, non-empty segments of a URL path
+fn segments(path: &str) -> Vec<String> {
+    path.split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(percent_decode)
+        .collect()
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let hex = bytes.get(index + 1..index + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[index], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn match_pattern(pattern: &[Segment], path: &[String]) -> Option<HashMap<String, Value>> {
+    let mut params = HashMap::new();
+    for (index, segment) in pattern.iter().enumerate() {
+        match segment {
+            Segment::Rest(name) => {
+                params.insert(name.clone(), Value::String(path.get(index..).unwrap_or_default().join("/")));
+                return Some(params);
+            }
+            Segment::Literal(literal) if path.get(index) == Some(literal) => {}
+            Segment::Param(name) => {
+                params.insert(name.clone(), Value::String(path.get(index)?.clone()));
+            }
+            Segment::Literal(_) => return None,
+        }
+    }
+    (pattern.len() == path.len()).then_some(params)
+}
+
+// A file under the directory, never outside of it; directories serve their index.html
+fn resolve_file(directory: &Path, rest: &[String]) -> Option<PathBuf> {
+    let mut path = directory.to_path_buf();
+    for segment in rest {
+        let mut components = Path::new(segment).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(name)), None) => path.push(name),
+            _ => return None,
+        }
+    }
+    if path.is_dir() {
+        path.push("index.html");
+    }
+    let path = path.canonicalize().ok()?;
+    (path.starts_with(directory) && path.is_file()).then_some(path)
+}
+
+fn serve_file(path: &Path) -> Response {
+    match fs::read(path) {
+        Ok(contents) => Response::new(200, content_type(path), contents),
+        Err(e) => Response::text(500, &format!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+fn content_type(path: &Path) -> &'static str {
+    let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+    match extension.to_lowercase().as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" => "application/json",
+        "txt" | "rzn" => "text/plain; charset=utf-8",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "ico" => "image/x-icon",
+        "wasm" => "application/wasm",
+        "pdf" => "application/pdf",
+        _ => "application/octet-stream",
+    }
+}
+
+extern "C" fn on_interrupt(_: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+// Turn Ctrl-C and SIGTERM into a graceful stop while serving
+fn watch_interrupts() -> [libc::sighandler_t; 2] {
+    INTERRUPTED.store(false, Ordering::SeqCst);
+    let handler = on_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    // SAFETY: the handler only stores to an atomic
+    unsafe { [libc::signal(libc::SIGINT, handler), libc::signal(libc::SIGTERM, handler)] }
+}
+
+fn restore_interrupts(previous: [libc::sighandler_t; 2]) {
+    // SAFETY: puts back the handlers that were installed before serve()
+    unsafe {
+        libc::signal(libc::SIGINT, previous[0]);
+        libc::signal(libc::SIGTERM, previous[1]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpStream;
+
+    #[test]
+    fn test_routes_match_params_and_rest() {
+        let pattern = parse_pattern("/files/:owner/*path").unwrap();
+        let params = match_pattern(&pattern, &segments("/files/ada/notes/a%20b.txt")).unwrap();
+        assert_eq!(params["owner"], Value::String("ada".to_string()));
+        assert_eq!(params["path"], Value::String("notes/a b.txt".to_string()));
+        assert!(match_pattern(&pattern, &segments("/files")).is_none());
+        assert!(match_pattern(&parse_pattern("/users/:id").unwrap(), &segments("/users/1/posts")).is_none());
+        assert!(parse_pattern("/*rest/more").is_err());
+
+        let request = Value::Map(HashMap::from([
+            ("query".to_string(), form_map("tag=a&tag=b%2Cc&page=2")),
+            ("body".to_string(), Value::String("{\"x\": 1}".to_string())),
+        ]));
+        assert_eq!(script_text(&request), "{body: \"{\\\"x\\\": 1}\", query: {page: 2, tag: [a, \"b,c\"]}}");
+    }
+
+    // Send a request over a fresh connection and return the status line, headers and body
+    fn send(port: u16, request: &str) -> String {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_script_serves_loopback_requests() {
+        let dir = std::env::temp_dir().join(format!("razen_httpserver_test_{}", std::process::id()));
+        fs::create_dir_all(dir.join("public")).unwrap();
+        fs::write(dir.join("public/index.html"), "<h1>Home</h1>").unwrap();
+        fs::write(dir.join("secret.txt"), "hidden").unwrap();
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let run = crate::functions::run_script_in_background(&dir.join("server.rzn"), &format!(r#"lib httpserver;
+
+var server = HttpServer[create]({port});
+num hits = 0;
+
+fun count(request) {{
+    hits = hits + 1;
+    str reply = "hits " + hits;
+    return reply;
+}}
+
+fun get_user(request) {{
+    if (request.params.id == "0") {{
+        return HttpServer[json]({{"error": "no such user"}}, 404);
+    }}
+    return HttpServer[json]({{"id": request.params.id, "page": request.query.page}});
+}}
+
+fun create_user(request) {{
+    return HttpServer[json]({{"created": request.data.name}}, 201);
+}}
+
+fun old_home(request) {{
+    return HttpServer[redirect]("/");
+}}
+
+fun fail(request) {{
+    throw "broken";
+}}
+
+fun shutdown(request) {{
+    HttpServer[stop](server);
+    return "bye " + request.headers["x-name"];
+}}
+
+HttpServer[route](server, "GET", "/users/:id", "get_user");
+HttpServer[route](server, "POST", "/users", "create_user");
+HttpServer[route](server, "GET", "/home", "old_home");
+HttpServer[route](server, "GET", "/fail", "fail");
+HttpServer[route](server, "GET", "/count", "count");
+HttpServer[route](server, "POST", "/shutdown", "shutdown");
+HttpServer[static_files](server, "/", "public");
+show "served " + HttpServer[serve](server);
+show "final " + hits;
+show "reply " + reply;
+"#));
+        for _ in 0..500 {
+            if TcpStream::connect(("127.0.0.1", port)).is_ok() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        // The connection used to wait for the server counts as a request
+        let get = |path: &str| send(port, &format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path));
+
+        let user = get("/users/7?page=2");
+        assert!(user.starts_with("HTTP/1.1 200 OK\r\n"), "{}", user);
+        assert!(user.contains("Content-Type: application/json\r\n"));
+        assert!(user.ends_with("\r\n\r\n{\"id\":7,\"page\":2}"), "{}", user);
+        assert!(get("/users/0").starts_with("HTTP/1.1 404 Not Found\r\n"));
+
+        let body = "{\"name\": \"Ada Lovelace\"}";
+        let created = send(port, &format!("POST /users HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}", body.len(), body));
+        assert!(created.starts_with("HTTP/1.1 201 Created\r\n") && created.ends_with("{\"created\":\"Ada Lovelace\"}"), "{}", created);
+        let invalid = send(port, "POST /users HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: 4\r\n\r\n{bad");
+        assert!(invalid.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{}", invalid);
+
+        assert!(get("/").ends_with("\r\n\r\n<h1>Home</h1>"));
+        assert!(get("/home").contains("\r\nLocation: /\r\n"));
+        assert!(get("/../secret.txt").starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(get("/fail").starts_with("HTTP/1.1 500 Internal Server Error\r\n"));
+        // Handlers share the script's globals, from one request to the next and after serve,
+        // while variables they declare stay local
+        for expected in ["hits 1", "hits 2", "hits 3"] {
+            let counted = get("/count");
+            assert!(counted.ends_with(&format!("\r\n\r\n{}", expected)), "{}", counted);
+        }
+        let wrong_method = send(port, "DELETE /users/7 HTTP/1.1\r\n\r\n");
+        assert!(wrong_method.starts_with("HTTP/1.1 405 Method Not Allowed\r\n") && wrong_method.contains("\r\nAllow: GET\r\n"));
+
+        let bye = send(port, "POST /shutdown HTTP/1.1\r\nX-Name: test\r\n\r\n");
+        assert!(bye.ends_with("\r\n\r\nbye test"), "{}", bye);
+        let output = run.join().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(output.replace('\r', ""), "served 14\nfinal 3\nreply undefined\n");
+        // Stopping released the port
+        assert!(TcpStream::connect(("127.0.0.1", port)).is_err());
+    }
+}
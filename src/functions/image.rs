@@ -1,7 +1,109 @@
 use crate::value::Value;
+use super::color::{parse_color, to_hex};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, ImageFormat, Rgba, RgbaImage};
 use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::sync::Mutex;
 
-/// Loads an image from a file
+// Images are decoded into RGBA pixel buffers kept here. Scripts get a map with the
+// buffer's "image" id and its size; transforms return a new image, while set_pixel
+// and the draw functions change the image they are given.
+
+const DEFAULT_JPEG_QUALITY: u8 = 90;
+
+// Largest pixel buffer create() and resize() will make, 16384 x 16384 RGBA pixels
+const MAX_IMAGE_BYTES: u64 = 1 << 30;
+
+lazy_static::lazy_static! {
+    static ref IMAGES: Mutex<Images> = Mutex::new(Images { images: HashMap::new(), next_id: 1 });
+}
+
+struct Images {
+    images: HashMap<i64, RgbaImage>,
+    next_id: i64,
+}
+
+// Keep a pixel buffer and describe it to the script
+fn store(pixels: RgbaImage, extra: Vec<(&str, Value)>) -> Value {
+    let (width, height) = pixels.dimensions();
+    let mut images = IMAGES.lock().unwrap();
+    let id = images.next_id;
+    images.next_id += 1;
+    images.images.insert(id, pixels);
+
+    let mut image = HashMap::new();
+    image.insert("image".to_string(), Value::Int(id));
+    image.insert("width".to_string(), Value::Int(width as i64));
+    image.insert("height".to_string(), Value::Int(height as i64));
+    for (key, value) in extra {
+        image.insert(key.to_string(), value);
+    }
+    Value::Map(image)
+}
+
+// Images are passed around as the map from load() or create(), or just the id
+fn image_id(value: &Value) -> Result<i64, String> {
+    match value {
+        Value::Map(map) => match map.get("image") {
+            Some(id) => id.as_int(),
+            None => Err("Expected an image from Image.load or Image.create".to_string()),
+        },
+        Value::Int(id) => Ok(*id),
+        other => Err(format!("Expected an image, got {}", other)),
+    }
+}
+
+fn with_image<T>(value: &Value, f: impl FnOnce(&mut RgbaImage) -> Result<T, String>) -> Result<T, String> {
+    let id = image_id(value)?;
+    let mut images = IMAGES.lock().unwrap();
+    match images.images.get_mut(&id) {
+        Some(pixels) => f(pixels),
+        None => Err(format!("Invalid image: {}", id)),
+    }
+}
+
+// Make a new image from an existing one
+fn transform(value: &Value, f: impl FnOnce(&RgbaImage) -> Result<RgbaImage, String>) -> Result<Value, String> {
+    let pixels = with_image(value, |pixels| f(pixels))?;
+    Ok(store(pixels, Vec::new()))
+}
+
+fn dimension(value: &Value, name: &str) -> Result<u32, String> {
+    let size = value.as_int()?;
+    if size <= 0 || size > u32::MAX as i64 {
+        return Err(format!("{} must be a positive number, got {}", name, size));
+    }
+    Ok(size as u32)
+}
+
+// Refuse buffers that are too big to allocate rather than aborting the interpreter
+fn check_size(width: u32, height: u32) -> Result<usize, String> {
+    let bytes = (width as u64 * height as u64).saturating_mul(4);
+    if bytes > MAX_IMAGE_BYTES {
+        return Err(format!("Image of {}x{} pixels is too large (at most {} MiB of pixels)", width, height, MAX_IMAGE_BYTES >> 20));
+    }
+    let mut buffer: Vec<u8> = Vec::new();
+    buffer.try_reserve_exact(bytes as usize)
+        .map_err(|_| format!("Not enough memory for an image of {}x{} pixels", width, height))?;
+    Ok(bytes as usize)
+}
+
+fn format_name(format: ImageFormat) -> &'static str {
+    match format {
+        ImageFormat::Png => "png",
+        ImageFormat::Jpeg => "jpeg",
+        ImageFormat::Bmp => "bmp",
+        ImageFormat::Pnm => "ppm",
+        _ => "unknown",
+    }
+}
+
+/// Loads a PNG, JPEG, BMP or PPM image from a file
+/// Example: load("photo.png") => {"image": 1, "width": 800, "height": 600, "format": "png", "path": "photo.png"}
 pub fn load(args: Vec<Value>) -> Result<Value, String> {
     if args.len() != 1 {
         return Err("load() requires exactly 1 argument: path".to_string());
@@ -12,151 +114,423 @@ pub fn load(args: Vec<Value>) -> Result<Value, String> {
         _ => return Err("Path must be a string".to_string()),
     };
 
-    // In a real implementation, this would use an image processing library
-    // For now, we'll simulate loading an image by returning a map with metadata
-    let mut image_data = HashMap::new();
-    image_data.insert("path".to_string(), Value::String(path.clone()));
-    image_data.insert("width".to_string(), Value::Float(800.0));
-    image_data.insert("height".to_string(), Value::Float(600.0));
-    image_data.insert("format".to_string(), Value::String("png".to_string()));
-    
-    Ok(Value::Map(image_data))
+    // The contents decide the format, whatever the extension says
+    let reader = image::io::Reader::open(path)
+        .map_err(|e| format!("Cannot open image {}: {}", path, e))?
+        .with_guessed_format()
+        .map_err(|e| format!("Cannot read image {}: {}", path, e))?;
+    let format = reader.format();
+    let decoded = reader.decode().map_err(|e| format!("Cannot decode image {}: {}", path, e))?;
+
+    Ok(store(decoded.to_rgba8(), vec![
+        ("format", Value::String(format.map_or("unknown", format_name).to_string())),
+        ("path", Value::String(path.clone())),
+    ]))
+}
+
+/// Creates a blank image filled with a color (transparent when none is given)
+/// Example: create(64, 32, "#ffffff") => {"image": 2, "width": 64, "height": 32}
+/// Example: create(16, 16, [255, 0, 0]) => {"image": 3, "width": 16, "height": 16}
+pub fn create(args: Vec<Value>) -> Result<Value, String> {
+    let width = dimension(&args[0], "Width")?;
+    let height = dimension(&args[1], "Height")?;
+    let color = match args.get(2) {
+        Some(Value::Null) | None => [0, 0, 0, 0],
+        Some(color) => parse_color(color)?,
+    };
+    check_size(width, height)?;
+    Ok(store(RgbaImage::from_pixel(width, height, Rgba(color)), Vec::new()))
 }
 
-/// Saves an image to a file
+/// Saves an image to a file as PNG, JPEG, BMP or PPM, chosen by the format argument or the file extension.
+/// JPEG quality runs from 1 to 100 (default 90)
+/// Example: save(img, "out.png") => {"success": true, "path": "out.png", "message": "Image saved to out.png"}
+/// Example: save(img, "out.jpg", "jpeg", 75) => {"success": true, "path": "out.jpg", "message": "Image saved to out.jpg"}
 pub fn save(args: Vec<Value>) -> Result<Value, String> {
-    if args.len() != 2 {
-        return Err("save() requires exactly 2 arguments: image and path".to_string());
+    if args.len() < 2 {
+        return Err("save() requires at least 2 arguments: image and path".to_string());
     }
 
-    let image = match &args[0] {
-        Value::Map(_) => "image object",
-        _ => return Err("First argument must be an image object".to_string()),
-    };
-
     let path = match &args[1] {
         Value::String(s) => s,
         _ => return Err("Second argument must be a string path".to_string()),
     };
+    let format_name = match args.get(2) {
+        Some(Value::Null) | None => Path::new(path).extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .ok_or_else(|| format!("Cannot tell the image format of {}: give it an extension or a format", path))?,
+        Some(format) => format.as_string()?.to_lowercase(),
+    };
+    let format = match format_name.as_str() {
+        "png" => ImageFormat::Png,
+        "jpg" | "jpeg" => ImageFormat::Jpeg,
+        "bmp" => ImageFormat::Bmp,
+        "ppm" | "pnm" => ImageFormat::Pnm,
+        other => return Err(format!("Unsupported image format: {} (expected png, jpeg, bmp or ppm)", other)),
+    };
+    let quality = match args.get(3) {
+        Some(Value::Null) | None => DEFAULT_JPEG_QUALITY,
+        Some(quality) => quality.as_int()?.clamp(1, 100) as u8,
+    };
+
+    let pixels = with_image(&args[0], |pixels| Ok(pixels.clone()))?;
+    let result = match format {
+        // Neither JPEG nor PPM stores transparency
+        ImageFormat::Jpeg => File::create(path).map_err(image::ImageError::IoError).and_then(|file| {
+            let rgb = DynamicImage::ImageRgba8(pixels).to_rgb8();
+            JpegEncoder::new_with_quality(BufWriter::new(file), quality).encode_image(&rgb)
+        }),
+        ImageFormat::Pnm => DynamicImage::ImageRgba8(pixels).to_rgb8().save_with_format(path, format),
+        _ => pixels.save_with_format(path, format),
+    };
+    result.map_err(|e| format!("Cannot save image to {}: {}", path, e))?;
 
-    // In a real implementation, this would save the image to disk
-    println!("Saving image to {}", path);
-    
     // Create a result object with success information
     let mut result = std::collections::HashMap::new();
     result.insert("success".to_string(), Value::Bool(true));
     result.insert("path".to_string(), Value::String(path.clone()));
     result.insert("message".to_string(), Value::String(format!("Image saved to {}", path)));
-    
+
     Ok(Value::Map(result))
 }
 
-/// Resizes an image to the specified dimensions
+/// Resizes an image to the specified dimensions, with "bilinear" (default) or "nearest" sampling
+/// Example: resize(img, 400, 300) => {"image": 4, "width": 400, "height": 300}
+/// Example: resize(img, 64, 64, "nearest") => {"image": 5, "width": 64, "height": 64}
 pub fn resize(args: Vec<Value>) -> Result<Value, String> {
-    if args.len() != 3 {
-        return Err("resize() requires exactly 3 arguments: image, width, height".to_string());
+    if args.len() < 3 {
+        return Err("resize() requires at least 3 arguments: image, width, height".to_string());
     }
 
-    let image = match &args[0] {
-        Value::Map(obj) => obj,
-        _ => return Err("First argument must be an image object".to_string()),
-    };
-
-    let width = match &args[1] {
-        Value::Int(n) => *n as u32,
-        Value::Float(n) => *n as u32,
-        _ => return Err("Width must be a number".to_string()),
-    };
-    
-    println!("Resizing image to {}x{}", width, match &args[2] {
-        Value::Int(n) => *n,
-        Value::Float(n) => *n as i64,
-        _ => 0,
-    });
-
-    let height = match &args[2] {
-        Value::Int(n) => *n as u32,
-        Value::Float(n) => *n as u32,
-        _ => return Err("Height must be a number".to_string()),
+    let width = dimension(&args[1], "Width")?;
+    let height = dimension(&args[2], "Height")?;
+    let filter = match args.get(3) {
+        Some(Value::Null) | None => FilterType::Triangle,
+        Some(filter) => match filter.as_string()?.to_lowercase().as_str() {
+            "bilinear" => FilterType::Triangle,
+            "nearest" => FilterType::Nearest,
+            other => return Err(format!("Unknown resize filter: {} (expected bilinear or nearest)", other)),
+        },
     };
+    check_size(width, height)?;
+    transform(&args[0], |pixels| Ok(imageops::resize(pixels, width, height, filter)))
+}
 
-    // In a real implementation, this would resize the image
-    // For now, we'll create a new image object with the new dimensions
-    let mut resized = image.clone();
-    resized.insert("width".to_string(), Value::Int(width as i64));
-    resized.insert("height".to_string(), Value::Int(height as i64));
-    resized.insert("resized".to_string(), Value::Bool(true));
-    resized.insert("original_path".to_string(), match image.get("path") {
-        Some(path) => path.clone(),
-        None => Value::String("unknown".to_string()),
-    });
-    
-    Ok(Value::Map(resized))
-}
-
-/// Crops an image to the specified region
+/// Crops an image to the specified region, which must lie inside the image
+/// Example: crop(img, 10, 10, 50, 50) => {"image": 6, "width": 50, "height": 50}
 pub fn crop(args: Vec<Value>) -> Result<Value, String> {
     if args.len() != 5 {
         return Err("crop() requires exactly 5 arguments: image, x, y, width, height".to_string());
     }
 
-    let image = match &args[0] {
-        Value::Map(obj) => obj,
-        _ => return Err("First argument must be an image object".to_string()),
-    };
+    let x = args[1].as_int()?;
+    let y = args[2].as_int()?;
+    let width = dimension(&args[3], "Width")?;
+    let height = dimension(&args[4], "Height")?;
+    transform(&args[0], |pixels| {
+        let (image_width, image_height) = pixels.dimensions();
+        if x < 0 || y < 0 || x + width as i64 > image_width as i64 || y + height as i64 > image_height as i64 {
+            return Err(format!("Crop region {}x{} at ({}, {}) is outside the {}x{} image", width, height, x, y, image_width, image_height));
+        }
+        Ok(imageops::crop_imm(pixels, x as u32, y as u32, width, height).to_image())
+    })
+}
 
-    let x = match &args[1] {
-        Value::Int(n) => *n as u32,
-        Value::Float(n) => *n as u32,
-        _ => return Err("X coordinate must be a number".to_string()),
+/// Rotates an image clockwise by a multiple of 90 degrees
+/// Example: rotate(img, 90) => {"image": 7, "width": 600, "height": 800}
+pub fn rotate(args: Vec<Value>) -> Result<Value, String> {
+    let degrees = args[1].as_int()?;
+    if degrees % 90 != 0 {
+        return Err(format!("Images can only be rotated by multiples of 90 degrees, got {}", degrees));
+    }
+    transform(&args[0], |pixels| Ok(match degrees.rem_euclid(360) {
+        90 => imageops::rotate90(pixels),
+        180 => imageops::rotate180(pixels),
+        270 => imageops::rotate270(pixels),
+        _ => pixels.clone(),
+    }))
+}
+
+/// Mirrors an image "horizontal"ly (left to right, the default) or "vertical"ly
+/// Example: flip(img) => {"image": 8, "width": 800, "height": 600}
+/// Example: flip(img, "vertical") => {"image": 9, "width": 800, "height": 600}
+pub fn flip(args: Vec<Value>) -> Result<Value, String> {
+    let direction = match args.get(1) {
+        Some(Value::Null) | None => "horizontal".to_string(),
+        Some(direction) => direction.as_string()?.to_lowercase(),
     };
-    
-    println!("Cropping image at position ({}, {}) with size {}x{}", x, 
-        match &args[2] {
-            Value::Int(n) => *n,
-            Value::Float(n) => *n as i64,
-            _ => 0,
-        }, 
-        match &args[3] {
-            Value::Int(n) => *n,
-            Value::Float(n) => *n as i64,
-            _ => 0,
-        },
-        match &args[4] {
-            Value::Int(n) => *n,
-            Value::Float(n) => *n as i64,
-            _ => 0,
+    transform(&args[0], |pixels| match direction.as_str() {
+        "horizontal" => Ok(imageops::flip_horizontal(pixels)),
+        "vertical" => Ok(imageops::flip_vertical(pixels)),
+        other => Err(format!("Unknown flip direction: {} (expected horizontal or vertical)", other)),
+    })
+}
+
+/// Converts an image to shades of gray, keeping its transparency
+/// Example: grayscale(img) => {"image": 10, "width": 800, "height": 600}
+pub fn grayscale(args: Vec<Value>) -> Result<Value, String> {
+    transform(&args[0], |pixels| Ok(DynamicImage::ImageRgba8(pixels.clone()).grayscale().to_rgba8()))
+}
+
+/// Brightens (positive) or darkens (negative) every pixel by up to 255
+/// Example: brightness(img, 40) => {"image": 11, "width": 800, "height": 600}
+pub fn brightness(args: Vec<Value>) -> Result<Value, String> {
+    let amount = args[1].as_int()?.clamp(-255, 255) as i32;
+    transform(&args[0], |pixels| Ok(imageops::brighten(pixels, amount)))
+}
+
+/// Raises (positive) or lowers (negative) the contrast of an image by a percentage
+/// Example: contrast(img, 25) => {"image": 12, "width": 800, "height": 600}
+pub fn contrast(args: Vec<Value>) -> Result<Value, String> {
+    let percent = args[1].as_float()? as f32;
+    transform(&args[0], |pixels| Ok(imageops::contrast(pixels, percent)))
+}
+
+fn pixel_position(pixels: &RgbaImage, x: &Value, y: &Value) -> Result<(u32, u32), String> {
+    let (x, y) = (x.as_int()?, y.as_int()?);
+    let (width, height) = pixels.dimensions();
+    if x < 0 || y < 0 || x >= width as i64 || y >= height as i64 {
+        return Err(format!("Pixel ({}, {}) is outside the {}x{} image", x, y, width, height));
+    }
+    Ok((x as u32, y as u32))
+}
+
+/// Gets the color of a pixel as a hex string, with an alpha byte when it is not opaque
+/// Example: get_pixel(img, 0, 0) => "#ff8800"
+pub fn get_pixel(args: Vec<Value>) -> Result<Value, String> {
+    with_image(&args[0], |pixels| {
+        let (x, y) = pixel_position(pixels, &args[1], &args[2])?;
+        Ok(Value::String(to_hex(pixels.get_pixel(x, y).0)))
+    })
+}
+
+/// Sets the color of a pixel, given as a hex string or an [r, g, b] or [r, g, b, a] array
+/// Example: set_pixel(img, 0, 0, "#ff8800") => true
+pub fn set_pixel(args: Vec<Value>) -> Result<Value, String> {
+    let color = parse_color(&args[3])?;
+    with_image(&args[0], |pixels| {
+        let (x, y) = pixel_position(pixels, &args[1], &args[2])?;
+        pixels.put_pixel(x, y, Rgba(color));
+        Ok(Value::Bool(true))
+    })
+}
+
+// Drawing clips to the image, so shapes may hang over its edges. Coordinates are
+// i128 so that shapes far outside the image cannot overflow.
+fn plot(pixels: &mut RgbaImage, x: i128, y: i128, color: Rgba<u8>) {
+    if x >= 0 && y >= 0 && x < pixels.width() as i128 && y < pixels.height() as i128 {
+        pixels.put_pixel(x as u32, y as u32, color);
+    }
+}
+
+// Fill row y from x1 to x2 inclusive, visiting only the pixels inside the image
+fn span(pixels: &mut RgbaImage, x1: i128, x2: i128, y: i128, color: Rgba<u8>) {
+    if y < 0 || y >= pixels.height() as i128 {
+        return;
+    }
+    for x in x1.max(0)..=x2.min(pixels.width() as i128 - 1) {
+        pixels.put_pixel(x as u32, y as u32, color);
+    }
+}
+
+// Largest whole number whose square is at most n
+fn isqrt(n: i128) -> i128 {
+    let mut root = (n as f64).sqrt() as i128;
+    while root * root > n {
+        root -= 1;
+    }
+    while (root + 1) * (root + 1) <= n {
+        root += 1;
+    }
+    root
+}
+
+// Cut a line down to the part inside a width x height box (Liang-Barsky), or None if it misses
+fn clip_line(from: (f64, f64), to: (f64, f64), width: f64, height: f64) -> Option<((f64, f64), (f64, f64))> {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let (mut start, mut end) = (0.0f64, 1.0f64);
+    for (p, q) in [(-dx, from.0), (dx, width - 1.0 - from.0), (-dy, from.1), (dy, height - 1.0 - from.1)] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else if p < 0.0 {
+            start = start.max(q / p);
+        } else {
+            end = end.min(q / p);
         }
-    );
+    }
+    (start <= end).then_some(((from.0 + start * dx, from.1 + start * dy), (from.0 + end * dx, from.1 + end * dy)))
+}
 
-    let y = match &args[2] {
-        Value::Int(n) => *n as u32,
-        Value::Float(n) => *n as u32,
-        _ => return Err("Y coordinate must be a number".to_string()),
-    };
+/// Draws a one pixel wide line between two points
+/// Example: draw_line(img, 0, 0, 99, 49, "#000000") => true
+pub fn draw_line(args: Vec<Value>) -> Result<Value, String> {
+    let from = (args[1].as_int()? as f64, args[2].as_int()? as f64);
+    let to = (args[3].as_int()? as f64, args[4].as_int()? as f64);
+    let color = Rgba(parse_color(&args[5])?);
+    with_image(&args[0], |pixels| {
+        // Only the part of the line inside the image is walked
+        let Some((from, to)) = clip_line(from, to, pixels.width() as f64, pixels.height() as f64) else {
+            return Ok(Value::Bool(true));
+        };
+        let (right, bottom) = (pixels.width() as f64 - 1.0, pixels.height() as f64 - 1.0);
+        let (mut x, mut y) = (from.0.round().clamp(0.0, right) as i128, from.1.round().clamp(0.0, bottom) as i128);
+        let (x2, y2) = (to.0.round().clamp(0.0, right) as i128, to.1.round().clamp(0.0, bottom) as i128);
 
-    let width = match &args[3] {
-        Value::Float(n) => *n as u32,
-        _ => return Err("Width must be a number".to_string()),
+        // Bresenham's algorithm
+        let (dx, dy) = ((x2 - x).abs(), -(y2 - y).abs());
+        let (step_x, step_y) = (if x < x2 { 1 } else { -1 }, if y < y2 { 1 } else { -1 });
+        let mut error = dx + dy;
+        loop {
+            plot(pixels, x, y, color);
+            if x == x2 && y == y2 {
+                break;
+            }
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if doubled <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+        Ok(Value::Bool(true))
+    })
+}
+
+/// Draws a rectangle outline, or a filled rectangle
+/// Example: draw_rect(img, 10, 10, 30, 20, "#ff0000") => true
+/// Example: draw_rect(img, 10, 10, 30, 20, [0, 0, 255], true) => true
+pub fn draw_rect(args: Vec<Value>) -> Result<Value, String> {
+    let (x, y) = (args[1].as_int()?, args[2].as_int()?);
+    let (width, height) = (dimension(&args[3], "Width")? as i64, dimension(&args[4], "Height")? as i64);
+    let color = Rgba(parse_color(&args[5])?);
+    let filled = match args.get(6) {
+        Some(Value::Null) | None => false,
+        Some(filled) => filled.as_bool()?,
     };
+    let (x, y, width, height) = (x as i128, y as i128, width as i128, height as i128);
+    with_image(&args[0], |pixels| {
+        let (right, bottom) = (x + width - 1, y + height - 1);
+        if filled {
+            for py in y.max(0)..=bottom.min(pixels.height() as i128 - 1) {
+                span(pixels, x, right, py, color);
+            }
+        } else {
+            span(pixels, x, right, y, color);
+            span(pixels, x, right, bottom, color);
+            for py in y.max(0)..=bottom.min(pixels.height() as i128 - 1) {
+                plot(pixels, x, py, color);
+                plot(pixels, right, py, color);
+            }
+        }
+        Ok(Value::Bool(true))
+    })
+}
 
-    let height = match &args[4] {
-        Value::Float(n) => *n as u32,
-        _ => return Err("Height must be a number".to_string()),
+/// Draws a circle outline, or a filled circle, around a center point
+/// Example: draw_circle(img, 50, 50, 20, "#00ff00") => true
+/// Example: draw_circle(img, 50, 50, 20, "#00ff00", true) => true
+pub fn draw_circle(args: Vec<Value>) -> Result<Value, String> {
+    let (cx, cy) = (args[1].as_int()?, args[2].as_int()?);
+    let radius = args[3].as_int()?;
+    if radius < 0 {
+        return Err(format!("Radius must not be negative, got {}", radius));
+    }
+    let color = Rgba(parse_color(&args[4])?);
+    let filled = match args.get(5) {
+        Some(Value::Null) | None => false,
+        Some(filled) => filled.as_bool()?,
     };
+    let (cx, cy, radius) = (cx as i128, cy as i128, radius as i128);
+    with_image(&args[0], |pixels| {
+        // Walk the rows the circle covers inside the image. On the row `offset` above or
+        // below the center the circle reaches out to the widest x with x² + offset² <= r²;
+        // an outline fills in from there to just past the next row's reach, so it has no gaps.
+        let reach = |offset: i128| match offset > radius {
+            true => -1,
+            false => isqrt(radius * radius - offset * offset),
+        };
+        for py in (cy - radius).max(0)..=(cy + radius).min(pixels.height() as i128 - 1) {
+            let offset = (py - cy).abs();
+            let outer = reach(offset);
+            if filled {
+                span(pixels, cx - outer, cx + outer, py, color);
+            } else {
+                let inner = (reach(offset + 1) + 1).min(outer);
+                span(pixels, cx - outer, cx - inner, py, color);
+                span(pixels, cx + inner, cx + outer, py, color);
+            }
+        }
+        Ok(Value::Bool(true))
+    })
+}
+
+/// Releases the pixels of an image that is no longer needed
+/// Example: free(img) => true
+pub fn free(args: Vec<Value>) -> Result<Value, String> {
+    let id = image_id(&args[0])?;
+    Ok(Value::Bool(IMAGES.lock().unwrap().images.remove(&id).is_some()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_images_survive_a_round_trip_through_each_format() {
+        let dir = std::env::temp_dir().join(format!("razen_image_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let image = create(vec![Value::Int(8), Value::Int(4), Value::String("#204080".to_string())]).unwrap();
+        set_pixel(vec![image.clone(), Value::Int(7), Value::Int(3), Value::Array(vec![Value::Int(255), Value::Int(0), Value::Int(0)])]).unwrap();
+
+        for (file, lossless) in [("a.png", true), ("a.bmp", true), ("a.ppm", true), ("a.jpg", false)] {
+            let path = dir.join(file).display().to_string();
+            save(vec![image.clone(), Value::String(path.clone())]).unwrap();
+            let Value::Map(loaded) = load(vec![Value::String(path)]).unwrap() else { panic!("expected a map") };
+            assert_eq!((loaded["width"].clone(), loaded["height"].clone()), (Value::Int(8), Value::Int(4)), "{}", file);
+            if lossless {
+                let corner = get_pixel(vec![Value::Map(loaded.clone()), Value::Int(7), Value::Int(3)]).unwrap();
+                assert_eq!(corner, Value::String("#ff0000".to_string()), "{}", file);
+                let fill = get_pixel(vec![Value::Map(loaded.clone()), Value::Int(0), Value::Int(0)]).unwrap();
+                assert_eq!(fill, Value::String("#204080".to_string()), "{}", file);
+            }
+            free(vec![Value::Map(loaded)]).unwrap();
+        }
+        let Value::Map(jpeg) = load(vec![Value::String(dir.join("a.jpg").display().to_string())]).unwrap() else { panic!("expected a map") };
+        assert_eq!(jpeg["format"], Value::String("jpeg".to_string()));
+
+        let missing = load(vec![Value::String(dir.join("missing.png").display().to_string())]).unwrap_err();
+        assert!(missing.starts_with("Cannot open image"), "{}", missing);
+        std::fs::write(dir.join("bad.png"), "not an image").unwrap();
+        assert!(load(vec![Value::String(dir.join("bad.png").display().to_string())]).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 
-    // In a real implementation, this would crop the image
-    // For now, we'll create a new image object with the new dimensions
-    let mut cropped = image.clone();
-    cropped.insert("width".to_string(), Value::Int(width as i64));
-    cropped.insert("height".to_string(), Value::Int(height as i64));
-    cropped.insert("crop_x".to_string(), Value::Int(x as i64));
-    cropped.insert("crop_y".to_string(), Value::Int(y as i64));
-    cropped.insert("cropped".to_string(), Value::Bool(true));
-    cropped.insert("original_path".to_string(), match image.get("path") {
-        Some(path) => path.clone(),
-        None => Value::String("unknown".to_string()),
-    });
-    
-    Ok(Value::Map(cropped))
+    #[test]
+    fn test_huge_shapes_are_clipped_and_huge_images_refused() {
+        let image = create(vec![Value::Int(4), Value::Int(4), Value::String("#000000".to_string())]).unwrap();
+        let white = || Value::String("#ffffff".to_string());
+        let pixel = |x: i64, y: i64| get_pixel(vec![image.clone(), Value::Int(x), Value::Int(y)]).unwrap();
+        let huge = Value::Int(i64::MAX / 2);
+
+        let wide = Value::Int(u32::MAX as i64);
+        draw_rect(vec![image.clone(), Value::Int(-1), Value::Int(-1), wide.clone(), wide.clone(), white(), Value::Bool(true)]).unwrap();
+        assert_eq!(pixel(3, 3), white());
+        draw_rect(vec![image.clone(), Value::Int(i64::MAX), Value::Int(0), wide.clone(), wide, white()]).unwrap();
+        draw_circle(vec![image.clone(), Value::Int(0), Value::Int(0), huge.clone(), Value::String("#ff0000".to_string())]).unwrap();
+        draw_circle(vec![image.clone(), Value::Int(1), Value::Int(1), huge.clone(), Value::String("#00ff00".to_string()), Value::Bool(true)]).unwrap();
+        assert_eq!(pixel(0, 3), Value::String("#00ff00".to_string()));
+        draw_line(vec![image.clone(), Value::Int(i64::MIN), Value::Int(i64::MIN), Value::Int(i64::MAX), Value::Int(i64::MAX), white()]).unwrap();
+        draw_line(vec![image.clone(), Value::Int(-1_000_000_000), Value::Int(2), Value::Int(1_000_000_000), Value::Int(2), Value::String("#0000ff".to_string())]).unwrap();
+        assert_eq!((pixel(0, 2), pixel(3, 2)), (Value::String("#0000ff".to_string()), Value::String("#0000ff".to_string())));
+
+        let too_big = create(vec![Value::Int(100_000), Value::Int(100_000)]).unwrap_err();
+        assert!(too_big.contains("too large"), "{}", too_big);
+        assert!(resize(vec![image.clone(), Value::Int(u32::MAX as i64), Value::Int(u32::MAX as i64)]).unwrap_err().contains("too large"));
+        free(vec![image]).unwrap();
+    }
 }
@@ -40,7 +40,7 @@ pub fn stringify(args: Vec<Value>) -> Result<Value, String> {
 }
 
 /// Helper function to convert a JSON value to a Razen value
-fn json_to_razen_value(json_value: JsonValue) -> Result<Value, String> {
+pub fn json_to_razen_value(json_value: JsonValue) -> Result<Value, String> {
     match json_value {
         JsonValue::Null => Ok(Value::Null),
         JsonValue::Bool(b) => Ok(Value::Bool(b)),
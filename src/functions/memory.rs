@@ -569,3 +569,19 @@ pub fn buffer_copy(args: Vec<Value>) -> Result<Value, String> {
         Err(e) => Err(e.to_string()),
     }
 }
+
+/// Copy a buffer's bytes out, so foreign code can work on them without holding the lock
+pub(crate) fn buffer_contents(id: usize) -> Result<Vec<u8>, String> {
+    MEMORY_MANAGER.lock().buffers.get(&id)
+        .map(|buffer| buffer.to_vec())
+        .ok_or_else(|| MemoryError::InvalidBuffer(id).to_string())
+}
+
+/// Write bytes produced by foreign code back into a buffer of the same size
+pub(crate) fn set_buffer_contents(id: usize, data: &[u8]) -> Result<(), String> {
+    let mut manager = MEMORY_MANAGER.lock();
+    let buffer = manager.buffers.get_mut(&id)
+        .ok_or_else(|| MemoryError::InvalidBuffer(id).to_string())?;
+    buffer[..data.len()].copy_from_slice(data);
+    Ok(())
+}
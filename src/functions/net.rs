@@ -1,72 +1,76 @@
+use super::httpfixtures;
 use crate::value::Value;
-use std::process::Command;
 use std::collections::HashMap;
 use reqwest::blocking::Client;
-use std::time::Duration;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use url::Url;
 
-/// Ping a host to check connectivity
+// Sockets are handed out to scripts as integer handles, like BinaryLib's files.
+// A read returns what has arrived (up to a maximum) instead of waiting for the
+// full count; an empty result means the other side closed the connection.
+
+const DEFAULT_PING_TIMEOUT: Duration = Duration::from_secs(2);
+const DEFAULT_READ_SIZE: usize = 4096;
+// Largest UDP payload
+const MAX_DATAGRAM: usize = 65507;
+
+lazy_static::lazy_static! {
+    static ref SOCKETS: Mutex<Sockets> = Mutex::new(Sockets { sockets: HashMap::new(), next_handle: 1 });
+}
+
+struct Sockets {
+    // Each socket has its own lock, so a blocking read does not hold up the others
+    sockets: HashMap<usize, Arc<Mutex<Socket>>>,
+    next_handle: usize,
+}
+
+enum Socket {
+    Tcp(Stream<TcpStream>),
+    Tls(Stream<native_tls::TlsStream<TcpStream>>),
+    Listener(TcpListener),
+    Udp(UdpSocket),
+}
+
+// A connection and the bytes read past the last line read_line() returned
+struct Stream<S> {
+    stream: S,
+    pending: Vec<u8>,
+}
+
+/// Check that a host accepts TCP connections, on the given port or the URL's (443 and 80 for plain hosts).
+/// The timeout is in seconds and defaults to 2
 /// Example: ping("google.com") => true
 /// Example: ping("https://google.com") => true
+/// Example: ping("localhost", 5432, 0.5) => false
 pub fn ping(args: Vec<Value>) -> Result<Value, String> {
-    if args.len() != 1 {
-        return Err("Net.ping requires exactly 1 argument: host".to_string());
-    }
-    
-    let url = args[0].as_string()?;
-    
-    // Extract hostname from URL if needed
-    let host = match Url::parse(&url) {
-        Ok(parsed_url) => {
-            parsed_url.host_str().unwrap_or("").to_string()
-        }
-        Err(_) => {
-            // Fallback to simple host extraction
-            if url.starts_with("http://") || url.starts_with("https://") {
-                url.trim_start_matches("http://")
-                   .trim_start_matches("https://")
-                   .split('/')
-                   .next()
-                   .unwrap_or("")
-                   .split(':')
-                   .next()
-                   .unwrap_or("")
-                   .to_string()
-            } else {
-                url.to_string()
-            }
-        }
+    let target = args[0].as_string()?;
+    let timeout = match args.get(2) {
+        Some(timeout) => duration_arg(timeout)?.unwrap_or(DEFAULT_PING_TIMEOUT),
+        None => DEFAULT_PING_TIMEOUT,
     };
-    
+
+    // Extract hostname (and port) from URL if needed
+    let (host, url_port) = match Url::parse(&target) {
+        Ok(url) if url.host_str().is_some() => (url.host_str().unwrap_or_default().to_string(), url.port_or_known_default()),
+        _ => match target.rsplit_once(':') {
+            Some((host, port)) if !host.contains(':') && port.parse::<u16>().is_ok() => (host.to_string(), port.parse().ok()),
+            _ => (target.clone(), None),
+        },
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']').to_string();
     if host.is_empty() {
         return Ok(Value::Bool(false));
     }
 
-    let ping_cmd = if cfg!(target_os = "windows") {
-        Command::new("ping")
-            .arg("-n")
-            .arg("1")
-            .arg("-w")
-            .arg("1000")
-            .arg(&host)
-            .output()
-    } else {
-        Command::new("ping")
-            .arg("-c")
-            .arg("1")
-            .arg("-W")
-            .arg("1")
-            .arg(&host)
-            .output()
+    let ports = match args.get(1) {
+        Some(port) if *port != Value::Null => vec![port_arg(port)?],
+        _ => url_port.map(|port| vec![port]).unwrap_or_else(|| vec![443, 80]),
     };
-
-    match ping_cmd {
-        Ok(output) => {
-            let success = output.status.success();
-            Ok(Value::Bool(success))
-        }
-        Err(_) => Ok(Value::Bool(false)),
-    }
+    let reachable = ports.into_iter().any(|port| connect_any(&host, port, Some(timeout)).is_ok());
+    Ok(Value::Bool(reachable))
 }
 
 /// Sends a GET request to the given URL
@@ -85,16 +89,16 @@ pub fn get(args: Vec<Value>) -> Result<Value, String> {
         .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
     
     // Send the GET request
-    let response = client.get(&url)
-        .send()
+    let request = client.get(&url)
+        .build()
+        .map_err(|e| format!("Failed to execute HTTP GET request: {}", e))?;
+    let response = httpfixtures::execute(&client, request, None)
         .map_err(|e| format!("Failed to execute HTTP GET request: {}", e))?;
     
-    if response.status().is_success() {
-        let text = response.text()
-            .map_err(|e| format!("Failed to read response body: {}", e))?;
-        Ok(Value::String(text))
+    if response.status.is_success() {
+        Ok(Value::String(response.text()?))
     } else {
-        Err(format!("HTTP GET request failed with status: {}", response.status()))
+        Err(format!("HTTP GET request failed with status: {}", response.status))
     }
 }
 
@@ -115,16 +119,507 @@ pub fn post(args: Vec<Value>) -> Result<Value, String> {
         .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
     
     // Send the POST request
-    let response = client.post(&url)
+    let request = client.post(&url)
         .body(data)
-        .send()
+        .build()
+        .map_err(|e| format!("Failed to execute HTTP POST request: {}", e))?;
+    let response = httpfixtures::execute(&client, request, None)
         .map_err(|e| format!("Failed to execute HTTP POST request: {}", e))?;
     
-    if response.status().is_success() {
-        let text = response.text()
-            .map_err(|e| format!("Failed to read response body: {}", e))?;
-        Ok(Value::String(text))
+    if response.status.is_success() {
+        Ok(Value::String(response.text()?))
     } else {
-        Err(format!("HTTP POST request failed with status: {}", response.status()))
+        Err(format!("HTTP POST request failed with status: {}", response.status))
+    }
+}
+
+impl<S: Read + Write> Stream<S> {
+    fn new(stream: S) -> Self {
+        Stream { stream, pending: Vec::new() }
+    }
+
+    // Whatever has arrived, up to `max` bytes; empty once the other side closed
+    fn read_some(&mut self, max: usize) -> std::io::Result<Vec<u8>> {
+        if !self.pending.is_empty() {
+            let count = max.min(self.pending.len());
+            return Ok(self.pending.drain(..count).collect());
+        }
+        let mut buffer = vec![0; max];
+        let count = self.stream.read(&mut buffer)?;
+        buffer.truncate(count);
+        Ok(buffer)
+    }
+
+    // The next line without its line ending, or None at the end of the stream.
+    // Bytes read before a timeout are kept for the next call.
+    fn read_line(&mut self) -> std::io::Result<Option<Vec<u8>>> {
+        loop {
+            if let Some(end) = self.pending.iter().position(|byte| *byte == b'\n') {
+                let mut line: Vec<u8> = self.pending.drain(..=end).collect();
+                line.pop();
+                if line.last() == Some(&b'\r') {
+                    line.pop();
+                }
+                return Ok(Some(line));
+            }
+            let mut buffer = [0; DEFAULT_READ_SIZE];
+            let count = self.stream.read(&mut buffer)?;
+            if count == 0 {
+                return Ok((!self.pending.is_empty()).then(|| std::mem::take(&mut self.pending)));
+            }
+            self.pending.extend_from_slice(&buffer[..count]);
+        }
+    }
+}
+
+impl Socket {
+    fn tcp_stream(&self) -> Option<&TcpStream> {
+        match self {
+            Socket::Tcp(tcp) => Some(&tcp.stream),
+            Socket::Tls(tls) => Some(tls.stream.get_ref()),
+            _ => None,
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Socket::Tcp(_) => "TCP connection",
+            Socket::Tls(_) => "TLS connection",
+            Socket::Listener(_) => "TCP listener",
+            Socket::Udp(_) => "UDP socket",
+        }
+    }
+}
+
+fn register(socket: Socket) -> Value {
+    let mut sockets = SOCKETS.lock().unwrap();
+    let handle = sockets.next_handle;
+    sockets.next_handle += 1;
+    sockets.sockets.insert(handle, Arc::new(Mutex::new(socket)));
+    Value::Int(handle as i64)
+}
+
+fn socket(handle: &Value) -> Result<(usize, Arc<Mutex<Socket>>), String> {
+    let handle = handle.as_int().map_err(|_| format!("Invalid socket: {}", handle))? as usize;
+    let sockets = SOCKETS.lock().unwrap();
+    let socket = sockets.sockets.get(&handle).ok_or_else(|| format!("Socket {} is not open", handle))?;
+    Ok((handle, Arc::clone(socket)))
+}
+
+// Timeouts are given in seconds; null or 0 waits without a limit
+fn duration_arg(value: &Value) -> Result<Option<Duration>, String> {
+    match value {
+        Value::Null => Ok(None),
+        value => {
+            let seconds = value.as_float()?;
+            if seconds.is_nan() || seconds < 0.0 {
+                return Err(format!("Invalid timeout {}", value));
+            }
+            Ok((seconds > 0.0).then(|| Duration::from_secs_f64(seconds)))
+        }
+    }
+}
+
+fn timeout_arg(args: &[Value], index: usize) -> Result<Option<Duration>, String> {
+    args.get(index).map_or(Ok(None), duration_arg)
+}
+
+fn port_arg(value: &Value) -> Result<u16, String> {
+    let port = value.as_int()?;
+    u16::try_from(port).map_err(|_| format!("Invalid port {}", port))
+}
+
+fn size_arg(args: &[Value], index: usize, default: usize) -> Result<usize, String> {
+    match args.get(index) {
+        None | Some(Value::Null) => Ok(default),
+        Some(value) => match value.as_int()? {
+            size if size > 0 => Ok(size as usize),
+            size => Err(format!("Invalid read size {}", size)),
+        },
+    }
+}
+
+// Strings are sent as UTF-8, arrays as bytes
+fn bytes_arg(value: &Value) -> Result<Vec<u8>, String> {
+    match value {
+        Value::Array(items) => items.iter()
+            .map(|item| item.as_int().ok().and_then(|byte| u8::try_from(byte).ok())
+                .ok_or_else(|| format!("Invalid byte {}", item)))
+            .collect(),
+        value => Ok(value.as_string()?.into_bytes()),
+    }
+}
+
+fn byte_array(bytes: Vec<u8>) -> Value {
+    Value::Array(bytes.into_iter().map(|byte| Value::Int(byte as i64)).collect())
+}
+
+fn addresses(host: &str, port: u16) -> Result<Vec<SocketAddr>, String> {
+    let addresses: Vec<SocketAddr> = (host, port).to_socket_addrs()
+        .map_err(|e| format!("Failed to resolve {}: {}", host, e))?
+        .collect();
+    match addresses.is_empty() {
+        true => Err(format!("Failed to resolve {}: no addresses", host)),
+        false => Ok(addresses),
+    }
+}
+
+// Connect to the first address of a host that answers
+fn connect_any(host: &str, port: u16, timeout: Option<Duration>) -> Result<TcpStream, String> {
+    let mut last_error = None;
+    for address in addresses(host, port)? {
+        let result = match timeout {
+            Some(timeout) => TcpStream::connect_timeout(&address, timeout),
+            None => TcpStream::connect(address),
+        };
+        match result {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(format!("Failed to connect to {}:{}: {}", host, port, last_error.map(|e| e.to_string()).unwrap_or_default()))
+}
+
+fn io_error(action: &str, handle: usize, error: std::io::Error) -> String {
+    match error.kind() {
+        ErrorKind::WouldBlock | ErrorKind::TimedOut => format!("Timed out {} socket {}", action, handle),
+        _ => format!("Failed {} socket {}: {}", action, handle, error),
+    }
+}
+
+fn address_map(address: SocketAddr) -> Value {
+    Value::Map(HashMap::from([
+        ("host".to_string(), Value::String(address.ip().to_string())),
+        ("port".to_string(), Value::Int(address.port() as i64)),
+    ]))
+}
+
+/// Look up the IP addresses of a host name
+/// Example: resolve("localhost") => ["127.0.0.1", "::1"]
+pub fn resolve(args: Vec<Value>) -> Result<Value, String> {
+    let host = args[0].as_string()?;
+    let mut found: Vec<String> = Vec::new();
+    for address in addresses(&host, 0)? {
+        let ip = address.ip().to_string();
+        if !found.contains(&ip) {
+            found.push(ip);
+        }
+    }
+    Ok(Value::Array(found.into_iter().map(Value::String).collect()))
+}
+
+/// Open a TCP connection, waiting at most `timeout` seconds for it
+/// Example: tcp_connect("example.com", 80) => 1
+/// Example: tcp_connect("localhost", 6379, 0.5) => 2
+pub fn tcp_connect(args: Vec<Value>) -> Result<Value, String> {
+    let host = args[0].as_string()?;
+    let port = port_arg(&args[1])?;
+    let stream = connect_any(&host, port, timeout_arg(&args, 2)?)?;
+    Ok(register(Socket::Tcp(Stream::new(stream))))
+}
+
+/// Open a TLS connection; with verify set to false, self-signed and mismatched certificates are accepted
+/// Example: tls_connect("example.com", 443) => 3
+/// Example: tls_connect("localhost", 8443, 2, false) => 4
+pub fn tls_connect(args: Vec<Value>) -> Result<Value, String> {
+    let host = args[0].as_string()?;
+    let port = port_arg(&args[1])?;
+    let timeout = timeout_arg(&args, 2)?;
+    let verify = match args.get(3) {
+        None | Some(Value::Null) => true,
+        Some(verify) => verify.as_bool()?,
+    };
+
+    let connector = native_tls::TlsConnector::builder()
+        .danger_accept_invalid_certs(!verify)
+        .danger_accept_invalid_hostnames(!verify)
+        .build()
+        .map_err(|e| format!("Failed to set up TLS: {}", e))?;
+    let stream = connect_any(&host, port, timeout)?;
+    // The handshake gets the same time limit as the connection
+    let _ = stream.set_read_timeout(timeout);
+    let _ = stream.set_write_timeout(timeout);
+    let stream = connector.connect(&host, stream)
+        .map_err(|e| format!("TLS handshake with {}:{} failed: {}", host, port, e))?;
+    Ok(register(Socket::Tls(Stream::new(stream))))
+}
+
+/// Listen for TCP connections on a port (0 picks a free one) of a host (127.0.0.1 by default)
+/// Example: tcp_listen(9000) => 5
+/// Example: tcp_listen(0, "0.0.0.0") => 6
+pub fn tcp_listen(args: Vec<Value>) -> Result<Value, String> {
+    let port = port_arg(&args[0])?;
+    let host = match args.get(1) {
+        Some(host) => host.as_string()?,
+        None => "127.0.0.1".to_string(),
+    };
+    let listener = TcpListener::bind((host.as_str(), port))
+        .map_err(|e| format!("Failed to listen on {}:{}: {}", host, port, e))?;
+    Ok(register(Socket::Listener(listener)))
+}
+
+/// Wait for a connection on a listener and return a socket for it
+/// Example: accept(5) => 7
+/// Example: accept(5, 10) => 8
+pub fn accept(args: Vec<Value>) -> Result<Value, String> {
+    let (handle, socket) = socket(&args[0])?;
+    let timeout = timeout_arg(&args, 1)?;
+    let socket = socket.lock().unwrap();
+    let Socket::Listener(listener) = &*socket else {
+        return Err(format!("Socket {} is a {}, not a TCP listener", handle, socket.kind()));
+    };
+
+    let error = |e| io_error("accepting on", handle, e);
+    let stream = match timeout {
+        None => {
+            listener.set_nonblocking(false).map_err(error)?;
+            listener.accept().map_err(error)?.0
+        }
+        // Poll, since listeners have no accept timeout
+        Some(timeout) => {
+            listener.set_nonblocking(true).map_err(error)?;
+            let deadline = Instant::now() + timeout;
+            loop {
+                match listener.accept() {
+                    Ok((stream, _)) => break stream,
+                    Err(e) if e.kind() == ErrorKind::WouldBlock && Instant::now() < deadline => {
+                        std::thread::sleep(Duration::from_millis(5));
+                    }
+                    Err(e) => return Err(error(e)),
+                }
+            }
+        }
+    };
+    stream.set_nonblocking(false).map_err(error)?;
+    drop(socket);
+    Ok(register(Socket::Tcp(Stream::new(stream))))
+}
+
+// Read from a connection with an optional time limit
+fn read_stream<T>(args: &[Value], read: impl FnOnce(&mut Socket) -> std::io::Result<T>) -> Result<T, String> {
+    let (handle, socket) = socket(&args[0])?;
+    let timeout = timeout_arg(args, 2)?;
+    let mut socket = socket.lock().unwrap();
+    let tcp = socket.tcp_stream()
+        .ok_or_else(|| format!("Socket {} is a {}; use accept or recv_from", handle, socket.kind()))?;
+    tcp.set_read_timeout(timeout).map_err(|e| io_error("reading from", handle, e))?;
+    read(&mut socket).map_err(|e| io_error("reading from", handle, e))
+}
+
+fn read_some(socket: &mut Socket, max: usize) -> std::io::Result<Vec<u8>> {
+    match socket {
+        Socket::Tcp(tcp) => tcp.read_some(max),
+        Socket::Tls(tls) => tls.read_some(max),
+        _ => unreachable!("only connections are read"),
+    }
+}
+
+/// Read the text that has arrived on a connection, up to max_bytes (4096); "" once the other side has closed it.
+/// Waits at most `timeout` seconds for data
+/// Example: read(1) => "HTTP/1.0 200 OK..."
+/// Example: read(1, 100, 5) => "+PONG"
+pub fn read(args: Vec<Value>) -> Result<Value, String> {
+    let max = size_arg(&args, 1, DEFAULT_READ_SIZE)?;
+    let bytes = read_stream(&args, |socket| read_some(socket, max))?;
+    Ok(Value::String(String::from_utf8_lossy(&bytes).into_owned()))
+}
+
+/// Read the bytes that have arrived on a connection, up to max_bytes (4096); [] once the other side has closed it
+/// Example: read_bytes(1, 2) => [1, 2]
+pub fn read_bytes(args: Vec<Value>) -> Result<Value, String> {
+    let max = size_arg(&args, 1, DEFAULT_READ_SIZE)?;
+    let bytes = read_stream(&args, |socket| read_some(socket, max))?;
+    Ok(byte_array(bytes))
+}
+
+/// Read the next line of a connection without its line ending; null once the other side has closed it
+/// Example: read_line(1) => "220 smtp.example.com ESMTP"
+/// Example: read_line(1, 5) => "+OK"
+pub fn read_line(args: Vec<Value>) -> Result<Value, String> {
+    // The timeout is the second argument here
+    let args = [args[0].clone(), Value::Null, args.get(1).cloned().unwrap_or(Value::Null)];
+    let line = read_stream(&args, |socket| match socket {
+        Socket::Tcp(tcp) => tcp.read_line(),
+        Socket::Tls(tls) => tls.read_line(),
+        _ => unreachable!("only connections are read"),
+    })?;
+    Ok(line.map_or(Value::Null, |line| Value::String(String::from_utf8_lossy(&line).into_owned())))
+}
+
+/// Send text or an array of bytes over a connection, returning the number of bytes sent
+/// Example: write(1, "PING\r\n") => 6
+/// Example: write(1, [0, 255], 5) => 2
+pub fn write(args: Vec<Value>) -> Result<Value, String> {
+    let (handle, socket) = socket(&args[0])?;
+    let bytes = bytes_arg(&args[1])?;
+    let timeout = timeout_arg(&args, 2)?;
+    let mut socket = socket.lock().unwrap();
+    let error = |e| io_error("writing to", handle, e);
+    let tcp = socket.tcp_stream()
+        .ok_or_else(|| format!("Socket {} is a {}; use send_to for UDP", handle, socket.kind()))?;
+    tcp.set_write_timeout(timeout).map_err(error)?;
+    match &mut *socket {
+        Socket::Tcp(tcp) => tcp.stream.write_all(&bytes).and_then(|_| tcp.stream.flush()),
+        Socket::Tls(tls) => tls.stream.write_all(&bytes).and_then(|_| tls.stream.flush()),
+        _ => unreachable!("only connections are written"),
+    }.map_err(error)?;
+    Ok(Value::Int(bytes.len() as i64))
+}
+
+/// Send a line of text followed by "\r\n", the line ending of most text protocols
+/// Example: write_line(1, "PING") => 6
+pub fn write_line(args: Vec<Value>) -> Result<Value, String> {
+    let mut args = args;
+    args[1] = Value::String(args[1].as_string()? + "\r\n");
+    write(args)
+}
+
+/// Open a UDP socket on a port (0 picks a free one) of a host (127.0.0.1 by default)
+/// Example: udp_bind(0) => 9
+/// Example: udp_bind(5353, "0.0.0.0") => 10
+pub fn udp_bind(args: Vec<Value>) -> Result<Value, String> {
+    let port = port_arg(&args[0])?;
+    let host = match args.get(1) {
+        Some(host) => host.as_string()?,
+        None => "127.0.0.1".to_string(),
+    };
+    let socket = UdpSocket::bind((host.as_str(), port))
+        .map_err(|e| format!("Failed to bind UDP socket to {}:{}: {}", host, port, e))?;
+    Ok(register(Socket::Udp(socket)))
+}
+
+/// Send a datagram of text or bytes from a UDP socket to a host and port
+/// Example: send_to(9, "hello", "127.0.0.1", 5353) => 5
+pub fn send_to(args: Vec<Value>) -> Result<Value, String> {
+    let (handle, socket) = socket(&args[0])?;
+    let bytes = bytes_arg(&args[1])?;
+    let host = args[2].as_string()?;
+    let port = port_arg(&args[3])?;
+    let socket = socket.lock().unwrap();
+    let Socket::Udp(udp) = &*socket else {
+        return Err(format!("Socket {} is a {}, not a UDP socket", handle, socket.kind()));
+    };
+    let address = addresses(&host, port)?[0];
+    let sent = udp.send_to(&bytes, address).map_err(|e| io_error("sending from", handle, e))?;
+    Ok(Value::Int(sent as i64))
+}
+
+/// Wait for a datagram on a UDP socket; returns its text, bytes and the sender's host and port
+/// Example: recv_from(9) => {data: "hello", bytes: [104, ...], host: "127.0.0.1", port: 5353}
+/// Example: recv_from(9, 512, 2) => {data: "pong", bytes: [...], host: "10.0.0.2", port: 9000}
+pub fn recv_from(args: Vec<Value>) -> Result<Value, String> {
+    let (handle, socket) = socket(&args[0])?;
+    let max = size_arg(&args, 1, MAX_DATAGRAM)?;
+    let timeout = timeout_arg(&args, 2)?;
+    let socket = socket.lock().unwrap();
+    let Socket::Udp(udp) = &*socket else {
+        return Err(format!("Socket {} is a {}, not a UDP socket", handle, socket.kind()));
+    };
+
+    let error = |e| io_error("receiving on", handle, e);
+    udp.set_read_timeout(timeout).map_err(error)?;
+    let mut buffer = vec![0; max];
+    let (count, sender) = udp.recv_from(&mut buffer).map_err(error)?;
+    buffer.truncate(count);
+
+    let Value::Map(mut datagram) = address_map(sender) else { unreachable!() };
+    datagram.insert("data".to_string(), Value::String(String::from_utf8_lossy(&buffer).into_owned()));
+    datagram.insert("bytes".to_string(), byte_array(buffer));
+    Ok(Value::Map(datagram))
+}
+
+/// Host and port a socket is bound to on this machine, e.g. to find the port picked for port 0
+/// Example: local_address(5) => {host: "127.0.0.1", port: 9000}
+pub fn local_address(args: Vec<Value>) -> Result<Value, String> {
+    let (handle, socket) = socket(&args[0])?;
+    let socket = socket.lock().unwrap();
+    let address = match &*socket {
+        Socket::Listener(listener) => listener.local_addr(),
+        Socket::Udp(udp) => udp.local_addr(),
+        connection => connection.tcp_stream().unwrap().local_addr(),
+    };
+    address.map(address_map).map_err(|e| format!("Failed to read address of socket {}: {}", handle, e))
+}
+
+/// Host and port at the other end of a connection
+/// Example: peer_address(7) => {host: "127.0.0.1", port: 51234}
+pub fn peer_address(args: Vec<Value>) -> Result<Value, String> {
+    let (handle, socket) = socket(&args[0])?;
+    let socket = socket.lock().unwrap();
+    let tcp = socket.tcp_stream()
+        .ok_or_else(|| format!("Socket {} is a {}, not a connection", handle, socket.kind()))?;
+    tcp.peer_addr().map(address_map).map_err(|e| format!("Failed to read address of socket {}: {}", handle, e))
+}
+
+/// Close a socket; connections are shut down so the other side sees the end of the stream
+/// Example: close(1) => true
+pub fn close(args: Vec<Value>) -> Result<Value, String> {
+    let handle = args[0].as_int().map_err(|_| format!("Invalid socket: {}", args[0]))? as usize;
+    let Some(socket) = SOCKETS.lock().unwrap().sockets.remove(&handle) else {
+        return Ok(Value::Bool(false));
+    };
+    let mut socket = socket.lock().unwrap();
+    match &mut *socket {
+        Socket::Tls(tls) => {
+            let _ = tls.stream.shutdown();
+        }
+        Socket::Tcp(tcp) => {
+            let _ = tcp.stream.shutdown(std::net::Shutdown::Both);
+        }
+        _ => {}
+    }
+    Ok(Value::Bool(true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    fn string(text: &str) -> Value {
+        Value::String(text.to_string())
+    }
+
+    #[test]
+    fn test_tls_connection_to_local_server() {
+        let dir = std::env::temp_dir().join(format!("razen_net_tls_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let generated = Command::new("openssl")
+            .args(["req", "-x509", "-newkey", "rsa:2048", "-nodes", "-days", "1", "-subj", "/CN=localhost"])
+            .args(["-keyout", "key.pem", "-out", "cert.pem"])
+            .current_dir(&dir)
+            .output();
+        if !generated.is_ok_and(|output| output.status.success()) {
+            std::fs::remove_dir_all(&dir).unwrap();
+            return;
+        }
+        let identity = native_tls::Identity::from_pkcs8(
+            &std::fs::read(dir.join("cert.pem")).unwrap(),
+            &std::fs::read(dir.join("key.pem")).unwrap(),
+        ).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let acceptor = native_tls::TlsAcceptor::new(identity).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = Value::Int(listener.local_addr().unwrap().port() as i64);
+        let server = std::thread::spawn(move || {
+            // The first client rejects the self-signed certificate
+            let _ = acceptor.accept(listener.accept().unwrap().0);
+            let mut stream = Stream::new(acceptor.accept(listener.accept().unwrap().0).unwrap());
+            let line = stream.read_line().unwrap().unwrap();
+            stream.stream.write_all(format!("echo {}\n", String::from_utf8(line).unwrap()).as_bytes()).unwrap();
+            stream.stream.shutdown().unwrap();
+        });
+
+        let rejected = tls_connect(vec![string("localhost"), port.clone(), Value::Int(5)]).unwrap_err();
+        assert!(rejected.contains("TLS handshake with localhost"), "{}", rejected);
+
+        let socket = tls_connect(vec![string("localhost"), port, Value::Int(5), Value::Bool(false)]).unwrap();
+        assert_eq!(write_line(vec![socket.clone(), string("hello")]).unwrap(), Value::Int(7));
+        assert_eq!(read_line(vec![socket.clone(), Value::Int(5)]).unwrap(), string("echo hello"));
+        assert_eq!(read_line(vec![socket.clone(), Value::Int(5)]).unwrap(), Value::Null);
+        assert_eq!(close(vec![socket.clone()]).unwrap(), Value::Bool(true));
+        assert!(read(vec![socket]).unwrap_err().contains("is not open"));
+        server.join().unwrap();
     }
 }
@@ -0,0 +1,251 @@
+use crate::value::Value;
+use rand::Rng;
+
+// Numbers are read and written the same way everywhere: an optional sign,
+// digits with '.' as the decimal point and an optional exponent. Separators
+// like "1,000" or "1.000,5" are never guessed at; `group` adds them on output.
+
+// Parse a decimal number; whole numbers that fit an int become ints
+pub(crate) fn parse_decimal(text: &str) -> Option<Value> {
+    let text = text.trim();
+    let digits = text.strip_prefix(['+', '-']).unwrap_or(text);
+    let (mantissa, exponent) = match digits.find(['e', 'E']) {
+        Some(index) => (&digits[..index], Some(&digits[index + 1..])),
+        None => (digits, None),
+    };
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let all_digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+    if whole.is_empty() && fraction.is_empty() || !all_digits(whole) || !all_digits(fraction) {
+        return None;
+    }
+    if let Some(exponent) = exponent {
+        let exponent = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+        if exponent.is_empty() || !all_digits(exponent) {
+            return None;
+        }
+    }
+
+    if !mantissa.contains('.') && exponent.is_none() {
+        if let Ok(value) = text.parse::<i64>() {
+            return Some(Value::Int(value));
+        }
+    }
+    text.parse::<f64>().ok().filter(|value| value.is_finite()).map(Value::Float)
+}
+
+// Parse an integer in the given radix, allowing a 0x/0o/0b prefix that matches it
+fn parse_radix(text: &str, radix: u32) -> Result<i64, String> {
+    let trimmed = text.trim();
+    let (negative, unsigned) = match trimmed.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+    };
+    let prefix = match radix {
+        16 => Some("0x"),
+        8 => Some("0o"),
+        2 => Some("0b"),
+        _ => None,
+    };
+    let digits = prefix
+        .and_then(|prefix| unsigned.strip_prefix(prefix).or_else(|| unsigned.strip_prefix(&prefix.to_uppercase())))
+        .unwrap_or(unsigned)
+        .replace('_', "");
+    if digits.is_empty() || digits.starts_with(['+', '-']) {
+        return Err(format!("'{}' is not a base {} number", text, radix));
+    }
+    let signed = if negative { format!("-{}", digits) } else { digits };
+    i64::from_str_radix(&signed, radix).map_err(|_| format!("'{}' is not a base {} number", text, radix))
+}
+
+fn radix_arg(args: &[Value], index: usize, function: &str) -> Result<Option<u32>, String> {
+    match args.get(index) {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => {
+            let radix = value.as_int()?;
+            if !(2..=36).contains(&radix) {
+                return Err(format!("Num.{}: radix must be between 2 and 36, got {}", function, radix));
+            }
+            Ok(Some(radix as u32))
+        }
+    }
+}
+
+fn number_arg(value: &Value, function: &str) -> Result<f64, String> {
+    match value {
+        Value::Int(i) => Ok(*i as f64),
+        Value::Float(f) => Ok(*f),
+        other => Err(format!("Num.{} expects a number, got {}", function, other.to_string())),
+    }
+}
+
+/// Parse text as a number. Without a radix, decimal numbers and 0x/0o/0b integers are read;
+/// with one, an integer in that base. '.' is always the decimal point and '_' may separate digits.
+/// Example: parse("3.5e2") => 350.0
+/// Example: parse("ff", 16) => 255
+/// Example: parse("0b1010") => 10
+pub fn parse(args: Vec<Value>) -> Result<Value, String> {
+    if args.is_empty() || args.len() > 2 {
+        return Err("Num.parse requires 1 or 2 arguments: text, radix".to_string());
+    }
+    let text = args[0].to_string();
+    if let Some(radix) = radix_arg(&args, 1, "parse")? {
+        return parse_radix(&text, radix).map(Value::Int);
+    }
+
+    let unsigned = text.trim().trim_start_matches(['+', '-']);
+    for (prefix, radix) in [("0x", 16), ("0o", 8), ("0b", 2)] {
+        if unsigned.get(..2).is_some_and(|start| start.eq_ignore_ascii_case(prefix)) {
+            return parse_radix(&text, radix).map(Value::Int);
+        }
+    }
+    parse_decimal(&text.replace('_', ""))
+        .ok_or_else(|| match text.contains(',') {
+            true => format!("'{}' is not a number (use '.' as the decimal point and no thousands separators)", text),
+            false => format!("'{}' is not a number", text),
+        })
+}
+
+/// Write a whole number in a radix from 2 to 36 (10 by default), using lowercase digits
+/// Example: format(255, 16) => "ff"
+/// Example: format(-5, 2) => "-101"
+pub fn format(args: Vec<Value>) -> Result<Value, String> {
+    if args.is_empty() || args.len() > 2 {
+        return Err("Num.format requires 1 or 2 arguments: value, radix".to_string());
+    }
+    let radix = radix_arg(&args, 1, "format")?.unwrap_or(10);
+    let value = match &args[0] {
+        Value::Int(i) => *i,
+        Value::Float(f) if radix == 10 => return Ok(Value::String(f.to_string())),
+        Value::Float(f) if f.fract() == 0.0 && f.abs() < i64::MAX as f64 => *f as i64,
+        other => return Err(format!("Num.format expects a whole number for radix {}, got {}", radix, other.to_string())),
+    };
+
+    let mut magnitude = value.unsigned_abs();
+    let mut digits = Vec::new();
+    loop {
+        digits.push(std::char::from_digit((magnitude % radix as u64) as u32, radix).unwrap());
+        magnitude /= radix as u64;
+        if magnitude == 0 {
+            break;
+        }
+    }
+    if value < 0 {
+        digits.push('-');
+    }
+    Ok(Value::String(digits.iter().rev().collect()))
+}
+
+/// Write a number with a fixed number of decimals
+/// Example: fixed(3.14159, 2) => "3.14"
+/// Example: fixed(2, 1) => "2.0"
+pub fn fixed(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() != 2 {
+        return Err("Num.fixed requires exactly 2 arguments: value, decimals".to_string());
+    }
+    let value = number_arg(&args[0], "fixed")?;
+    let decimals = args[1].as_int()?;
+    if !(0..=17).contains(&decimals) {
+        return Err(format!("Num.fixed: decimals must be between 0 and 17, got {}", decimals));
+    }
+    Ok(Value::String(format!("{:.*}", decimals as usize, value)))
+}
+
+/// Write a number with its whole part grouped in thousands (',' by default)
+/// Example: group(1234567.5) => "1,234,567.5"
+/// Example: group(1234567, " ") => "1 234 567"
+pub fn group(args: Vec<Value>) -> Result<Value, String> {
+    if args.is_empty() || args.len() > 2 {
+        return Err("Num.group requires 1 or 2 arguments: value, separator".to_string());
+    }
+    number_arg(&args[0], "group")?;
+    let separator = match args.get(1) {
+        Some(separator) => separator.to_string(),
+        None => ",".to_string(),
+    };
+
+    let text = args[0].to_string();
+    let (sign, unsigned) = match text.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", text.as_str()),
+    };
+    let (whole, fraction) = match unsigned.split_once('.') {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (unsigned, None),
+    };
+    let mut grouped = String::new();
+    for (index, digit) in whole.chars().enumerate() {
+        if index > 0 && (whole.len() - index) % 3 == 0 {
+            grouped.push_str(&separator);
+        }
+        grouped.push(digit);
+    }
+    let fraction = fraction.map(|fraction| format!(".{}", fraction)).unwrap_or_default();
+    Ok(Value::String(format!("{}{}{}", sign, grouped, fraction)))
+}
+
+/// Round a number half away from zero, to a whole number or to some decimals
+/// Example: round(3.6) => 4
+/// Example: round(-1.25, 1) => -1.3
+pub fn round(args: Vec<Value>) -> Result<Value, String> {
+    if args.is_empty() || args.len() > 2 {
+        return Err("Num.round requires 1 or 2 arguments: value, decimals".to_string());
+    }
+    let value = number_arg(&args[0], "round")?;
+    match args.get(1).map(Value::as_int).transpose()? {
+        None | Some(0) => Ok(Value::Int(value.round() as i64)),
+        Some(decimals) => {
+            // Round the shortest decimal form, so 1.005 is not taken as 1.00499…
+            let scaled: f64 = format!("{}e{}", value, decimals).parse().map_err(|_| "Num.round: invalid number".to_string())?;
+            let rounded: f64 = format!("{}e{}", scaled.round(), -decimals).parse().map_err(|_| "Num.round: invalid number".to_string())?;
+            Ok(Value::Float(rounded))
+        }
+    }
+}
+
+/// Get a random number from 0 (inclusive) to max (exclusive, 1 by default)
+/// Example: random(10) => 7.294
+pub fn random(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() > 1 {
+        return Err("Num.random takes at most 1 argument: max".to_string());
+    }
+    let max = match args.first() {
+        Some(value) => number_arg(value, "random")?,
+        None => 1.0,
+    };
+    if max <= 0.0 {
+        return Err(format!("Num.random: max must be positive, got {}", max));
+    }
+    Ok(Value::Float(rand::thread_rng().gen_range(0.0..max)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(value: &str) -> Value {
+        Value::String(value.to_string())
+    }
+
+    #[test]
+    fn test_parse_and_format_are_locale_independent() {
+        assert_eq!(parse_decimal("-12"), Some(Value::Int(-12)));
+        assert_eq!(parse_decimal("1.5e3"), Some(Value::Float(1500.0)));
+        assert_eq!(parse_decimal(".5"), Some(Value::Float(0.5)));
+        for invalid in ["1,5", "1.000,5", "inf", "NaN", "1e", "", ".", "--1"] {
+            assert_eq!(parse_decimal(invalid), None, "{}", invalid);
+        }
+
+        assert_eq!(parse(vec![text("1_000")]).unwrap(), Value::Int(1000));
+        assert_eq!(parse(vec![text("-0xFF")]).unwrap(), Value::Int(-255));
+        assert_eq!(parse(vec![text("z"), Value::Int(36)]).unwrap(), Value::Int(35));
+        assert!(parse(vec![text("12"), Value::Int(2)]).is_err());
+        assert!(parse(vec![text("1,234")]).unwrap_err().contains("decimal point"));
+
+        assert_eq!(format(vec![Value::Int(i64::MIN), Value::Int(16)]).unwrap(), text("-8000000000000000"));
+        assert_eq!(format(vec![Value::Int(0), Value::Int(2)]).unwrap(), text("0"));
+        assert_eq!(group(vec![Value::Int(-1234)]).unwrap(), text("-1,234"));
+        assert_eq!(group(vec![Value::Int(123)]).unwrap(), text("123"));
+        assert_eq!(round(vec![Value::Float(1.005), Value::Int(2)]).unwrap(), Value::Float(1.01));
+        assert_eq!(round(vec![Value::Float(-2.5)]).unwrap(), Value::Int(-3));
+    }
+}
@@ -2,8 +2,9 @@ use crate::value::Value;
 use std::process::{Command, Child, Stdio};
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
-use std::io::{BufReader, BufRead};
+use std::io::{BufReader, BufRead, Read, Write};
 use std::thread;
+use std::time::{Duration, Instant};
 
 // Global process manager to track running processes
 lazy_static::lazy_static! {
@@ -13,6 +14,7 @@ lazy_static::lazy_static! {
 // Process manager to track running processes
 struct ProcessManager {
     processes: HashMap<usize, Child>,
+    deadlines: HashMap<usize, (Instant, Duration)>, // Kill time and timeout of processes spawned with one
     next_process_id: usize,
 }
 
@@ -20,6 +22,7 @@ impl ProcessManager {
     fn new() -> Self {
         ProcessManager {
             processes: HashMap::new(),
+            deadlines: HashMap::new(),
             next_process_id: 1,
         }
     }
@@ -52,13 +55,13 @@ pub fn create(args: Vec<Value>) -> Result<Value, String> {
     let command_str = args[0].as_string()?;
     
     // Split the command into program and arguments
-    let mut parts = command_str.split_whitespace();
-    let program = parts.next().ok_or_else(|| "Empty command".to_string())?;
-    let arguments: Vec<&str> = parts.collect();
+    let parts = split_command(&command_str)?;
+    let (program, arguments) = parts.split_first().ok_or_else(|| "Empty command".to_string())?;
     
     // Create the process
     let process = Command::new(program)
         .args(arguments)
+        .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .spawn()
@@ -80,16 +83,19 @@ pub fn wait(args: Vec<Value>) -> Result<Value, String> {
     let process_id = args[0].as_int()? as usize;
     
     // Remove the process from the manager
-    let mut process = PROCESS_MANAGER.lock().unwrap().remove_process(process_id)?;
+    let (mut process, deadline) = {
+        let mut manager = PROCESS_MANAGER.lock().unwrap();
+        let process = manager.remove_process(process_id)?;
+        (process, manager.deadlines.remove(&process_id))
+    };
     
-    // Wait for the process to complete
-    match process.wait() {
-        Ok(status) => {
-            let exit_code = status.code().unwrap_or(-1);
-            Ok(Value::Int(exit_code as i64))
-        },
-        Err(e) => Err(format!("Failed to wait for process: {}", e)),
-    }
+    // Close stdin so processes reading it see end of input
+    drop(process.stdin.take());
+    
+    // Wait for the process to complete, killing it if it was spawned with a timeout that passes
+    let mut processes = [process];
+    let codes = wait_all(&mut processes, deadline)?;
+    Ok(Value::Int(codes[0]))
 }
 
 /// Check if a process is running
@@ -304,3 +310,332 @@ pub fn write_stdin(args: Vec<Value>) -> Result<Value, String> {
         Err(e) => Err(e),
     }
 }
+
+// Split a command line into words, honouring single quotes, double quotes and backslashes,
+// so `grep -n 'two words' "my file.txt"` keeps its quoted arguments together
+pub(crate) fn split_command(command: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote: Option<char> = None;
+    let mut chars = command.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') | (None, '\\') => {
+                if let Some(next) = chars.next() {
+                    word.push(next);
+                }
+                in_word = true;
+            }
+            (Some(_), c) => word.push(c),
+            (None, '\'') | (None, '"') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+
+    if quote.is_some() {
+        return Err(format!("Unterminated quote in command: {}", command));
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+// How a spawned process gets its standard input
+#[derive(Clone, Copy, PartialEq)]
+enum StdinMode {
+    Pipe,
+    Inherit,
+    Null,
+}
+
+// Options map shared by run, spawn and pipeline
+struct SpawnOptions {
+    env: Vec<(String, String)>,
+    cwd: Option<String>,
+    stdin: StdinMode,
+    input: Option<String>,       // Text written to stdin, which must be piped
+    timeout: Option<Duration>,
+}
+
+fn spawn_options(value: Option<&Value>, default_stdin: StdinMode) -> Result<SpawnOptions, String> {
+    let mut options = SpawnOptions {
+        env: Vec::new(),
+        cwd: None,
+        stdin: default_stdin,
+        input: None,
+        timeout: None,
+    };
+
+    let map = match value {
+        None | Some(Value::Null) => return Ok(options),
+        Some(Value::Map(map)) => map,
+        Some(other) => return Err(format!("Process options must be a map, got {}", other)),
+    };
+
+    for (key, value) in map {
+        match key.as_str() {
+            "env" => match value {
+                Value::Map(vars) => {
+                    options.env = vars.iter().map(|(name, value)| (name.clone(), value.to_string())).collect();
+                }
+                _ => return Err("Process option 'env' must be a map of variable names to values".to_string()),
+            },
+            "cwd" => options.cwd = Some(value.as_string()?),
+            "stdin" => {
+                options.stdin = match value.as_string()?.as_str() {
+                    "pipe" => StdinMode::Pipe,
+                    "inherit" => StdinMode::Inherit,
+                    "null" => StdinMode::Null,
+                    other => return Err(format!("Process option 'stdin' must be pipe, inherit or null, got {}", other)),
+                }
+            }
+            "input" => options.input = Some(value.as_string()?),
+            "timeout" => {
+                let seconds = value.as_float()?;
+                if seconds <= 0.0 {
+                    return Err("Process option 'timeout' must be a positive number of seconds".to_string());
+                }
+                options.timeout = Some(Duration::from_secs_f64(seconds));
+            }
+            other => return Err(format!("Unknown process option '{}' (expected env, cwd, stdin, input or timeout)", other)),
+        }
+    }
+
+    if options.input.is_some() {
+        if options.stdin != default_stdin && options.stdin != StdinMode::Pipe {
+            return Err("Process option 'input' requires stdin to be pipe".to_string());
+        }
+        options.stdin = StdinMode::Pipe;
+    }
+
+    Ok(options)
+}
+
+fn argv_list(value: Option<&Value>) -> Result<Vec<String>, String> {
+    match value {
+        None | Some(Value::Null) => Ok(Vec::new()),
+        Some(Value::Array(items)) => Ok(items.iter().map(|item| item.to_string()).collect()),
+        Some(other) => Err(format!("Process arguments must be an array, got {}", other)),
+    }
+}
+
+fn build_command(program: &str, argv: &[String], options: &SpawnOptions) -> Command {
+    let mut command = Command::new(program);
+    command.args(argv)
+        .envs(options.env.iter().map(|(name, value)| (name, value)))
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if let Some(cwd) = &options.cwd {
+        command.current_dir(cwd);
+    }
+    command.stdin(match options.stdin {
+        StdinMode::Pipe => Stdio::piped(),
+        StdinMode::Inherit => Stdio::inherit(),
+        StdinMode::Null => Stdio::null(),
+    });
+    command
+}
+
+// Read a pipe to the end on a separate thread so a full pipe never blocks the child
+pub(crate) fn read_pipe<R: Read + Send + 'static>(pipe: Option<R>) -> thread::JoinHandle<String> {
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut bytes);
+        }
+        String::from_utf8_lossy(&bytes).to_string()
+    })
+}
+
+fn write_input(child: &mut Child, input: Option<String>) {
+    let stdin = child.stdin.take();
+    if let (Some(mut stdin), Some(input)) = (stdin, input) {
+        thread::spawn(move || {
+            let _ = stdin.write_all(input.as_bytes());
+        });
+    }
+}
+
+// Wait for every child, killing them all once the deadline passes
+fn wait_all(children: &mut [Child], deadline: Option<(Instant, Duration)>) -> Result<Vec<i64>, String> {
+    let mut codes = vec![None; children.len()];
+    loop {
+        for (child, code) in children.iter_mut().zip(codes.iter_mut()) {
+            if code.is_none() {
+                if let Some(status) = child.try_wait().map_err(|e| format!("Failed to wait for process: {}", e))? {
+                    *code = Some(status.code().unwrap_or(-1) as i64);
+                }
+            }
+        }
+        if codes.iter().all(Option::is_some) {
+            return Ok(codes.into_iter().flatten().collect());
+        }
+        if let Some((deadline, timeout)) = deadline {
+            if Instant::now() >= deadline {
+                for child in children.iter_mut() {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                }
+                return Err(format!("Process timed out after {} seconds", timeout.as_secs_f64()));
+            }
+        }
+        thread::sleep(Duration::from_millis(5));
+    }
+}
+
+fn result_map(code: i64, stdout: String, stderr: String) -> Value {
+    let mut result = HashMap::new();
+    result.insert("code".to_string(), Value::Int(code));
+    result.insert("stdout".to_string(), Value::String(stdout));
+    result.insert("stderr".to_string(), Value::String(stderr));
+    Value::Map(result)
+}
+
+// Collect the output of a started child and wait for it to exit
+fn finish(mut child: Child, input: Option<String>, deadline: Option<(Instant, Duration)>) -> Result<Value, String> {
+    write_input(&mut child, input);
+    let stdout = read_pipe(child.stdout.take());
+    let stderr = read_pipe(child.stderr.take());
+
+    let mut children = [child];
+    let codes = wait_all(&mut children, deadline)?;
+
+    Ok(result_map(codes[0], stdout.join().unwrap_or_default(), stderr.join().unwrap_or_default()))
+}
+
+/// Run a program with an argument array and wait for it to finish.
+/// Options: env (map), cwd, stdin (pipe, inherit or null), input (text for stdin) and timeout (seconds).
+/// Example: run("git", ["commit", "-m", "two words"], {"cwd": "repo"}) => {code: 0, stdout: "...", stderr: ""}
+pub fn run(args: Vec<Value>) -> Result<Value, String> {
+    if args.is_empty() || args.len() > 3 {
+        return Err("Process.run requires 1 to 3 arguments: program, [args], [options]".to_string());
+    }
+
+    let program = args[0].as_string()?;
+    let argv = argv_list(args.get(1))?;
+    let options = spawn_options(args.get(2), StdinMode::Null)?;
+
+    let child = build_command(&program, &argv, &options)
+        .spawn()
+        .map_err(|e| format!("Failed to start {}: {}", program, e))?;
+    let deadline = options.timeout.map(|timeout| (Instant::now() + timeout, timeout));
+
+    finish(child, options.input, deadline)
+}
+
+/// Start a program in the background and return its process ID.
+/// Stdin is piped unless the options say otherwise, so write_stdin works.
+/// Text given as input is written in the background and stdin is closed after it.
+/// Example: spawn("cat", [], {"env": {"LANG": "C"}}) => 1
+pub fn spawn(args: Vec<Value>) -> Result<Value, String> {
+    if args.is_empty() || args.len() > 3 {
+        return Err("Process.spawn requires 1 to 3 arguments: program, [args], [options]".to_string());
+    }
+
+    let program = args[0].as_string()?;
+    let argv = argv_list(args.get(1))?;
+    let options = spawn_options(args.get(2), StdinMode::Pipe)?;
+
+    let mut child = build_command(&program, &argv, &options)
+        .spawn()
+        .map_err(|e| format!("Failed to start {}: {}", program, e))?;
+    if options.input.is_some() {
+        write_input(&mut child, options.input);
+    }
+
+    let mut manager = PROCESS_MANAGER.lock().unwrap();
+    let process_id = manager.register_process(child);
+    if let Some(timeout) = options.timeout {
+        manager.deadlines.insert(process_id, (Instant::now() + timeout, timeout));
+    }
+
+    Ok(Value::Int(process_id as i64))
+}
+
+/// Close stdin of a spawned process, wait for it and return its exit code and output
+/// Example: output(1) => {code: 0, stdout: "hello", stderr: ""}
+pub fn output(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() != 1 {
+        return Err("Process.output requires exactly 1 argument: process_id".to_string());
+    }
+
+    let process_id = args[0].as_int()? as usize;
+    let (child, deadline) = {
+        let mut manager = PROCESS_MANAGER.lock().unwrap();
+        let child = manager.remove_process(process_id)?;
+        (child, manager.deadlines.remove(&process_id))
+    };
+
+    finish(child, None, deadline)
+}
+
+/// Run several programs with each one's stdout connected to the next one's stdin.
+/// Each stage is an array of the program followed by its arguments; the options apply to every stage.
+/// The result has the last stage's exit code and stdout, and the stderr of all stages.
+/// Example: pipeline([["cat", "log.txt"], ["grep", "ERROR"], ["wc", "-l"]]) => {code: 0, stdout: "3\n", stderr: ""}
+pub fn pipeline(args: Vec<Value>) -> Result<Value, String> {
+    if args.is_empty() || args.len() > 2 {
+        return Err("Process.pipeline requires 1 or 2 arguments: stages, [options]".to_string());
+    }
+
+    let stages = args[0].as_array()?;
+    if stages.is_empty() {
+        return Err("Process.pipeline requires at least one stage".to_string());
+    }
+    let options = spawn_options(args.get(1), StdinMode::Null)?;
+    let deadline = options.timeout.map(|timeout| (Instant::now() + timeout, timeout));
+
+    let mut children: Vec<Child> = Vec::new();
+    let mut stderr_readers = Vec::new();
+    for (index, stage) in stages.iter().enumerate() {
+        let words = argv_list(Some(stage))?;
+        let (program, argv) = words.split_first()
+            .ok_or_else(|| format!("Pipeline stage {} is empty", index + 1))?;
+
+        let mut command = build_command(program, argv, &options);
+        if let Some(previous) = children.last_mut() {
+            let stdout = previous.stdout.take()
+                .ok_or_else(|| "Failed to connect pipeline stages".to_string())?;
+            command.stdin(Stdio::from(stdout));
+        }
+
+        match command.spawn() {
+            Ok(mut child) => {
+                stderr_readers.push(read_pipe(child.stderr.take()));
+                children.push(child);
+            }
+            Err(e) => {
+                for child in children.iter_mut() {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                }
+                return Err(format!("Failed to start {}: {}", program, e));
+            }
+        }
+    }
+
+    write_input(&mut children[0], options.input);
+    let stdout = read_pipe(children.last_mut().and_then(|child| child.stdout.take()));
+    let codes = wait_all(&mut children, deadline)?;
+
+    let stderr: String = stderr_readers.into_iter()
+        .map(|reader| reader.join().unwrap_or_default())
+        .collect();
+    Ok(result_map(*codes.last().unwrap(), stdout.join().unwrap_or_default(), stderr))
+}
@@ -0,0 +1,100 @@
+use crate::value::Value;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+lazy_static::lazy_static! {
+    // Values shared by reference, by ID; IDs are not reused after free()
+    static ref REFERENCES: Mutex<References> = Mutex::new(References { next_id: 1, values: HashMap::new() });
+}
+
+struct References {
+    next_id: i64,
+    values: HashMap<i64, Value>,
+}
+
+fn reference_arg(args: &[Value], function: &str) -> Result<i64, String> {
+    match args.first() {
+        Some(Value::Int(id)) => Ok(*id),
+        Some(other) => Err(format!("Ref.{} expects a reference, got {}", function, other.to_string())),
+        None => Err(format!("Ref.{} requires a reference", function)),
+    }
+}
+
+fn missing(id: i64) -> String {
+    format!("Reference {} does not exist or was freed", id)
+}
+
+/// Store a value and return a reference to it, which can be passed around and changed in place
+/// Example: point([1, 2]) => 1
+pub fn point(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() != 1 {
+        return Err("Ref.point requires exactly 1 argument: value".to_string());
+    }
+    let mut references = REFERENCES.lock().unwrap();
+    let id = references.next_id;
+    references.next_id += 1;
+    references.values.insert(id, args[0].clone());
+    Ok(Value::Int(id))
+}
+
+/// Get the value a reference points to
+/// Example: read(1) => [1, 2]
+pub fn read(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() != 1 {
+        return Err("Ref.read requires exactly 1 argument: reference".to_string());
+    }
+    let id = reference_arg(&args, "read")?;
+    REFERENCES.lock().unwrap().values.get(&id).cloned().ok_or_else(|| missing(id))
+}
+
+/// Change the value a reference points to, returning the previous value
+/// Example: write(1, [3]) => [1, 2]
+pub fn write(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() != 2 {
+        return Err("Ref.write requires exactly 2 arguments: reference, value".to_string());
+    }
+    let id = reference_arg(&args, "write")?;
+    let mut references = REFERENCES.lock().unwrap();
+    let value = references.values.get_mut(&id).ok_or_else(|| missing(id))?;
+    Ok(std::mem::replace(value, args[1].clone()))
+}
+
+/// Release a reference; returns whether it existed
+/// Example: free(1) => true
+pub fn free(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() != 1 {
+        return Err("Ref.free requires exactly 1 argument: reference".to_string());
+    }
+    let id = reference_arg(&args, "free")?;
+    Ok(Value::Bool(REFERENCES.lock().unwrap().values.remove(&id).is_some()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_references_are_shared_until_freed() {
+        let reference = point(vec![Value::Array(vec![Value::Int(1), Value::Int(2)])]).unwrap();
+        let other = point(vec![Value::Null]).unwrap();
+        assert_ne!(reference, other);
+
+        assert_eq!(read(vec![reference.clone()]).unwrap(), Value::Array(vec![Value::Int(1), Value::Int(2)]));
+        assert_eq!(write(vec![reference.clone(), Value::Int(3)]).unwrap(), Value::Array(vec![Value::Int(1), Value::Int(2)]));
+        assert_eq!(read(vec![reference.clone()]).unwrap(), Value::Int(3));
+        assert_eq!(read(vec![other.clone()]).unwrap(), Value::Null);
+
+        assert_eq!(free(vec![reference.clone()]).unwrap(), Value::Bool(true));
+        assert_eq!(free(vec![reference.clone()]).unwrap(), Value::Bool(false));
+        let Value::Int(id) = reference else { panic!("references are integers") };
+        assert_eq!(read(vec![reference]).unwrap_err(), missing(id));
+        free(vec![other]).unwrap();
+    }
+
+    #[test]
+    fn test_arguments_must_be_references() {
+        assert_eq!(read(vec![Value::String("x".to_string())]).unwrap_err(), "Ref.read expects a reference, got x");
+        assert_eq!(write(vec![Value::Int(1)]).unwrap_err(), "Ref.write requires exactly 2 arguments: reference, value");
+        assert_eq!(point(vec![]).unwrap_err(), "Ref.point requires exactly 1 argument: value");
+    }
+}
@@ -0,0 +1,374 @@
+use crate::library::CallContext;
+use crate::value::Value;
+use super::json::{json_to_razen_value, razen_value_to_json};
+use serde_json::{json, Value as JsonValue};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// The store is an append-only log of JSON records, one per line:
+//   {"ns":"app","op":"set","key":"k","value":...}
+//   {"ns":"app","op":"delete","key":"k"}
+//   {"ns":"app","op":"clear"}
+//   {"op":"batch","records":[...]}   (a committed transaction)
+// A line cut short by a crash is ignored when the log is read back, so a
+// transaction is either stored completely or not at all.
+
+// Log lines kept before the log is rewritten with only the live keys
+const COMPACT_AFTER: usize = 64;
+
+lazy_static::lazy_static! {
+    // Stores opened by this process, by log file
+    static ref STORES: Mutex<HashMap<PathBuf, Store>> = Mutex::new(HashMap::new());
+    // Namespace picked with namespace(), instead of the script name
+    static ref NAMESPACE: Mutex<Option<String>> = Mutex::new(None);
+}
+
+type Namespaces = HashMap<String, BTreeMap<String, JsonValue>>;
+
+struct Store {
+    path: PathBuf,
+    data: Namespaces,
+    lines: usize,
+    // The log does not end with a complete line, so it is rewritten before the next append
+    torn: bool,
+    // Data before the transaction started and the records written since
+    transaction: Option<(Namespaces, Vec<JsonValue>)>,
+}
+
+impl Store {
+    fn open(path: PathBuf) -> Result<Store, String> {
+        let mut store = Store { path, data: HashMap::new(), lines: 0, torn: false, transaction: None };
+        let log = match fs::read_to_string(&store.path) {
+            Ok(log) => log,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(store),
+            Err(e) => return Err(format!("Failed to read storage file {}: {}", store.path.display(), e)),
+        };
+
+        store.torn = !log.is_empty() && !log.ends_with('\n');
+        let lines: Vec<&str> = log.lines().filter(|line| !line.trim().is_empty()).collect();
+        for (index, line) in lines.iter().enumerate() {
+            match serde_json::from_str::<JsonValue>(line) {
+                Ok(record) => apply(&mut store.data, &record),
+                // Only the last write can have been interrupted
+                Err(_) if index + 1 == lines.len() => break,
+                Err(e) => return Err(format!("Storage file {} is corrupt at line {}: {}", store.path.display(), index + 1, e)),
+            }
+            store.lines += 1;
+        }
+        Ok(store)
+    }
+
+    fn write(&mut self, record: JsonValue) -> Result<(), String> {
+        apply(&mut self.data, &record);
+        match &mut self.transaction {
+            Some((_, records)) => {
+                records.push(record);
+                Ok(())
+            }
+            None => self.append(record),
+        }
+    }
+
+    fn append(&mut self, record: JsonValue) -> Result<(), String> {
+        let live: usize = self.data.values().map(BTreeMap::len).sum();
+        if live == 0 || self.torn || (self.lines >= COMPACT_AFTER && self.lines > live * 4) {
+            return self.compact();
+        }
+
+        let error = |e: std::io::Error| format!("Failed to write storage file {}: {}", self.path.display(), e);
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path).map_err(error)?;
+        writeln!(file, "{}", record).map_err(error)?;
+        file.sync_data().map_err(error)?;
+        self.lines += 1;
+        Ok(())
+    }
+
+    // Rewrite the log with one record per live key; an empty store removes the file
+    fn compact(&mut self) -> Result<(), String> {
+        let records: Vec<String> = self.data.iter()
+            .flat_map(|(namespace, entries)| entries.iter().map(move |(key, value)| {
+                json!({"ns": namespace, "op": "set", "key": key, "value": value}).to_string()
+            }))
+            .collect();
+
+        if records.is_empty() {
+            if self.path.exists() {
+                fs::remove_file(&self.path)
+                    .map_err(|e| format!("Failed to remove storage file {}: {}", self.path.display(), e))?;
+            }
+        } else {
+            let temporary = self.path.with_extension("tmp");
+            fs::write(&temporary, records.join("\n") + "\n")
+                .and_then(|_| fs::rename(&temporary, &self.path))
+                .map_err(|e| format!("Failed to write storage file {}: {}", self.path.display(), e))?;
+        }
+        self.lines = records.len();
+        self.torn = false;
+        Ok(())
+    }
+}
+
+fn apply(data: &mut Namespaces, record: &JsonValue) {
+    if record["op"] == "batch" {
+        for record in record["records"].as_array().into_iter().flatten() {
+            apply(data, record);
+        }
+        return;
+    }
+
+    let Some(namespace) = record["ns"].as_str() else { return };
+    let key = record["key"].as_str().unwrap_or_default();
+    match record["op"].as_str() {
+        Some("set") => {
+            data.entry(namespace.to_string()).or_default().insert(key.to_string(), record["value"].clone());
+        }
+        Some("delete") => {
+            if let Some(entries) = data.get_mut(namespace) {
+                entries.remove(key);
+            }
+        }
+        Some("clear") => {
+            data.remove(namespace);
+        }
+        _ => {}
+    }
+}
+
+// RAZEN_STORAGE names the log file; by default it sits next to the script
+fn store_path(context: &CallContext) -> PathBuf {
+    if let Some(path) = std::env::var_os("RAZEN_STORAGE") {
+        return PathBuf::from(path);
+    }
+    let dir = context.script()
+        .and_then(Path::parent)
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    dir.join(".razen_storage.log")
+}
+
+// Each script gets its own keys, named after the script file
+fn current_namespace(context: &CallContext) -> String {
+    if let Some(namespace) = NAMESPACE.lock().unwrap().clone() {
+        return namespace;
+    }
+    context.script()
+        .and_then(Path::file_stem)
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "default".to_string())
+}
+
+fn with_store<T>(context: &CallContext, action: impl FnOnce(&mut Store, String) -> Result<T, String>) -> Result<T, String> {
+    let path = store_path(context);
+    let namespace = current_namespace(context);
+    let mut stores = STORES.lock().unwrap();
+    if !stores.contains_key(&path) {
+        let store = Store::open(path.clone())?;
+        stores.insert(path.clone(), store);
+    }
+    action(stores.get_mut(&path).unwrap(), namespace)
+}
+
+fn key_arg(args: &[Value], function: &str) -> Result<String, String> {
+    match args.first() {
+        Some(Value::Null) | None => Err(format!("Storage.{} requires a key", function)),
+        Some(key) => Ok(key.to_string()),
+    }
+}
+
+/// Store a value under a key; maps, arrays and numbers keep their type
+/// Example: set("score", 42) => true
+pub fn set(context: &CallContext, args: Vec<Value>) -> Result<Value, String> {
+    if args.len() != 2 {
+        return Err("Storage.set requires exactly 2 arguments: key, value".to_string());
+    }
+    let key = key_arg(&args, "set")?;
+    let value = razen_value_to_json(&args[1])?;
+    with_store(context, |store, namespace| {
+        store.write(json!({"ns": namespace, "op": "set", "key": key, "value": value}))?;
+        Ok(Value::Bool(true))
+    })
+}
+
+/// Get the value stored under a key, or the default (null) when there is none
+/// Example: get("score") => 42
+/// Example: get("missing", 0) => 0
+pub fn get(context: &CallContext, args: Vec<Value>) -> Result<Value, String> {
+    if args.is_empty() || args.len() > 2 {
+        return Err("Storage.get requires 1 or 2 arguments: key, default".to_string());
+    }
+    let key = key_arg(&args, "get")?;
+    let stored = with_store(context, |store, namespace| {
+        Ok(store.data.get(&namespace).and_then(|entries| entries.get(&key)).cloned())
+    })?;
+    match stored {
+        Some(value) => json_to_razen_value(value),
+        None => Ok(args.get(1).cloned().unwrap_or(Value::Null)),
+    }
+}
+
+/// Check whether a key is stored
+/// Example: has("score") => true
+pub fn has(context: &CallContext, args: Vec<Value>) -> Result<Value, String> {
+    if args.len() != 1 {
+        return Err("Storage.has requires exactly 1 argument: key".to_string());
+    }
+    let key = key_arg(&args, "has")?;
+    with_store(context, |store, namespace| {
+        Ok(Value::Bool(store.data.get(&namespace).is_some_and(|entries| entries.contains_key(&key))))
+    })
+}
+
+/// Delete a key, returning whether it was stored
+/// Example: delete("score") => true
+pub fn delete(context: &CallContext, args: Vec<Value>) -> Result<Value, String> {
+    if args.len() != 1 {
+        return Err("Storage.delete requires exactly 1 argument: key".to_string());
+    }
+    let key = key_arg(&args, "delete")?;
+    with_store(context, |store, namespace| {
+        let stored = store.data.get(&namespace).is_some_and(|entries| entries.contains_key(&key));
+        if stored {
+            store.write(json!({"ns": namespace, "op": "delete", "key": key}))?;
+        }
+        Ok(Value::Bool(stored))
+    })
+}
+
+/// List the stored keys in order
+/// Example: keys() => ["name", "score"]
+pub fn keys(context: &CallContext, args: Vec<Value>) -> Result<Value, String> {
+    if !args.is_empty() {
+        return Err("Storage.keys takes no arguments".to_string());
+    }
+    with_store(context, |store, namespace| {
+        let keys = store.data.get(&namespace)
+            .map(|entries| entries.keys().map(|key| Value::String(key.clone())).collect())
+            .unwrap_or_default();
+        Ok(Value::Array(keys))
+    })
+}
+
+/// Delete every key of the current namespace, returning how many there were
+/// Example: clear() => 2
+pub fn clear(context: &CallContext, args: Vec<Value>) -> Result<Value, String> {
+    if !args.is_empty() {
+        return Err("Storage.clear takes no arguments".to_string());
+    }
+    with_store(context, |store, namespace| {
+        let count = store.data.get(&namespace).map_or(0, BTreeMap::len);
+        if count > 0 {
+            store.write(json!({"ns": namespace, "op": "clear"}))?;
+        }
+        Ok(Value::Int(count as i64))
+    })
+}
+
+/// Start a transaction; writes are kept back until commit() and dropped by rollback()
+/// Example: begin() => true
+pub fn begin(context: &CallContext, args: Vec<Value>) -> Result<Value, String> {
+    if !args.is_empty() {
+        return Err("Storage.begin takes no arguments".to_string());
+    }
+    with_store(context, |store, _| {
+        if store.transaction.is_some() {
+            return Err("A storage transaction is already open".to_string());
+        }
+        store.transaction = Some((store.data.clone(), Vec::new()));
+        Ok(Value::Bool(true))
+    })
+}
+
+/// Store the writes of the open transaction all at once
+/// Example: commit() => true
+pub fn commit(context: &CallContext, args: Vec<Value>) -> Result<Value, String> {
+    if !args.is_empty() {
+        return Err("Storage.commit takes no arguments".to_string());
+    }
+    with_store(context, |store, _| {
+        let (_, records) = store.transaction.take().ok_or("No storage transaction is open")?;
+        if !records.is_empty() {
+            store.append(json!({"op": "batch", "records": records}))?;
+        }
+        Ok(Value::Bool(true))
+    })
+}
+
+/// Drop the writes of the open transaction
+/// Example: rollback() => true
+pub fn rollback(context: &CallContext, args: Vec<Value>) -> Result<Value, String> {
+    if !args.is_empty() {
+        return Err("Storage.rollback takes no arguments".to_string());
+    }
+    with_store(context, |store, _| {
+        let (data, _) = store.transaction.take().ok_or("No storage transaction is open")?;
+        store.data = data;
+        Ok(Value::Bool(true))
+    })
+}
+
+/// Get the namespace keys are stored in (the script name by default), or switch to another one
+/// Example: namespace() => "app"
+/// Example: namespace("shared") => "shared"
+pub fn namespace(context: &CallContext, args: Vec<Value>) -> Result<Value, String> {
+    if args.len() > 1 {
+        return Err("Storage.namespace takes at most 1 argument: name".to_string());
+    }
+    if let Some(name) = args.first() {
+        *NAMESPACE.lock().unwrap() = Some(name.to_string());
+    }
+    Ok(Value::String(current_namespace(context)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(text: &str) -> Value {
+        Value::String(text.to_string())
+    }
+
+    #[test]
+    fn test_store_survives_reopening_and_rolls_back() {
+        let dir = std::env::temp_dir().join(format!("razen_storage_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("app.rzn");
+        let context = CallContext::new("storage").with_script(Some(&script));
+        let log = dir.join(".razen_storage.log");
+
+        set(&context, vec![string("name"), string("ada")]).unwrap();
+        set(&context, vec![string("scores"), Value::Array(vec![Value::Int(1), Value::Int(2)])]).unwrap();
+        begin(&context, vec![]).unwrap();
+        set(&context, vec![string("name"), string("grace")]).unwrap();
+        assert_eq!(get(&context, vec![string("name")]).unwrap(), string("grace"));
+        rollback(&context, vec![]).unwrap();
+
+        begin(&context, vec![]).unwrap();
+        delete(&context, vec![string("scores")]).unwrap();
+        set(&context, vec![string("level"), Value::Int(3)]).unwrap();
+        commit(&context, vec![]).unwrap();
+
+        // Read the log back as another process would
+        STORES.lock().unwrap().remove(&log);
+        assert_eq!(get(&context, vec![string("name")]).unwrap(), string("ada"));
+        assert_eq!(get(&context, vec![string("level")]).unwrap(), Value::Int(3));
+        assert_eq!(keys(&context, vec![]).unwrap(), Value::Array(vec![string("level"), string("name")]));
+
+        // Other scripts do not see these keys
+        let other = dir.join("other.rzn");
+        let other_context = CallContext::new("storage").with_script(Some(&other));
+        assert_eq!(get(&other_context, vec![string("name"), Value::Int(0)]).unwrap(), Value::Int(0));
+
+        // A write cut short is ignored
+        let mut file = OpenOptions::new().append(true).open(&log).unwrap();
+        write!(file, "{{\"ns\":\"app\",\"op\":\"cle").unwrap();
+        STORES.lock().unwrap().remove(&log);
+        assert_eq!(has(&context, vec![string("name")]).unwrap(), Value::Bool(true));
+
+        assert_eq!(clear(&context, vec![]).unwrap(), Value::Int(2));
+        assert!(!log.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
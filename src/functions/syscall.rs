@@ -3,6 +3,17 @@ use std::process::Command;
 use std::env;
 use std::path::PathBuf;
 use std::collections::HashMap;
+use std::sync::Mutex;
+
+lazy_static::lazy_static! {
+    // Arguments given to the script after its path (`razen run script.rzn -- a b c`)
+    static ref SCRIPT_ARGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+}
+
+/// Set the arguments returned by `args()`
+pub fn set_script_args(args: Vec<String>) {
+    *SCRIPT_ARGS.lock().unwrap() = args;
+}
 
 /// Get the current process ID
 /// Example: getpid() => 1234
@@ -46,9 +57,8 @@ pub fn execute(args: Vec<Value>) -> Result<Value, String> {
     let command = args[0].as_string()?;
     
     // Split the command into program and arguments
-    let mut parts = command.split_whitespace();
-    let program = parts.next().ok_or_else(|| "Empty command".to_string())?;
-    let arguments: Vec<&str> = parts.collect();
+    let parts = super::process::split_command(&command)?;
+    let (program, arguments) = parts.split_first().ok_or_else(|| "Empty command".to_string())?;
     
     // Execute the command
     let output = Command::new(program)
@@ -114,15 +124,15 @@ pub fn environ(args: Vec<Value>) -> Result<Value, String> {
     Ok(Value::Array(result))
 }
 
-/// Get command line arguments
-/// Example: args() => ["program_name", "arg1", "arg2"]
+/// Get the script's command line arguments
+/// Example: args() => ["arg1", "arg2"]
 pub fn args(args: Vec<Value>) -> Result<Value, String> {
     if !args.is_empty() {
         return Err("Syscall.args takes no arguments".to_string());
     }
     
-    // Get command line arguments
-    let args: Vec<String> = env::args().collect();
+    // Only the arguments given to the script, not the interpreter's own
+    let args = SCRIPT_ARGS.lock().unwrap().clone();
     
     // Convert to Razen array
     let result: Vec<Value> = args.into_iter()
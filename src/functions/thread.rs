@@ -1,14 +1,121 @@
 use crate::value::Value;
 use std::thread;
-use std::sync::{Arc, Mutex};
-use std::collections::HashMap;
-use std::time::Duration;
+use std::sync::{Arc, Condvar, Mutex};
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::thread::ThreadId;
 
 // Global thread manager to track threads
 lazy_static::lazy_static! {
     static ref THREAD_MANAGER: Arc<Mutex<ThreadManager>> = Arc::new(Mutex::new(ThreadManager::new()));
+
+    // Bumped on every send/close so that select() can sleep until any channel changes
+    static ref CHANNEL_SIGNAL: (Mutex<u64>, Condvar) = (Mutex::new(0), Condvar::new());
+}
+
+// Message queue shared between the threads that hold a channel ID
+struct Channel {
+    state: Mutex<ChannelState>,
+    not_empty: Condvar,
+    not_full: Condvar,
+}
+
+struct ChannelState {
+    queue: VecDeque<Value>,
+    capacity: Option<usize>, // None for unbounded channels
+    closed: bool,
+}
+
+// Outcome of a non-blocking receive attempt
+enum TryRecv {
+    Value(Value),
+    Empty,
+    Closed,
+}
+
+impl Channel {
+    fn new(capacity: Option<usize>) -> Self {
+        Channel {
+            state: Mutex::new(ChannelState {
+                queue: VecDeque::new(),
+                capacity,
+                closed: false,
+            }),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+        }
+    }
+
+    fn send(&self, value: Value) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        while !state.closed && state.capacity.is_some_and(|cap| state.queue.len() >= cap) {
+            state = self.not_full.wait(state).unwrap();
+        }
+        if state.closed {
+            return Err("Cannot send on a closed channel".to_string());
+        }
+        state.queue.push_back(value);
+        drop(state);
+        self.not_empty.notify_one();
+        notify_channel_change();
+        Ok(())
+    }
+
+    fn try_recv(&self) -> TryRecv {
+        let mut state = self.state.lock().unwrap();
+        match state.queue.pop_front() {
+            Some(value) => {
+                drop(state);
+                self.not_full.notify_one();
+                TryRecv::Value(value)
+            }
+            None if state.closed => TryRecv::Closed,
+            None => TryRecv::Empty,
+        }
+    }
+
+    // Wait for a value until the deadline (or forever when there is none)
+    fn recv_until(&self, deadline: Option<Instant>) -> TryRecv {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(value) = state.queue.pop_front() {
+                drop(state);
+                self.not_full.notify_one();
+                return TryRecv::Value(value);
+            }
+            if state.closed {
+                return TryRecv::Closed;
+            }
+            state = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return TryRecv::Empty;
+                    }
+                    self.not_empty.wait_timeout(state, deadline - now).unwrap().0
+                }
+                None => self.not_empty.wait(state).unwrap(),
+            };
+        }
+    }
+
+    fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.not_empty.notify_all();
+        self.not_full.notify_all();
+        notify_channel_change();
+    }
+
+    fn len(&self) -> usize {
+        self.state.lock().unwrap().queue.len()
+    }
+}
+
+fn notify_channel_change() {
+    let (generation, signal) = &*CHANNEL_SIGNAL;
+    *generation.lock().unwrap() += 1;
+    signal.notify_all();
 }
 
 // Thread manager to track running threads
@@ -17,6 +124,8 @@ struct ThreadManager {
     next_thread_id: usize,
     mutexes: HashMap<usize, Arc<Mutex<()>>>,
     next_mutex_id: usize,
+    channels: HashMap<usize, Arc<Channel>>,
+    next_channel_id: usize,
     thread_count: AtomicUsize,
 }
 
@@ -27,6 +136,8 @@ impl ThreadManager {
             next_thread_id: 1,
             mutexes: HashMap::new(),
             next_mutex_id: 1,
+            channels: HashMap::new(),
+            next_channel_id: 1,
             thread_count: AtomicUsize::new(1), // Start with 1 for the main thread
         }
     }
@@ -74,6 +185,26 @@ impl ThreadManager {
             Err(format!("Invalid mutex ID: {}", id))
         }
     }
+
+    fn create_channel(&mut self, capacity: Option<usize>) -> usize {
+        let id = self.next_channel_id;
+        self.next_channel_id += 1;
+        self.channels.insert(id, Arc::new(Channel::new(capacity)));
+        id
+    }
+
+    fn get_channel(&self, id: usize) -> Result<Arc<Channel>, String> {
+        self.channels.get(&id)
+            .cloned()
+            .ok_or_else(|| format!("Invalid channel ID: {}", id))
+    }
+}
+
+// Look up a channel without keeping the manager locked while we block on it
+fn channel_arg(value: &Value) -> Result<(usize, Arc<Channel>), String> {
+    let id = value.as_int()? as usize;
+    let channel = THREAD_MANAGER.lock().unwrap().get_channel(id)?;
+    Ok((id, channel))
 }
 
 /// Create a new thread
@@ -276,3 +407,217 @@ pub fn cpu_count(args: Vec<Value>) -> Result<Value, String> {
     
     Ok(Value::Int(count as i64))
 }
+
+/// Create a channel for passing values between threads.
+/// With no argument the channel is unbounded; otherwise send blocks once `capacity` values are queued.
+/// Example: channel(16) => 1
+pub fn channel(args: Vec<Value>) -> Result<Value, String> {
+    let capacity = match args.len() {
+        0 => None,
+        1 => {
+            let capacity = args[0].as_int()?;
+            if capacity < 1 {
+                return Err("Thread.channel capacity must be at least 1".to_string());
+            }
+            Some(capacity as usize)
+        }
+        _ => return Err("Thread.channel takes at most 1 argument: capacity".to_string()),
+    };
+
+    let channel_id = THREAD_MANAGER.lock().unwrap().create_channel(capacity);
+
+    Ok(Value::Int(channel_id as i64))
+}
+
+/// Send a value on a channel, blocking while a bounded channel is full.
+/// The value is copied, so the receiver never shares it with the sender.
+/// Example: send(1, "job") => true
+pub fn send(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() != 2 {
+        return Err("Thread.send requires exactly 2 arguments: channel_id, value".to_string());
+    }
+
+    let (channel_id, channel) = channel_arg(&args[0])?;
+    channel.send(args[1].clone())
+        .map_err(|e| format!("Thread.send: {} (channel {})", e, channel_id))?;
+
+    Ok(Value::Bool(true))
+}
+
+/// Receive a value from a channel, blocking until one arrives.
+/// Fails once the channel is closed and drained.
+/// Example: recv(1) => "job"
+pub fn recv(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() != 1 {
+        return Err("Thread.recv requires exactly 1 argument: channel_id".to_string());
+    }
+
+    let (channel_id, channel) = channel_arg(&args[0])?;
+    match channel.recv_until(None) {
+        TryRecv::Value(value) => Ok(value),
+        _ => Err(format!("Thread.recv: channel {} is closed", channel_id)),
+    }
+}
+
+/// Receive a value if one is queued, otherwise return null immediately.
+/// Example: try_recv(1) => null
+pub fn try_recv(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() != 1 {
+        return Err("Thread.try_recv requires exactly 1 argument: channel_id".to_string());
+    }
+
+    let (channel_id, channel) = channel_arg(&args[0])?;
+    match channel.try_recv() {
+        TryRecv::Value(value) => Ok(value),
+        TryRecv::Empty => Ok(Value::Null),
+        TryRecv::Closed => Err(format!("Thread.try_recv: channel {} is closed", channel_id)),
+    }
+}
+
+/// Receive a value, waiting at most the given number of milliseconds.
+/// Returns null on timeout.
+/// Example: recv_timeout(1, 500) => "job"
+pub fn recv_timeout(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() != 2 {
+        return Err("Thread.recv_timeout requires exactly 2 arguments: channel_id, milliseconds".to_string());
+    }
+
+    let (channel_id, channel) = channel_arg(&args[0])?;
+    let ms = args[1].as_int()?.max(0) as u64;
+    match channel.recv_until(Some(Instant::now() + Duration::from_millis(ms))) {
+        TryRecv::Value(value) => Ok(value),
+        TryRecv::Empty => Ok(Value::Null),
+        TryRecv::Closed => Err(format!("Thread.recv_timeout: channel {} is closed", channel_id)),
+    }
+}
+
+/// Close a channel. Queued values can still be received; further sends fail.
+/// Example: close(1) => true
+pub fn close(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() != 1 {
+        return Err("Thread.close requires exactly 1 argument: channel_id".to_string());
+    }
+
+    let (_, channel) = channel_arg(&args[0])?;
+    channel.close();
+
+    Ok(Value::Bool(true))
+}
+
+/// Wait on several channels and receive from the first one that has a value.
+/// Returns a map with the channel ID and the value, or null if the optional timeout expires.
+/// Fails when every channel is closed and drained.
+/// Example: select([1, 2], 1000) => {channel: 2, value: "done"}
+pub fn select(args: Vec<Value>) -> Result<Value, String> {
+    if args.is_empty() || args.len() > 2 {
+        return Err("Thread.select requires 1 or 2 arguments: channel_ids, [timeout_ms]".to_string());
+    }
+
+    let channels = args[0].as_array()?
+        .iter()
+        .map(channel_arg)
+        .collect::<Result<Vec<_>, String>>()?;
+    if channels.is_empty() {
+        return Err("Thread.select requires at least one channel".to_string());
+    }
+
+    let deadline = match args.get(1) {
+        Some(ms) => Some(Instant::now() + Duration::from_millis(ms.as_int()?.max(0) as u64)),
+        None => None,
+    };
+
+    let (generation, signal) = &*CHANNEL_SIGNAL;
+    loop {
+        // Remember the generation before polling so a send that races with us is not missed
+        let seen = *generation.lock().unwrap();
+
+        let mut open = false;
+        for (channel_id, channel) in &channels {
+            match channel.try_recv() {
+                TryRecv::Value(value) => {
+                    let mut result = HashMap::new();
+                    result.insert("channel".to_string(), Value::Int(*channel_id as i64));
+                    result.insert("value".to_string(), value);
+                    return Ok(Value::Map(result));
+                }
+                TryRecv::Empty => open = true,
+                TryRecv::Closed => {}
+            }
+        }
+        if !open {
+            return Err("Thread.select: all channels are closed".to_string());
+        }
+
+        let mut current = generation.lock().unwrap();
+        while *current == seen {
+            current = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Ok(Value::Null);
+                    }
+                    signal.wait_timeout(current, deadline - now).unwrap().0
+                }
+                None => signal.wait(current).unwrap(),
+            };
+        }
+    }
+}
+
+/// Get the number of values currently queued on a channel
+/// Example: channel_len(1) => 3
+pub fn channel_len(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() != 1 {
+        return Err("Thread.channel_len requires exactly 1 argument: channel_id".to_string());
+    }
+
+    let (_, channel) = channel_arg(&args[0])?;
+
+    Ok(Value::Int(channel.len() as i64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_channel_between_threads() {
+        let ch = channel(vec![Value::Int(2)]).unwrap();
+
+        let producer_ch = ch.clone();
+        let producer = thread::spawn(move || {
+            for i in 0..5 {
+                send(vec![producer_ch.clone(), Value::Int(i)]).unwrap();
+            }
+            close(vec![producer_ch]).unwrap();
+        });
+
+        let mut received = Vec::new();
+        while let Ok(value) = recv(vec![ch.clone()]) {
+            received.push(value);
+        }
+        producer.join().unwrap();
+
+        assert_eq!(received, (0..5).map(Value::Int).collect::<Vec<_>>());
+        assert!(send(vec![ch, Value::Int(9)]).is_err());
+    }
+
+    #[test]
+    fn test_select_and_timeouts() {
+        let a = channel(vec![]).unwrap();
+        let b = channel(vec![]).unwrap();
+
+        assert_eq!(try_recv(vec![a.clone()]).unwrap(), Value::Null);
+        assert_eq!(recv_timeout(vec![a.clone(), Value::Int(10)]).unwrap(), Value::Null);
+        assert_eq!(select(vec![Value::Array(vec![a.clone(), b.clone()]), Value::Int(10)]).unwrap(), Value::Null);
+
+        send(vec![b.clone(), Value::String("done".to_string())]).unwrap();
+        let picked = select(vec![Value::Array(vec![a.clone(), b.clone()])]).unwrap().as_map().unwrap();
+        assert_eq!(picked["channel"], b);
+        assert_eq!(picked["value"], Value::String("done".to_string()));
+
+        close(vec![a.clone()]).unwrap();
+        close(vec![b.clone()]).unwrap();
+        assert!(select(vec![Value::Array(vec![a, b])]).is_err());
+    }
+}
@@ -0,0 +1,94 @@
+use crate::value::Value;
+
+// Script values reach libraries as text and are parsed back, so a string that
+// holds a number ("42") is checked as a number, like the number it prints as.
+
+// Name of a value's type, as used in library signatures
+pub(crate) fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Int(_) => "int",
+        Value::Float(_) => "float",
+        Value::String(_) => "string",
+        Value::Bool(_) => "bool",
+        Value::Array(_) => "array",
+        Value::Map(_) => "map",
+        Value::Null => "null",
+    }
+}
+
+fn single<'a>(args: &'a [Value], function: &str) -> Result<&'a Value, String> {
+    match args {
+        [value] => Ok(value),
+        _ => Err(format!("TypeCheck.{} requires exactly 1 argument: value", function)),
+    }
+}
+
+/// Get the type of a value: int, float, string, bool, array, map or null
+/// Example: type_of(3.14) => "float"
+/// Example: type_of([1, 2]) => "array"
+pub fn type_of(args: Vec<Value>) -> Result<Value, String> {
+    let value = single(&args, "type_of")?;
+    Ok(Value::String(type_name(value).to_string()))
+}
+
+/// Check whether a value is a number (int or float)
+/// Example: is_num(42) => true
+/// Example: is_num("hello") => false
+pub fn is_num(args: Vec<Value>) -> Result<Value, String> {
+    let value = single(&args, "is_num")?;
+    Ok(Value::Bool(matches!(value, Value::Int(_) | Value::Float(_))))
+}
+
+/// Check whether a value is a whole number
+/// Example: is_int(42) => true
+/// Example: is_int(4.2) => false
+pub fn is_int(args: Vec<Value>) -> Result<Value, String> {
+    let value = single(&args, "is_int")?;
+    Ok(Value::Bool(matches!(value, Value::Int(_))))
+}
+
+/// Check whether a value is a number with a fractional part
+/// Example: is_float(4.2) => true
+pub fn is_float(args: Vec<Value>) -> Result<Value, String> {
+    let value = single(&args, "is_float")?;
+    Ok(Value::Bool(matches!(value, Value::Float(_))))
+}
+
+/// Check whether a value is a string
+/// Example: is_str("hello") => true
+/// Example: is_str(123) => false
+pub fn is_str(args: Vec<Value>) -> Result<Value, String> {
+    let value = single(&args, "is_str")?;
+    Ok(Value::Bool(matches!(value, Value::String(_))))
+}
+
+/// Check whether a value is true or false
+/// Example: is_bool(true) => true
+/// Example: is_bool(1) => false
+pub fn is_bool(args: Vec<Value>) -> Result<Value, String> {
+    let value = single(&args, "is_bool")?;
+    Ok(Value::Bool(matches!(value, Value::Bool(_))))
+}
+
+/// Check whether a value is a list (array)
+/// Example: is_list([1, 2, 3]) => true
+pub fn is_list(args: Vec<Value>) -> Result<Value, String> {
+    let value = single(&args, "is_list")?;
+    Ok(Value::Bool(matches!(value, Value::Array(_))))
+}
+
+/// Check whether a value is a map
+/// Example: is_map({"a": 1}) => true
+/// Example: is_map([1, 2]) => false
+pub fn is_map(args: Vec<Value>) -> Result<Value, String> {
+    let value = single(&args, "is_map")?;
+    Ok(Value::Bool(matches!(value, Value::Map(_))))
+}
+
+/// Check whether a value is null
+/// Example: is_null(null) => true
+/// Example: is_null(0) => false
+pub fn is_null(args: Vec<Value>) -> Result<Value, String> {
+    let value = single(&args, "is_null")?;
+    Ok(Value::Bool(matches!(value, Value::Null)))
+}
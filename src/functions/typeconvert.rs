@@ -0,0 +1,130 @@
+use crate::value::Value;
+use super::num::parse_decimal;
+use super::typecheck::type_name;
+
+// Every conversion has two modes. Lenient (the default) always produces a value
+// where one makes sense: "12abc" reads as 12 and unreadable text as 0. Strict
+// fails unless the value converts exactly: "12abc" and 12.5 are not ints.
+
+fn value_and_mode(args: &[Value], function: &str) -> Result<(Value, bool), String> {
+    match args {
+        [value] => Ok((value.clone(), false)),
+        [value, strict] => Ok((value.clone(), strict.as_bool()?)),
+        _ => Err(format!("TypeConvert.{} requires 1 or 2 arguments: value, strict", function)),
+    }
+}
+
+fn cannot_convert(value: &Value, target: &str) -> String {
+    format!("Cannot convert {} {} to {}", type_name(value), value.to_string(), target)
+}
+
+// The number a text starts with, e.g. 12.5 for "12.5kg"
+fn leading_number(text: &str) -> Option<Value> {
+    let text = text.trim();
+    let candidate = text.find(|c: char| !(c.is_ascii_digit() || "+-.eE".contains(c))).unwrap_or(text.len());
+    (1..=candidate).rev().find_map(|end| parse_decimal(&text[..end]))
+}
+
+fn to_number(value: &Value, strict: bool, target: &str) -> Result<Value, String> {
+    match value {
+        Value::Int(_) | Value::Float(_) => Ok(value.clone()),
+        Value::Bool(b) if !strict => Ok(Value::Int(i64::from(*b))),
+        Value::Null if !strict => Ok(Value::Int(0)),
+        Value::String(text) if strict => parse_decimal(text).ok_or_else(|| cannot_convert(value, target)),
+        Value::String(text) => Ok(parse_decimal(text).or_else(|| leading_number(text)).unwrap_or(Value::Int(0))),
+        _ => Err(cannot_convert(value, target)),
+    }
+}
+
+/// Convert a value to a whole number. Lenient mode truncates floats and reads the number
+/// text starts with; strict mode only accepts values that are exactly a whole number.
+/// Example: to_int("42") => 42
+/// Example: to_int("12.7kg") => 12
+/// Example: to_int(12.5, true) => error
+pub fn to_int(args: Vec<Value>) -> Result<Value, String> {
+    let (value, strict) = value_and_mode(&args, "to_int")?;
+    match to_number(&value, strict, "int")? {
+        Value::Float(f) if strict && f.fract() != 0.0 => Err(cannot_convert(&value, "int")),
+        Value::Float(f) if f.is_finite() && f.abs() < i64::MAX as f64 => Ok(Value::Int(f.trunc() as i64)),
+        Value::Float(_) => Err(cannot_convert(&value, "int")),
+        number => Ok(number),
+    }
+}
+
+/// Convert a value to a float. Lenient mode reads the number text starts with;
+/// strict mode only accepts numbers and text that is exactly a number.
+/// Example: to_float("3.14") => 3.14
+/// Example: to_float("abc") => 0.0
+/// Example: to_float("abc", true) => error
+pub fn to_float(args: Vec<Value>) -> Result<Value, String> {
+    let (value, strict) = value_and_mode(&args, "to_float")?;
+    let number = to_number(&value, strict, "float")?;
+    Ok(Value::Float(number.as_float()?))
+}
+
+/// Convert a value to text, the way `show` prints it
+/// Example: to_str(123) => "123"
+/// Example: to_str([1, 2]) => "[1, 2]"
+pub fn to_str(args: Vec<Value>) -> Result<Value, String> {
+    if args.len() != 1 {
+        return Err("TypeConvert.to_str requires exactly 1 argument: value".to_string());
+    }
+    Ok(Value::String(args[0].to_string()))
+}
+
+/// Convert a value to true or false. Lenient mode treats 0, "", "false", "no", "off",
+/// null and empty lists and maps as false and anything else as true; strict mode
+/// only accepts booleans, 0, 1 and the text "true" or "false".
+/// Example: to_bool("yes") => true
+/// Example: to_bool([]) => false
+/// Example: to_bool("yes", true) => error
+pub fn to_bool(args: Vec<Value>) -> Result<Value, String> {
+    let (value, strict) = value_and_mode(&args, "to_bool")?;
+    let result = match &value {
+        Value::Bool(b) => Some(*b),
+        Value::Int(0) => Some(false),
+        Value::Int(1) => Some(true),
+        Value::String(text) if text.eq_ignore_ascii_case("true") => Some(true),
+        Value::String(text) if text.eq_ignore_ascii_case("false") => Some(false),
+        _ if strict => None,
+        Value::Int(_) => Some(true),
+        Value::Float(f) => Some(*f != 0.0),
+        Value::String(text) => {
+            let text = text.trim().to_lowercase();
+            Some(!matches!(text.as_str(), "" | "0" | "no" | "off" | "null"))
+        }
+        Value::Array(items) => Some(!items.is_empty()),
+        Value::Map(entries) => Some(!entries.is_empty()),
+        Value::Null => Some(false),
+    };
+    result.map(Value::Bool).ok_or_else(|| cannot_convert(&value, "bool"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(value: &str) -> Value {
+        Value::String(value.to_string())
+    }
+
+    #[test]
+    fn test_strict_and_lenient_conversions() {
+        assert_eq!(to_int(vec![text(" 42 ")]).unwrap(), Value::Int(42));
+        assert_eq!(to_int(vec![text("-7.9 degrees")]).unwrap(), Value::Int(-7));
+        assert_eq!(to_int(vec![text("abc")]).unwrap(), Value::Int(0));
+        assert_eq!(to_int(vec![Value::Float(3.0), Value::Bool(true)]).unwrap(), Value::Int(3));
+        assert!(to_int(vec![text("12abc"), Value::Bool(true)]).is_err());
+        assert!(to_int(vec![Value::Float(1e300)]).is_err());
+        assert!(to_int(vec![Value::Array(vec![])]).unwrap_err().contains("Cannot convert array"));
+
+        assert_eq!(to_float(vec![text("2.5e1")]).unwrap(), Value::Float(25.0));
+        assert_eq!(to_float(vec![Value::Bool(true)]).unwrap(), Value::Float(1.0));
+        assert!(to_float(vec![text("1,5"), Value::Bool(true)]).is_err());
+
+        assert_eq!(to_bool(vec![text("Off")]).unwrap(), Value::Bool(false));
+        assert_eq!(to_bool(vec![Value::Int(2)]).unwrap(), Value::Bool(true));
+        assert_eq!(to_bool(vec![text("FALSE"), Value::Bool(true)]).unwrap(), Value::Bool(false));
+        assert!(to_bool(vec![Value::Int(2), Value::Bool(true)]).is_err());
+    }
+}
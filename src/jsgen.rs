@@ -0,0 +1,908 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use crate::ast::{Expression, Program, Statement};
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::structs::{Kind, StructType};
+
+/// File name of the runtime that generated modules import
+pub const RUNTIME_FILE: &str = "razen_runtime.js";
+
+/// Source of the runtime, written next to every generated module
+pub const RUNTIME: &str = include_str!("jsgen/runtime.js");
+
+const INDENT: &str = "    ";
+
+// Razen libraries the runtime provides: library name, JavaScript object and functions
+const LIBRARIES: &[(&str, &str, &[&str])] = &[
+    ("arrlib", "ArrLib", &["push", "pop", "join", "length", "unique", "sort", "reverse", "slice"]),
+    ("strlib", "StrLib", &[
+        "upper", "lower", "substring", "replace", "length", "split", "trim",
+        "starts_with", "ends_with", "contains", "repeat",
+    ]),
+    ("mathlib", "MathLib", &[
+        "add", "subtract", "multiply", "divide", "power", "sqrt", "abs", "round", "floor", "ceil",
+        "sin", "cos", "tan", "log", "exp", "random", "max", "min", "modulo",
+    ]),
+    ("timelib", "TimeLib", &["now", "add", "year", "month", "day"]),
+    ("random", "Random", &["int", "float", "choice", "shuffle"]),
+    ("storage", "Storage", &["set", "get", "has", "delete", "keys", "clear", "namespace"]),
+    ("dom", "DOM", &[
+        "by_id", "query", "query_all", "create", "text", "html", "value", "attr", "add_class",
+        "remove_class", "toggle_class", "has_class", "append", "remove", "clear", "on", "focus",
+    ]),
+    ("window", "Window", &["alert", "confirm", "prompt", "timeout", "interval", "clear_timer", "location", "navigate"]),
+    // Built into JavaScript, so never imported
+    ("json", "JSON", &["parse", "stringify"]),
+];
+
+// Helpers the runtime exports for statements
+const HELPERS: &[&str] = &["show", "format", "errorMessage", "read", "exit", "debug", "trace", "assert", "defineStruct", "equals"];
+
+// Words a JavaScript variable cannot use, and globals generated code relies on
+const RESERVED: &[&str] = &[
+    "arguments", "await", "case", "catch", "class", "const", "default", "delete", "do", "enum", "eval",
+    "extends", "function", "implements", "instanceof", "interface", "let", "new", "package", "private",
+    "protected", "public", "static", "super", "switch", "this", "typeof", "undefined", "void", "with",
+    "yield", "Infinity", "NaN", "Array", "Error", "JSON", "Math", "Object",
+];
+
+// Binding strength of generated expressions, as in the formatter
+const ASSIGNMENT: u8 = 1;
+const PREFIX: u8 = 9;
+const CALL: u8 = 11;
+const ATOM: u8 = 13;
+
+/// Compile Razen source to an ES module
+pub fn compile_source(source: &str) -> Result<String, String> {
+    let mut parser = Parser::new(Lexer::new(source.to_string()));
+    parser.set_preserve_trivia(true);
+    let program = parser.parse_program();
+    if !parser.get_errors().is_empty() {
+        return Err(format!("Cannot compile a file with syntax errors:\n  {}", parser.get_errors().join("\n  ")));
+    }
+    compile_program(&program)
+}
+
+/// Compile a program to an ES module that imports what it uses from the runtime
+pub fn compile_program(program: &Program) -> Result<String, String> {
+    let mut generator = Generator::new(&program.statements);
+    generator.module(&program.statements)?;
+    Ok(generator.finish())
+}
+
+/// Compile a source file to `output` and write the runtime next to it
+pub fn compile_file(source: &Path, output: &Path) -> Result<(), String> {
+    let text = fs::read_to_string(source).map_err(|e| format!("Failed to read {}: {}", source.display(), e))?;
+    let module = compile_source(&text)?;
+    fs::write(output, module).map_err(|e| format!("Failed to write {}: {}", output.display(), e))?;
+    let runtime = output.with_file_name(RUNTIME_FILE);
+    fs::write(&runtime, RUNTIME).map_err(|e| format!("Failed to write {}: {}", runtime.display(), e))
+}
+
+// Name a JavaScript variable can use for a Razen name
+fn js_name(name: &str) -> String {
+    let taken = RESERVED.contains(&name)
+        || HELPERS.contains(&name)
+        || LIBRARIES.iter().any(|(_, object, _)| *object == name);
+    if taken { format!("{}_", name) } else { name.to_string() }
+}
+
+fn is_js_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+// Name a statement declares as a variable
+fn declared_name(statement: &Statement) -> Option<&str> {
+    match statement {
+        Statement::VariableDeclaration { name, .. }
+        | Statement::VolatileDeclaration { name, .. }
+        | Statement::ConstDeclaration { name, .. }
+        | Statement::ReadStatement { name } => Some(name),
+        _ => None,
+    }
+}
+
+// Blocks run as part of the enclosing function, with the iterator of a for loop
+fn child_blocks(statement: &Statement) -> Vec<(&[Statement], Option<&str>)> {
+    match statement {
+        Statement::IfStatement { consequence, alternative, .. } => {
+            let mut blocks = vec![(consequence.as_slice(), None)];
+            blocks.extend(alternative.iter().map(|block| (block.as_slice(), None)));
+            blocks
+        },
+        Statement::WhileStatement { body, .. } => vec![(body.as_slice(), None)],
+        Statement::ForStatement { iterator, body, .. } => vec![(body.as_slice(), Some(iterator.as_str()))],
+        Statement::TryStatement { try_block, catch_block, finally_block, .. } => {
+            let mut blocks = vec![(try_block.as_slice(), None)];
+            blocks.extend(catch_block.iter().chain(finally_block).map(|block| (block.as_slice(), None)));
+            blocks
+        },
+        Statement::BlockStatement { statements } | Statement::WhenStatement { body: statements, .. } => vec![(statements.as_slice(), None)],
+        Statement::LoadStatement { block, .. } => vec![(block.as_slice(), None)],
+        _ => Vec::new(),
+    }
+}
+
+// Functions nested in a statement, with their parameters
+fn functions(statement: &Statement) -> Vec<(&[String], &[Statement])> {
+    match statement {
+        Statement::FunctionDeclaration { parameters, body, .. }
+        | Statement::InlineFunctionDeclaration { parameters, body, .. }
+        | Statement::StaticMethodDeclaration { parameters, body, .. } => vec![(parameters.as_slice(), body.as_slice())],
+        Statement::ClassDeclaration { body, .. } | Statement::FinalClassDeclaration { body, .. } => {
+            body.iter().flat_map(functions).collect()
+        },
+        _ => Vec::new(),
+    }
+}
+
+fn statement_expressions(statement: &Statement) -> Vec<&Expression> {
+    match statement {
+        Statement::VariableDeclaration { value, .. }
+        | Statement::VolatileDeclaration { value, .. }
+        | Statement::ReturnStatement { value } => value.iter().collect(),
+        Statement::ExitStatement { code } => code.iter().collect(),
+        Statement::ConstDeclaration { value, .. }
+        | Statement::ShowStatement { value, .. }
+        | Statement::ThrowStatement { value }
+        | Statement::DebugStatement { value }
+        | Statement::TraceStatement { value } => vec![value],
+        Statement::ExpressionStatement { expression } => vec![expression],
+        Statement::IfStatement { condition, .. } | Statement::WhileStatement { condition, .. } => vec![condition],
+        Statement::ForStatement { iterable, .. } => vec![iterable],
+        Statement::AssertStatement { condition, message } => std::iter::once(condition).chain(message).collect(),
+        Statement::LoadStatement { cycles, .. } => vec![cycles],
+        _ => Vec::new(),
+    }
+}
+
+// Call `visit` with every variable an expression reads or writes
+fn expression_names<'a>(expression: &'a Expression, visit: &mut impl FnMut(&'a str)) {
+    match expression {
+        Expression::Identifier(name) => visit(name),
+        Expression::PrefixExpression { right, .. } => expression_names(right, visit),
+        // The right side of `a.b` is a property name
+        Expression::InfixExpression { left, operator, .. } if operator == "." || operator == "::" => {
+            expression_names(left, visit)
+        },
+        Expression::InfixExpression { left, right, .. }
+        | Expression::AssignmentExpression { left, right, .. }
+        | Expression::IndexExpression { left, index: right } => {
+            expression_names(left, visit);
+            expression_names(right, visit);
+        },
+        Expression::CallExpression { function, arguments } => {
+            expression_names(function, visit);
+            arguments.iter().for_each(|argument| expression_names(argument, visit));
+        },
+        Expression::ArrayLiteral { elements } => elements.iter().for_each(|element| expression_names(element, visit)),
+        Expression::MapLiteral { pairs } => pairs.iter().for_each(|(key, value)| {
+            expression_names(key, visit);
+            expression_names(value, visit);
+        }),
+        Expression::LibraryCall { arguments, .. } | Expression::NamespaceCall { arguments, .. } => {
+            arguments.iter().for_each(|argument| expression_names(argument, visit))
+        },
+        Expression::StructLiteral { fields, .. } => fields.iter().for_each(|(_, value)| expression_names(value, visit)),
+        Expression::StringLiteral(_) | Expression::NumberLiteral(_) | Expression::BooleanLiteral(_) | Expression::NullLiteral => {},
+    }
+}
+
+fn assigned_in_expression<'a>(expression: &'a Expression, assigned: &mut Vec<&'a str>) {
+    if let Expression::AssignmentExpression { left, right, .. } = expression {
+        if let Expression::Identifier(name) = &**left {
+            assigned.push(name);
+        }
+        assigned_in_expression(right, assigned);
+    }
+}
+
+// Variables assigned in a function body or the module, outside nested functions
+fn assigned_names<'a>(statements: &'a [Statement], assigned: &mut Vec<&'a str>) {
+    for statement in statements {
+        for expression in statement_expressions(statement) {
+            assigned_in_expression(expression, assigned);
+        }
+        for (block, _) in child_blocks(statement) {
+            assigned_names(block, assigned);
+        }
+    }
+}
+
+// Count how often each variable appears. A nested function counts once for every
+// variable it uses from outside.
+fn count_names<'a>(statements: &'a [Statement], counts: &mut HashMap<&'a str, usize>) {
+    for statement in statements {
+        if let Some(name) = declared_name(statement) {
+            *counts.entry(name).or_default() += 1;
+        }
+        for expression in statement_expressions(statement) {
+            expression_names(expression, &mut |name| *counts.entry(name).or_default() += 1);
+        }
+        for (block, iterator) in child_blocks(statement) {
+            if let Some(iterator) = iterator {
+                *counts.entry(iterator).or_default() += 1;
+            }
+            count_names(block, counts);
+        }
+        for (parameters, body) in functions(statement) {
+            let mut inner = HashMap::new();
+            count_names(body, &mut inner);
+            let mut local = HashSet::new();
+            declared_names(body, &mut local);
+            for name in inner.into_keys() {
+                if !local.contains(name) && !parameters.iter().any(|parameter| parameter == name) {
+                    *counts.entry(name).or_default() += 1;
+                }
+            }
+        }
+    }
+}
+
+// Variables declared anywhere in a function body or the module, outside nested functions
+fn declared_names<'a>(statements: &'a [Statement], names: &mut HashSet<&'a str>) {
+    for statement in statements {
+        names.extend(declared_name(statement));
+        for (block, iterator) in child_blocks(statement) {
+            names.extend(iterator);
+            declared_names(block, names);
+        }
+    }
+}
+
+// Blocks nested anywhere in a function body or the module, outside nested functions
+fn nested_blocks<'a>(statements: &'a [Statement], blocks: &mut Vec<(&'a [Statement], Option<&'a str>)>) {
+    for statement in statements {
+        for (block, iterator) in child_blocks(statement) {
+            blocks.push((block, iterator));
+            nested_blocks(block, blocks);
+        }
+    }
+}
+
+fn operator_precedence(operator: &str) -> u8 {
+    match operator {
+        "=" | "+=" | "-=" | "*=" | "/=" | "%=" => ASSIGNMENT,
+        "||" => 2,
+        "&&" => 3,
+        "==" | "!=" => 4,
+        "<" | "<=" | ">" | ">=" => 5,
+        "+" | "-" => 6,
+        "*" | "/" | "%" => 7,
+        "**" => 8,
+        _ => CALL,
+    }
+}
+
+fn precedence(expression: &Expression) -> u8 {
+    match expression {
+        Expression::AssignmentExpression { .. } => ASSIGNMENT,
+        Expression::InfixExpression { operator, .. } => operator_precedence(operator),
+        Expression::PrefixExpression { .. } => PREFIX,
+        Expression::CallExpression { .. }
+        | Expression::IndexExpression { .. }
+        | Expression::LibraryCall { .. }
+        | Expression::NamespaceCall { .. } => CALL,
+        _ => ATOM,
+    }
+}
+
+struct Generator {
+    out: String,
+    indent: usize,
+    imports: BTreeSet<&'static str>,
+    classes: HashSet<String>,
+    enums: HashSet<String>,
+    structs: HashMap<String, StructType>, // Structs declared so far
+    globals: HashSet<String>,     // Variables of the module, which functions share
+    scopes: Vec<HashSet<String>>, // Variables declared so far in the function being generated
+    in_function: bool,
+    skipped: bool,                // The last statement produced no code
+}
+
+impl Generator {
+    fn new(statements: &[Statement]) -> Self {
+        let mut globals: HashSet<String> = HashSet::new();
+        let mut declared = HashSet::new();
+        declared_names(statements, &mut declared);
+        let mut assigned = Vec::new();
+        assigned_names(statements, &mut assigned);
+        globals.extend(declared.into_iter().chain(assigned).map(str::to_string));
+
+        let mut classes = HashSet::new();
+        let mut enums = HashSet::new();
+        for statement in statements {
+            match statement {
+                Statement::ClassDeclaration { name, .. } | Statement::FinalClassDeclaration { name, .. } => {
+                    classes.insert(name.clone());
+                },
+                Statement::EnumDeclaration { name, .. } => {
+                    enums.insert(name.clone());
+                },
+                _ => {},
+            }
+        }
+
+        Generator {
+            out: String::new(),
+            indent: 0,
+            imports: BTreeSet::new(),
+            classes,
+            enums,
+            structs: HashMap::new(),
+            globals,
+            scopes: Vec::new(),
+            in_function: false,
+            skipped: false,
+        }
+    }
+
+    fn finish(self) -> String {
+        let mut module = String::from("// Generated by `razen compile --target=js`; edit the Razen source instead\n");
+        if !self.imports.is_empty() {
+            let imports: Vec<&str> = self.imports.into_iter().collect();
+            module.push_str(&format!("import {{ {} }} from \"./{}\";\n", imports.join(", "), RUNTIME_FILE));
+        }
+        module.push('\n');
+        module.push_str(self.out.trim_start_matches('\n'));
+        module
+    }
+
+    fn line(&mut self, text: &str) {
+        for _ in 0..self.indent {
+            self.out.push_str(INDENT);
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn import(&mut self, name: &'static str) -> &'static str {
+        self.imports.insert(name);
+        name
+    }
+
+    fn visible(&self, name: &str) -> bool {
+        self.scopes.iter().any(|scope| scope.contains(name))
+    }
+
+    // Razen variables live until their function returns, while JavaScript `let` ends with
+    // the block. Variables declared in a block and used outside it, declared twice at
+    // different depths, or assigned without a declaration are declared when the function
+    // starts; the rest are declared where Razen declares them.
+    fn hoisted_names(&self, body: &[Statement], parameters: &[String]) -> Vec<String> {
+        let top: HashSet<&str> = body.iter().filter_map(declared_name).collect();
+        let mut totals = HashMap::new();
+        count_names(body, &mut totals);
+
+        let mut blocks = Vec::new();
+        nested_blocks(body, &mut blocks);
+        let mut declaring: Vec<(&str, Vec<usize>)> = Vec::new();
+        for (index, (block, iterator)) in blocks.iter().enumerate() {
+            for name in block.iter().filter_map(declared_name).chain(*iterator) {
+                match declaring.iter_mut().find(|(declared, _)| *declared == name) {
+                    Some((_, indexes)) if indexes.contains(&index) => {},
+                    Some((_, indexes)) => indexes.push(index),
+                    None => declaring.push((name, vec![index])),
+                }
+            }
+        }
+
+        let is_parameter = |name: &str| parameters.iter().any(|parameter| parameter == name);
+        let mut hoisted: Vec<String> = Vec::new();
+        for (name, indexes) in &declaring {
+            let inside: usize = indexes.iter()
+                .map(|&index| {
+                    let (block, iterator) = blocks[index];
+                    let mut counts = HashMap::new();
+                    count_names(block, &mut counts);
+                    counts.get(name).copied().unwrap_or(0) + usize::from(iterator == Some(*name))
+                })
+                .sum();
+            if !is_parameter(name) && (top.contains(name) || inside != totals[name]) {
+                hoisted.push(name.to_string());
+            }
+        }
+
+        let mut assigned = Vec::new();
+        assigned_names(body, &mut assigned);
+        for name in assigned {
+            let declared = is_parameter(name) || top.contains(name) || declaring.iter().any(|(declared, _)| *declared == name);
+            let global = self.in_function && self.globals.contains(name);
+            if !declared && !global && !hoisted.iter().any(|hoisted| hoisted == name) {
+                hoisted.push(name.to_string());
+            }
+        }
+        hoisted
+    }
+
+    fn module(&mut self, statements: &[Statement]) -> Result<(), String> {
+        let hoisted = self.hoisted_names(statements, &[]);
+        if !hoisted.is_empty() {
+            let names: Vec<String> = hoisted.iter().map(|name| js_name(name)).collect();
+            self.line(&format!("let {};", names.join(", ")));
+        }
+        self.scopes = vec![hoisted.into_iter().collect()];
+        self.statements(statements)
+    }
+
+    fn function(&mut self, header: &str, parameters: &[String], body: &[Statement]) -> Result<(), String> {
+        let names: Vec<String> = parameters.iter().map(|parameter| js_name(parameter)).collect();
+        self.line(&format!("{}({}) {{", header, names.join(", ")));
+
+        let was_in_function = std::mem::replace(&mut self.in_function, true);
+        let hoisted = self.hoisted_names(body, parameters);
+        self.indent += 1;
+        if !hoisted.is_empty() {
+            let names: Vec<String> = hoisted.iter().map(|name| js_name(name)).collect();
+            self.line(&format!("let {};", names.join(", ")));
+        }
+        let scope = parameters.iter().cloned().chain(hoisted).collect();
+        let outer = std::mem::replace(&mut self.scopes, vec![scope]);
+        let result = self.statements(body);
+        self.scopes = outer;
+        self.indent -= 1;
+        self.in_function = was_in_function;
+
+        self.line("}");
+        result
+    }
+
+    // Indented statements in a scope of their own; the caller writes the braces
+    fn block(&mut self, statements: &[Statement], scope: HashSet<String>) -> Result<(), String> {
+        self.indent += 1;
+        self.scopes.push(scope);
+        let result = self.statements(statements);
+        self.scopes.pop();
+        self.indent -= 1;
+        result
+    }
+
+    fn statements(&mut self, statements: &[Statement]) -> Result<(), String> {
+        for statement in statements {
+            self.statement(statement)?;
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self, statement: &Statement) -> Result<(), String> {
+        let skipped = std::mem::replace(&mut self.skipped, false);
+        match statement {
+            Statement::VariableDeclaration { name, value, .. } | Statement::VolatileDeclaration { name, value, .. } => {
+                let value = match value {
+                    Some(value) => self.expression(value)?,
+                    None => "null".to_string(),
+                };
+                self.declaration("let", name, &value);
+            },
+            Statement::ConstDeclaration { name, value } => {
+                let value = self.expression(value)?;
+                self.declaration("const", name, &value);
+            },
+            Statement::ReadStatement { name } => {
+                let value = format!("{}()", self.import("read"));
+                self.declaration("let", name, &value);
+            },
+            Statement::FunctionDeclaration { name, parameters, body }
+            | Statement::InlineFunctionDeclaration { name, parameters, body } => {
+                self.function(&format!("function {}", js_name(name)), parameters, body)?;
+            },
+            Statement::StaticMethodDeclaration { name, parameters, body, .. } => {
+                self.function(&format!("static {}", name), parameters, body)?;
+            },
+            Statement::ReturnStatement { value } => match value {
+                Some(value) => {
+                    let value = self.expression(value)?;
+                    self.line(&format!("return {};", value));
+                },
+                None => self.line("return;"),
+            },
+            Statement::ExpressionStatement { expression } => {
+                let expression = self.expression(expression)?;
+                self.line(&format!("{};", expression));
+            },
+            Statement::BlockStatement { statements } | Statement::WhenStatement { body: statements, .. } => {
+                self.line("{");
+                self.block(statements, HashSet::new())?;
+                self.line("}");
+            },
+            Statement::IfStatement { .. } => self.if_statement(statement, "if")?,
+            Statement::WhileStatement { condition, body } => {
+                let condition = self.expression(condition)?;
+                self.line(&format!("while ({}) {{", condition));
+                self.block(body, HashSet::new())?;
+                self.line("}");
+            },
+            Statement::ForStatement { iterator, iterable, body } => {
+                let iterable = self.expression(iterable)?;
+                let name = js_name(iterator);
+                if self.visible(iterator) {
+                    self.line(&format!("for ({} of {}) {{", name, iterable));
+                } else {
+                    let mut assigned = Vec::new();
+                    assigned_names(body, &mut assigned);
+                    let keyword = if assigned.contains(&iterator.as_str()) { "let" } else { "const" };
+                    self.line(&format!("for ({} {} of {}) {{", keyword, name, iterable));
+                }
+                self.block(body, HashSet::from([iterator.clone()]))?;
+                self.line("}");
+            },
+            Statement::BreakStatement => self.line("break;"),
+            Statement::ContinueStatement => self.line("continue;"),
+            // Colors are for terminals; the console keeps its own styling
+            Statement::ShowStatement { value, .. } => {
+                let value = self.expression(value)?;
+                let show = self.import("show");
+                self.line(&format!("{}({});", show, value));
+            },
+            Statement::TryStatement { try_block, catch_param, catch_block, finally_block } => {
+                self.line("try {");
+                self.block(try_block, HashSet::new())?;
+                match (catch_param, catch_block) {
+                    (Some(param), Some(catch_block)) => {
+                        let name = js_name(param);
+                        self.line(&format!("}} catch ({}) {{", name));
+                        let message = self.import("errorMessage");
+                        self.indent += 1;
+                        self.line(&format!("{} = {}({});", name, message, name));
+                        self.indent -= 1;
+                        self.block(catch_block, HashSet::from([param.clone()]))?;
+                    },
+                    (None, Some(catch_block)) => {
+                        self.line("} catch {");
+                        self.block(catch_block, HashSet::new())?;
+                    },
+                    // Errors in a try without catch or finally are ignored, as in the interpreter
+                    (_, None) if finally_block.is_none() => self.line("} catch {"),
+                    (_, None) => {},
+                }
+                if let Some(finally_block) = finally_block {
+                    self.line("} finally {");
+                    self.block(finally_block, HashSet::new())?;
+                }
+                self.line("}");
+            },
+            Statement::ThrowStatement { value } => {
+                let value = self.expression(value)?;
+                self.line(&format!("throw new Error({});", value));
+            },
+            Statement::ExitStatement { code } => {
+                let code = match code {
+                    Some(code) => self.expression(code)?,
+                    None => String::new(),
+                };
+                let exit = self.import("exit");
+                self.line(&format!("{}({});", exit, code));
+            },
+            Statement::ModuleExport { name } => self.line(&format!("export {{ {} }};", js_name(name))),
+            Statement::DebugStatement { value } => {
+                let value = self.expression(value)?;
+                let debug = self.import("debug");
+                self.line(&format!("{}({});", debug, value));
+            },
+            Statement::TraceStatement { value } => {
+                let value = self.expression(value)?;
+                let trace = self.import("trace");
+                self.line(&format!("{}({});", trace, value));
+            },
+            Statement::AssertStatement { condition, message } => {
+                let mut arguments = vec![self.expression(condition)?];
+                if let Some(message) = message {
+                    arguments.push(self.expression(message)?);
+                }
+                let assert = self.import("assert");
+                self.line(&format!("{}({});", assert, arguments.join(", ")));
+            },
+            Statement::ClassDeclaration { name, body } | Statement::FinalClassDeclaration { name, body } => {
+                self.line(&format!("class {} {{", js_name(name)));
+                self.indent += 1;
+                for statement in body {
+                    match statement {
+                        Statement::StaticMethodDeclaration { .. } | Statement::Comment { .. } | Statement::BlankLine => {
+                            self.statement(statement)?;
+                        },
+                        Statement::FunctionDeclaration { name, parameters, body } => {
+                            self.function(&format!("static {}", name), parameters, body)?;
+                        },
+                        _ => return Err(format!("Class {} can only contain static methods in JavaScript", name)),
+                    }
+                }
+                self.indent -= 1;
+                self.line("}");
+            },
+            Statement::EnumDeclaration { name, variants } => {
+                // Variants without a value are numbered from 0, skipping those with one
+                let mut next = 0;
+                let mut entries = Vec::new();
+                for (variant, value) in variants {
+                    let value = match value {
+                        Some(value) => self.expression(value)?,
+                        None => {
+                            next += 1;
+                            (next - 1).to_string()
+                        },
+                    };
+                    entries.push(format!("{}: {}", variant, value));
+                }
+                self.line(&format!("const {} = Object.freeze({{ {} }});", js_name(name), entries.join(", ")));
+            },
+            Statement::StructDeclaration { name, fields } => {
+                let struct_type = StructType::declare(name, fields, &self.structs)?;
+                let kinds: Vec<String> = struct_type.fields.iter()
+                    .map(|(field, kind)| format!("{}: {}", field, serde_json::to_string(kind.name()).unwrap()))
+                    .collect();
+                let define = self.import("defineStruct");
+                self.line(&format!("const {} = {}({:?}, {{ {} }});", js_name(name), define, name, kinds.join(", ")));
+                self.structs.insert(name.clone(), struct_type);
+            },
+            Statement::Comment { text, trailing } => {
+                let comment = format!("//{}", text.trim_end());
+                if *trailing && !skipped && self.out.ends_with('\n') {
+                    self.out.pop();
+                    self.out.push(' ');
+                    self.out.push_str(&comment);
+                    self.out.push('\n');
+                } else {
+                    self.line(&comment);
+                }
+            },
+            Statement::BlankLine => {
+                if !self.out.is_empty() && !self.out.ends_with("\n\n") && !self.out.ends_with("{\n") {
+                    self.out.push('\n');
+                }
+            },
+            // The document type and `lib` imports only matter to the interpreter, and
+            // tests run with `razen test`
+            Statement::DocumentTypeDeclaration { .. } | Statement::LibStatement { .. } | Statement::TestDeclaration { .. } => {
+                self.skipped = true;
+            },
+            _ => return Err(format!("{} is not supported by the JavaScript backend", unsupported(statement))),
+        }
+        Ok(())
+    }
+
+    fn declaration(&mut self, keyword: &str, name: &str, value: &str) {
+        let js = js_name(name);
+        if self.visible(name) {
+            self.line(&format!("{} = {};", js, value));
+        } else {
+            self.scopes.last_mut().unwrap().insert(name.to_string());
+            self.line(&format!("{} {} = {};", keyword, js, value));
+        }
+    }
+
+    // `if`, with elif and else branches chained as `} else if (...) {`
+    fn if_statement(&mut self, statement: &Statement, keyword: &str) -> Result<(), String> {
+        let Statement::IfStatement { condition, consequence, alternative } = statement else {
+            unreachable!("if_statement called with another statement");
+        };
+        let condition = self.expression(condition)?;
+        self.line(&format!("{} ({}) {{", keyword, condition));
+        self.block(consequence, HashSet::new())?;
+        match alternative.as_deref() {
+            Some([elif @ Statement::IfStatement { .. }]) => self.if_statement(elif, "} else if"),
+            Some(alternative) => {
+                self.line("} else {");
+                self.block(alternative, HashSet::new())?;
+                self.line("}");
+                Ok(())
+            },
+            None => {
+                self.line("}");
+                Ok(())
+            },
+        }
+    }
+
+    fn parenthesized(&mut self, expression: &Expression, needs_parens: bool) -> Result<String, String> {
+        let text = self.expression(expression)?;
+        Ok(if needs_parens { format!("({})", text) } else { text })
+    }
+
+    // Left side of a call, index or property access
+    fn postfix_target(&mut self, expression: &Expression) -> Result<String, String> {
+        let needs_parens = precedence(expression) < CALL || matches!(expression, Expression::NumberLiteral(_));
+        self.parenthesized(expression, needs_parens)
+    }
+
+    fn arguments(&mut self, arguments: &[Expression]) -> Result<String, String> {
+        let arguments = arguments.iter().map(|argument| self.expression(argument)).collect::<Result<Vec<_>, _>>()?;
+        Ok(arguments.join(", "))
+    }
+
+    fn expression(&mut self, expression: &Expression) -> Result<String, String> {
+        Ok(match expression {
+            Expression::Identifier(name) => js_name(name),
+            Expression::StringLiteral(value) => serde_json::to_string(value).unwrap(),
+            Expression::NumberLiteral(value) if value.fract() == 0.0 && value.abs() < 1e15 => format!("{}", *value as i64),
+            Expression::NumberLiteral(value) => format!("{}", value),
+            Expression::BooleanLiteral(value) => format!("{}", value),
+            Expression::NullLiteral => "null".to_string(),
+            Expression::PrefixExpression { operator, right } => {
+                format!("{}{}", operator, self.parenthesized(right, precedence(right) < PREFIX)?)
+            },
+            Expression::InfixExpression { left, operator, right } if operator == "." || operator == "::" => {
+                let Expression::Identifier(property) = &**right else {
+                    return Err(format!("Expected a property name after '{}'", operator));
+                };
+                format!("{}.{}", self.postfix_target(left)?, property)
+            },
+            // Structs compare field by field, which == doesn't do for objects
+            Expression::InfixExpression { left, operator, right }
+                if (operator == "==" || operator == "!=") && !self.structs.is_empty() && !is_primitive(left) && !is_primitive(right) =>
+            {
+                let negation = if operator == "!=" { "!" } else { "" };
+                let equals = self.import("equals");
+                format!("{}{}({}, {})", negation, equals, self.expression(left)?, self.expression(right)?)
+            },
+            Expression::InfixExpression { left, operator, right } if operator == "//" => {
+                format!("Math.floor({} / {})", self.parenthesized(left, precedence(left) < 7)?, self.parenthesized(right, precedence(right) <= 7)?)
+            },
+            Expression::InfixExpression { left, operator, right } => {
+                let level = operator_precedence(operator);
+                // JavaScript needs parentheses around either side of a power
+                let left_parens = if operator == "**" { precedence(left) <= level } else { precedence(left) < level };
+                format!(
+                    "{} {} {}",
+                    self.parenthesized(left, left_parens)?,
+                    operator,
+                    self.parenthesized(right, precedence(right) <= level)?
+                )
+            },
+            Expression::AssignmentExpression { left, operator, right } => {
+                format!("{} {} {}", self.parenthesized(left, precedence(left) <= ASSIGNMENT)?, operator, self.expression(right)?)
+            },
+            Expression::CallExpression { function, arguments } => {
+                format!("{}({})", self.postfix_target(function)?, self.arguments(arguments)?)
+            },
+            Expression::ArrayLiteral { elements } => format!("[{}]", self.arguments(elements)?),
+            Expression::IndexExpression { left, index } => match (&**left, &**index) {
+                (Expression::Identifier(name), Expression::Identifier(variant)) if self.enums.contains(name) => {
+                    format!("{}.{}", js_name(name), variant)
+                },
+                _ => format!("{}[{}]", self.postfix_target(left)?, self.expression(index)?),
+            },
+            Expression::MapLiteral { pairs } if pairs.is_empty() => "{}".to_string(),
+            Expression::MapLiteral { pairs } => {
+                let mut entries = Vec::new();
+                for (key, value) in pairs {
+                    let key = match key {
+                        Expression::StringLiteral(key) if is_js_identifier(key) => key.clone(),
+                        Expression::StringLiteral(_) | Expression::NumberLiteral(_) => self.expression(key)?,
+                        _ => format!("[{}]", self.expression(key)?),
+                    };
+                    entries.push(format!("{}: {}", key, self.expression(value)?));
+                }
+                format!("{{ {} }}", entries.join(", "))
+            },
+            Expression::LibraryCall { library, function, arguments } => {
+                let (Expression::Identifier(library), Expression::Identifier(function)) = (&**library, &**function) else {
+                    return Err("Library calls must name the library and function directly in JavaScript".to_string());
+                };
+                self.library_call(library, function, arguments)?
+            },
+            Expression::NamespaceCall { namespace, function, arguments } => self.library_call(namespace, function, arguments)?,
+            Expression::StructLiteral { name, fields } => {
+                let struct_type = self.structs.get(name).cloned().ok_or_else(|| format!("Unknown struct: {}", name))?;
+                let values = struct_type.order(fields, Kind::of_literal)?;
+                let mut entries = Vec::new();
+                for ((field, _), value) in struct_type.fields.iter().zip(values) {
+                    entries.push(format!("{}: {}", field, self.expression(value)?));
+                }
+                match entries.is_empty() {
+                    true => format!("{}({{}})", js_name(name)),
+                    false => format!("{}({{ {} }})", js_name(name), entries.join(", ")),
+                }
+            },
+        })
+    }
+
+    // Static methods of the program's classes, or a library of the runtime
+    fn library_call(&mut self, library: &str, function: &str, arguments: &[Expression]) -> Result<String, String> {
+        let arguments = self.arguments(arguments)?;
+        if self.classes.contains(library) {
+            return Ok(format!("{}.{}({})", js_name(library), function, arguments));
+        }
+
+        let Some((_, object, functions)) = LIBRARIES.iter().find(|(name, _, _)| name.eq_ignore_ascii_case(library)) else {
+            return Err(format!("Library '{}' is not available in JavaScript", library));
+        };
+        if !functions.contains(&function) {
+            return Err(format!("{}.{} is not available in JavaScript", object, function));
+        }
+        if *object != "JSON" {
+            self.import(object);
+        }
+        Ok(format!("{}.{}({})", object, function, arguments))
+    }
+}
+
+// Literals that == already compares by value
+fn is_primitive(expression: &Expression) -> bool {
+    matches!(expression, Expression::NullLiteral) || matches!(Kind::of_literal(expression), Some(Kind::Number | Kind::String | Kind::Bool))
+}
+
+fn unsupported(statement: &Statement) -> &'static str {
+    match statement {
+        Statement::ModuleImport { .. } | Statement::ImportStatement { .. } => "Importing modules",
+        Statement::NativeLibStatement { .. } => "Loading native libraries",
+        Statement::ApiDeclaration { .. } | Statement::ApiCall { .. } => "Declaring APIs",
+        Statement::ConnectStatement { .. } => "Connecting to services",
+        Statement::LoadStatement { .. } => "Loading animations",
+        _ => "Compiler construction",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn test_variables_follow_razen_scoping() {
+        let source = "type web;\n\n# Totals\nnum total = 0;\nfun add(items) {\n    for (item in items) {\n        if (item > 0) {\n            num last = item;\n        }\n        total = total + item; # shared\n    }\n    return last;\n}\nshow StrLib[upper](\"a\" + add([1, 2]) // 2 ** 2);\n";
+
+        assert_eq!(compile_source(source).unwrap(), "// Generated by `razen compile --target=js`; edit the Razen source instead\nimport { StrLib, show } from \"./razen_runtime.js\";\n\n// Totals\nlet total = 0;\nfunction add(items) {\n    let last;\n    for (const item of items) {\n        if (item > 0) {\n            last = item;\n        }\n        total = total + item; // shared\n    }\n    return last;\n}\nshow(StrLib.upper(\"a\" + Math.floor(add([1, 2]) / 2 ** 2)));\n");
+        assert!(compile_source("connect db = from(\"postgres://localhost\");").unwrap_err().contains("not supported"));
+        assert!(compile_source("show FileLib[read](\"x\");").unwrap_err().contains("not available"));
+    }
+
+    #[test]
+    fn test_runtime_defines_every_library_function() {
+        for (_, object, functions) in LIBRARIES.iter().filter(|(_, object, _)| *object != "JSON") {
+            let start = RUNTIME.find(&format!("export const {} = {{", object)).unwrap_or_else(|| panic!("{} missing", object));
+            let end = start + RUNTIME[start..].find("\n};").unwrap();
+            for function in *functions {
+                assert!(RUNTIME[start..end].contains(&format!("\n    {}:", function)), "{}.{} missing", object, function);
+            }
+        }
+        for helper in HELPERS {
+            assert!(RUNTIME.contains(&format!("export function {}(", helper)), "{} missing", helper);
+        }
+    }
+
+    #[test]
+    fn test_generated_module_runs_in_node() {
+        if Command::new("node").arg("--version").output().is_err() {
+            return;
+        }
+        let source = "enum Color { RED, GREEN = 5, BLUE }\nclass Shapes {\n    static area(w, h) {\n        return w * h;\n    }\n}\nvar counts = {\"a-b\": 1, \"c\": [1, 2]};\ntry {\n    throw \"boom\";\n} catch (e) {\n    show \"caught \" + e;\n}\nnum i = 0;\nwhile (i < 3) {\n    i += 1;\n    if (i == 2) {\n        continue;\n    } else {\n        if (i == 3) {\n            show Color[BLUE];\n        } else {\n            show ArrLib[push]([i], Shapes[area](2, 3));\n        }\n    }\n}\nshow counts;\nshow JSON[stringify](counts);\n";
+        assert_eq!(
+            run_in_node(source, "main"),
+            "caught boom\n[1, 6]\n1\n{a-b: 1, c: [1, 2]}\n{\"a-b\":1,\"c\":[1,2]}\n"
+        );
+    }
+
+    #[test]
+    fn test_structs_match_the_interpreter() {
+        assert!(compile_source("struct P { x: number }\nvar p = P { x: \"1\" };").unwrap_err().contains("P.x expects number, got string"));
+        assert!(compile_source("struct P { x: number }\nvar p = P {};").unwrap_err().contains("Missing field 'x'"));
+        if Command::new("node").arg("--version").output().is_err() {
+            return;
+        }
+        let source = fs::read_to_string("razen-tests/struct_test.rzn").unwrap();
+        let expected = fs::read_to_string("razen-tests/struct_test.expected").unwrap();
+        let stdout = expected.split("==== stdout ====\n").nth(1).unwrap().split("\n==== stderr ====").next().unwrap();
+        assert_eq!(run_in_node(&source, "structs"), stdout.replace('\r', ""));
+    }
+
+    // Stdout of a compiled program run by node
+    fn run_in_node(source: &str, name: &str) -> String {
+        let module = compile_source(source).unwrap();
+
+        let dir = std::env::temp_dir().join(format!("razen_jsgen_{}_{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("package.json"), "{\"type\": \"module\"}").unwrap();
+        fs::write(dir.join("main.js"), &module).unwrap();
+        fs::write(dir.join(RUNTIME_FILE), RUNTIME).unwrap();
+        let output = Command::new("node").arg(dir.join("main.js")).output().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(output.status.success(), "{}\n{}", module, String::from_utf8_lossy(&output.stderr));
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+}
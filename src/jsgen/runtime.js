@@ -0,0 +1,416 @@
+// Razen runtime for programs compiled with `razen compile --target=js`.
+// Written next to the generated module as razen_runtime.js; generated code
+// imports the helpers and libraries it uses from here.
+
+// Text of a value the way `show` prints it in the interpreter
+export function format(value) {
+    if (value === null || value === undefined) {
+        return "null";
+    }
+    if (Array.isArray(value)) {
+        return "[" + value.map(format).join(", ") + "]";
+    }
+    if (value[STRUCT]) {
+        const { name, fields } = value[STRUCT];
+        const entries = Object.keys(fields).map((field) => {
+            const item = value[field];
+            return field + ": " + (typeof item === "string" ? JSON.stringify(item) : format(item));
+        });
+        return entries.length === 0 ? name + " {}" : name + " { " + entries.join(", ") + " }";
+    }
+    if (typeof value === "object" && Object.getPrototypeOf(value) === Object.prototype) {
+        return "{" + Object.entries(value).map(([key, item]) => key + ": " + format(item)).join(", ") + "}";
+    }
+    return String(value);
+}
+
+export function show(value) {
+    console.log(format(value));
+}
+
+// Caught errors are the thrown message, as in the interpreter
+export function errorMessage(error) {
+    return error instanceof Error ? error.message : format(error);
+}
+
+// `read` asks for a line with the browser's prompt; null where there is none
+export function read() {
+    return typeof globalThis.prompt === "function" ? globalThis.prompt("") : null;
+}
+
+export function exit(code = 0) {
+    if (globalThis.process && typeof globalThis.process.exit === "function") {
+        globalThis.process.exit(code);
+    }
+    throw new Error("exit(" + code + ")");
+}
+
+export function debug(value) {
+    console.debug("[debug] " + format(value));
+}
+
+export function trace(value) {
+    console.trace(format(value));
+}
+
+export function assert(condition, message) {
+    if (!condition) {
+        throw new Error(message === undefined ? "Assertion failed" : "Assertion failed: " + format(message));
+    }
+}
+
+// Struct types: instances keep their declared fields, check the kind of every value
+// stored in one, and print as `Task { id: 1, text: "Write docs" }`
+const STRUCT = Symbol("struct");
+const FIELDS = Symbol("fields");
+
+function hasKind(value, kind) {
+    switch (kind) {
+        case "number":
+            return typeof value === "number";
+        case "string":
+            return typeof value === "string";
+        case "bool":
+            return typeof value === "boolean";
+        case "array":
+            return Array.isArray(value);
+        case "map":
+            return typeof value === "object" && value !== null && Object.getPrototypeOf(value) === Object.prototype;
+        case "any":
+            return true;
+        default:
+            return value !== null && typeof value === "object" && value[STRUCT] !== undefined && value[STRUCT].name === kind;
+    }
+}
+
+export function defineStruct(name, fields) {
+    const type = { name, fields };
+    const prototype = {
+        toJSON() {
+            return { ...this[FIELDS] };
+        },
+        toString() {
+            return format(this);
+        },
+    };
+    Object.defineProperty(prototype, STRUCT, { value: type });
+    for (const [field, kind] of Object.entries(fields)) {
+        Object.defineProperty(prototype, field, {
+            enumerable: true,
+            get() {
+                return this[FIELDS][field];
+            },
+            set(value) {
+                if (!hasKind(value, kind)) {
+                    throw new Error("Field " + name + "." + field + " expects " + kind + ", got " + format(value));
+                }
+                this[FIELDS][field] = value;
+            },
+        });
+    }
+
+    return (values) => {
+        for (const field of Object.keys(values)) {
+            if (!(field in fields)) {
+                throw new Error(name + " has no field '" + field + "'");
+            }
+        }
+        const instance = Object.create(prototype);
+        Object.defineProperty(instance, FIELDS, { value: {} });
+        for (const field of Object.keys(fields)) {
+            if (!(field in values)) {
+                throw new Error("Missing field '" + field + "' for " + name);
+            }
+            instance[field] = values[field];
+        }
+        // Unknown fields are errors, as in the interpreter, instead of undefined
+        const checkField = (target, field) => {
+            if (typeof field === "string" && !(field in target)) {
+                throw new Error(name + " has no field '" + field + "'");
+            }
+        };
+        return new Proxy(Object.seal(instance), {
+            get(target, field, receiver) {
+                checkField(target, field);
+                return Reflect.get(target, field, receiver);
+            },
+            set(target, field, value, receiver) {
+                checkField(target, field);
+                return Reflect.set(target, field, value, receiver);
+            },
+        });
+    };
+}
+
+// == for programs with structs: instances of the same struct are equal when their fields are
+export function equals(a, b) {
+    if (a && b && a[STRUCT] && b[STRUCT]) {
+        return a[STRUCT] === b[STRUCT] && Object.keys(a[STRUCT].fields).every((field) => equals(a[field], b[field]));
+    }
+    return a == b;
+}
+
+function checkArray(items, name) {
+    if (!Array.isArray(items)) {
+        throw new Error(name + " expects an array, got " + format(items));
+    }
+    return items;
+}
+
+// Libraries; functions return new values instead of changing their arguments, like the Rust ones
+
+export const ArrLib = {
+    push: (items, value) => [...checkArray(items, "ArrLib.push"), value],
+    pop: (items) => {
+        if (checkArray(items, "ArrLib.pop").length === 0) {
+            throw new Error("Cannot pop from empty array");
+        }
+        return items[items.length - 1];
+    },
+    join: (items, separator) => checkArray(items, "ArrLib.join").map(format).join(separator),
+    length: (items) => checkArray(items, "ArrLib.length").length,
+    unique: (items) => [...new Set(checkArray(items, "ArrLib.unique"))],
+    sort: (items) => [...checkArray(items, "ArrLib.sort")].sort((a, b) => (a < b ? -1 : a > b ? 1 : 0)),
+    reverse: (items) => [...checkArray(items, "ArrLib.reverse")].reverse(),
+    slice: (items, start, end) => checkArray(items, "ArrLib.slice").slice(start, end),
+};
+
+export const StrLib = {
+    upper: (text) => String(text).toUpperCase(),
+    lower: (text) => String(text).toLowerCase(),
+    substring: (text, start, end) => String(text).substring(start, end),
+    replace: (text, pattern, replacement) => String(text).split(pattern).join(replacement),
+    length: (text) => [...String(text)].length,
+    split: (text, delimiter) => String(text).split(delimiter),
+    trim: (text) => String(text).trim(),
+    starts_with: (text, prefix) => String(text).startsWith(prefix),
+    ends_with: (text, suffix) => String(text).endsWith(suffix),
+    contains: (text, substring) => String(text).includes(substring),
+    repeat: (text, count) => String(text).repeat(count),
+};
+
+export const MathLib = {
+    add: (a, b) => a + b,
+    subtract: (a, b) => a - b,
+    multiply: (a, b) => a * b,
+    divide: (a, b) => {
+        if (b === 0) {
+            throw new Error("Division by zero");
+        }
+        return a / b;
+    },
+    power: Math.pow,
+    sqrt: Math.sqrt,
+    abs: Math.abs,
+    round: Math.round,
+    floor: Math.floor,
+    ceil: Math.ceil,
+    sin: Math.sin,
+    cos: Math.cos,
+    tan: Math.tan,
+    log: (value, base) => Math.log(value) / Math.log(base),
+    exp: Math.exp,
+    random: Math.random,
+    max: Math.max,
+    min: Math.min,
+    modulo: (a, b) => a % b,
+};
+
+export const TimeLib = {
+    now: () => Date.now(),
+    add: (timestamp, milliseconds) => timestamp + milliseconds,
+    year: (timestamp) => new Date(timestamp).getFullYear(),
+    month: (timestamp) => new Date(timestamp).getMonth() + 1,
+    day: (timestamp) => new Date(timestamp).getDate(),
+};
+
+export const Random = {
+    int: (min, max) => min + Math.floor(Math.random() * (max - min + 1)),
+    float: (min, max) => min + Math.random() * (max - min),
+    choice: (items) => checkArray(items, "Random.choice")[Math.floor(Math.random() * items.length)],
+    shuffle: (items) => {
+        const shuffled = [...checkArray(items, "Random.shuffle")];
+        for (let i = shuffled.length - 1; i > 0; i--) {
+            const j = Math.floor(Math.random() * (i + 1));
+            [shuffled[i], shuffled[j]] = [shuffled[j], shuffled[i]];
+        }
+        return shuffled;
+    },
+};
+
+// Storage keeps JSON values in localStorage, under the page's namespace by default
+let storageNamespace = globalThis.location ? globalThis.location.pathname : "default";
+
+function storageKey(key) {
+    return "razen:" + storageNamespace + ":" + key;
+}
+
+function storageKeys() {
+    const prefix = storageKey("");
+    const keys = [];
+    for (let i = 0; i < localStorage.length; i++) {
+        const key = localStorage.key(i);
+        if (key.startsWith(prefix)) {
+            keys.push(key.slice(prefix.length));
+        }
+    }
+    return keys.sort();
+}
+
+export const Storage = {
+    set: (key, value) => {
+        localStorage.setItem(storageKey(key), JSON.stringify(value));
+        return true;
+    },
+    get: (key, fallback = null) => {
+        const stored = localStorage.getItem(storageKey(key));
+        return stored === null ? fallback : JSON.parse(stored);
+    },
+    has: (key) => localStorage.getItem(storageKey(key)) !== null,
+    delete: (key) => {
+        const stored = Storage.has(key);
+        localStorage.removeItem(storageKey(key));
+        return stored;
+    },
+    keys: storageKeys,
+    clear: () => {
+        const keys = storageKeys();
+        keys.forEach((key) => localStorage.removeItem(storageKey(key)));
+        return keys.length;
+    },
+    namespace: (name) => {
+        if (name !== undefined) {
+            storageNamespace = String(name);
+        }
+        return storageNamespace;
+    },
+};
+
+// DOM bridge: elements are passed around as values, and handlers are Razen functions
+
+function element(target) {
+    const found = typeof target === "string" ? document.getElementById(target) : target;
+    if (!found) {
+        throw new Error("No element " + format(target));
+    }
+    return found;
+}
+
+export const DOM = {
+    by_id: (id) => document.getElementById(id),
+    query: (selector) => document.querySelector(selector),
+    query_all: (selector) => Array.from(document.querySelectorAll(selector)),
+    create: (tag, properties = {}) => Object.assign(document.createElement(tag), properties),
+    text: (target, text) => {
+        if (text !== undefined) {
+            element(target).textContent = format(text);
+        }
+        return element(target).textContent;
+    },
+    html: (target, html) => {
+        if (html !== undefined) {
+            element(target).innerHTML = html;
+        }
+        return element(target).innerHTML;
+    },
+    value: (target, value) => {
+        if (value !== undefined) {
+            element(target).value = value;
+        }
+        return element(target).value;
+    },
+    attr: (target, name, value) => {
+        if (value !== undefined) {
+            element(target).setAttribute(name, value);
+        }
+        return element(target).getAttribute(name);
+    },
+    add_class: (target, name) => element(target).classList.add(name),
+    remove_class: (target, name) => element(target).classList.remove(name),
+    toggle_class: (target, name, force) => element(target).classList.toggle(name, force),
+    has_class: (target, name) => element(target).classList.contains(name),
+    append: (parent, child) => element(parent).appendChild(child),
+    remove: (target) => element(target).remove(),
+    clear: (target) => {
+        element(target).innerHTML = "";
+    },
+    on: (target, event, handler) => {
+        const node = target === "document" ? document : target === "window" ? window : element(target);
+        node.addEventListener(event, handler);
+    },
+    focus: (target) => element(target).focus(),
+};
+
+export const Window = {
+    alert: (message) => window.alert(format(message)),
+    confirm: (message) => window.confirm(format(message)),
+    prompt: (message, fallback = "") => window.prompt(format(message), fallback),
+    timeout: (handler, milliseconds) => setTimeout(handler, milliseconds),
+    interval: (handler, milliseconds) => setInterval(handler, milliseconds),
+    clear_timer: (id) => {
+        clearTimeout(id);
+        clearInterval(id);
+    },
+    location: () => window.location.href,
+    navigate: (url) => {
+        window.location.href = url;
+    },
+};
+
+// Run a module built with `razen compile --target=wasm32-unknown-unknown`, from a URL or its bytes.
+// Printed lines go to the console, `read` uses the browser's prompt, and the result is the exit status.
+export async function runWasm(source) {
+    const bytes = typeof source === "string" ? await (await fetch(source)).arrayBuffer() : source;
+    let memory;
+    let line = "";
+    let input = new Uint8Array(0);
+    const decoder = new TextDecoder();
+
+    class Exit {
+        constructor(status) {
+            this.status = status;
+        }
+    }
+
+    const { instance } = await WebAssembly.instantiate(bytes, {
+        razen: {
+            write: (pointer, length) => {
+                line += decoder.decode(new Uint8Array(memory.buffer, pointer, length)).replace(/\r/g, "");
+                const lines = line.split("\n");
+                line = lines.pop();
+                lines.forEach((text) => console.log(text));
+            },
+            read: (pointer, capacity) => {
+                if (input.length === 0) {
+                    const answer = read();
+                    if (answer === null) {
+                        return 0;
+                    }
+                    input = new TextEncoder().encode(answer + "\n");
+                }
+                const count = Math.min(capacity, input.length);
+                new Uint8Array(memory.buffer, pointer, count).set(input.subarray(0, count));
+                input = input.subarray(count);
+                return count;
+            },
+            exit: (status) => {
+                throw new Exit(status);
+            },
+        },
+    });
+    memory = instance.exports.memory;
+
+    let status;
+    try {
+        status = Number(instance.exports.main());
+    } catch (error) {
+        if (!(error instanceof Exit)) {
+            throw error;
+        }
+        status = error.status;
+    }
+    if (line !== "") {
+        console.log(line);
+    }
+    return status;
+}
@@ -199,7 +199,8 @@ impl Lexer {
             '#' => {
                 let comment = self.read_comment();
                 let len = comment.len();
-                Token::new(TokenType::Comment, comment, self.line, self.column - len)
+                // Leave the newline for skip_whitespace so line numbers stay correct
+                return Token::new(TokenType::Comment, comment, self.line, self.column - len);
             },
             '"' => {
                 let string = self.read_string();
@@ -1,18 +1,257 @@
 use std::collections::HashMap;
 use std::fmt;
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::{Arc, RwLock};
 
 use crate::value::Value;
 use crate::functions::apilib;
+use crate::plugin::{self, NativeFunction};
+
+// Doc comments of the functions in src/functions, collected by build.rs
+mod docs {
+    include!(concat!(env!("OUT_DIR"), "/function_docs.rs"));
+}
+
+// Registers a compiled-in function with its signature and the doc comment from its source
+macro_rules! register {
+    ($library:ident, $signature:literal, $module:ident::$function:ident) => {
+        $library.register_function($signature, crate::functions::$module::$function, docs::function_doc(stringify!($module), stringify!($function)))
+    };
+    ($library:ident, $signature:literal, $module:ident::$function:ident, context) => {
+        $library.register_context_function($signature, crate::functions::$module::$function, docs::function_doc(stringify!($module), stringify!($function)))
+    };
+}
 
 /// LibraryFunction represents a callable function in a library
 pub type LibraryFunction = fn(Vec<Value>) -> Result<Value, String>;
 
+/// A library function that also receives the context of the call
+pub type ContextFunction = fn(&CallContext, Vec<Value>) -> Result<Value, String>;
+
+/// Runs a Razen function by name with stringified arguments, returning its result
+pub type ScriptRunner = Rc<dyn Fn(&str, Vec<String>) -> Result<String, String>>;
+
+/// Per-call context through which library functions can reach the interpreter that called them
+pub struct CallContext<'a> {
+    library: &'a str,
+    runner: Option<&'a dyn Fn() -> ScriptRunner>,
+    script: Option<&'a Path>,
+}
+
+impl<'a> CallContext<'a> {
+    pub fn new(library: &'a str) -> Self {
+        CallContext { library, runner: None, script: None }
+    }
+
+    /// The script making the call, when it was run from a file
+    pub fn with_script(mut self, script: Option<&'a Path>) -> Self {
+        self.script = script;
+        self
+    }
+
+    pub fn script(&self) -> Option<&'a Path> {
+        self.script
+    }
+
+    /// Let functions run Razen code; the runner is only built when one asks for it
+    pub fn with_runner(mut self, runner: &'a dyn Fn() -> ScriptRunner) -> Self {
+        self.runner = Some(runner);
+        self
+    }
+
+    /// Runner for Razen functions, if the caller is an interpreter
+    pub fn script_runner(&self) -> Option<ScriptRunner> {
+        self.runner.map(|runner| runner())
+    }
+}
+
+// Functions are either compiled in or exported by a native plugin
+#[derive(Clone)]
+enum Function {
+    Builtin(LibraryFunction),
+    Contextual(ContextFunction),
+    Native(Arc<NativeFunction>),
+}
+
+impl Function {
+    fn call(&self, context: &CallContext, args: Vec<Value>) -> Result<Value, String> {
+        match self {
+            Function::Builtin(function) => function(args),
+            Function::Contextual(function) => function(context, args),
+            Function::Native(function) => function.call(args),
+        }
+    }
+}
+
+/// Declared parameters and return type of a library function
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signature {
+    pub name: String,
+    pub parameters: Vec<String>,      // int, float, number, string, bool, array, map or any
+    pub names: Vec<String>,           // Parameter names, empty where the declaration has none
+    pub optional: usize,              // Trailing parameters marked with '?'
+    pub variadic: bool,               // Last parameter ends with "..."
+    pub returns: String,
+}
+
+impl Signature {
+    /// Parse a declaration such as "add(int, int) -> int" or "add(a: int, b: int) -> int"
+    pub fn parse(text: &str) -> Result<Signature, String> {
+        let invalid = || format!("Invalid function signature '{}'", text);
+        let (head, returns) = match text.split_once("->") {
+            Some((head, returns)) => (head.trim(), returns.trim().to_string()),
+            None => (text.trim(), "any".to_string()),
+        };
+        let open = head.find('(').ok_or_else(invalid)?;
+        let params = head[open + 1..].strip_suffix(')').ok_or_else(invalid)?;
+        let name = head[..open].trim().to_string();
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(invalid());
+        }
+
+        let mut signature = Signature { name, parameters: Vec::new(), names: Vec::new(), optional: 0, variadic: false, returns };
+        let params: Vec<&str> = params.split(',').map(str::trim).filter(|p| !p.is_empty()).collect();
+        for (index, param) in params.iter().enumerate() {
+            let mut param = *param;
+            if let Some(rest) = param.strip_suffix("...") {
+                if index + 1 != params.len() {
+                    return Err(invalid());
+                }
+                signature.variadic = true;
+                param = rest;
+            } else if let Some(rest) = param.strip_suffix('?') {
+                signature.optional += 1;
+                param = rest;
+            } else if signature.optional > 0 {
+                return Err(invalid());
+            }
+            let (name, param) = match param.split_once(':') {
+                Some((name, param)) => (name.trim(), param.trim()),
+                None => ("", param),
+            };
+            if name.contains(|c: char| !c.is_alphanumeric() && c != '_') {
+                return Err(invalid());
+            }
+            if !is_type_name(param) {
+                return Err(format!("Unknown type '{}' in signature '{}'", param, text));
+            }
+            signature.names.push(name.to_string());
+            signature.parameters.push(param.to_string());
+        }
+        if !is_type_name(&signature.returns) {
+            return Err(format!("Unknown type '{}' in signature '{}'", signature.returns, text));
+        }
+        Ok(signature)
+    }
+
+    /// Declared type of the argument at an index; variadic parameters cover the rest
+    pub fn parameter_type(&self, index: usize) -> Option<&str> {
+        match self.parameters.get(index) {
+            Some(kind) => Some(kind),
+            None if self.variadic => self.parameters.last().map(String::as_str),
+            None => None,
+        }
+    }
+
+    /// Check the argument count and types before the function sees them
+    pub fn check(&self, library: &str, args: &[Value]) -> Result<(), String> {
+        let required = self.parameters.len() - self.optional - usize::from(self.variadic);
+        let too_many = !self.variadic && args.len() > self.parameters.len();
+        if args.len() < required || too_many {
+            return Err(format!("{}.{} expects {} but got {} argument(s)", library, self.name, self, args.len()));
+        }
+        for (index, arg) in args.iter().enumerate() {
+            let expected = &self.parameters[index.min(self.parameters.len() - 1)];
+            if !matches_type(expected, arg) {
+                return Err(format!("{}.{} argument {} must be {}, got {}", library, self.name, index + 1, expected, arg));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Signature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let required = self.parameters.len() - self.optional - usize::from(self.variadic);
+        let params: Vec<String> = self.parameters.iter().enumerate().map(|(index, param)| {
+            let param = match self.names.get(index) {
+                Some(name) if !name.is_empty() => format!("{}: {}", name, param),
+                _ => param.clone(),
+            };
+            if self.variadic && index + 1 == self.parameters.len() {
+                format!("{}...", param)
+            } else if index >= required {
+                format!("{}?", param)
+            } else {
+                param
+            }
+        }).collect();
+        write!(f, "{}({}) -> {}", self.name, params.join(", "), self.returns)
+    }
+}
+
+fn is_type_name(name: &str) -> bool {
+    matches!(name, "int" | "float" | "number" | "string" | "bool" | "array" | "map" | "any" | "null")
+}
+
+fn matches_type(expected: &str, value: &Value) -> bool {
+    match (expected, value) {
+        ("any", _) => true,
+        ("int", Value::Int(_)) => true,
+        ("float" | "number", Value::Int(_) | Value::Float(_)) => true,
+        // Script values are loosely typed, so anything prints as a string
+        ("string", _) => true,
+        ("bool", Value::Bool(_)) => true,
+        ("array", Value::Array(_)) => true,
+        ("map", Value::Map(_)) => true,
+        ("null", Value::Null) => true,
+        _ => false,
+    }
+}
+
+// A registered function with the signature its arguments are checked against
+struct Entry {
+    function: Function,
+    signature: Signature,
+    doc: String,
+}
+
+/// A library function resolved ahead of time, called without looking it up again
+#[derive(Clone)]
+pub struct FunctionHandle(Arc<Entry>);
+
+impl FunctionHandle {
+    /// The signature the function was registered with
+    pub fn signature(&self) -> &Signature {
+        &self.0.signature
+    }
+
+    /// Check the arguments against the signature and call the function
+    pub fn call(&self, context: &CallContext, args: Vec<Value>) -> Result<Value, String> {
+        self.0.signature.check(context.library, &args)?;
+        self.0.function.call(context, args)
+    }
+}
+
+impl fmt::Debug for FunctionHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "FunctionHandle({})", self.0.signature)
+    }
+}
+
+/// Signature and documentation of a library function, for `razen doc` and tooling
+#[derive(Debug, Clone)]
+pub struct FunctionDoc {
+    pub signature: Signature,
+    pub doc: String,
+}
+
 /// Library represents a collection of functions
 #[derive(Clone)]
 pub struct Library {
     name: String,
-    functions: HashMap<String, LibraryFunction>,
+    functions: HashMap<String, Arc<Entry>>,
 }
 
 impl Library {
@@ -24,17 +263,34 @@ impl Library {
         }
     }
 
-    /// Register a function in the library
-    pub fn register_function(&mut self, name: &str, function: LibraryFunction) {
-        self.functions.insert(name.to_string(), function);
+    /// Register a function under the name in its signature, e.g. "push(items: array, value: any) -> array"
+    pub fn register_function(&mut self, signature: &str, function: LibraryFunction, doc: &str) {
+        let signature = Signature::parse(signature)
+            .unwrap_or_else(|e| panic!("Library {}: {}", self.name, e));
+        let entry = Entry { function: Function::Builtin(function), signature, doc: doc.to_string() };
+        self.functions.insert(entry.signature.name.clone(), Arc::new(entry));
     }
 
-    /// Call a function in the library
-    pub fn call_function(&self, function_name: &str, args: Vec<Value>) -> Result<Value, String> {
-        match self.functions.get(function_name) {
-            Some(function) => function(args),
-            None => Err(format!("Function '{}' not found in library '{}'", function_name, self.name)),
-        }
+    /// Register a function that receives the call context, e.g. to run Razen callbacks
+    pub fn register_context_function(&mut self, signature: &str, function: ContextFunction, doc: &str) {
+        let signature = Signature::parse(signature)
+            .unwrap_or_else(|e| panic!("Library {}: {}", self.name, e));
+        let entry = Entry { function: Function::Contextual(function), signature, doc: doc.to_string() };
+        self.functions.insert(entry.signature.name.clone(), Arc::new(entry));
+    }
+
+    /// Register a function exported by a native plugin under its declared name
+    pub fn register_native_function(&mut self, function: Arc<NativeFunction>) {
+        let signature = function.signature().clone();
+        let entry = Entry { function: Function::Native(function), signature, doc: String::new() };
+        self.functions.insert(entry.signature.name.clone(), Arc::new(entry));
+    }
+
+    // Look up a function so it can be called after the registry lock is released
+    fn function(&self, function_name: &str) -> Result<FunctionHandle, String> {
+        self.functions.get(function_name)
+            .map(|entry| FunctionHandle(Arc::clone(entry)))
+            .ok_or_else(|| format!("Function '{}' not found in library '{}'", function_name, self.name))
     }
 
     /// Get the name of the library
@@ -51,6 +307,15 @@ impl Library {
     pub fn function_names(&self) -> Vec<String> {
         self.functions.keys().cloned().collect()
     }
+
+    /// Signatures and docs of all functions, sorted by name
+    pub fn function_docs(&self) -> Vec<FunctionDoc> {
+        let mut docs: Vec<FunctionDoc> = self.functions.values()
+            .map(|entry| FunctionDoc { signature: entry.signature.clone(), doc: entry.doc.clone() })
+            .collect();
+        docs.sort_by(|a, b| a.signature.name.cmp(&b.signature.name));
+        docs
+    }
 }
 
 impl fmt::Debug for Library {
@@ -84,15 +349,11 @@ impl LibraryManager {
         self.libraries.get(&name.to_lowercase())
     }
 
-    /// Call a library function
-    pub fn call_library(&self, library_name: &str, function_name: &str, args: Vec<Value>) -> Result<Value, String> {
-        // Handle case-insensitive library names
-        let library_name = library_name.to_lowercase();
-        
-        // Support both PascalCase and lowercase for library names
-        match self.libraries.get(&library_name) {
-            Some(library) => library.call_function(function_name, args),
-            None => Err(format!("Library '{}' not found", library_name)),
+    /// Look up a library function (case-insensitive library names)
+    fn find_function(&self, library_name: &str, function_name: &str) -> Result<FunctionHandle, String> {
+        match self.libraries.get(&library_name.to_lowercase()) {
+            Some(library) => library.function(function_name),
+            None => Err(format!("Library '{}' not found", library_name.to_lowercase())),
         }
     }
 
@@ -105,476 +366,774 @@ impl LibraryManager {
     fn register_standard_libraries(&mut self) {
         // Array library
         let mut arr_lib = Library::new("arrlib");
-        arr_lib.register_function("push", crate::functions::arrlib::push);
-        arr_lib.register_function("pop", crate::functions::arrlib::pop);
-        arr_lib.register_function("join", crate::functions::arrlib::join);
-        arr_lib.register_function("length", crate::functions::arrlib::length);
+        register!(arr_lib, "push(items: array, value: any) -> array", arrlib::push);
+        register!(arr_lib, "pop(items: array) -> any", arrlib::pop);
+        register!(arr_lib, "join(items: array, separator: string) -> string", arrlib::join);
+        register!(arr_lib, "length(items: array) -> int", arrlib::length);
         // These functions are not implemented yet, so we'll comment them out for now
         // arr_lib.register_function("map", crate::functions::arrlib::map);
         // arr_lib.register_function("filter", crate::functions::arrlib::filter);
-        arr_lib.register_function("unique", crate::functions::arrlib::unique);
+        register!(arr_lib, "unique(items: array) -> array", arrlib::unique);
+        register!(arr_lib, "sort(items: array) -> array", arrlib::sort);
+        register!(arr_lib, "reverse(items: array) -> array", arrlib::reverse);
+        register!(arr_lib, "slice(items: array, start: number, end: number) -> array", arrlib::slice);
         self.register_library(arr_lib);
 
         // String library
         let mut str_lib = Library::new("strlib");
-        str_lib.register_function("upper", crate::functions::strlib::upper);
-        str_lib.register_function("lower", crate::functions::strlib::lower);
-        str_lib.register_function("substring", crate::functions::strlib::substring);
-        str_lib.register_function("replace", crate::functions::strlib::replace);
-        str_lib.register_function("length", crate::functions::strlib::length);
-        str_lib.register_function("split", crate::functions::strlib::split);
-        str_lib.register_function("trim", crate::functions::strlib::trim);
-        str_lib.register_function("starts_with", crate::functions::strlib::starts_with);
-        str_lib.register_function("ends_with", crate::functions::strlib::ends_with);
-        str_lib.register_function("contains", crate::functions::strlib::contains);
-        str_lib.register_function("repeat", crate::functions::strlib::repeat);
+        register!(str_lib, "upper(text: string) -> string", strlib::upper);
+        register!(str_lib, "lower(text: string) -> string", strlib::lower);
+        register!(str_lib, "substring(text: string, start: number, end: number) -> string", strlib::substring);
+        register!(str_lib, "replace(text: string, pattern: string, replacement: string) -> string", strlib::replace);
+        register!(str_lib, "length(text: string) -> int", strlib::length);
+        register!(str_lib, "split(text: string, delimiter: string) -> array", strlib::split);
+        register!(str_lib, "trim(text: string) -> string", strlib::trim);
+        register!(str_lib, "starts_with(text: string, prefix: string) -> bool", strlib::starts_with);
+        register!(str_lib, "ends_with(text: string, suffix: string) -> bool", strlib::ends_with);
+        register!(str_lib, "contains(text: string, substring: string) -> bool", strlib::contains);
+        register!(str_lib, "repeat(text: string, count: number) -> string", strlib::repeat);
         self.register_library(str_lib);
 
         // Math library
         let mut math_lib = Library::new("mathlib");
-        math_lib.register_function("add", crate::functions::mathlib::add);
-        math_lib.register_function("subtract", crate::functions::mathlib::subtract);
-        math_lib.register_function("multiply", crate::functions::mathlib::multiply);
-        math_lib.register_function("divide", crate::functions::mathlib::divide);
-        math_lib.register_function("power", crate::functions::mathlib::power);
-        math_lib.register_function("sqrt", crate::functions::mathlib::sqrt);
-        math_lib.register_function("abs", crate::functions::mathlib::abs);
-        math_lib.register_function("round", crate::functions::mathlib::round);
-        math_lib.register_function("floor", crate::functions::mathlib::floor);
-        math_lib.register_function("ceil", crate::functions::mathlib::ceil);
-        math_lib.register_function("sin", crate::functions::mathlib::sin);
-        math_lib.register_function("cos", crate::functions::mathlib::cos);
-        math_lib.register_function("tan", crate::functions::mathlib::tan);
-        math_lib.register_function("log", crate::functions::mathlib::log);
-        math_lib.register_function("exp", crate::functions::mathlib::exp);
-        math_lib.register_function("random", crate::functions::mathlib::random);
-        math_lib.register_function("max", crate::functions::mathlib::max);
-        math_lib.register_function("min", crate::functions::mathlib::min);
-        math_lib.register_function("modulo", crate::functions::mathlib::modulo);
+        register!(math_lib, "add(a: number, b: number) -> float", mathlib::add);
+        register!(math_lib, "subtract(a: number, b: number) -> float", mathlib::subtract);
+        register!(math_lib, "multiply(a: number, b: number) -> float", mathlib::multiply);
+        register!(math_lib, "divide(a: number, b: number) -> float", mathlib::divide);
+        register!(math_lib, "power(base: number, exponent: number) -> float", mathlib::power);
+        register!(math_lib, "sqrt(value: number) -> float", mathlib::sqrt);
+        register!(math_lib, "abs(value: number) -> float", mathlib::abs);
+        register!(math_lib, "round(value: number) -> float", mathlib::round);
+        register!(math_lib, "floor(value: number) -> float", mathlib::floor);
+        register!(math_lib, "ceil(value: number) -> float", mathlib::ceil);
+        register!(math_lib, "sin(angle: number) -> float", mathlib::sin);
+        register!(math_lib, "cos(angle: number) -> float", mathlib::cos);
+        register!(math_lib, "tan(angle: number) -> float", mathlib::tan);
+        register!(math_lib, "log(value: number, base: number) -> float", mathlib::log);
+        register!(math_lib, "exp(value: number) -> float", mathlib::exp);
+        register!(math_lib, "random() -> float", mathlib::random);
+        register!(math_lib, "max(values: number...) -> float", mathlib::max);
+        register!(math_lib, "min(values: number...) -> float", mathlib::min);
+        register!(math_lib, "modulo(a: number, b: number) -> float", mathlib::modulo);
         self.register_library(math_lib);
 
         // Time library
         let mut time_lib = Library::new("timelib");
-        time_lib.register_function("now", crate::functions::timelib::now);
-        time_lib.register_function("format", crate::functions::timelib::format);
-        time_lib.register_function("parse", crate::functions::timelib::parse);
-        time_lib.register_function("add", crate::functions::timelib::add);
-        time_lib.register_function("year", crate::functions::timelib::year);
-        time_lib.register_function("month", crate::functions::timelib::month);
-        time_lib.register_function("day", crate::functions::timelib::day);
+        register!(time_lib, "now() -> int", timelib::now);
+        register!(time_lib, "format(timestamp: number, format_string: string) -> string", timelib::format);
+        register!(time_lib, "parse(date_string: string, format_string: string) -> int", timelib::parse);
+        register!(time_lib, "add(timestamp: number, milliseconds: number) -> int", timelib::add);
+        register!(time_lib, "year(timestamp: number) -> int", timelib::year);
+        register!(time_lib, "month(timestamp: number) -> int", timelib::month);
+        register!(time_lib, "day(timestamp: number) -> int", timelib::day);
         self.register_library(time_lib);
 
         // Random library
         let mut random_lib = Library::new("random");
-        random_lib.register_function("int", crate::functions::randomlib::int);
-        random_lib.register_function("float", crate::functions::randomlib::float);
-        random_lib.register_function("choice", crate::functions::randomlib::choice);
-        random_lib.register_function("shuffle", crate::functions::randomlib::shuffle);
+        register!(random_lib, "int(min: number, max: number) -> int", randomlib::int);
+        register!(random_lib, "float(min: number, max: number) -> float", randomlib::float);
+        register!(random_lib, "choice(items: array) -> any", randomlib::choice);
+        register!(random_lib, "shuffle(items: array) -> array", randomlib::shuffle);
         self.register_library(random_lib);
 
         // File library (legacy)
         let mut file_lib = Library::new("file");
-        file_lib.register_function("read", crate::functions::filelib::read);
-        file_lib.register_function("write", crate::functions::filelib::write);
-        file_lib.register_function("append", crate::functions::filelib::append);
-        file_lib.register_function("exists", crate::functions::filelib::exists);
-        file_lib.register_function("delete", crate::functions::filelib::delete);
+        register!(file_lib, "read(path: string) -> string", filelib::read);
+        register!(file_lib, "write(path: string, content: string) -> bool", filelib::write);
+        register!(file_lib, "append(path: string, content: string) -> bool", filelib::append);
+        register!(file_lib, "exists(path: string) -> bool", filelib::exists);
+        register!(file_lib, "delete(path: string) -> bool", filelib::delete);
         self.register_library(file_lib);
 
         // Filesystem library (extended)
         let mut fs_lib = Library::new("filesystem");
-        fs_lib.register_function("exists", crate::functions::filesystemlib::exists);
-        fs_lib.register_function("is_file", crate::functions::filesystemlib::is_file);
-        fs_lib.register_function("is_dir", crate::functions::filesystemlib::is_dir);
-        fs_lib.register_function("create_dir", crate::functions::filesystemlib::create_dir);
-        fs_lib.register_function("remove", crate::functions::filesystemlib::remove);
-        fs_lib.register_function("read_file", crate::functions::filesystemlib::read_file);
-        fs_lib.register_function("write_file", crate::functions::filesystemlib::write_file);
-        fs_lib.register_function("list_dir", crate::functions::filesystemlib::list_dir);
-        fs_lib.register_function("metadata", crate::functions::filesystemlib::metadata);
-        fs_lib.register_function("absolute_path", crate::functions::filesystemlib::absolute_path);
-        fs_lib.register_function("copy", crate::functions::filesystemlib::copy_file);
-        fs_lib.register_function("move", crate::functions::filesystemlib::move_file);
-        fs_lib.register_function("extension", crate::functions::filesystemlib::extension);
-        fs_lib.register_function("file_stem", crate::functions::filesystemlib::file_stem);
-        fs_lib.register_function("parent_dir", crate::functions::filesystemlib::parent_dir);
-        fs_lib.register_function("join_path", crate::functions::filesystemlib::join_path);
-        fs_lib.register_function("change_dir", crate::functions::filesystemlib::change_dir);
-        fs_lib.register_function("current_dir", crate::functions::filesystemlib::current_dir);
-        fs_lib.register_function("temp_file", crate::functions::filesystemlib::temp_file);
-        fs_lib.register_function("temp_dir", crate::functions::filesystemlib::temp_dir);
+        register!(fs_lib, "exists(path: string) -> bool", filesystemlib::exists);
+        register!(fs_lib, "is_file(path: string) -> bool", filesystemlib::is_file);
+        register!(fs_lib, "is_dir(path: string) -> bool", filesystemlib::is_dir);
+        register!(fs_lib, "create_dir(path: string, recursive: any?, mode: any?) -> bool", filesystemlib::create_dir);
+        register!(fs_lib, "remove(path: string, recursive: any?) -> bool", filesystemlib::remove);
+        register!(fs_lib, "read_file(path: string, binary: any?) -> string", filesystemlib::read_file);
+        register!(fs_lib, "write_file(path: string, content: string, append: any?, binary: any?) -> bool", filesystemlib::write_file);
+        register!(fs_lib, "list_dir(path: string, detailed: any?) -> array", filesystemlib::list_dir);
+        register!(fs_lib, "metadata(path: string) -> map", filesystemlib::metadata);
+        register!(fs_lib, "absolute_path(path: string) -> string", filesystemlib::absolute_path);
+        register!(fs_lib, "copy(from: string, to: string) -> bool", filesystemlib::copy_file);
+        register!(fs_lib, "move(from: string, to: string) -> any", filesystemlib::move_file);
+        register!(fs_lib, "extension(path: string) -> string", filesystemlib::extension);
+        register!(fs_lib, "file_stem(path: string) -> string", filesystemlib::file_stem);
+        register!(fs_lib, "parent_dir(path: string) -> string", filesystemlib::parent_dir);
+        register!(fs_lib, "join_path(parts: string...) -> string", filesystemlib::join_path);
+        register!(fs_lib, "change_dir(path: string) -> any", filesystemlib::change_dir);
+        register!(fs_lib, "current_dir() -> string", filesystemlib::current_dir);
+        register!(fs_lib, "temp_file(prefix: string?) -> string", filesystemlib::temp_file);
+        register!(fs_lib, "temp_dir(prefix: string?) -> string", filesystemlib::temp_dir);
         self.register_library(fs_lib);
         
         // API library
         let mut api_lib = Library::new("apilib");
-        api_lib.register_function("get", crate::functions::apilib::get);
-        api_lib.register_function("post", crate::functions::apilib::post);
-        api_lib.register_function("putmethod", crate::functions::apilib::putmethod);
-        api_lib.register_function("delete", crate::functions::apilib::delete);
-        api_lib.register_function("patch", crate::functions::apilib::patch);
-        api_lib.register_function("call", crate::functions::apilib::call);
-        api_lib.register_function("parse_json", crate::functions::apilib::parse_json);
-        api_lib.register_function("to_json", crate::functions::apilib::to_json);
-        api_lib.register_function("create_api", crate::functions::apilib::create_api);
-        api_lib.register_function("execute_api", crate::functions::apilib::execute_api);
-        api_lib.register_function("url_encode", crate::functions::apilib::url_encode);
-        api_lib.register_function("url_decode", crate::functions::apilib::url_decode);
-        api_lib.register_function("form_data", crate::functions::apilib::form_data);
-        api_lib.register_function("is_success", crate::functions::apilib::is_success);
-        api_lib.register_function("is_client_error", crate::functions::apilib::is_client_error);
-        api_lib.register_function("is_server_error", crate::functions::apilib::is_server_error);
+        register!(api_lib, "get(url: string, params: any?, headers: any?, timeout: any?) -> any", apilib::get);
+        register!(api_lib, "post(url: string, data: any, headers: any?, timeout: any?) -> any", apilib::post);
+        register!(api_lib, "putmethod(url: string, data: any, headers: any?, timeout: any?) -> any", apilib::putmethod);
+        register!(api_lib, "delete(url: string, headers: any?, timeout: any?) -> any", apilib::delete);
+        register!(api_lib, "patch(url: string, data: any, headers: any?, timeout: any?) -> any", apilib::patch);
+        register!(api_lib, "call(url: string, options: any?) -> any", apilib::call);
+        register!(api_lib, "parse_json(json_string: string) -> any", apilib::parse_json);
+        register!(api_lib, "to_json(value: any) -> string", apilib::to_json);
+        register!(api_lib, "create_api(url: string, api_key: string?, auth_type: string?, timeout: any?, options: map?) -> map", apilib::create_api);
+        register!(api_lib, "execute_api(api_config: any, method: string, endpoint: string?, params_or_data: any?, timeout: any?) -> any", apilib::execute_api);
+        register!(api_lib, "upload(api_config: map, endpoint: string, files: map, fields: map?, timeout: number?) -> any", apilib::upload, context);
+        register!(api_lib, "download(api_config: map, endpoint: string, path: string, progress: string?, timeout: number?) -> map", apilib::download, context);
+        register!(api_lib, "set_header(api_config: map, name: string, value: any?) -> bool", apilib::set_header);
+        register!(api_lib, "cookies(api_config: map, endpoint: string?) -> map", apilib::cookies);
+        register!(api_lib, "set_cookie(api_config: map, name: string, value: string) -> bool", apilib::set_cookie);
+        register!(api_lib, "close_api(api_config: map) -> bool", apilib::close_api);
+        register!(api_lib, "url_encode(text: string) -> string", apilib::url_encode);
+        register!(api_lib, "url_decode(text: string) -> string", apilib::url_decode);
+        register!(api_lib, "form_data(value: any) -> string", apilib::form_data);
+        register!(api_lib, "is_success(status_code: any) -> bool", apilib::is_success);
+        register!(api_lib, "is_client_error(status_code: any) -> bool", apilib::is_client_error);
+        register!(api_lib, "is_server_error(status_code: any) -> bool", apilib::is_server_error);
         self.register_library(api_lib);
 
+        // HTTP server library (routes are handled by Razen functions)
+        let mut http_server_lib = Library::new("httpserver");
+        register!(http_server_lib, "create(port: int?, host: string?) -> int", httpserverlib::create);
+        register!(http_server_lib, "port(server: int) -> int", httpserverlib::port);
+        register!(http_server_lib, "route(server: int, method: string, path: string, handler: string) -> bool", httpserverlib::route);
+        register!(http_server_lib, "static_files(server: int, prefix: string, directory: string) -> bool", httpserverlib::static_files, context);
+        register!(http_server_lib, "serve(server: int, max_requests: int?) -> int", httpserverlib::serve, context);
+        register!(http_server_lib, "stop(server: int) -> bool", httpserverlib::stop);
+        register!(http_server_lib, "json(value: any, status: int?) -> bool", httpserverlib::json);
+        register!(http_server_lib, "response(body: any, status: int?, headers: map?) -> bool", httpserverlib::response);
+        register!(http_server_lib, "redirect(location: string, status: int?) -> bool", httpserverlib::redirect);
+        self.register_library(http_server_lib);
+
         // JSON library
         let mut json_lib = Library::new("json");
-        json_lib.register_function("parse", crate::functions::jsonlib::parse);
-        json_lib.register_function("stringify", crate::functions::jsonlib::stringify);
+        register!(json_lib, "parse(json_string: string) -> any", jsonlib::parse);
+        register!(json_lib, "stringify(value: any) -> string", jsonlib::stringify);
         self.register_library(json_lib);
 
+        // Storage library (persistent key-value store, namespaced per script)
+        let mut storage_lib = Library::new("storage");
+        register!(storage_lib, "set(key: string, value: any) -> bool", storagelib::set, context);
+        register!(storage_lib, "get(key: string, default: any?) -> any", storagelib::get, context);
+        register!(storage_lib, "has(key: string) -> bool", storagelib::has, context);
+        register!(storage_lib, "delete(key: string) -> bool", storagelib::delete, context);
+        register!(storage_lib, "keys() -> array", storagelib::keys, context);
+        register!(storage_lib, "clear() -> int", storagelib::clear, context);
+        register!(storage_lib, "begin() -> bool", storagelib::begin, context);
+        register!(storage_lib, "commit() -> bool", storagelib::commit, context);
+        register!(storage_lib, "rollback() -> bool", storagelib::rollback, context);
+        register!(storage_lib, "namespace(name: string?) -> string", storagelib::namespace, context);
+        self.register_library(storage_lib);
+
+        // Type checking library
+        let mut type_check_lib = Library::new("typechecklib");
+        register!(type_check_lib, "type_of(value: any) -> string", typechecklib::type_of);
+        register!(type_check_lib, "is_num(value: any) -> bool", typechecklib::is_num);
+        register!(type_check_lib, "is_int(value: any) -> bool", typechecklib::is_int);
+        register!(type_check_lib, "is_float(value: any) -> bool", typechecklib::is_float);
+        register!(type_check_lib, "is_str(value: any) -> bool", typechecklib::is_str);
+        register!(type_check_lib, "is_bool(value: any) -> bool", typechecklib::is_bool);
+        register!(type_check_lib, "is_list(value: any) -> bool", typechecklib::is_list);
+        register!(type_check_lib, "is_map(value: any) -> bool", typechecklib::is_map);
+        register!(type_check_lib, "is_null(value: any) -> bool", typechecklib::is_null);
+        // Names used by properties/functions/type_checking.rzn
+        register!(type_check_lib, "is_number(value: any) -> bool", typechecklib::is_num);
+        register!(type_check_lib, "is_string(value: any) -> bool", typechecklib::is_str);
+        register!(type_check_lib, "is_boolean(value: any) -> bool", typechecklib::is_bool);
+        register!(type_check_lib, "is_array(value: any) -> bool", typechecklib::is_list);
+        self.register_library(type_check_lib);
+
+        // Type conversion library
+        let mut type_convert_lib = Library::new("typeconvertlib");
+        register!(type_convert_lib, "to_int(value: any, strict: bool?) -> int", typeconvertlib::to_int);
+        register!(type_convert_lib, "to_float(value: any, strict: bool?) -> float", typeconvertlib::to_float);
+        register!(type_convert_lib, "to_str(value: any) -> string", typeconvertlib::to_str);
+        register!(type_convert_lib, "to_bool(value: any, strict: bool?) -> bool", typeconvertlib::to_bool);
+        self.register_library(type_convert_lib);
+
+        // Number parsing and formatting library
+        let mut num_lib = Library::new("numlib");
+        register!(num_lib, "parse(text: string, radix: int?) -> number", numlib::parse);
+        register!(num_lib, "format(value: number, radix: int?) -> string", numlib::format);
+        register!(num_lib, "fixed(value: number, decimals: int) -> string", numlib::fixed);
+        register!(num_lib, "group(value: number, separator: string?) -> string", numlib::group);
+        register!(num_lib, "round(value: number, decimals: int?) -> number", numlib::round);
+        register!(num_lib, "random(max: number?) -> float", numlib::random);
+        self.register_library(num_lib);
+
+        // Console input/output library
+        let mut io_lib = Library::new("iolib");
+        register!(io_lib, "say(values: any...) -> null", iolib::say);
+        register!(io_lib, "write(values: any...) -> null", iolib::write);
+        register!(io_lib, "error(values: any...) -> null", iolib::error);
+        register!(io_lib, "ask(question: string?) -> string", iolib::ask);
+        register!(io_lib, "read() -> string", iolib::read);
+        self.register_library(io_lib);
+
+        // Reference library (mutable values shared by ID)
+        let mut ref_lib = Library::new("reflib");
+        register!(ref_lib, "point(value: any) -> int", reflib::point);
+        register!(ref_lib, "read(reference: int) -> any", reflib::read);
+        register!(ref_lib, "write(reference: int, value: any) -> any", reflib::write);
+        register!(ref_lib, "free(reference: int) -> bool", reflib::free);
+        self.register_library(ref_lib);
+
         // Bolt library
         let mut bolt_lib = Library::new("bolt");
-        bolt_lib.register_function("run", crate::functions::boltlib::run);
-        bolt_lib.register_function("parallel", crate::functions::boltlib::parallel);
-        bolt_lib.register_function("threads", crate::functions::boltlib::threads);
+        register!(bolt_lib, "run(task_name: string) -> bool", boltlib::run);
+        register!(bolt_lib, "parallel(items: array, function: string) -> array", boltlib::parallel);
+        register!(bolt_lib, "threads(count: number, task_name: string) -> array", boltlib::threads);
         self.register_library(bolt_lib);
 
         // Seed library
         let mut seed_lib = Library::new("seed");
-        seed_lib.register_function("generate", crate::functions::seedlib::generate);
-        seed_lib.register_function("map_seed", crate::functions::seedlib::map_seed);
-        seed_lib.register_function("noise_map", crate::functions::seedlib::noise_map);
-        seed_lib.register_function("name", crate::functions::seedlib::name);
+        register!(seed_lib, "generate(length: number) -> string", seedlib::generate);
+        register!(seed_lib, "map_seed(seed: string, width: number, height: number) -> array", seedlib::map_seed);
+        register!(seed_lib, "noise_map(seed: string, width: number, height: number, scale: number) -> array", seedlib::noise_map);
+        register!(seed_lib, "name(seed: string) -> string", seedlib::name);
         self.register_library(seed_lib);
 
         // Memory library for memory management operations
         let mut memory_lib = Library::new("memorylib");
-        memory_lib.register_function("addressof", crate::functions::memorylib::addressof);
-        memory_lib.register_function("deref", crate::functions::memorylib::deref);
-        memory_lib.register_function("add_offset", crate::functions::memorylib::add_offset);
-        memory_lib.register_function("alloc", crate::functions::memorylib::alloc);
-        memory_lib.register_function("free", crate::functions::memorylib::free);
-        memory_lib.register_function("write_byte", crate::functions::memorylib::write_byte);
-        memory_lib.register_function("read_byte", crate::functions::memorylib::read_byte);
-        memory_lib.register_function("create_buffer", crate::functions::memorylib::create_buffer);
-        memory_lib.register_function("free_buffer", crate::functions::memorylib::free_buffer);
-        memory_lib.register_function("buffer_write_string", crate::functions::memorylib::buffer_write_string);
-        memory_lib.register_function("buffer_read_string", crate::functions::memorylib::buffer_read_string);
-        memory_lib.register_function("buffer_copy", crate::functions::memorylib::buffer_copy);
-        memory_lib.register_function("stats", crate::functions::memorylib::stats);
+        register!(memory_lib, "addressof(value: any) -> int", memorylib::addressof);
+        register!(memory_lib, "deref(address: number) -> int", memorylib::deref);
+        register!(memory_lib, "add_offset(address: number, offset: number) -> int", memorylib::add_offset);
+        register!(memory_lib, "alloc(size: number) -> int", memorylib::alloc);
+        register!(memory_lib, "free(address: number) -> bool", memorylib::free);
+        register!(memory_lib, "write_byte(address: number, offset: number, value: number) -> bool", memorylib::write_byte);
+        register!(memory_lib, "read_byte(address: number, offset: number) -> int", memorylib::read_byte);
+        register!(memory_lib, "create_buffer(size: number) -> int", memorylib::create_buffer);
+        register!(memory_lib, "free_buffer(buffer_id: number) -> bool", memorylib::free_buffer);
+        register!(memory_lib, "buffer_write_string(buffer_id: number, text: string) -> bool", memorylib::buffer_write_string);
+        register!(memory_lib, "buffer_read_string(buffer_id: number, offset: number, length: number) -> string", memorylib::buffer_read_string);
+        register!(memory_lib, "buffer_copy(src_id: number, src_offset: number, dst_id: number, dst_offset: number, length: number) -> bool", memorylib::buffer_copy);
+        register!(memory_lib, "stats() -> map", memorylib::stats);
         self.register_library(memory_lib);
 
         // Binary library for binary file operations
         let mut binary_lib = Library::new("binarylib");
-        binary_lib.register_function("create", crate::functions::binarylib::create);
-        binary_lib.register_function("open", crate::functions::binarylib::open);
-        binary_lib.register_function("close", crate::functions::binarylib::close);
-        binary_lib.register_function("write_bytes", crate::functions::binarylib::write_bytes);
-        binary_lib.register_function("read_bytes", crate::functions::binarylib::read_bytes);
-        binary_lib.register_function("seek", crate::functions::binarylib::seek);
-        binary_lib.register_function("tell", crate::functions::binarylib::tell);
-        binary_lib.register_function("bytes_to_string", crate::functions::binarylib::bytes_to_string);
-        binary_lib.register_function("string_to_bytes", crate::functions::binarylib::string_to_bytes);
-        binary_lib.register_function("stats", crate::functions::binarylib::stats);
+        register!(binary_lib, "create(path: string) -> bool", binarylib::create);
+        register!(binary_lib, "open(path: string, mode: string) -> int", binarylib::open);
+        register!(binary_lib, "close(handle: number) -> bool", binarylib::close);
+        register!(binary_lib, "write_bytes(handle: number, bytes: any) -> int", binarylib::write_bytes);
+        register!(binary_lib, "read_bytes(handle: number, count: number) -> array", binarylib::read_bytes);
+        register!(binary_lib, "seek(handle: number, offset: number, whence: string?) -> int", binarylib::seek);
+        register!(binary_lib, "tell(file_handle: number) -> int", binarylib::tell);
+        register!(binary_lib, "bytes_to_string(bytes: array) -> string", binarylib::bytes_to_string);
+        register!(binary_lib, "string_to_bytes(text: string) -> array", binarylib::string_to_bytes);
+        register!(binary_lib, "stats() -> map", binarylib::stats);
         self.register_library(binary_lib);
 
         // Bitwise library for bit manipulation
         let mut bitwise_lib = Library::new("bitwiselib");
-        bitwise_lib.register_function("and", crate::functions::bitwiselib::and);
-        bitwise_lib.register_function("or", crate::functions::bitwiselib::or);
-        bitwise_lib.register_function("xor", crate::functions::bitwiselib::xor);
-        bitwise_lib.register_function("not", crate::functions::bitwiselib::not);
-        bitwise_lib.register_function("left_shift", crate::functions::bitwiselib::left_shift);
-        bitwise_lib.register_function("right_shift", crate::functions::bitwiselib::right_shift);
-        bitwise_lib.register_function("unsigned_right_shift", crate::functions::bitwiselib::unsigned_right_shift);
-        bitwise_lib.register_function("get_bit", crate::functions::bitwiselib::get_bit);
-        bitwise_lib.register_function("set_bit", crate::functions::bitwiselib::set_bit);
-        bitwise_lib.register_function("count_bits", crate::functions::bitwiselib::count_bits);
-        bitwise_lib.register_function("to_binary", crate::functions::bitwiselib::to_binary);
-        bitwise_lib.register_function("to_hex", crate::functions::bitwiselib::to_hex);
-        bitwise_lib.register_function("from_binary", crate::functions::bitwiselib::from_binary);
-        bitwise_lib.register_function("from_hex", crate::functions::bitwiselib::from_hex);
+        register!(bitwise_lib, "and(a: number, b: number) -> int", bitwiselib::and);
+        register!(bitwise_lib, "or(a: number, b: number) -> int", bitwiselib::or);
+        register!(bitwise_lib, "xor(a: number, b: number) -> int", bitwiselib::xor);
+        register!(bitwise_lib, "not(value: number, bits: number) -> int", bitwiselib::not);
+        register!(bitwise_lib, "left_shift(value: number, shift: number) -> int", bitwiselib::left_shift);
+        register!(bitwise_lib, "right_shift(value: number, shift: number) -> int", bitwiselib::right_shift);
+        register!(bitwise_lib, "unsigned_right_shift(value: number, shift: number) -> int", bitwiselib::unsigned_right_shift);
+        register!(bitwise_lib, "get_bit(value: number, bit_position: number) -> int", bitwiselib::get_bit);
+        register!(bitwise_lib, "set_bit(value: number, bit_position: number, bit_value: number) -> int", bitwiselib::set_bit);
+        register!(bitwise_lib, "count_bits(value: number) -> int", bitwiselib::count_bits);
+        register!(bitwise_lib, "to_binary(value: number) -> string", bitwiselib::to_binary);
+        register!(bitwise_lib, "to_hex(value: number) -> string", bitwiselib::to_hex);
+        register!(bitwise_lib, "from_binary(binary_string: string) -> int", bitwiselib::from_binary);
+        register!(bitwise_lib, "from_hex(hex_string: string) -> int", bitwiselib::from_hex);
         self.register_library(bitwise_lib);
 
         // Syscall library for system operations
         let mut syscall_lib = Library::new("systemlib");
-        syscall_lib.register_function("getpid", crate::functions::syscalllib::getpid);
-        syscall_lib.register_function("getcwd", crate::functions::syscalllib::getcwd);
-        syscall_lib.register_function("execute", crate::functions::syscalllib::execute);
-        syscall_lib.register_function("getenv", crate::functions::syscalllib::getenv);
-        syscall_lib.register_function("setenv", crate::functions::syscalllib::setenv);
-        syscall_lib.register_function("environ", crate::functions::syscalllib::environ);
-        syscall_lib.register_function("args", crate::functions::syscalllib::args);
-        syscall_lib.register_function("path_exists", crate::functions::syscalllib::path_exists);
-        syscall_lib.register_function("realpath", crate::functions::syscalllib::realpath);
-        syscall_lib.register_function("exit", crate::functions::syscalllib::exit);
-        syscall_lib.register_function("sleep", crate::functions::syscalllib::sleep);
-        syscall_lib.register_function("hostname", crate::functions::syscalllib::hostname);
-        syscall_lib.register_function("username", crate::functions::syscalllib::username);
-        syscall_lib.register_function("current_time", crate::functions::systemlib::current_time);
-        syscall_lib.register_function("system_name", crate::functions::systemlib::system_name);
+        register!(syscall_lib, "getpid() -> int", syscalllib::getpid);
+        register!(syscall_lib, "getcwd() -> string", syscalllib::getcwd);
+        register!(syscall_lib, "execute(command: string) -> string", syscalllib::execute);
+        register!(syscall_lib, "getenv(variable_name: string) -> any", syscalllib::getenv);
+        register!(syscall_lib, "setenv(variable_name: string, value: string) -> bool", syscalllib::setenv);
+        register!(syscall_lib, "environ() -> array", syscalllib::environ);
+        register!(syscall_lib, "args() -> array", syscalllib::args);
+        register!(syscall_lib, "path_exists(path: string) -> bool", syscalllib::path_exists);
+        register!(syscall_lib, "realpath(path: string) -> string", syscalllib::realpath);
+        register!(syscall_lib, "exit(status: number?) -> null", syscalllib::exit);
+        register!(syscall_lib, "sleep(milliseconds: number) -> bool", syscalllib::sleep);
+        register!(syscall_lib, "hostname() -> string", syscalllib::hostname);
+        register!(syscall_lib, "username() -> string", syscalllib::username);
+        register!(syscall_lib, "current_time() -> int", systemlib::current_time);
+        register!(syscall_lib, "system_name() -> string", systemlib::system_name);
         self.register_library(syscall_lib);
 
         // Process library for process management
         let mut process_lib = Library::new("processlib");
-        process_lib.register_function("create", crate::functions::processlib::create);
-        process_lib.register_function("wait", crate::functions::processlib::wait);
-        process_lib.register_function("is_running", crate::functions::processlib::is_running);
-        process_lib.register_function("kill", crate::functions::processlib::kill);
-        process_lib.register_function("signal", crate::functions::processlib::signal);
-        process_lib.register_function("info", crate::functions::processlib::info);
-        process_lib.register_function("read_stdout", crate::functions::processlib::read_stdout);
-        process_lib.register_function("read_stderr", crate::functions::processlib::read_stderr);
-        process_lib.register_function("write_stdin", crate::functions::processlib::write_stdin);
+        register!(process_lib, "create(command: string) -> int", processlib::create);
+        register!(process_lib, "wait(process_id: number) -> int", processlib::wait);
+        register!(process_lib, "is_running(process_id: number) -> bool", processlib::is_running);
+        register!(process_lib, "kill(process_id: number) -> bool", processlib::kill);
+        register!(process_lib, "signal(process_id: number, signal: string) -> bool", processlib::signal);
+        register!(process_lib, "info() -> array", processlib::info);
+        register!(process_lib, "read_stdout(process_id: number) -> string", processlib::read_stdout);
+        register!(process_lib, "read_stderr(process_id: number) -> string", processlib::read_stderr);
+        register!(process_lib, "write_stdin(process_id: number, data: string) -> bool", processlib::write_stdin);
+        register!(process_lib, "run(program: string, args: any?, options: any?) -> any", processlib::run);
+        register!(process_lib, "spawn(program: string, args: any?, options: any?) -> int", processlib::spawn);
+        register!(process_lib, "output(process_id: number) -> any", processlib::output);
+        register!(process_lib, "pipeline(stages: array, options: any?) -> any", processlib::pipeline);
         self.register_library(process_lib);
 
         // Thread library for threading operations
         let mut thread_lib = Library::new("threadlib");
-        thread_lib.register_function("create", crate::functions::threadlib::create);
-        thread_lib.register_function("join", crate::functions::threadlib::join);
-        thread_lib.register_function("is_running", crate::functions::threadlib::is_running);
-        thread_lib.register_function("sleep", crate::functions::threadlib::sleep);
-        thread_lib.register_function("mutex_create", crate::functions::threadlib::mutex_create);
-        thread_lib.register_function("mutex_lock", crate::functions::threadlib::mutex_lock);
-        thread_lib.register_function("mutex_unlock", crate::functions::threadlib::mutex_unlock);
-        thread_lib.register_function("mutex_destroy", crate::functions::threadlib::mutex_destroy);
-        thread_lib.register_function("current", crate::functions::threadlib::current);
-        thread_lib.register_function("cpu_count", crate::functions::threadlib::cpu_count);
-        thread_lib.register_function("thread_id", crate::functions::threadlib::thread_id);
-        thread_lib.register_function("thread_count", crate::functions::threadlib::thread_count);
+        register!(thread_lib, "create(function_name: string) -> int", threadlib::create);
+        register!(thread_lib, "join(thread_id: number) -> bool", threadlib::join);
+        register!(thread_lib, "is_running(thread_id: number) -> bool", threadlib::is_running);
+        register!(thread_lib, "sleep(milliseconds: number) -> bool", threadlib::sleep);
+        register!(thread_lib, "mutex_create() -> int", threadlib::mutex_create);
+        register!(thread_lib, "mutex_lock(mutex_id: number) -> bool", threadlib::mutex_lock);
+        register!(thread_lib, "mutex_unlock(mutex_id: number) -> bool", threadlib::mutex_unlock);
+        register!(thread_lib, "mutex_destroy(mutex_id: number) -> bool", threadlib::mutex_destroy);
+        register!(thread_lib, "current() -> int", threadlib::current);
+        register!(thread_lib, "cpu_count() -> int", threadlib::cpu_count);
+        register!(thread_lib, "thread_id() -> int", threadlib::thread_id);
+        register!(thread_lib, "thread_count() -> int", threadlib::thread_count);
+        register!(thread_lib, "channel(capacity: number?) -> int", threadlib::channel);
+        register!(thread_lib, "send(channel_id: any, value: any) -> bool", threadlib::send);
+        register!(thread_lib, "recv(channel_id: any) -> any", threadlib::recv);
+        register!(thread_lib, "try_recv(channel_id: any) -> null", threadlib::try_recv);
+        register!(thread_lib, "recv_timeout(channel_id: any, milliseconds: number) -> null", threadlib::recv_timeout);
+        register!(thread_lib, "close(channel_id: any) -> bool", threadlib::close);
+        register!(thread_lib, "select(channel_ids: array, timeout_ms: any?) -> any", threadlib::select);
+        register!(thread_lib, "channel_len(channel_id: any) -> int", threadlib::channel_len);
         self.register_library(thread_lib);
 
+        // FFI library for calling C functions in shared libraries
+        let mut ffi_lib = Library::new("ffilib");
+        register!(ffi_lib, "open(path: string?) -> int", ffilib::open);
+        register!(ffi_lib, "func(library_id: number, symbol: string, param_types: any, return_type: string) -> int", ffilib::func);
+        register!(ffi_lib, "call(function_id: number, args: any...) -> any", ffilib::call, context);
+        register!(ffi_lib, "callback(function_name: string, param_types: any, return_type: string) -> int", ffilib::callback);
+        register!(ffi_lib, "free_callback(callback: any) -> bool", ffilib::free_callback);
+        register!(ffi_lib, "define_struct(fields: array) -> int", ffilib::define_struct);
+        register!(ffi_lib, "struct_size(struct_id: any) -> int", ffilib::struct_size);
+        register!(ffi_lib, "struct_offset(struct_id: any, field: any) -> int", ffilib::struct_offset);
+        register!(ffi_lib, "struct_alloc(struct_id: any) -> any", ffilib::struct_alloc);
+        register!(ffi_lib, "struct_get(struct_id: any, address: any, field: any) -> any", ffilib::struct_get);
+        register!(ffi_lib, "struct_set(struct_id: any, address: any, field: any, value: any) -> bool", ffilib::struct_set);
+        register!(ffi_lib, "read_string(address: any) -> any", ffilib::read_string);
+        register!(ffi_lib, "close(library_id: number) -> bool", ffilib::close);
+        self.register_library(ffi_lib);
+
+        // Args library for command line parsing
+        let mut args_lib = Library::new("argslib");
+        register!(args_lib, "parser(program_name: string, description: any?) -> int", argslib::parser);
+        register!(args_lib, "flag(parser_id: any, name: any, short: any?, help: any?) -> any", argslib::flag);
+        register!(args_lib, "option(parser_id: any, name: any, short: any?, help: any?, type: any?, default: any?) -> any", argslib::option);
+        register!(args_lib, "positional(parser_id: any, name: any, help: any?, type: any?, default: any?) -> any", argslib::positional);
+        register!(args_lib, "require(parser_id: any, name: any) -> bool", argslib::require);
+        register!(args_lib, "command(parser_id: any, name: any, help: any?) -> int", argslib::command);
+        register!(args_lib, "usage(parser_id: any) -> string", argslib::usage);
+        register!(args_lib, "parse(parser_id: any, args: any?) -> map", argslib::parse);
+        self.register_library(args_lib);
+
         // Compiler library for compiler operations
         let mut compiler_lib = Library::new("compilerlib");
-        compiler_lib.register_function("create_node", crate::functions::compilerlib::create_node);
-        compiler_lib.register_function("add_child", crate::functions::compilerlib::add_child);
-        compiler_lib.register_function("node_to_string", crate::functions::compilerlib::node_to_string);
-        compiler_lib.register_function("create_symbol_table", crate::functions::compilerlib::create_symbol_table);
-        compiler_lib.register_function("add_symbol", crate::functions::compilerlib::add_symbol);
-        compiler_lib.register_function("lookup_symbol", crate::functions::compilerlib::lookup_symbol);
-        compiler_lib.register_function("generate_ir", crate::functions::compilerlib::generate_ir);
-        compiler_lib.register_function("optimize_ir", crate::functions::compilerlib::optimize_ir);
-        compiler_lib.register_function("generate_assembly", crate::functions::compilerlib::generate_assembly);
-        compiler_lib.register_function("parse", crate::functions::compilerlib::parse);
-        compiler_lib.register_function("tokenize", crate::functions::compilerlib::tokenize);
-        compiler_lib.register_function("compile", crate::functions::compilerlib::compile);
+        register!(compiler_lib, "create_node(node_type: string, name: string, data_type: string?, value: string?) -> int", compilerlib::create_node);
+        register!(compiler_lib, "add_child(parent_id: number, child_id: number) -> bool", compilerlib::add_child);
+        register!(compiler_lib, "node_to_string(node_id: number) -> string", compilerlib::node_to_string);
+        register!(compiler_lib, "create_symbol_table() -> int", compilerlib::create_symbol_table);
+        register!(compiler_lib, "add_symbol(table_id: number, name: string, data_type: string, address: number) -> bool", compilerlib::add_symbol);
+        register!(compiler_lib, "lookup_symbol(table_id: number, name: string) -> array", compilerlib::lookup_symbol);
+        register!(compiler_lib, "generate_ir(source_code: string) -> string", compilerlib::generate_ir);
+        register!(compiler_lib, "optimize_ir(ir_code: string) -> string", compilerlib::optimize_ir);
+        register!(compiler_lib, "generate_assembly(ir_code: string) -> string", compilerlib::generate_assembly);
+        register!(compiler_lib, "parse(source_code: string) -> int", compilerlib::parse);
+        register!(compiler_lib, "tokenize(source_code: string) -> array", compilerlib::tokenize);
+        register!(compiler_lib, "compile(source_code: string) -> array", compilerlib::compile);
         self.register_library(compiler_lib);
 
         // Register Lexer library functions
         let mut lexer_lib = Library::new("lexerlib");
-        lexer_lib.register_function("create_lexer", crate::functions::lexerlib::create_lexer);
-        lexer_lib.register_function("tokenize", crate::functions::lexerlib::tokenize);
-        lexer_lib.register_function("define_token", crate::functions::lexerlib::define_token);
+        register!(lexer_lib, "create_lexer(config: any) -> any", lexerlib::create_lexer);
+        register!(lexer_lib, "tokenize(lexer: any, input: string) -> array", lexerlib::tokenize);
+        register!(lexer_lib, "define_token(name: string, pattern: string) -> map", lexerlib::define_token);
         self.register_library(lexer_lib);
 
         // Register Parser library functions
         let mut parser_lib = Library::new("parserlib");
-        parser_lib.register_function("create_parser", crate::functions::parserlib::create_parser);
-        parser_lib.register_function("parse", crate::functions::parserlib::parse);
-        parser_lib.register_function("define_rule", crate::functions::parserlib::define_rule);
-        parser_lib.register_function("create_grammar", crate::functions::parserlib::create_grammar);
+        register!(parser_lib, "create_parser(config: any) -> any", parserlib::create_parser);
+        register!(parser_lib, "parse(parser: any, tokens: any) -> map", parserlib::parse);
+        register!(parser_lib, "define_rule(name: string, production: string, node_type: string?) -> map", parserlib::define_rule);
+        register!(parser_lib, "create_grammar(name: string, properties: map) -> map", parserlib::create_grammar);
         self.register_library(parser_lib);
 
         // Register AST library functions
         let mut ast_lib = Library::new("astlib");
-        ast_lib.register_function("create_node", crate::functions::astlib::create_node);
-        ast_lib.register_function("define_node_type", crate::functions::astlib::define_node_type);
-        ast_lib.register_function("traverse", crate::functions::astlib::traverse);
-        ast_lib.register_function("create_visitor", crate::functions::astlib::create_visitor);
+        register!(ast_lib, "create_node(type: string, properties: map) -> map", astlib::create_node);
+        register!(ast_lib, "define_node_type(name: string, properties: map) -> map", astlib::define_node_type);
+        register!(ast_lib, "traverse(ast: any, visitor: any) -> any", astlib::traverse);
+        register!(ast_lib, "create_visitor(name: string, methods: array) -> map", astlib::create_visitor);
         self.register_library(ast_lib);
 
         // Register Symbol library functions
         let mut symbol_lib = Library::new("symbollib");
-        symbol_lib.register_function("create_symbol_table", crate::functions::symbollib::create_symbol_table);
-        symbol_lib.register_function("define_symbol", crate::functions::symbollib::define_symbol);
-        symbol_lib.register_function("add_symbol", crate::functions::symbollib::add_symbol);
-        symbol_lib.register_function("lookup_symbol", crate::functions::symbollib::lookup_symbol);
+        register!(symbol_lib, "create_symbol_table(name: string) -> map", symbollib::create_symbol_table);
+        register!(symbol_lib, "define_symbol(name: string, attributes: array) -> map", symbollib::define_symbol);
+        register!(symbol_lib, "add_symbol(symbol_table: map, name: string, attributes: any) -> map", symbollib::add_symbol);
+        register!(symbol_lib, "lookup_symbol(symbol_table: map, name: string) -> any", symbollib::lookup_symbol);
         self.register_library(symbol_lib);
 
         // Register Type library functions
         let mut type_lib = Library::new("typelib");
-        type_lib.register_function("define_type", crate::functions::typelib::define_type);
-        type_lib.register_function("check_type", crate::functions::typelib::check_type);
-        type_lib.register_function("create_type_system", crate::functions::typelib::create_type_system);
-        type_lib.register_function("infer_type", crate::functions::typelib::infer_type);
+        register!(type_lib, "define_type(name: string, operations: array) -> map", typelib::define_type);
+        register!(type_lib, "check_type(value: any, type_name: string) -> bool", typelib::check_type);
+        register!(type_lib, "create_type_system(types: array) -> map", typelib::create_type_system);
+        register!(type_lib, "infer_type(expression: any, type_system: any) -> string", typelib::infer_type);
         self.register_library(type_lib);
 
         // Register IR library functions
         let mut ir_lib = Library::new("irlib");
-        ir_lib.register_function("create_instruction", crate::functions::irlib::create_instruction);
-        ir_lib.register_function("generate", crate::functions::irlib::generate);
-        ir_lib.register_function("optimize", crate::functions::irlib::optimize);
-        ir_lib.register_function("to_string", crate::functions::irlib::to_string);
+        register!(ir_lib, "create_instruction(opcode: string, operands: array) -> map", irlib::create_instruction);
+        register!(ir_lib, "generate(ast: any) -> array", irlib::generate);
+        register!(ir_lib, "optimize(ir_code: any, optimizations: any) -> any", irlib::optimize);
+        register!(ir_lib, "to_string(ir_code: array) -> string", irlib::to_string);
         self.register_library(ir_lib);
 
         // Register CodeGen library functions
         let mut codegen_lib = Library::new("codegenlib");
-        codegen_lib.register_function("create_generator", crate::functions::codegenlib::create_generator);
-        codegen_lib.register_function("generate", crate::functions::codegenlib::generate);
-        codegen_lib.register_function("define_target", crate::functions::codegenlib::define_target);
-        codegen_lib.register_function("emit_code", crate::functions::codegenlib::emit_code);
+        register!(codegen_lib, "create_generator(target: string, config: map) -> map", codegenlib::create_generator);
+        register!(codegen_lib, "generate(generator: any, ir_code: array) -> string", codegenlib::generate);
+        register!(codegen_lib, "define_target(name: string, properties: map) -> map", codegenlib::define_target);
+        register!(codegen_lib, "emit_code(code: string, filename: string) -> bool", codegenlib::emit_code);
         self.register_library(codegen_lib);
 
         // Register Optimize library functions
         let mut optimize_lib = Library::new("optimizelib");
-        optimize_lib.register_function("create_pass", crate::functions::optimizelib::create_pass);
-        optimize_lib.register_function("apply", crate::functions::optimizelib::apply);
-        optimize_lib.register_function("analyze", crate::functions::optimizelib::analyze);
-        optimize_lib.register_function("create_pipeline", crate::functions::optimizelib::create_pipeline);
+        register!(optimize_lib, "create_pass(name: string, description: string) -> map", optimizelib::create_pass);
+        register!(optimize_lib, "apply(ir_code: any, passes: array) -> any", optimizelib::apply);
+        register!(optimize_lib, "analyze(ir_code: any) -> map", optimizelib::analyze);
+        register!(optimize_lib, "create_pipeline(name: string, passes: array) -> map", optimizelib::create_pipeline);
         self.register_library(optimize_lib);
 
         // Register Color library functions
         let mut color_lib = Library::new("color");
-        color_lib.register_function("hex_to_rgb", crate::functions::colorlib::hex_to_rgb);
-        color_lib.register_function("rgb_to_hex", crate::functions::colorlib::rgb_to_hex);
-        color_lib.register_function("lighten", crate::functions::colorlib::lighten);
-        color_lib.register_function("darken", crate::functions::colorlib::darken);
-        color_lib.register_function("get_ansi_color", crate::functions::colorlib::get_ansi_color);
+        register!(color_lib, "hex_to_rgb(hex: string) -> array", colorlib::hex_to_rgb);
+        register!(color_lib, "rgb_to_hex(rgb_items: array) -> string", colorlib::rgb_to_hex);
+        register!(color_lib, "lighten(hex: string, percent: any) -> any", colorlib::lighten);
+        register!(color_lib, "darken(hex: string, percent: any) -> any", colorlib::darken);
+        register!(color_lib, "get_ansi_color(color_name: string) -> string", colorlib::get_ansi_color);
         self.register_library(color_lib);
 
         // Register crypto library functions
         let mut crypto_lib = Library::new("crypto");
-        crypto_lib.register_function("hash", crate::functions::cryptolib::hash);
-        crypto_lib.register_function("encrypt", crate::functions::cryptolib::encrypt);
-        crypto_lib.register_function("decrypt", crate::functions::cryptolib::decrypt);
+        register!(crypto_lib, "hash(text: string) -> string", cryptolib::hash);
+        register!(crypto_lib, "encrypt(text: string, key: string) -> string", cryptolib::encrypt);
+        register!(crypto_lib, "decrypt(encrypted_string: string, key: string) -> string", cryptolib::decrypt);
+        register!(crypto_lib, "sha512(text: string) -> string", cryptolib::sha512);
+        register!(crypto_lib, "sha1(text: string) -> string", cryptolib::sha1);
+        register!(crypto_lib, "md5(text: string) -> string", cryptolib::md5);
+        register!(crypto_lib, "hmac_sha256(key: string, message: string) -> string", cryptolib::hmac_sha256);
+        register!(crypto_lib, "constant_time_equals(a: string, b: string) -> bool", cryptolib::constant_time_equals);
+        register!(crypto_lib, "random_bytes(count: int, encoding: string?) -> string", cryptolib::random_bytes);
+        register!(crypto_lib, "hash_password(password: string, iterations: int?) -> string", cryptolib::hash_password);
+        register!(crypto_lib, "verify_password(password: string, hash: string) -> bool", cryptolib::verify_password);
+        register!(crypto_lib, "ed25519_keypair() -> map", cryptolib::ed25519_keypair);
+        register!(crypto_lib, "ed25519_sign(message: string, secret_key: string) -> string", cryptolib::ed25519_sign);
+        register!(crypto_lib, "ed25519_verify(message: string, signature: string, public_key: string) -> bool", cryptolib::ed25519_verify);
+        register!(crypto_lib, "hex_encode(text: string) -> string", cryptolib::hex_encode);
+        register!(crypto_lib, "hex_decode(hex: string) -> string", cryptolib::hex_decode);
+        register!(crypto_lib, "base64_encode(text: string) -> string", cryptolib::base64_encode);
+        register!(crypto_lib, "base64_decode(encoded: string) -> string", cryptolib::base64_decode);
+        register!(crypto_lib, "base64url_encode(text: string) -> string", cryptolib::base64url_encode);
+        register!(crypto_lib, "base64url_decode(encoded: string) -> string", cryptolib::base64url_decode);
         self.register_library(crypto_lib);
 
         // Register regex library functions
         let mut regex_lib = Library::new("regex");
-        regex_lib.register_function("match", crate::functions::regexlib::match_pattern);
-        regex_lib.register_function("search", crate::functions::regexlib::search);
-        regex_lib.register_function("replace", crate::functions::regexlib::replace);
+        register!(regex_lib, "match(text: string, pattern: string) -> bool", regexlib::match_pattern);
+        register!(regex_lib, "search(text: string, pattern: string) -> any", regexlib::search);
+        register!(regex_lib, "replace(text: string, pattern: string, replacement: string) -> string", regexlib::replace);
         self.register_library(regex_lib);
 
         // Register UUID library functions
         let mut uuid_lib = Library::new("uuid");
-        uuid_lib.register_function("generate", crate::functions::uuidlib::generate);
-        uuid_lib.register_function("parse", crate::functions::uuidlib::parse);
-        uuid_lib.register_function("is_valid", crate::functions::uuidlib::is_valid);
+        register!(uuid_lib, "generate() -> string", uuidlib::generate);
+        register!(uuid_lib, "parse(uuid_string: string) -> map", uuidlib::parse);
+        register!(uuid_lib, "is_valid(uuid_string: string) -> bool", uuidlib::is_valid);
         self.register_library(uuid_lib);
 
         // Register OS library functions
         let mut os_lib = Library::new("os");
-        os_lib.register_function("env", crate::functions::oslib::env_var);
-        os_lib.register_function("cwd", crate::functions::oslib::cwd);
-        os_lib.register_function("platform", crate::functions::oslib::platform);
+        register!(os_lib, "env(variable_name: string) -> any", oslib::env_var);
+        register!(os_lib, "cwd() -> string", oslib::cwd);
+        register!(os_lib, "platform() -> string", oslib::platform);
         self.register_library(os_lib);
 
         // Register Validation library functions
         let mut validation_lib = Library::new("validation");
-        validation_lib.register_function("email", crate::functions::validationlib::email);
-        validation_lib.register_function("phone", crate::functions::validationlib::phone);
-        validation_lib.register_function("required", crate::functions::validationlib::required);
-        validation_lib.register_function("min_length", crate::functions::validationlib::min_length);
+        register!(validation_lib, "email(email_string: string) -> bool", validationlib::email);
+        register!(validation_lib, "phone(phone_string: string) -> bool", validationlib::phone);
+        register!(validation_lib, "required(value: any) -> bool", validationlib::required);
+        register!(validation_lib, "min_length(text: string, min_length: any) -> bool", validationlib::min_length);
         self.register_library(validation_lib);
 
         // Register System library functions
         let mut system_lib = Library::new("system");
-        system_lib.register_function("exec", crate::functions::systemlib::exec);
-        system_lib.register_function("uptime", crate::functions::systemlib::uptime);
-        system_lib.register_function("info", crate::functions::systemlib::info);
-        system_lib.register_function("current_time", crate::functions::systemlib::current_time);
-        system_lib.register_function("system_name", crate::functions::systemlib::system_name);
+        register!(system_lib, "exec(command: string) -> string", systemlib::exec);
+        register!(system_lib, "uptime() -> int", systemlib::uptime);
+        register!(system_lib, "info() -> map", systemlib::info);
+        register!(system_lib, "current_time() -> int", systemlib::current_time);
+        register!(system_lib, "system_name() -> string", systemlib::system_name);
         self.register_library(system_lib); 
 
         // Register Box library functions
         let mut box_lib = Library::new("boxlib");
-        box_lib.register_function("put", crate::functions::boxutillib::put);
-        box_lib.register_function("get", crate::functions::boxutillib::get);
-        box_lib.register_function("is_box", crate::functions::boxutillib::is_box);
+        register!(box_lib, "put(value: any) -> map", boxutillib::put);
+        register!(box_lib, "get(box: any) -> any", boxutillib::get);
+        register!(box_lib, "is_box(value: any) -> bool", boxutillib::is_box);
         self.register_library(box_lib);
 
         // Register Log library functions
         let mut log_lib = Library::new("loglib");
-        log_lib.register_function("infolog", crate::functions::loglib::info);
-        log_lib.register_function("warnlog", crate::functions::loglib::warn);
-        log_lib.register_function("errorlog", crate::functions::loglib::error);
-        log_lib.register_function("debuglog", crate::functions::loglib::debug);
+        register!(log_lib, "infolog(message: any) -> bool", loglib::info);
+        register!(log_lib, "warnlog(message: any) -> bool", loglib::warn);
+        register!(log_lib, "errorlog(message: any) -> bool", loglib::error);
+        register!(log_lib, "debuglog(message: any) -> bool", loglib::debug);
         self.register_library(log_lib);
 
         // Register HT (Head/Tails) library functions
         let mut ht_lib = Library::new("htlib");
-        ht_lib.register_function("coin", crate::functions::htlib::coin);
-        ht_lib.register_function("bool_tos", crate::functions::htlib::bool_tos);
+        register!(ht_lib, "coin() -> string", htlib::coin);
+        register!(ht_lib, "bool_tos() -> bool", htlib::bool_tos);
         self.register_library(ht_lib);
 
         // Register Audio library functions
         let mut audio_lib = Library::new("audio");
-        audio_lib.register_function("play", crate::functions::audiolib::play);
-        audio_lib.register_function("pause", crate::functions::audiolib::pause);
-        audio_lib.register_function("stop", crate::functions::audiolib::stop);
-        audio_lib.register_function("record", crate::functions::audiolib::record);
+        register!(audio_lib, "load(path: string) -> map", audiolib::load);
+        register!(audio_lib, "save(sound: map, path: string, bits: int?) -> map", audiolib::save);
+        register!(audio_lib, "sine(frequency: number, duration: number, sample_rate: int?, amplitude: number?) -> map", audiolib::sine);
+        register!(audio_lib, "square(frequency: number, duration: number, sample_rate: int?, amplitude: number?) -> map", audiolib::square);
+        register!(audio_lib, "noise(duration: number, sample_rate: int?, amplitude: number?, seed: int?) -> map", audiolib::noise);
+        register!(audio_lib, "gain(sound: map, factor: number) -> map", audiolib::gain);
+        register!(audio_lib, "mix(first: map, second: map) -> map", audiolib::mix);
+        register!(audio_lib, "resample(sound: map, sample_rate: int) -> map", audiolib::resample);
+        register!(audio_lib, "trim(sound: map, start: number, end: number?) -> map", audiolib::trim);
+        register!(audio_lib, "fade(sound: map, fade_in: number, fade_out: number?) -> map", audiolib::fade);
+        register!(audio_lib, "rms(sound: map) -> float", audiolib::rms);
+        register!(audio_lib, "peak(sound: map) -> float", audiolib::peak);
+        register!(audio_lib, "play(sound: any) -> bool", audiolib::play);
+        register!(audio_lib, "pause() -> bool", audiolib::pause);
+        register!(audio_lib, "stop() -> bool", audiolib::stop);
         self.register_library(audio_lib);
 
         // Register Image library functions
         let mut image_lib = Library::new("image");
-        image_lib.register_function("load", crate::functions::imagelib::load);
-        image_lib.register_function("save", crate::functions::imagelib::save);
-        image_lib.register_function("resize", crate::functions::imagelib::resize);
-        image_lib.register_function("crop", crate::functions::imagelib::crop);
+        register!(image_lib, "load(path: string) -> map", imagelib::load);
+        register!(image_lib, "create(width: int, height: int, color: any?) -> map", imagelib::create);
+        register!(image_lib, "save(image: map, path: string, format: string?, quality: int?) -> map", imagelib::save);
+        register!(image_lib, "resize(image: map, width: number, height: any, filter: string?) -> map", imagelib::resize);
+        register!(image_lib, "crop(image: map, x: number, y: any, width: any, height: any) -> map", imagelib::crop);
+        register!(image_lib, "rotate(image: map, degrees: int) -> map", imagelib::rotate);
+        register!(image_lib, "flip(image: map, direction: string?) -> map", imagelib::flip);
+        register!(image_lib, "grayscale(image: map) -> map", imagelib::grayscale);
+        register!(image_lib, "brightness(image: map, amount: int) -> map", imagelib::brightness);
+        register!(image_lib, "contrast(image: map, percent: number) -> map", imagelib::contrast);
+        register!(image_lib, "get_pixel(image: map, x: int, y: int) -> string", imagelib::get_pixel);
+        register!(image_lib, "set_pixel(image: map, x: int, y: int, color: any) -> bool", imagelib::set_pixel);
+        register!(image_lib, "draw_line(image: map, x1: int, y1: int, x2: int, y2: int, color: any) -> bool", imagelib::draw_line);
+        register!(image_lib, "draw_rect(image: map, x: int, y: int, width: int, height: int, color: any, filled: bool?) -> bool", imagelib::draw_rect);
+        register!(image_lib, "draw_circle(image: map, x: int, y: int, radius: int, color: any, filled: bool?) -> bool", imagelib::draw_circle);
+        register!(image_lib, "free(image: map) -> bool", imagelib::free);
         self.register_library(image_lib);
 
         // Register Date library functions
         let mut date_lib = Library::new("date");
         // Use our own date functions for all operations
-        date_lib.register_function("now", crate::functions::datelib::now);
-        date_lib.register_function("year", crate::functions::datelib::year);
-        date_lib.register_function("month", crate::functions::datelib::month);
-        date_lib.register_function("day", crate::functions::datelib::day);
-        date_lib.register_function("format", crate::functions::datelib::format);
-        date_lib.register_function("parse", crate::functions::datelib::parse);
-        date_lib.register_function("add_days", crate::functions::datelib::add_days);
-        date_lib.register_function("add_months", crate::functions::datelib::add_months);
-        date_lib.register_function("add_years", crate::functions::datelib::add_years);
-        date_lib.register_function("weekday", crate::functions::datelib::weekday);
-        date_lib.register_function("weekday_name", crate::functions::datelib::weekday_name);
-        date_lib.register_function("days_in_month", crate::functions::datelib::days_in_month);
-        date_lib.register_function("is_leap_year", crate::functions::datelib::is_leap_year);
-        date_lib.register_function("diff_days", crate::functions::datelib::diff_days);
+        register!(date_lib, "now() -> int", datelib::now);
+        register!(date_lib, "year() -> int", datelib::year);
+        register!(date_lib, "month() -> int", datelib::month);
+        register!(date_lib, "day() -> int", datelib::day);
+        register!(date_lib, "format(timestamp: number, format_string: string) -> string", datelib::format);
+        register!(date_lib, "parse(date_string: string, format_string: string) -> int", datelib::parse);
+        register!(date_lib, "add_days(timestamp: number, days: number) -> int", datelib::add_days);
+        register!(date_lib, "add_months(timestamp: number, months: number) -> int", datelib::add_months);
+        register!(date_lib, "add_years(timestamp: number, years: number) -> int", datelib::add_years);
+        register!(date_lib, "weekday(timestamp: number) -> int", datelib::weekday);
+        register!(date_lib, "weekday_name(timestamp: number) -> string", datelib::weekday_name);
+        register!(date_lib, "days_in_month(year: number, month: number) -> int", datelib::days_in_month);
+        register!(date_lib, "is_leap_year(year: number) -> bool", datelib::is_leap_year);
+        register!(date_lib, "diff_days(timestamp1: number, timestamp2: number) -> int", datelib::diff_days);
         self.register_library(date_lib);
 
         // Register Net library functions
         let mut net_lib = Library::new("netlib");
-        net_lib.register_function("ping", crate::functions::netlib::ping);
-        net_lib.register_function("get", crate::functions::netlib::get);
-        net_lib.register_function("post", crate::functions::netlib::post);
+        register!(net_lib, "ping(host: string, port: int?, timeout: number?) -> bool", netlib::ping);
+        register!(net_lib, "get(url: string) -> string", netlib::get);
+        register!(net_lib, "post(url: string, data: any) -> string", netlib::post);
+        register!(net_lib, "resolve(host: string) -> array", netlib::resolve);
+        register!(net_lib, "tcp_connect(host: string, port: int, timeout: number?) -> int", netlib::tcp_connect);
+        register!(net_lib, "tls_connect(host: string, port: int, timeout: number?, verify: bool?) -> int", netlib::tls_connect);
+        register!(net_lib, "tcp_listen(port: int, host: string?) -> int", netlib::tcp_listen);
+        register!(net_lib, "accept(listener: int, timeout: number?) -> int", netlib::accept);
+        register!(net_lib, "read(socket: int, max_bytes: int?, timeout: number?) -> string", netlib::read);
+        register!(net_lib, "read_bytes(socket: int, max_bytes: int?, timeout: number?) -> array", netlib::read_bytes);
+        register!(net_lib, "read_line(socket: int, timeout: number?) -> any", netlib::read_line);
+        register!(net_lib, "write(socket: int, data: any, timeout: number?) -> int", netlib::write);
+        register!(net_lib, "write_line(socket: int, text: string, timeout: number?) -> int", netlib::write_line);
+        register!(net_lib, "udp_bind(port: int, host: string?) -> int", netlib::udp_bind);
+        register!(net_lib, "send_to(socket: int, data: any, host: string, port: int) -> int", netlib::send_to);
+        register!(net_lib, "recv_from(socket: int, max_bytes: int?, timeout: number?) -> map", netlib::recv_from);
+        register!(net_lib, "local_address(socket: int) -> map", netlib::local_address);
+        register!(net_lib, "peer_address(socket: int) -> map", netlib::peer_address);
+        register!(net_lib, "close(socket: int) -> bool", netlib::close);
         self.register_library(net_lib);
     }
 }
 
-// Global library manager instance
+// Global library registry. It is only written while libraries are registered (at startup
+// and when a plugin is imported); calls resolve a handle and run without holding the lock.
 lazy_static::lazy_static! {
-    static ref LIBRARY_MANAGER: std::sync::Mutex<LibraryManager> = std::sync::Mutex::new(LibraryManager::new());
+    static ref LIBRARY_MANAGER: RwLock<LibraryManager> = RwLock::new(LibraryManager::new());
 }
 
 /// Initialize the library system
 pub fn initialize() {
-    let mut manager = LIBRARY_MANAGER.lock().unwrap();
+    let mut manager = LIBRARY_MANAGER.write().unwrap();
     manager.initialize_standard_libraries();
 }
 
-/// Call a library function
-pub fn call_library(library_name: &str, function_name: &str, args: Vec<Value>) -> Result<Value, String> {
-    let manager = LIBRARY_MANAGER.lock().unwrap();
-    manager.call_library(library_name, function_name, args)
+/// Resolve a library function once, e.g. when compiling a call to it
+pub fn resolve(library_name: &str, function_name: &str) -> Option<FunctionHandle> {
+    LIBRARY_MANAGER.read().unwrap().find_function(library_name, function_name).ok()
+}
+
+/// Call a library function, looking it up by name
+pub fn call_library(context: &CallContext, function_name: &str, args: Vec<Value>) -> Result<Value, String> {
+    let function = LIBRARY_MANAGER.read().unwrap().find_function(context.library, function_name)?;
+    // Called without the lock held, so callbacks can call library functions again
+    function.call(context, args)
+}
+
+/// Check whether a library provides a function, in Rust or through a plugin
+pub fn has_function(library_name: &str, function_name: &str) -> bool {
+    LIBRARY_MANAGER.read().unwrap()
+        .get_library(library_name)
+        .is_some_and(|library| library.has_function(function_name))
+}
+
+/// Make a library imported with `lib` available. Paths load a native plugin;
+/// unknown names are looked up as plugins in the plugin search directories.
+pub fn import_library(name: &str, script_dir: Option<&Path>) -> Result<(), String> {
+    let path = if plugin::is_plugin_path(name) {
+        PathBuf::from(name)
+    } else if LIBRARY_MANAGER.read().unwrap().get_library(name).is_some() {
+        return Ok(());
+    } else {
+        match plugin::find_plugin(name, script_dir) {
+            Some(path) => path,
+            // Libraries written in Razen live in properties/libs and need no loading
+            None => return Ok(()),
+        }
+    };
+
+    if let Some(library) = plugin::load_plugin(&path)? {
+        register_library(library);
+    }
+    Ok(())
 }
 
 /// Register a custom library
 pub fn register_library(library: Library) {
-    let mut manager = LIBRARY_MANAGER.lock().unwrap();
+    let mut manager = LIBRARY_MANAGER.write().unwrap();
     manager.register_library(library);
 }
 
 /// Get a list of all registered libraries
 pub fn get_library_names() -> Vec<String> {
-    let manager = LIBRARY_MANAGER.lock().unwrap();
+    let manager = LIBRARY_MANAGER.read().unwrap();
     manager.libraries.keys().cloned().collect()
 }
 
 /// Get a list of all functions in a library
 pub fn get_library_functions(library_name: &str) -> Result<Vec<String>, String> {
-    let manager = LIBRARY_MANAGER.lock().unwrap();
+    let manager = LIBRARY_MANAGER.read().unwrap();
     match manager.get_library(library_name) {
         Some(library) => Ok(library.function_names()),
         None => Err(format!("Library '{}' not found", library_name)),
     }
-}
\ No newline at end of file
+}
+
+/// Get the signatures and docs of all functions in a library
+pub fn get_library_docs(library_name: &str) -> Result<Vec<FunctionDoc>, String> {
+    let manager = LIBRARY_MANAGER.read().unwrap();
+    match manager.get_library(library_name) {
+        Some(library) => Ok(library.function_docs()),
+        None => Err(format!("Library '{}' not found", library_name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signature_checks_arity_and_types() {
+        let signature = Signature::parse("greet(string, int?) -> string").unwrap();
+        assert_eq!(signature.to_string(), "greet(string, int?) -> string");
+        assert!(signature.check("hello", &[Value::String("ada".to_string())]).is_ok());
+        assert!(signature.check("hello", &[Value::String("ada".to_string()), Value::Int(2)]).is_ok());
+        assert!(signature.check("hello", &[]).unwrap_err().contains("expects greet(string, int?) -> string"));
+        assert!(signature.check("hello", &[Value::Int(1), Value::Bool(true)]).unwrap_err().contains("argument 2 must be int"));
+
+        let sum = Signature::parse("sum(number...) -> number").unwrap();
+        assert!(sum.check("math", &[Value::Int(1), Value::Float(2.5), Value::Int(3)]).is_ok());
+        assert!(Signature::parse("bad(int?, int)").is_err());
+        assert!(Signature::parse("bad(widget)").is_err());
+
+        assert_eq!(signature.parameter_type(0), Some("string"));
+        assert_eq!(signature.parameter_type(2), None);
+        assert_eq!(sum.parameter_type(5), Some("number"));
+
+        let named = Signature::parse("push(items: array, value: any?) -> array").unwrap();
+        assert_eq!(named.names, vec!["items", "value"]);
+        assert_eq!(named.to_string(), "push(items: array, value: any?) -> array");
+        assert!(named.check("arrlib", &[Value::Int(1)]).unwrap_err().contains("argument 1 must be array"));
+    }
+
+    #[test]
+    fn test_standard_library_functions_are_documented() {
+        let mut manager = LibraryManager::new();
+        manager.initialize_standard_libraries();
+
+        let docs = manager.get_library("arrlib").unwrap().function_docs();
+        let push = docs.iter().find(|doc| doc.signature.name == "push").unwrap();
+        assert_eq!(push.signature.to_string(), "push(items: array, value: any) -> array");
+        assert!(push.doc.contains("Example: push([1, 2, 3], 4)"));
+    }
+
+    #[test]
+    fn test_handles_call_back_into_the_registry() {
+        // Runs a "callback" through the context, which calls another library function
+        fn apply(context: &CallContext, args: Vec<Value>) -> Result<Value, String> {
+            let runner = context.script_runner().ok_or("no interpreter")?;
+            runner("twice", vec![args[0].to_string()]).map(Value::String)
+        }
+
+        let mut library = Library::new("reentrant");
+        library.register_context_function("apply(value: any) -> string", apply, "");
+        library.register_function("upper(text: string) -> string", crate::functions::strlib::upper, "");
+        register_library(library);
+
+        let handle = resolve("Reentrant", "apply").unwrap();
+        let runner = || -> ScriptRunner {
+            Rc::new(|_: &str, args: Vec<String>| {
+                let context = CallContext::new("reentrant");
+                let upper = call_library(&context, "upper", vec![Value::String(args[0].repeat(2))])?;
+                Ok(upper.to_string())
+            })
+        };
+        let context = CallContext::new("reentrant").with_runner(&runner);
+        assert_eq!(handle.call(&context, vec![Value::String("ab".to_string())]).unwrap(), Value::String("ABAB".to_string()));
+        assert!(handle.call(&CallContext::new("reentrant"), vec![]).unwrap_err().contains("expects apply"));
+        assert!(handle.call(&CallContext::new("reentrant"), vec![Value::Null]).unwrap_err().contains("no interpreter"));
+        assert!(resolve("reentrant", "missing").is_none());
+    }
+}
@@ -5,11 +5,56 @@ use inkwell::passes::PassManager;
 use inkwell::values::{FunctionValue, PointerValue, BasicMetadataValueEnum, BasicValueEnum, BasicValue};
 use inkwell::types::{BasicTypeEnum, BasicMetadataTypeEnum, BasicType};
 use inkwell::AddressSpace;
+use inkwell::module::Linkage;
+use inkwell::targets::{CodeModel, FileType, InitializationConfig, RelocMode, Target, TargetMachine, TargetTriple};
+use inkwell::OptimizationLevel;
 use std::collections::HashMap;
+use std::path::Path;
 
 // Assuming your IR enum and Value enum are accessible via crate:: path
 use crate::compiler::IR as RazenIR;
 use crate::value::Value as RazenValue;
+use crate::wasm_runtime::{self, Host, Runtime};
+
+// What compiled code runs on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompileTarget {
+    Native,
+    Wasm32,     // wasm32-unknown-unknown, with input and output provided by the embedder
+    Wasm32Wasi, // wasm32-wasi, for any WASI runtime
+}
+
+impl CompileTarget {
+    pub fn parse(name: &str) -> Option<CompileTarget> {
+        match name {
+            "native" => Some(CompileTarget::Native),
+            "wasm32" | "wasm32-unknown-unknown" => Some(CompileTarget::Wasm32),
+            "wasm32-wasi" => Some(CompileTarget::Wasm32Wasi),
+            _ => None,
+        }
+    }
+
+    pub fn triple(&self) -> String {
+        match self {
+            CompileTarget::Native => TargetMachine::get_default_triple().as_str().to_string_lossy().into_owned(),
+            CompileTarget::Wasm32 => "wasm32-unknown-unknown".to_string(),
+            CompileTarget::Wasm32Wasi => "wasm32-wasi".to_string(),
+        }
+    }
+
+    pub fn is_wasm(&self) -> bool {
+        *self != CompileTarget::Native
+    }
+}
+
+// Values known at compile time; FFI declarations must be built from these
+#[derive(Debug, Clone)]
+enum Constant<'ctx> {
+    Str(String),
+    Strings(Vec<String>),
+    Library,
+    Foreign(FunctionValue<'ctx>, Vec<String>, String), // function, parameter types, return type
+}
 
 pub struct LlvmCompiler<'ctx> {
     pub context: &'ctx Context,
@@ -25,6 +70,17 @@ pub struct LlvmCompiler<'ctx> {
     
     // Stack for managing values during compilation
     value_stack: Vec<BasicValueEnum<'ctx>>,
+
+    // Compile-time values of stack entries and variables, for FFILib declarations
+    constants: HashMap<BasicValueEnum<'ctx>, Constant<'ctx>>,
+    variable_constants: HashMap<String, Constant<'ctx>>,
+    // Shared libraries opened with FFILib[open], which the program must be linked against
+    pub linked_libraries: Vec<String>,
+
+    target: CompileTarget,
+    target_machine: Option<TargetMachine>,
+    // Print, input and string support; only built for WebAssembly, where there is no C library
+    runtime: Option<Runtime<'ctx>>,
 }
 
 impl<'ctx> LlvmCompiler<'ctx> {
@@ -79,9 +135,46 @@ impl<'ctx> LlvmCompiler<'ctx> {
             variables: HashMap::new(),
             functions: HashMap::new(),
             value_stack: Vec::new(),
+            constants: HashMap::new(),
+            variable_constants: HashMap::new(),
+            linked_libraries: Vec::new(),
+            target: CompileTarget::Native,
+            target_machine: None,
+            runtime: None,
         }
     }
 
+    pub fn with_target(context: &'ctx Context, module_name: &str, enable_optimizations: bool, target: CompileTarget) -> Result<Self, String> {
+        let mut compiler = LlvmCompiler::new(context, module_name, enable_optimizations);
+        if !target.is_wasm() {
+            return Ok(compiler);
+        }
+
+        Target::initialize_webassembly(&InitializationConfig::default());
+        let triple = TargetTriple::create(&target.triple());
+        let llvm_target = Target::from_triple(&triple).map_err(|e| format!("LLVM has no WebAssembly target: {}", e))?;
+        let optimization = if enable_optimizations { OptimizationLevel::Default } else { OptimizationLevel::None };
+        let machine = llvm_target
+            .create_target_machine(&triple, "generic", "", optimization, RelocMode::Default, CodeModel::Default)
+            .ok_or_else(|| format!("Could not create a target machine for {}", target.triple()))?;
+
+        compiler.module.set_triple(&triple);
+        compiler.module.set_data_layout(&machine.get_target_data().get_data_layout());
+        let host = if target == CompileTarget::Wasm32Wasi { Host::Wasi } else { Host::Embedder };
+        compiler.runtime = Some(wasm_runtime::build(context, &compiler.module, host));
+        compiler.target = target;
+        compiler.target_machine = Some(machine);
+        Ok(compiler)
+    }
+
+    // Write the module as an object file for the target, ready for the linker
+    pub fn write_object(&self, path: &Path) -> Result<(), String> {
+        let machine = self.target_machine.as_ref()
+            .ok_or_else(|| "Object files are only written for WebAssembly targets".to_string())?;
+        machine.write_to_file(&self.module, FileType::Object, path)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
     // --- Type Conversion --- 
     fn to_llvm_type(&self, razen_type: &RazenValue) -> BasicTypeEnum<'ctx> {
         // This is a simplified mapping. Real type mapping can be more complex,
@@ -131,6 +224,20 @@ impl<'ctx> LlvmCompiler<'ctx> {
             self.compile_ir_instruction(instruction, function)?;
         }
 
+        // Falling off the end returns zero, like a script that finishes without exit
+        let terminated = self.builder.get_insert_block().and_then(|block| block.get_terminator()).is_some();
+        if !terminated {
+            self.builder.build_return(Some(&llvm_return_type.const_zero()));
+        }
+
+        if name == "main" {
+            match (self.target, &self.runtime) {
+                (CompileTarget::Wasm32Wasi, Some(runtime)) => wasm_runtime::add_start(self.context, &self.module, runtime, function),
+                (CompileTarget::Wasm32, _) => wasm_runtime::export(self.context, function),
+                _ => {}
+            }
+        }
+
         // Verify function
         if function.verify(true) {
             if let Some(fpm) = &self.fpm {
@@ -146,13 +253,19 @@ impl<'ctx> LlvmCompiler<'ctx> {
     fn compile_ir_instruction(&mut self, instruction: &RazenIR, current_function: FunctionValue<'ctx>) -> Result<(), String> {
         match instruction {
             RazenIR::PushNumber(val) => {
-                let i64_val = self.context.i64_type().const_int(*val as u64, true);
-                self.value_stack.push(i64_val.as_basic_value_enum());
+                if val.fract() == 0.0 {
+                    let i64_val = self.context.i64_type().const_int(*val as i64 as u64, true);
+                    self.value_stack.push(i64_val.as_basic_value_enum());
+                } else {
+                    let f64_val = self.context.f64_type().const_float(*val);
+                    self.value_stack.push(f64_val.as_basic_value_enum());
+                }
                 println!("[LLVM] Pushed number to stack: {}", val);
             }
             RazenIR::PushString(s) => {
                 let str_val = self.builder.build_global_string_ptr(s, ".str");
                 let str_ptr = str_val.as_pointer_value();
+                self.constants.insert(str_ptr.as_basic_value_enum(), Constant::Str(s.clone()));
                 self.value_stack.push(str_ptr.as_basic_value_enum());
                 println!("[LLVM] Pushed string to stack: {}", s);
             }
@@ -192,6 +305,9 @@ impl<'ctx> LlvmCompiler<'ctx> {
                     }
                 };
 
+                if let Some(constant) = self.variable_constants.get(name) {
+                    self.constants.insert(loaded_val, constant.clone());
+                }
                 self.value_stack.push(loaded_val);
                 println!("[LLVM] Loaded var '{}' to stack", name);
             }
@@ -199,6 +315,10 @@ impl<'ctx> LlvmCompiler<'ctx> {
             RazenIR::StoreVar(name) => {
                 let val_to_store = self.value_stack.pop()
                     .ok_or_else(|| "Stack underflow during StoreVar".to_string())?;
+                match self.constants.get(&val_to_store) {
+                    Some(constant) => { self.variable_constants.insert(name.clone(), constant.clone()); }
+                    None => { self.variable_constants.remove(name); }
+                }
 
                 if let Some((ptr_val, expected_type)) = self.variables.get(name) {
                     // Check if the type of val_to_store matches the expected type
@@ -230,6 +350,14 @@ impl<'ctx> LlvmCompiler<'ctx> {
                     (BasicValueEnum::FloatValue(l), BasicValueEnum::FloatValue(r)) => {
                         self.builder.build_float_add(l, r, "addtmp").as_basic_value_enum()
                     }
+                    // Adding to a string joins the text of both values
+                    (BasicValueEnum::PointerValue(_), _) | (_, BasicValueEnum::PointerValue(_)) if self.runtime.is_some() => {
+                        let runtime = self.runtime.as_ref().unwrap();
+                        let l = self.to_text(lhs)?;
+                        let r = self.to_text(rhs)?;
+                        self.builder.build_call(runtime.concat, &[l.into(), r.into()], "concat")
+                            .try_as_basic_value().left().unwrap()
+                    }
                     _ => return Err("Type mismatch in Add operation".to_string()),
                 };
                 
@@ -291,6 +419,24 @@ impl<'ctx> LlvmCompiler<'ctx> {
                 println!("[LLVM] Performed Divide operation");
             }
             
+            RazenIR::Negate => {
+                let value = self.value_stack.pop().ok_or_else(|| "Stack underflow during Negate".to_string())?;
+                let result = match value {
+                    BasicValueEnum::IntValue(v) => self.builder.build_int_neg(v, "negtmp").as_basic_value_enum(),
+                    BasicValueEnum::FloatValue(v) => self.builder.build_float_neg(v, "negtmp").as_basic_value_enum(),
+                    _ => return Err("Type mismatch in Negate operation".to_string()),
+                };
+                self.value_stack.push(result);
+                println!("[LLVM] Performed Negate operation");
+            }
+
+            RazenIR::Call(fn_name, _) if fn_name == "__import_lib" => {
+                // Built-in libraries need no loading; FFILib calls are compiled directly
+                self.value_stack.pop().ok_or_else(|| "Stack underflow during library import".to_string())?;
+                self.value_stack.push(self.context.bool_type().const_int(1, false).as_basic_value_enum());
+                println!("[LLVM] Imported library");
+            }
+
             RazenIR::Call(fn_name, arg_count) => {
                 if let Some(function_to_call) = self.functions.get(fn_name) {
                     // Pop arguments from stack
@@ -346,24 +492,96 @@ impl<'ctx> LlvmCompiler<'ctx> {
                 println!("[LLVM] Swapped top two stack values");
             }
             
+            RazenIR::Print if self.runtime.is_some() => {
+                let value = self.value_stack.pop().ok_or_else(|| "Stack underflow during Print".to_string())?;
+                let text = self.to_text(value)?;
+                self.builder.build_call(self.runtime.as_ref().unwrap().print, &[text.into()], "print");
+                println!("[LLVM] Print instruction");
+            }
+
             RazenIR::Print => {
                 // For now, just pop and discard the value
                 self.value_stack.pop().ok_or_else(|| "Stack underflow during Print".to_string())?;
                 println!("[LLVM] Print instruction (value discarded)");
             }
-            
+
+            RazenIR::ReadInput if self.runtime.is_some() => {
+                let line = self.builder.build_call(self.runtime.as_ref().unwrap().read_line, &[], "line")
+                    .try_as_basic_value().left().unwrap();
+                self.value_stack.push(line);
+                println!("[LLVM] Read input line");
+            }
+
+            RazenIR::Exit if self.runtime.is_some() => {
+                let exit_code = match self.value_stack.pop() {
+                    Some(BasicValueEnum::IntValue(status)) => {
+                        self.builder.build_int_truncate_or_bit_cast(status, self.context.i32_type(), "exit_status")
+                    },
+                    _ => self.context.i32_type().const_int(0, false),
+                };
+                self.builder.build_call(self.runtime.as_ref().unwrap().exit, &[exit_code.into()], "exit");
+                self.builder.build_unreachable();
+                println!("[LLVM] Added exit call");
+            }
+
             RazenIR::Exit => {
-                // Create a call to exit(0)
+                // Create a call to exit(status) with the status pushed before the instruction
                 let exit_fn = self.module.add_function("exit", 
                     self.context.i32_type().fn_type(&[self.context.i32_type().into()], false), 
                     None
                 );
-                let exit_code = self.context.i32_type().const_int(0, false);
+                let exit_code = match self.value_stack.pop() {
+                    Some(BasicValueEnum::IntValue(status)) => {
+                        self.builder.build_int_truncate(status, self.context.i32_type(), "exit_status")
+                    },
+                    _ => self.context.i32_type().const_int(0, false),
+                };
                 self.builder.build_call(exit_fn, &[exit_code.into()], "exit");
                 self.builder.build_unreachable();
                 println!("[LLVM] Added exit call");
             }
             
+            // Arrays of constant strings, such as FFI type lists, become global char* arrays
+            RazenIR::CreateArray(count) => {
+                let mut strings = Vec::with_capacity(*count);
+                let mut pointers = Vec::with_capacity(*count);
+                for _ in 0..*count {
+                    let value = self.value_stack.pop().ok_or_else(|| "Stack underflow during CreateArray".to_string())?;
+                    match (value, self.constants.get(&value)) {
+                        (BasicValueEnum::PointerValue(ptr), Some(Constant::Str(s))) => {
+                            strings.push(s.clone());
+                            pointers.push(ptr);
+                        }
+                        _ => return Err("Only arrays of constant strings are supported by the LLVM backend".to_string()),
+                    }
+                }
+                strings.reverse();
+                pointers.reverse();
+
+                let string_type = self.context.i8_type().ptr_type(AddressSpace::default());
+                let array = self.module.add_global(string_type.array_type(*count as u32), None, ".array");
+                array.set_initializer(&string_type.const_array(&pointers));
+                array.set_constant(true);
+                let array_ptr = array.as_pointer_value().as_basic_value_enum();
+                self.constants.insert(array_ptr, Constant::Strings(strings));
+                self.value_stack.push(array_ptr);
+                println!("[LLVM] Created constant string array of {} elements", count);
+            }
+
+            RazenIR::LibraryCall(lib_name, func_name, arg_count, _, _) if lib_name.eq_ignore_ascii_case("ffilib") => {
+                let mut args = Vec::with_capacity(*arg_count);
+                for _ in 0..*arg_count {
+                    args.push(self.value_stack.pop()
+                        .ok_or_else(|| format!("Stack underflow during FFILib[{}]", func_name))?);
+                }
+                args.reverse();
+
+                let function_name = func_name.rsplit('.').next().unwrap_or(func_name);
+                let result = self.compile_ffi_call(function_name, &args)?;
+                self.value_stack.push(result);
+                println!("[LLVM] Compiled FFILib[{}]", function_name);
+            }
+
             // Handle other IR instructions that exist in your codebase
             RazenIR::SetupTryCatch |
             RazenIR::ClearTryCatch |
@@ -372,7 +590,6 @@ impl<'ctx> LlvmCompiler<'ctx> {
             RazenIR::Modulo |
             RazenIR::Power |
             RazenIR::FloorDiv |
-            RazenIR::Negate |
             RazenIR::Equal |
             RazenIR::NotEqual |
             RazenIR::GreaterThan |
@@ -386,22 +603,170 @@ impl<'ctx> LlvmCompiler<'ctx> {
             RazenIR::JumpIfFalse(_) |
             RazenIR::JumpIfTrue(_) |
             RazenIR::ReadInput |
-            RazenIR::CreateArray(_) |
             RazenIR::GetIndex |
             RazenIR::SetIndex |
             RazenIR::CreateMap(_) |
+            RazenIR::CreateStruct(_) |
             RazenIR::GetKey |
             RazenIR::SetKey |
             RazenIR::DefineFunction(_, _) |
             RazenIR::Label(_) |
             RazenIR::Sleep |
-            RazenIR::LibraryCall(_, _, _) => {
+            RazenIR::LibraryCall(..) => {
                 return Err(format!("Unsupported Razen IR instruction for LLVM: {:?}", instruction));
             }
         }
         Ok(())
     }
 
+    // --- FFI ---
+    // Foreign functions are declared from constant signatures and called directly,
+    // so the generated code links against the C library instead of going through libffi.
+    fn compile_ffi_call(&mut self, function_name: &str, args: &[BasicValueEnum<'ctx>]) -> Result<BasicValueEnum<'ctx>, String> {
+        let constant = |index: usize| args.get(index).and_then(|value| self.constants.get(value)).cloned();
+
+        match function_name {
+            "open" => {
+                let path = match (args.first(), constant(0)) {
+                    (None, _) => None,
+                    (Some(BasicValueEnum::PointerValue(ptr)), None) if ptr.is_null() => None,
+                    (_, Some(Constant::Str(path))) if path.is_empty() => None,
+                    (_, Some(Constant::Str(path))) => Some(path),
+                    _ => return Err("FFILib[open] needs a constant library path in compiled programs".to_string()),
+                };
+                if let Some(path) = path {
+                    if !self.linked_libraries.contains(&path) {
+                        self.linked_libraries.push(path);
+                    }
+                }
+
+                let handle = self.context.i64_type().const_int(1, false).as_basic_value_enum();
+                self.constants.insert(handle, Constant::Library);
+                Ok(handle)
+            }
+            "func" => {
+                let (Some(Constant::Library), Some(Constant::Str(symbol)), Some(Constant::Strings(params)), Some(Constant::Str(returns))) =
+                    (constant(0), constant(1), constant(2), constant(3)) else {
+                    return Err("FFILib[func] needs a library handle and a constant symbol, parameter types and return type in compiled programs".to_string());
+                };
+
+                let param_types = params.iter()
+                    .map(|name| match self.ffi_type(name)? {
+                        Some(ty) => Ok(ty.into()),
+                        None => Err("void can only be used as a return type".to_string()),
+                    })
+                    .collect::<Result<Vec<BasicMetadataTypeEnum<'ctx>>, String>>()?;
+                let fn_type = match self.ffi_type(&returns)? {
+                    Some(ty) => ty.fn_type(&param_types, false),
+                    None => self.context.void_type().fn_type(&param_types, false),
+                };
+                let function = self.module.get_function(&symbol)
+                    .unwrap_or_else(|| self.module.add_function(&symbol, fn_type, Some(Linkage::External)));
+
+                let pointer = function.as_global_value().as_pointer_value().as_basic_value_enum();
+                self.constants.insert(pointer, Constant::Foreign(function, params, returns));
+                Ok(pointer)
+            }
+            "call" => {
+                let Some(Constant::Foreign(function, params, returns)) = constant(0) else {
+                    return Err("FFILib[call] needs a function declared with FFILib[func] in compiled programs".to_string());
+                };
+                if args.len() - 1 != params.len() {
+                    return Err(format!("{} expects {} argument(s), got {}",
+                        function.get_name().to_string_lossy(), params.len(), args.len() - 1));
+                }
+
+                let mut call_args = Vec::with_capacity(params.len());
+                for (value, ty) in args[1..].iter().zip(&params) {
+                    call_args.push(self.ffi_argument(*value, ty)?);
+                }
+                let call = self.builder.build_call(function, &call_args, "ffi_call");
+
+                // Results are widened to the backend's i64 and f64 values
+                Ok(match call.try_as_basic_value().left() {
+                    Some(BasicValueEnum::IntValue(v)) if v.get_type().get_bit_width() < 64 => {
+                        let i64_type = self.context.i64_type();
+                        if matches!(returns.as_str(), "uint" | "ulong" | "size_t") {
+                            self.builder.build_int_z_extend(v, i64_type, "ffi_result").as_basic_value_enum()
+                        } else {
+                            self.builder.build_int_s_extend(v, i64_type, "ffi_result").as_basic_value_enum()
+                        }
+                    }
+                    Some(BasicValueEnum::FloatValue(v)) if returns == "float" => {
+                        self.builder.build_float_ext(v, self.context.f64_type(), "ffi_result").as_basic_value_enum()
+                    }
+                    Some(value) => value,
+                    None => self.context.i8_type().ptr_type(AddressSpace::default()).const_null().as_basic_value_enum(),
+                })
+            }
+            other => Err(format!("FFILib[{}] is not supported by the LLVM backend; only open, func and call are", other)),
+        }
+    }
+
+    // LLVM type for an FFILib type name; None for void
+    fn ffi_type(&self, name: &str) -> Result<Option<BasicTypeEnum<'ctx>>, String> {
+        let ctype = crate::functions::ffilib::CType::parse(name)?;
+        use crate::functions::ffilib::CType;
+        Ok(Some(match ctype {
+            CType::Void => return Ok(None),
+            CType::Char => self.context.i8_type().into(),
+            CType::Short => self.context.i16_type().into(),
+            CType::Int | CType::UInt => self.context.i32_type().into(),
+            CType::Long | CType::ULong => self.context.custom_width_int_type(8 * std::mem::size_of::<std::os::raw::c_long>() as u32).into(),
+            CType::Size => self.context.custom_width_int_type(usize::BITS).into(),
+            CType::Float => self.context.f32_type().into(),
+            CType::Double => self.context.f64_type().into(),
+            CType::Pointer | CType::String | CType::Buffer | CType::Callback => {
+                self.context.i8_type().ptr_type(AddressSpace::default()).into()
+            }
+        }))
+    }
+
+    // Convert a backend value to the declared C parameter type
+    fn ffi_argument(&self, value: BasicValueEnum<'ctx>, ty: &str) -> Result<BasicMetadataValueEnum<'ctx>, String> {
+        let target = self.ffi_type(ty)?.ok_or_else(|| "void can only be used as a return type".to_string())?;
+        let converted: BasicValueEnum<'ctx> = match (target, value) {
+            (BasicTypeEnum::IntType(t), BasicValueEnum::IntValue(v)) => {
+                let width = v.get_type().get_bit_width();
+                if width > t.get_bit_width() {
+                    self.builder.build_int_truncate(v, t, "ffi_arg").into()
+                } else if width < t.get_bit_width() {
+                    self.builder.build_int_s_extend(v, t, "ffi_arg").into()
+                } else {
+                    v.into()
+                }
+            }
+            (BasicTypeEnum::IntType(t), BasicValueEnum::FloatValue(v)) => self.builder.build_float_to_signed_int(v, t, "ffi_arg").into(),
+            (BasicTypeEnum::FloatType(t), BasicValueEnum::IntValue(v)) => self.builder.build_signed_int_to_float(v, t, "ffi_arg").into(),
+            (BasicTypeEnum::FloatType(t), BasicValueEnum::FloatValue(v)) => self.builder.build_float_cast(v, t, "ffi_arg").into(),
+            (BasicTypeEnum::PointerType(t), BasicValueEnum::PointerValue(v)) => self.builder.build_pointer_cast(v, t, "ffi_arg").into(),
+            (BasicTypeEnum::PointerType(t), BasicValueEnum::IntValue(v)) => self.builder.build_int_to_ptr(v, t, "ffi_arg").into(),
+            _ => return Err(format!("Cannot pass {:?} as a C {}", value, ty)),
+        };
+        Ok(self.basic_value_to_metadata(converted))
+    }
+
+    // String form of a value for the WebAssembly runtime, as show prints it
+    fn to_text(&self, value: BasicValueEnum<'ctx>) -> Result<PointerValue<'ctx>, String> {
+        let runtime = self.runtime.as_ref().ok_or_else(|| "Text conversion needs the WebAssembly runtime".to_string())?;
+        let convert = |function, argument: BasicMetadataValueEnum<'ctx>| {
+            self.builder.build_call(function, &[argument], "text").try_as_basic_value().left().unwrap().into_pointer_value()
+        };
+        Ok(match value {
+            BasicValueEnum::PointerValue(ptr) if ptr.is_null() => {
+                self.builder.build_global_string_ptr("null", ".str").as_pointer_value()
+            }
+            BasicValueEnum::PointerValue(ptr) => ptr,
+            BasicValueEnum::IntValue(v) if v.get_type().get_bit_width() == 1 => convert(runtime.bool_to_string, v.into()),
+            BasicValueEnum::IntValue(v) => {
+                let wide = self.builder.build_int_s_extend_or_bit_cast(v, self.context.i64_type(), "wide");
+                convert(runtime.int_to_string, wide.into())
+            }
+            BasicValueEnum::FloatValue(v) => convert(runtime.float_to_string, v.into()),
+            other => return Err(format!("Cannot convert {:?} to text", other)),
+        })
+    }
+
     // Helper to create an alloca in the entry block of a function
     fn create_entry_block_alloca(&self, name: &str, ty: BasicTypeEnum<'ctx>, function: FunctionValue<'ctx>) -> Result<PointerValue<'ctx>, String> {
         let temp_builder = self.context.create_builder();
@@ -424,4 +789,29 @@ impl<'ctx> LlvmCompiler<'ctx> {
     pub fn dump_module(&self) {
         self.module.print_to_stderr();
     }
-}
\ No newline at end of file
+}
+
+// wasm-ld arguments that turn an object file into a module; wasm32-unknown-unknown modules
+// have no _start, so the embedder calls the exported main instead
+pub fn wasm_link_args(target: CompileTarget, object: &Path, output: &Path) -> Vec<String> {
+    let mut args = Vec::new();
+    if target == CompileTarget::Wasm32 {
+        args.push("--no-entry".to_string());
+        args.push("--export=main".to_string());
+    }
+    args.push(object.display().to_string());
+    args.push("-o".to_string());
+    args.push(output.display().to_string());
+    args
+}
+
+// Run wasm-ld, or the linker named by RAZEN_WASM_LD; false when there is none to run
+pub fn link_wasm(args: &[String]) -> Result<bool, String> {
+    let linker = std::env::var("RAZEN_WASM_LD").unwrap_or_else(|_| "wasm-ld".to_string());
+    match std::process::Command::new(&linker).args(args).output() {
+        Ok(output) if output.status.success() => Ok(true),
+        Ok(output) => Err(format!("{} failed: {}", linker, String::from_utf8_lossy(&output.stderr).trim())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(format!("Could not run {}: {}", linker, e)),
+    }
+}
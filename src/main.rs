@@ -3,38 +3,67 @@ mod ast;
 mod lexer;
 mod parser;
 mod compiler;
+mod structs;
 mod syntax;
 mod value;
 mod functions;
 mod library;
 mod llvm;
+mod wasm_runtime;
+mod testing;
+mod snapshot;
+mod formatter;
+mod plugin;
+mod docgen;
+mod jsgen;
 
 use std::env;
-use std::path::Path;
-use std::process;
 use std::fs;
-use std::io;
-use std::time::Instant;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process;
 
-use crate::llvm::LlvmCompiler;
+use crate::llvm::{CompileTarget, LlvmCompiler};
 use crate::value::Value as RazenValue; // Assuming RazenValue is needed for return type
 use inkwell::context::Context;
 
 fn print_usage() {
     println!("Usage: razen <command> [args]\n");
     println!("Commands:");
-    println!("  compile <file>     Compile a Razen source file to machine code");
-    println!("  run <file>         Compile and execute a Razen source file");
+    println!("  compile <file> [output] Compile a Razen source file to machine code, WebAssembly or JavaScript");
+    println!("  run <file> [-- args] Compile and execute a Razen source file, passing args to the script");
     println!("  test [dir|file]    Run tests in the specified directory or file");
+    println!("  fmt [paths]        Format source files in place (stdin to stdout without paths)");
+    println!("  doc [libraries]    Show the functions of built-in libraries with their signatures and docs");
     println!("  help               Display this help message");
     println!("\nOptions:");
     println!("  --debug            Enable debug mode with additional output");
     println!("  --clean-output     Only show program output (no IR or debug info)");
+    println!("\nCompile options:");
+    println!("  --target=<target>  native (default), wasm32-unknown-unknown, wasm32-wasi, or js, which writes an ES module and razen_runtime.js");
+    println!("\nTest options:");
+    println!("  --filter=<text>    Only run tests whose name contains <text>");
+    println!("  --fail-fast        Stop after the first failing test");
+    println!("  --snapshot         Compare each program's output with its .expected file");
+    println!("  --update           With --snapshot, write .expected files from the current output");
+    println!("  --format=<format>  Report format: text, junit, tap or json");
+    println!("  --output=<file>    Write the report to <file> instead of stdout");
+    println!("\nFormat options:");
+    println!("  --check            List unformatted files and fail instead of rewriting them");
+    println!("\nDoc options:");
+    println!("  --format=<format>  Output format: text, markdown, html or json");
+    println!("  --output=<file>    Write the docs to <file> instead of stdout");
 }
 
 fn main() {
     let args: Vec<String> = env::args().collect();
     
+    // Everything after `--` belongs to the script being run
+    let (args, mut script_args) = match args.iter().position(|arg| arg == "--") {
+        Some(split) => (args[..split].to_vec(), args[split + 1..].to_vec()),
+        None => (args, Vec::new()),
+    };
+    
     // Initialize the library system
     library::initialize();
     
@@ -57,18 +86,46 @@ fn main() {
     
     match filtered_args[1].as_str() {
         "compile" => {
-            if filtered_args.len() < 3 {
+            let target = filtered_args.iter().find_map(|arg| arg.strip_prefix("--target=")).unwrap_or("native");
+            let positional: Vec<&String> = filtered_args[2..].iter().filter(|arg| !arg.starts_with("--target=")).collect();
+            if positional.is_empty() {
                 println!("Error: Missing source file path");
                 process::exit(1);
             }
             
-            let source_path_str = &filtered_args[2];
-            let output_path_str = if filtered_args.len() > 3 {
-                filtered_args[3].clone() // Clone to own the String for output_path_str
+            let source_path_str = positional[0];
+            
+            let compile_target = match target {
+                "js" => {
+                    let output_path = positional.get(1).map(PathBuf::from).unwrap_or_else(|| Path::new(source_path_str).with_extension("js"));
+                    match jsgen::compile_file(Path::new(source_path_str), &output_path) {
+                        Ok(()) => {
+                            println!("JavaScript module written to {}", output_path.display());
+                            println!("Runtime written to {}", output_path.with_file_name(jsgen::RUNTIME_FILE).display());
+                        }
+                        Err(e) => {
+                            println!("Error: {}", e);
+                            process::exit(1);
+                        }
+                    }
+                    return;
+                },
+                other => match CompileTarget::parse(other) {
+                    Some(compile_target) => compile_target,
+                    None => {
+                        println!("Error: Unknown target '{}'; expected native, js, wasm32-unknown-unknown or wasm32-wasi", other);
+                        process::exit(1);
+                    }
+                },
+            };
+            
+            let output_path_str = if positional.len() > 1 {
+                positional[1].clone() // Clone to own the String for output_path_str
             } else {
                 let source_path_obj = Path::new(source_path_str);
                 let stem = source_path_obj.file_stem().unwrap_or_default().to_str().unwrap_or("output");
-                format!("{}.o", stem) // Default to .o for object file
+                let extension = if compile_target.is_wasm() { "wasm" } else { "o" };
+                format!("{}.{}", stem, extension) // Default to .o for object file
             };
             
             println!("Compiling {} to LLVM IR and then to {}", source_path_str, output_path_str);
@@ -85,7 +142,14 @@ fn main() {
                     // 2. Initialize LLVM Context and our LlvmCompiler
                     let context = Context::create();
                     let module_name = Path::new(source_path_str).file_stem().unwrap_or_default().to_str().unwrap_or("razen_module");
-                    let mut llvm_compiler = LlvmCompiler::new(&context, module_name, !debug_mode); // Enable optimizations if not in debug mode
+                    // Enable optimizations if not in debug mode
+                    let mut llvm_compiler = match LlvmCompiler::with_target(&context, module_name, !debug_mode, compile_target) {
+                        Ok(llvm_compiler) => llvm_compiler,
+                        Err(e) => {
+                            println!("LLVM Compilation Error: {}", e);
+                            process::exit(1);
+                        }
+                    };
 
                     // 3. Compile Razen IR to LLVM IR (into a 'main' function)
                     match llvm_compiler.compile_function("main", vec![], RazenValue::Int(0), &razen_ir_sequence) {
@@ -95,11 +159,35 @@ fn main() {
                                 llvm_compiler.dump_module(); // Print LLVM IR to stderr
                             }
 
+                            if compile_target.is_wasm() {
+                                let output_path = Path::new(&output_path_str);
+                                let object_path = output_path.with_extension("o");
+                                if let Err(e) = llvm_compiler.write_object(&object_path) {
+                                    println!("Error: {}", e);
+                                    process::exit(1);
+                                }
+                                println!("WebAssembly object written to {}", object_path.display());
+
+                                let link_args = llvm::wasm_link_args(compile_target, &object_path, output_path);
+                                match llvm::link_wasm(&link_args) {
+                                    Ok(true) => println!("WebAssembly module written to {}", output_path.display()),
+                                    Ok(false) => println!("No wasm-ld found; link with: wasm-ld {}", link_args.join(" ")),
+                                    Err(e) => {
+                                        println!("Error: {}", e);
+                                        process::exit(1);
+                                    }
+                                }
+                                return;
+                            }
+
                             // 4. Emit LLVM IR to a .ll file (placeholder for object file emission)
                             let ll_path = format!("{}.ll", Path::new(&output_path_str).file_stem().unwrap_or_default().to_str().unwrap_or("output"));
                             match llvm_compiler.module.print_to_file(&Path::new(&ll_path)) {
                                 Ok(_) => {
                                     println!("LLVM IR written to {}", ll_path);
+                                    if !llvm_compiler.linked_libraries.is_empty() {
+                                        println!("Link against: {}", llvm_compiler.linked_libraries.join(", "));
+                                    }
                                     println!("Compilation (to LLVM IR) successful!");
                                     println!("Next steps: Implement object file emission in llvm.rs and linking.");
                                 }
@@ -129,6 +217,10 @@ fn main() {
             
             let source_path = &filtered_args[2];
             
+            // Arguments after the path are passed to the script too, so shebang scripts get theirs
+            script_args.splice(0..0, filtered_args[3..].iter().cloned());
+            functions::syscalllib::set_script_args(script_args);
+            
             if !clean_output {
                 println!("Running {}", source_path);
                 
@@ -140,11 +232,17 @@ fn main() {
             match compiler::Compiler::from_file(source_path) {
                 Ok(compiler) => {
                     match compiler.execute() {
-                        Ok(_) => {
+                        Ok(0) => {
                             if !clean_output {
                                 println!("Execution completed successfully!");
                             }
                         },
+                        Ok(status) => {
+                            if !clean_output {
+                                println!("Program exited with status {}", status);
+                            }
+                            process::exit(status);
+                        },
                         Err(e) => {
                             println!("Execution error: {}", e);
                             process::exit(1);
@@ -158,140 +256,230 @@ fn main() {
             }
         },
         "test" => {
-            println!("Running tests");
+            let mut options = testing::TestOptions {
+                debug_mode,
+                ..Default::default()
+            };
+            let mut format = testing::ReportFormat::Text;
+            let mut output_path = None;
+            let mut test_path = None;
+            for arg in &filtered_args[2..] {
+                if arg == "--fail-fast" {
+                    options.fail_fast = true;
+                } else if arg == "--snapshot" {
+                    options.snapshot = true;
+                } else if arg == "--update" {
+                    options.update = true;
+                } else if let Some(filter) = arg.strip_prefix("--filter=") {
+                    options.filter = Some(filter.to_string());
+                } else if let Some(name) = arg.strip_prefix("--format=") {
+                    format = testing::ReportFormat::parse(name).unwrap_or_else(|e| {
+                        println!("Error: {}", e);
+                        process::exit(1);
+                    });
+                } else if let Some(path) = arg.strip_prefix("--output=") {
+                    output_path = Some(path.to_string());
+                } else if arg.starts_with("--") {
+                    println!("Error: Unknown test option '{}'", arg);
+                    process::exit(1);
+                } else if test_path.is_none() {
+                    test_path = Some(arg.as_str());
+                }
+            }
             
-            if debug_mode {
-                println!("Debug mode enabled");
+            if options.update && !options.snapshot {
+                println!("Error: --update can only be used with --snapshot");
+                process::exit(1);
             }
             
-            let test_path = if filtered_args.len() > 2 {
-                &filtered_args[2]
-            } else {
-                // Default to razen_tests directory
-                "razen_tests"
-            };
+            // A structured report written to stdout must not be mixed with progress output
+            options.quiet = format != testing::ReportFormat::Text && output_path.is_none();
+            
+            if !options.quiet {
+                println!("Running tests");
+                
+                if debug_mode {
+                    println!("Debug mode enabled");
+                }
+            }
             
-            println!("Test path: {}", test_path);
+            // Default to razen_tests directory
+            let test_path = test_path.unwrap_or("razen_tests");
+            
+            if !options.quiet {
+                println!("Test path: {}", test_path);
+            }
             
             // Check if path is a directory or file
             let path = Path::new(test_path);
             if path.is_dir() {
-                // Run all tests in directory
-                println!("Running all tests in directory: {}", test_path);
-                run_tests_in_directory(path, debug_mode);
+                if !options.quiet {
+                    println!("Running all tests in directory: {}", test_path);
+                }
             } else if path.is_file() {
-                // Run single test file
-                println!("Running test file: {}", test_path);
-                run_test_file(path, debug_mode);
+                if !options.quiet {
+                    println!("Running test file: {}", test_path);
+                }
             } else {
-                println!("Error: Test path '{}' does not exist", test_path);
+                eprintln!("Error: Test path '{}' does not exist", test_path);
                 process::exit(1);
             }
-        },
-        "help" | "-h" | "--help" => {
-            print_usage();
-        },
-        _ => {
-            println!("Unknown command: {}", filtered_args[1]);
-            print_usage();
-            process::exit(1);
-        }
-    }
-}
-
-// Run all tests in a directory
-fn run_tests_in_directory(dir_path: &Path, debug_mode: bool) -> io::Result<()> {
-    let mut passed = 0;
-    let mut failed = 0;
-    let mut total_time = 0.0;
-    
-    // Collect all .rzn files in the directory
-    let entries = fs::read_dir(dir_path)?;
-    let mut test_files = Vec::new();
-    
-    for entry in entries {
-        let entry = entry?;
-        let path = entry.path();
-        
-        if path.is_file() && path.extension().map_or(false, |ext| ext == "rzn") {
-            test_files.push(path);
-        } else if path.is_dir() {
-            // Recursively run tests in subdirectories
-            if debug_mode {
-                println!("Entering directory: {}", path.display());
-            }
-            run_tests_in_directory(&path, debug_mode)?;
-        }
-    }
-    
-    // Sort test files for consistent output
-    test_files.sort();
-    
-    println!("Found {} test files in {}", test_files.len(), dir_path.display());
-    
-    for test_file in test_files {
-        let (success, duration) = run_test_file(&test_file, debug_mode);
-        
-        if success {
-            passed += 1;
-        } else {
-            failed += 1;
-        }
-        
-        total_time += duration;
-    }
-    
-    println!("\nTest Summary for {}:", dir_path.display());
-    println!("  Passed: {}", passed);
-    println!("  Failed: {}", failed);
-    println!("  Total: {}", passed + failed);
-    println!("  Time: {:.2}s", total_time);
-    
-    Ok(())
-}
-
-// Run a single test file
-fn run_test_file(file_path: &Path, debug_mode: bool) -> (bool, f64) {
-    let start = Instant::now();
-    let file_name = file_path.file_name().unwrap_or_default().to_string_lossy();
-    
-    print!("Testing {}... ", file_name);
-    
-    if debug_mode {
-        println!();
-    }
-    
-    match compiler::Compiler::from_file(file_path.to_str().unwrap()) {
-        Ok(compiler) => {
-            match compiler.execute() {
-                Ok(_) => {
-                    let duration = start.elapsed().as_secs_f64();
-                    if !debug_mode {
-                        println!("PASS ({:.2}s)", duration);
-                    } else {
-                        println!("\nPASS ({:.2}s)", duration);
+            
+            match testing::run_tests(path, &options) {
+                Ok(summary) => {
+                    if !options.quiet {
+                        testing::print_summary(&summary, path);
+                    }
+                    
+                    // The text report is the summary printed above, which --output saves too
+                    let written = match &output_path {
+                        Some(output_path) => fs::File::create(output_path).and_then(|mut file| match format {
+                            testing::ReportFormat::Text => testing::write_summary(&summary, path, &mut file),
+                            _ => testing::write_report(&summary, format, &mut file),
+                        }),
+                        None => testing::write_report(&summary, format, &mut io::stdout()),
+                    };
+                    if let Err(e) = written {
+                        eprintln!("Error: Failed to write test report: {}", e);
+                        process::exit(1);
+                    }
+                    if let Some(output_path) = &output_path {
+                        println!("Test report written to {}", output_path);
+                    }
+                    
+                    if summary.failed() > 0 {
+                        process::exit(1);
                     }
-                    (true, duration)
                 },
                 Err(e) => {
-                    let duration = start.elapsed().as_secs_f64();
-                    if !debug_mode {
-                        println!("FAIL ({:.2}s)", duration);
-                    } else {
-                        println!("\nFAIL ({:.2}s): {}", duration, e);
+                    println!("Error: Failed to read tests from '{}': {}", test_path, e);
+                    process::exit(1);
+                }
+            }
+        },
+        "fmt" => {
+            let check = filtered_args[2..].iter().any(|arg| arg == "--check");
+            let mut paths = Vec::new();
+            for arg in &filtered_args[2..] {
+                if arg.starts_with("--") && arg != "--check" {
+                    eprintln!("Error: Unknown fmt option '{}'", arg);
+                    process::exit(1);
+                } else if arg != "--check" {
+                    paths.push(arg.as_str());
+                }
+            }
+            
+            // Without paths (or with "-") format stdin to stdout for editor integrations
+            if paths.is_empty() || paths == ["-"] {
+                let mut source = String::new();
+                if let Err(e) = io::stdin().read_to_string(&mut source) {
+                    eprintln!("Error: Failed to read stdin: {}", e);
+                    process::exit(1);
+                }
+                match formatter::format_source(&source) {
+                    Ok(formatted) => {
+                        if check {
+                            if formatted != source {
+                                eprintln!("<stdin> is not formatted");
+                                process::exit(1);
+                            }
+                        } else {
+                            print!("{}", formatted);
+                        }
+                    },
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        process::exit(1);
                     }
-                    (false, duration)
                 }
+                return;
+            }
+            
+            let mut failed = false;
+            let mut unformatted = 0;
+            for path in paths {
+                let files = match formatter::collect_files(Path::new(path)) {
+                    Ok(files) => files,
+                    Err(e) => {
+                        eprintln!("Error: Failed to read '{}': {}", path, e);
+                        failed = true;
+                        continue;
+                    }
+                };
+                for file in files {
+                    match formatter::format_file(&file, check) {
+                        Ok(true) => {
+                            unformatted += 1;
+                            if check {
+                                println!("{}", file.display());
+                            } else {
+                                println!("Formatted {}", file.display());
+                            }
+                        },
+                        Ok(false) => {},
+                        Err(e) => {
+                            eprintln!("Error: {}: {}", file.display(), e);
+                            failed = true;
+                        }
+                    }
+                }
+            }
+            
+            if check && unformatted > 0 {
+                eprintln!("{} file(s) need formatting (run razen fmt to fix)", unformatted);
+            }
+            if failed || (check && unformatted > 0) {
+                process::exit(1);
             }
         },
-        Err(e) => {
-            let duration = start.elapsed().as_secs_f64();
-            if !debug_mode {
-                println!("FAIL ({:.2}s)", duration);
-            } else {
-                println!("\nFAIL ({:.2}s): {}", duration, e);
+        "doc" => {
+            let mut format = docgen::DocFormat::Text;
+            let mut output = None;
+            let mut names = Vec::new();
+            for arg in &filtered_args[2..] {
+                if let Some(value) = arg.strip_prefix("--format=") {
+                    format = match docgen::DocFormat::parse(value) {
+                        Ok(format) => format,
+                        Err(e) => {
+                            eprintln!("Error: {}", e);
+                            process::exit(1);
+                        }
+                    };
+                } else if let Some(value) = arg.strip_prefix("--output=") {
+                    output = Some(value.to_string());
+                } else if arg.starts_with("--") {
+                    eprintln!("Error: Unknown doc option '{}'", arg);
+                    process::exit(1);
+                } else {
+                    names.push(arg.clone());
+                }
             }
-            (false, duration)
+            
+            let libraries = match docgen::collect(&names) {
+                Ok(libraries) => libraries,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    process::exit(1);
+                }
+            };
+            let docs = docgen::render(&libraries, format, names.is_empty());
+            match output {
+                Some(path) => {
+                    if let Err(e) = fs::write(&path, docs) {
+                        eprintln!("Error: Failed to write '{}': {}", path, e);
+                        process::exit(1);
+                    }
+                },
+                None => print!("{}", docs),
+            }
+        },
+        "help" | "-h" | "--help" => {
+            print_usage();
+        },
+        _ => {
+            println!("Unknown command: {}", filtered_args[1]);
+            print_usage();
+            process::exit(1);
         }
     }
 }
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
 
 use crate::ast::{Program, Statement, Expression};
@@ -27,7 +27,10 @@ pub struct Parser {
     lexer: Lexer,
     current_token: Token,
     peek_token: Token,
+    previous_line: usize,    // Line of the token before current_token
+    preserve_trivia: bool,   // Keep comments and blank lines (used by the formatter)
     errors: Vec<String>,
+    struct_names: HashSet<String>, // Structs declared so far; `Name {` after one starts a constructor
     // Maps for prefix and infix parsing functions
     prefix_parse_fns: HashMap<TokenType, fn(&mut Parser) -> Option<Expression>>,
     infix_parse_fns: HashMap<TokenType, fn(&mut Parser, Expression) -> Option<Expression>>,
@@ -42,7 +45,10 @@ impl Parser {
             lexer,
             current_token,
             peek_token,
+            previous_line: 0,
+            preserve_trivia: false,
             errors: Vec::new(),
+            struct_names: HashSet::new(),
             prefix_parse_fns: HashMap::new(),
             infix_parse_fns: HashMap::new(),
         };
@@ -221,7 +227,13 @@ impl Parser {
         self.infix_parse_fns.insert(token_type, func);
     }
     
+    // Keep comments and blank lines in the AST instead of skipping them
+    pub fn set_preserve_trivia(&mut self, preserve: bool) {
+        self.preserve_trivia = preserve;
+    }
+    
     fn next_token(&mut self) {
+        self.previous_line = self.current_token.line;
         self.current_token = self.peek_token.clone();
         self.peek_token = self.lexer.next_token();
     }
@@ -263,6 +275,7 @@ impl Parser {
         let mut program = Program::new();
         
         while !self.current_token_is(TokenType::EOF) {
+            self.push_blank_line(&mut program.statements);
             if let Some(stmt) = self.parse_statement() {
                 program.statements.push(stmt);
             }
@@ -272,6 +285,13 @@ impl Parser {
         program
     }
     
+    // Record an empty line before the current statement when keeping trivia
+    fn push_blank_line(&self, statements: &mut Vec<Statement>) {
+        if self.preserve_trivia && !statements.is_empty() && self.current_token.line > self.previous_line + 1 {
+            statements.push(Statement::BlankLine);
+        }
+    }
+    
     fn parse_statement(&mut self) -> Option<Statement> {
         match self.current_token.token_type {
             // Variable declaration keywords
@@ -333,6 +353,7 @@ impl Parser {
             // Performance and Type Safety Keywords
             TokenType::Const => self.parse_const_declaration(),
             TokenType::Enum => self.parse_enum_declaration(),
+            TokenType::Struct => self.parse_struct_declaration(),
             TokenType::Inline => self.parse_inline_function_declaration(),
             TokenType::Volatile => self.parse_volatile_declaration(),
             
@@ -346,7 +367,27 @@ impl Parser {
             // Library Keywords
             TokenType::Lib => self.parse_lib_statement(),
             
+            // Test blocks: `test` is only a keyword when followed by the test name
+            TokenType::Identifier if self.current_token.literal == "test" && self.peek_token_is(TokenType::StringLiteral) => {
+                self.parse_test_declaration()
+            },
+            
+            // Static methods in classes: `static name(...) { }`, optionally prefixed with `override`
+            TokenType::Identifier if self.current_token.literal == "static" && is_word(&self.peek_token.literal) => {
+                self.parse_static_method(false)
+            },
+            TokenType::Identifier if self.current_token.literal == "override" && self.peek_token.literal == "static" => {
+                self.next_token();
+                self.parse_static_method(true)
+            },
+            
             TokenType::Comment => {
+                if self.preserve_trivia {
+                    return Some(Statement::Comment {
+                        text: self.current_token.literal.clone(),
+                        trailing: self.previous_line == self.current_token.line,
+                    });
+                }
                 // Skip comments and return None to continue parsing
                 None
             },
@@ -606,6 +647,39 @@ impl Parser {
         })
     }
     
+    // Parse a static method; the current token is `static`. Method names may be
+    // keywords such as `get` or `bool`, since they are only used as `Class[name]`.
+    fn parse_static_method(&mut self, override_native: bool) -> Option<Statement> {
+        self.next_token();
+        let name = self.current_token.literal.clone();
+        if !is_word(&name) {
+            self.errors.push(format!(
+                "Expected method name after 'static' at line {}, column {}",
+                self.current_token.line, self.current_token.column
+            ));
+            return None;
+        }
+        
+        if !self.expect_peek(TokenType::LeftParen) {
+            return None;
+        }
+        
+        let parameters = self.parse_function_parameters();
+        
+        if !self.expect_peek(TokenType::LeftBrace) {
+            return None;
+        }
+        
+        let body = self.parse_block_statement();
+        
+        Some(Statement::StaticMethodDeclaration {
+            name,
+            parameters,
+            body,
+            override_native,
+        })
+    }
+    
     fn parse_function_parameters(&mut self) -> Vec<String> {
         let mut parameters = Vec::new();
         
@@ -669,6 +743,7 @@ impl Parser {
         
         // Parse statements until we reach the closing brace or EOF
         while !self.current_token_is(TokenType::RightBrace) && !self.current_token_is(TokenType::EOF) {
+            self.push_blank_line(&mut statements);
             if let Some(stmt) = self.parse_statement() {
                 statements.push(stmt);
             }
@@ -919,6 +994,10 @@ impl Parser {
         // Parse the map literal that contains grammar properties
         let expr = self.parse_expression(Precedence::Lowest)?;
         
+        if self.preserve_trivia {
+            return self.parse_source_declaration("grammar", name, expr);
+        }
+        
         // Extract properties from the map literal
         let properties = match expr {
             Expression::MapLiteral { pairs } => {
@@ -947,6 +1026,20 @@ impl Parser {
         })
     }
     
+    // Keep a compiler construction statement's value as written, including
+    // entries the typed statement ignores, so the formatter can print it back
+    fn parse_source_declaration(&mut self, keyword: &str, name: String, value: Expression) -> Option<Statement> {
+        if self.peek_token_is(TokenType::Semicolon) {
+            self.next_token();
+        }
+        
+        Some(Statement::SourceDeclaration {
+            keyword: keyword.to_string(),
+            name,
+            value,
+        })
+    }
+    
     fn parse_token_statement(&mut self) -> Option<Statement> {
         // Expect identifier after 'token' keyword
         if !self.expect_peek(TokenType::Identifier) {
@@ -963,7 +1056,13 @@ impl Parser {
         self.next_token();
         
         // Parse the string literal that contains the token pattern
-        let pattern = match self.parse_expression(Precedence::Lowest)? {
+        let expr = self.parse_expression(Precedence::Lowest)?;
+        
+        if self.preserve_trivia {
+            return self.parse_source_declaration("token", name, expr);
+        }
+        
+        let pattern = match expr {
             Expression::StringLiteral(pattern) => pattern,
             _ => String::new(),
         };
@@ -997,6 +1096,10 @@ impl Parser {
         // Parse the map literal that contains lexer configuration
         let expr = self.parse_expression(Precedence::Lowest)?;
         
+        if self.preserve_trivia {
+            return self.parse_source_declaration("lexer", name, expr);
+        }
+        
         // Extract configuration from the map literal
         let config = match expr {
             Expression::MapLiteral { pairs } => {
@@ -1043,6 +1146,10 @@ impl Parser {
         // Parse the map literal that contains parser configuration
         let expr = self.parse_expression(Precedence::Lowest)?;
         
+        if self.preserve_trivia {
+            return self.parse_source_declaration("parser", name, expr);
+        }
+        
         // Extract configuration from the map literal
         let config = match expr {
             Expression::MapLiteral { pairs } => {
@@ -1094,6 +1201,10 @@ impl Parser {
         // Parse the map literal that contains node properties
         let expr = self.parse_expression(Precedence::Lowest)?;
         
+        if self.preserve_trivia {
+            return self.parse_source_declaration("node", name, expr);
+        }
+        
         // Extract properties from the map literal
         let properties = match expr {
             Expression::MapLiteral { pairs } => {
@@ -1140,6 +1251,10 @@ impl Parser {
         // Parse the map literal or string literal for the rule
         let expr = self.parse_expression(Precedence::Lowest)?;
         
+        if self.preserve_trivia {
+            return self.parse_source_declaration("rule", name, expr);
+        }
+        
         let (production, node_type) = match expr {
             Expression::MapLiteral { pairs } => {
                 // Extract production and node_type from the map
@@ -1203,6 +1318,10 @@ impl Parser {
         // Parse the map literal that contains visitor configuration
         let expr = self.parse_expression(Precedence::Lowest)?;
         
+        if self.preserve_trivia {
+            return self.parse_source_declaration("visitor", name, expr);
+        }
+        
         // Extract methods from the map literal
         let methods = match expr {
             Expression::MapLiteral { pairs } => {
@@ -1256,6 +1375,10 @@ impl Parser {
         // Parse the map literal that contains symbol configuration
         let expr = self.parse_expression(Precedence::Lowest)?;
         
+        if self.preserve_trivia {
+            return self.parse_source_declaration("symbol", name, expr);
+        }
+        
         // Extract attributes from the map literal
         let attributes = match expr {
             Expression::MapLiteral { pairs } => {
@@ -1309,6 +1432,10 @@ impl Parser {
         // Parse the map literal that contains scope configuration
         let expr = self.parse_expression(Precedence::Lowest)?;
         
+        if self.preserve_trivia {
+            return self.parse_source_declaration("scope", name, expr);
+        }
+        
         // Extract parent from the map literal
         let parent = match expr {
             Expression::MapLiteral { pairs } => {
@@ -1365,6 +1492,10 @@ impl Parser {
         // Parse the map literal that contains type configuration
         let expr = self.parse_expression(Precedence::Lowest)?;
         
+        if self.preserve_trivia {
+            return self.parse_source_declaration("typesys", name, expr);
+        }
+        
         // Extract operations from the map literal
         let operations = match expr {
             Expression::MapLiteral { pairs } => {
@@ -1418,6 +1549,10 @@ impl Parser {
         // Parse the map literal that contains IR configuration
         let expr = self.parse_expression(Precedence::Lowest)?;
         
+        if self.preserve_trivia {
+            return self.parse_source_declaration("ir", name, expr);
+        }
+        
         // Extract opcode and operands from the map literal
         let (opcode, operands) = match expr {
             Expression::MapLiteral { pairs } => {
@@ -1481,6 +1616,10 @@ impl Parser {
         // Parse the map literal that contains codegen configuration
         let expr = self.parse_expression(Precedence::Lowest)?;
         
+        if self.preserve_trivia {
+            return self.parse_source_declaration("codegen", name, expr);
+        }
+        
         // Extract target and instructions from the map literal
         let (target, instructions) = match expr {
             Expression::MapLiteral { pairs } => {
@@ -1538,6 +1677,10 @@ impl Parser {
         // Parse the map literal that contains optimize configuration
         let expr = self.parse_expression(Precedence::Lowest)?;
         
+        if self.preserve_trivia {
+            return self.parse_source_declaration("optimize", name, expr);
+        }
+        
         // Extract description and passes from the map literal
         let (description, passes) = match expr {
             Expression::MapLiteral { pairs } => {
@@ -1601,6 +1744,10 @@ impl Parser {
         // Parse the map literal that contains target configuration
         let expr = self.parse_expression(Precedence::Lowest)?;
         
+        if self.preserve_trivia {
+            return self.parse_source_declaration("target", name, expr);
+        }
+        
         // Extract properties from the map literal
         let properties = match expr {
             Expression::MapLiteral { pairs } => {
@@ -1647,6 +1794,10 @@ impl Parser {
         // Parse the map literal that contains attribute values
         let expr = self.parse_expression(Precedence::Lowest)?;
         
+        if self.preserve_trivia {
+            return self.parse_source_declaration("attribute", name, expr);
+        }
+        
         // Extract values from the map literal
         let values = match expr {
             Expression::MapLiteral { pairs } => {
@@ -1690,11 +1841,29 @@ impl Parser {
     }
     
     fn parse_exit_statement(&mut self) -> Option<Statement> {
+        // Optional status code: exit(1);
+        let code = if self.peek_token_is(TokenType::LeftParen) {
+            self.next_token();
+            if self.peek_token_is(TokenType::RightParen) {
+                self.next_token();
+                None
+            } else {
+                self.next_token();
+                let code = self.parse_expression(Precedence::Lowest)?;
+                if !self.expect_peek(TokenType::RightParen) {
+                    return None;
+                }
+                Some(code)
+            }
+        } else {
+            None
+        };
+        
         if self.peek_token_is(TokenType::Semicolon) {
             self.next_token();
         }
         
-        Some(Statement::ExitStatement)
+        Some(Statement::ExitStatement { code })
     }
     
     /// Parse document type declaration (type web; type script; type cli;)
@@ -1841,9 +2010,6 @@ impl Parser {
     
     /// Parse assert statement (assert(condition, message?);)
     fn parse_assert_statement(&mut self) -> Option<Statement> {
-        // Skip 'assert' token
-        self.next_token();
-        
         // Expect left parenthesis
         if !self.expect_peek(TokenType::LeftParen) {
             return None;
@@ -1883,6 +2049,21 @@ impl Parser {
         Some(Statement::AssertStatement { condition, message })
     }
     
+    /// Parse test block (test "name" { ... })
+    fn parse_test_declaration(&mut self) -> Option<Statement> {
+        // Move to the test name
+        self.next_token();
+        let name = self.current_token.literal.clone();
+        
+        if !self.expect_peek(TokenType::LeftBrace) {
+            return None;
+        }
+        
+        let body = self.parse_block_statement();
+        
+        Some(Statement::TestDeclaration { name, body })
+    }
+    
     /// Parse trace statement (trace expression;)
     fn parse_trace_statement(&mut self) -> Option<Statement> {
         // Skip 'trace' token
@@ -1955,9 +2136,50 @@ impl Parser {
             },
             _ => self.current_token.literal.clone()
         };
+        if self.struct_names.contains(&identifier) && self.peek_token_is(TokenType::LeftBrace) {
+            return self.parse_struct_literal(identifier);
+        }
         Some(Expression::Identifier(identifier))
     }
     
+    // Parse a struct constructor (Name { field: value, ... })
+    fn parse_struct_literal(&mut self, name: String) -> Option<Expression> {
+        // Skip to '{' token
+        self.next_token();
+        let mut fields = Vec::new();
+        
+        while !self.peek_token_is(TokenType::RightBrace) {
+            self.next_token();
+            
+            if !is_word(&self.current_token.literal) {
+                self.errors.push(format!(
+                    "Expected field name in {} constructor at line {}, column {}",
+                    name, self.current_token.line, self.current_token.column
+                ));
+                return None;
+            }
+            let field = self.current_token.literal.clone();
+            
+            if !self.expect_peek(TokenType::Colon) {
+                return None;
+            }
+            self.next_token();
+            
+            let value = self.parse_expression(Precedence::Lowest)?;
+            fields.push((field, value));
+            
+            if !self.peek_token_is(TokenType::RightBrace) && !self.expect_peek(TokenType::Comma) {
+                return None;
+            }
+        }
+        
+        if !self.expect_peek(TokenType::RightBrace) {
+            return None;
+        }
+        
+        Some(Expression::StructLiteral { name, fields })
+    }
+    
     fn parse_string_literal(&mut self) -> Option<Expression> {
         Some(Expression::StringLiteral(self.current_token.literal.clone()))
     }
@@ -2089,7 +2311,11 @@ impl Parser {
         
         // Handle function name inside brackets differently
         // For MemoryLib[addressof], the function name needs to be treated as an identifier
-        let index = if self.current_token_is(TokenType::Identifier) {
+        // Keywords without an expression form (Convert[str], Convert[bool]) name static methods too
+        let keyword_name = !self.prefix_parse_fns.contains_key(&self.current_token.token_type)
+            && is_word(&self.current_token.literal)
+            && self.peek_token_is(TokenType::RightBracket);
+        let index = if self.current_token_is(TokenType::Identifier) || keyword_name {
             // If the current token is an identifier, create an identifier expression
             Expression::Identifier(self.current_token.literal.clone())
         } else {
@@ -2133,34 +2359,13 @@ impl Parser {
     fn parse_dot_expression(&mut self, left: Expression) -> Option<Expression> {
         self.next_token(); // Skip '.' token
         
-        if !self.current_token_is(TokenType::Identifier) {
-            // Handle library tokens as identifiers
-            let is_library_token = match self.current_token.token_type {
-                TokenType::Random | TokenType::HTLib | TokenType::Coin | TokenType::MathLib | 
-                TokenType::Ping | TokenType::Bolt | TokenType::Seed | TokenType::NetLib | 
-                TokenType::File | TokenType::Json | TokenType::Date | TokenType::StrLib | 
-                TokenType::ArrLib | TokenType::Os | TokenType::Regex | TokenType::Crypto | 
-                TokenType::Color | TokenType::System | TokenType::Ui | TokenType::Storage | 
-                TokenType::Audio | TokenType::Image | TokenType::Validation | 
-                TokenType::LogLib | TokenType::Uuid | TokenType::Get | TokenType::Post |
-                TokenType::Read | TokenType::Debug | TokenType::Assert | TokenType::Trace |
-                TokenType::Show | TokenType::Exit | TokenType::Api | TokenType::Call |
-                TokenType::Connect | TokenType::To | TokenType::Import | TokenType::Export |
-                TokenType::From | TokenType::As |
-                // Self-compilation library tokens
-                TokenType::MemoryLib | TokenType::BinaryLib | TokenType::BitwiseLib | 
-                TokenType::SystemLib | TokenType::ProcessLib | TokenType::ThreadLib | 
-                TokenType::CompilerLib => true,
-                _ => false
-            };
-            
-            if !is_library_token {
-                self.errors.push(format!(
-                    "Expected identifier after '.', got {:?} at line {}, column {}",
-                    self.current_token.token_type, self.current_token.line, self.current_token.column
-                ));
-                return None;
-            }
+        // Any word names a property, so keywords work too (el.value, classList.remove)
+        if !is_word(&self.current_token.literal) {
+            self.errors.push(format!(
+                "Expected identifier after '.', got {:?} at line {}, column {}",
+                self.current_token.token_type, self.current_token.line, self.current_token.column
+            ));
+            return None;
         }
         
         // Get the property name
@@ -2697,6 +2902,15 @@ impl Parser {
         // Move to the next token
         self.next_token();
         
+        // A string names a native plugin: lib "path/to/plugin.so";
+        if self.current_token_is(TokenType::StringLiteral) {
+            let path = self.current_token.literal.clone();
+            if self.peek_token_is(TokenType::Semicolon) {
+                self.next_token();
+            }
+            return Some(Statement::NativeLibStatement { path });
+        }
+        
         // Check if the next token is a valid library name (either an identifier or a library token)
         let is_valid_library = match self.current_token.token_type {
             TokenType::Identifier | 
@@ -2877,13 +3091,11 @@ impl Parser {
         }
         
         // TODO: Implement pattern matching statements
-        // For now, we'll just parse a block and return it directly
-        let block = self.parse_block_statement();
+        // For now, we'll just parse a block and keep the value for the formatter
+        let body = self.parse_block_statement();
         
-        // Return a simple block statement until pattern matching is implemented
-        Some(Statement::BlockStatement {
-            statements: block,
-        })
+        // Runs like a plain block until pattern matching is implemented
+        Some(Statement::WhenStatement { value, body })
     }
     
     fn parse_else_statement(&mut self) -> Option<Statement> {
@@ -3002,6 +3214,76 @@ impl Parser {
         })
     }
     
+    // Parse struct declaration (struct NAME { field: type, ... })
+    fn parse_struct_declaration(&mut self) -> Option<Statement> {
+        // Skip 'struct' token
+        self.next_token();
+        
+        // Expect identifier (struct name)
+        if !self.current_token_is(TokenType::Identifier) {
+            self.errors.push(format!(
+                "Expected identifier after 'struct' keyword at line {}, column {}",
+                self.current_token.line, self.current_token.column
+            ));
+            return None;
+        }
+        
+        let name = self.current_token.literal.clone();
+        
+        // Expect opening brace
+        if !self.expect_peek(TokenType::LeftBrace) {
+            return None;
+        }
+        
+        // Skip '{' token
+        self.next_token();
+        
+        // Parse fields; type names may be keywords such as str, num or map
+        let mut fields = Vec::new();
+        
+        while !self.current_token_is(TokenType::RightBrace) && !self.current_token_is(TokenType::EOF) {
+            if !is_word(&self.current_token.literal) {
+                self.errors.push(format!(
+                    "Expected field name in struct {} at line {}, column {}",
+                    name, self.current_token.line, self.current_token.column
+                ));
+                return None;
+            }
+            
+            let field_name = self.current_token.literal.clone();
+            
+            if !self.expect_peek(TokenType::Colon) {
+                return None;
+            }
+            self.next_token();
+            
+            if !is_word(&self.current_token.literal) {
+                self.errors.push(format!(
+                    "Expected type for field {}.{} at line {}, column {}",
+                    name, field_name, self.current_token.line, self.current_token.column
+                ));
+                return None;
+            }
+            
+            fields.push((field_name, self.current_token.literal.clone()));
+            
+            // Skip comma if present
+            if self.peek_token_is(TokenType::Comma) {
+                self.next_token();
+            }
+            
+            // Move to the next token
+            self.next_token();
+        }
+        
+        self.struct_names.insert(name.clone());
+        
+        Some(Statement::StructDeclaration {
+            name,
+            fields,
+        })
+    }
+    
     // Parse inline function declaration (inline fun name(params) { body })
     fn parse_inline_function_declaration(&mut self) -> Option<Statement> {
         // Skip 'inline' token
@@ -3152,6 +3434,11 @@ impl Parser {
     }
 }
 
+// Identifier-like text, including keywords
+fn is_word(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_alphabetic() || c == '_') && text.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -3223,4 +3510,4 @@ mod tests {
             _ => panic!("Expected FunctionDeclaration, got {:?}", program.statements[0]),
         }
     }
-}
\ No newline at end of file
+}
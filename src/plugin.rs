@@ -0,0 +1,308 @@
+// Native extension libraries loaded from shared objects.
+//
+// A plugin exports `razen_plugin_info`, which returns a static `RazenPluginInfo`
+// describing the plugin and its functions. Values cross the boundary as JSON
+// text so the ABI does not depend on the layout of `Value`. The matching
+// declarations for plugin authors live in razen-plugin/ (Rust helper crate and
+// C header).
+
+use std::collections::HashMap;
+use std::ffi::{c_char, CStr, CString};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::functions::jsonlib;
+use crate::library::{Library, Signature};
+use crate::value::Value;
+
+/// Version of the plugin ABI; plugins built against another version are rejected
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+/// Symbol every plugin must export
+const PLUGIN_ENTRY: &[u8] = b"razen_plugin_info";
+
+/// Called with the arguments as a JSON array. Writes a JSON value (status 0) or
+/// an error message (any other status) to `result`, to be released with `free`.
+pub type RazenPluginCall = extern "C" fn(args_json: *const c_char, result: *mut *mut c_char) -> i32;
+
+#[repr(C)]
+pub struct RazenPluginFunction {
+    pub signature: *const c_char,     // e.g. "greet(string, int?) -> string"
+    pub call: RazenPluginCall,
+}
+
+#[repr(C)]
+pub struct RazenPluginInfo {
+    pub abi_version: u32,
+    pub name: *const c_char,          // Library name used in scripts
+    pub version: *const c_char,       // Plugin version, for error messages
+    pub function_count: usize,
+    pub functions: *const RazenPluginFunction,
+    pub free: unsafe extern "C" fn(*mut c_char),
+}
+
+lazy_static::lazy_static! {
+    // Plugins already loaded, by canonical path, so a second `lib` is a no-op
+    static ref LOADED: Mutex<HashMap<PathBuf, String>> = Mutex::new(HashMap::new());
+}
+
+/// A function exported by a loaded plugin
+pub struct NativeFunction {
+    library: String,
+    signature: Signature,
+    call: RazenPluginCall,
+    free: unsafe extern "C" fn(*mut c_char),
+    // Keeps the shared object mapped while the function can still be called
+    _plugin: Arc<libloading::Library>,
+}
+
+impl NativeFunction {
+    pub fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    // Arguments were already checked against the signature by the library manager
+    pub fn call(&self, args: Vec<Value>) -> Result<Value, String> {
+        let json = jsonlib::razen_value_to_json(&Value::Array(args))?.to_string();
+        let json = CString::new(json).map_err(|_| "Plugin arguments cannot contain NUL bytes".to_string())?;
+
+        let mut result: *mut c_char = std::ptr::null_mut();
+        let status = (self.call)(json.as_ptr(), &mut result);
+        let text = if result.is_null() {
+            String::new()
+        } else {
+            // SAFETY: the plugin hands back a NUL-terminated string it allocated with its own free function
+            unsafe {
+                let text = CStr::from_ptr(result).to_string_lossy().into_owned();
+                (self.free)(result);
+                text
+            }
+        };
+
+        if status != 0 {
+            return Err(if text.is_empty() {
+                format!("{}.{} failed with status {}", self.library, self.signature.name, status)
+            } else {
+                text
+            });
+        }
+        if text.is_empty() {
+            return Ok(Value::Null);
+        }
+        jsonlib::parse(vec![Value::String(text)])
+            .map_err(|e| format!("{}.{} returned invalid JSON: {}", self.library, self.signature.name, e))
+    }
+}
+
+/// Names a plugin file may have for `lib name;`, e.g. libname.so and name.so on Linux
+fn plugin_file_names(name: &str) -> Vec<String> {
+    let suffix = std::env::consts::DLL_SUFFIX;
+    let mut names = vec![format!("{}{}", name, suffix)];
+    let prefix = std::env::consts::DLL_PREFIX;
+    if !prefix.is_empty() {
+        names.insert(0, format!("{}{}{}", prefix, name, suffix));
+    }
+    names
+}
+
+/// Look for a plugin called `name` in RAZEN_PLUGIN_PATH and then in `plugins/` next to the script
+pub fn find_plugin(name: &str, script_dir: Option<&Path>) -> Option<PathBuf> {
+    let mut dirs: Vec<PathBuf> = std::env::var_os("RAZEN_PLUGIN_PATH")
+        .map(|paths| std::env::split_paths(&paths).collect())
+        .unwrap_or_default();
+    if let Some(dir) = script_dir {
+        dirs.push(dir.join("plugins"));
+    }
+
+    dirs.iter()
+        .flat_map(|dir| plugin_file_names(name).into_iter().map(move |file| dir.join(file)))
+        .find(|path| path.is_file())
+}
+
+/// Whether a `lib` name refers to a file rather than a library name
+pub fn is_plugin_path(name: &str) -> bool {
+    name.contains('/') || name.contains('\\') || name.ends_with(std::env::consts::DLL_SUFFIX)
+}
+
+/// Load a plugin and return its library, or None if this file was loaded before
+pub fn load_plugin(path: &Path) -> Result<Option<Library>, String> {
+    let canonical = path.canonicalize()
+        .map_err(|e| format!("Cannot load plugin {}: {}", path.display(), e))?;
+    if LOADED.lock().unwrap().contains_key(&canonical) {
+        return Ok(None);
+    }
+
+    // SAFETY: loading runs the plugin's initialisers; plugins are trusted like the scripts that load them
+    let plugin = unsafe { libloading::Library::new(&canonical) }
+        .map_err(|e| format!("Cannot load plugin {}: {}", path.display(), e))?;
+    let plugin = Arc::new(plugin);
+
+    // SAFETY: the entry point's type is fixed by the plugin ABI
+    let entry: libloading::Symbol<extern "C" fn() -> *const RazenPluginInfo> = unsafe { plugin.get(PLUGIN_ENTRY) }
+        .map_err(|_| format!("{} is not a Razen plugin (missing razen_plugin_info)", path.display()))?;
+    let info = entry();
+    if info.is_null() {
+        return Err(format!("{} returned no plugin information", path.display()));
+    }
+    // SAFETY: the plugin returns a pointer to static data that lives as long as the library
+    let info = unsafe { &*info };
+
+    if info.abi_version != PLUGIN_ABI_VERSION {
+        return Err(format!(
+            "{} was built for plugin ABI version {}, but this Razen supports version {}",
+            path.display(), info.abi_version, PLUGIN_ABI_VERSION
+        ));
+    }
+
+    let name = plugin_string(info.name).ok_or_else(|| format!("{} has no library name", path.display()))?;
+    let version = plugin_string(info.version).unwrap_or_else(|| "unknown".to_string());
+    if crate::library::get_library_names().contains(&name.to_lowercase()) {
+        return Err(format!("Plugin {} {} from {} conflicts with an existing library", name, version, path.display()));
+    }
+
+    let mut library = Library::new(&name);
+    for index in 0..info.function_count {
+        // SAFETY: `functions` points to `function_count` entries
+        let function = unsafe { &*info.functions.add(index) };
+        let signature = plugin_string(function.signature)
+            .ok_or_else(|| format!("Plugin {} has a function without a signature", name))?;
+        let signature = Signature::parse(&signature)
+            .map_err(|e| format!("Plugin {} {}: {}", name, version, e))?;
+        let function = NativeFunction {
+            library: name.clone(),
+            signature,
+            call: function.call,
+            free: info.free,
+            _plugin: Arc::clone(&plugin),
+        };
+        library.register_native_function(Arc::new(function));
+    }
+
+    LOADED.lock().unwrap().insert(canonical, name);
+    Ok(Some(library))
+}
+
+fn plugin_string(ptr: *const c_char) -> Option<String> {
+    if ptr.is_null() {
+        return None;
+    }
+    // SAFETY: plugin strings are NUL-terminated static data
+    Some(unsafe { CStr::from_ptr(ptr) }.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    use crate::library::CallContext;
+
+    // Build the example plugin in razen-plugin/ and return the path of its shared object
+    fn build_greeter() -> PathBuf {
+        let crate_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("razen-plugin");
+        let target_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target").join("plugin-tests");
+        let status = Command::new(env!("CARGO"))
+            .args(["build", "--quiet", "--example", "greeter", "--manifest-path"])
+            .arg(crate_dir.join("Cargo.toml"))
+            .arg("--target-dir")
+            .arg(&target_dir)
+            .status()
+            .expect("failed to run cargo");
+        assert!(status.success(), "building the greeter example failed");
+        target_dir.join("debug").join("examples").join(&plugin_file_names("greeter")[0])
+    }
+
+    // Compile a single Rust file into a shared object in a temporary directory
+    fn compile_cdylib(name: &str, source: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("razen_plugin_test_{}_{}", std::process::id(), name));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source_path = dir.join(format!("{}.rs", name));
+        std::fs::write(&source_path, source).unwrap();
+        let output = dir.join(&plugin_file_names(name)[0]);
+        let status = Command::new(std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string()))
+            .args(["--crate-type", "cdylib", "--edition", "2021", "-o"])
+            .arg(&output)
+            .arg(&source_path)
+            .status()
+            .expect("failed to run rustc");
+        assert!(status.success(), "compiling {} failed", name);
+        output
+    }
+
+    #[test]
+    fn test_load_and_call_example_plugin() {
+        let path = build_greeter();
+        let library = load_plugin(&path).unwrap().expect("plugin loaded for the first time");
+        assert_eq!(library.name(), "greeter");
+        let mut names = library.function_names();
+        names.sort();
+        assert_eq!(names, vec!["greet", "sum"]);
+        assert!(load_plugin(&path).unwrap().is_none());
+
+        crate::library::register_library(library);
+        let context = CallContext::new("greeter");
+        let greet = crate::library::resolve("greeter", "greet").unwrap();
+        assert_eq!(greet.call(&context, vec![Value::String("Ada".to_string()), Value::Int(2)]).unwrap(),
+                   Value::String("Hello, Ada! Hello, Ada!".to_string()));
+        assert_eq!(greet.call(&context, vec![Value::String("Ada".to_string()), Value::Int(0)]).unwrap_err(),
+                   "Greeter.greet times must be at least 1");
+        let sum = crate::library::resolve("greeter", "sum").unwrap();
+        assert_eq!(sum.call(&context, vec![Value::Int(1), Value::Float(2.5)]).unwrap(), Value::Float(3.5));
+    }
+
+    #[test]
+    fn test_reject_plugin_for_another_abi_version() {
+        let path = compile_cdylib("future_abi", r#"
+            use std::ffi::c_char;
+
+            #[repr(C)]
+            pub struct Info {
+                abi_version: u32,
+                name: *const c_char,
+                version: *const c_char,
+                function_count: usize,
+                functions: *const u8,
+                free: unsafe extern "C" fn(*mut c_char),
+            }
+
+            struct Exported(Info);
+            unsafe impl Sync for Exported {}
+
+            unsafe extern "C" fn free(_: *mut c_char) {}
+
+            static INFO: Exported = Exported(Info {
+                abi_version: 2,
+                name: b"future\0".as_ptr() as *const c_char,
+                version: b"2.0.0\0".as_ptr() as *const c_char,
+                function_count: 0,
+                functions: std::ptr::null(),
+                free,
+            });
+
+            #[no_mangle]
+            pub extern "C" fn razen_plugin_info() -> *const Info {
+                &INFO.0
+            }
+        "#);
+        assert_eq!(load_plugin(&path).unwrap_err(), format!(
+            "{} was built for plugin ABI version 2, but this Razen supports version {}",
+            path.display(), PLUGIN_ABI_VERSION
+        ));
+    }
+
+    #[test]
+    fn test_reject_files_that_are_not_plugins() {
+        let path = compile_cdylib("not_a_plugin", r#"
+            #[no_mangle]
+            pub extern "C" fn answer() -> i32 {
+                42
+            }
+        "#);
+        assert_eq!(load_plugin(&path).unwrap_err(),
+                   format!("{} is not a Razen plugin (missing razen_plugin_info)", path.display()));
+
+        let text = path.with_file_name("notes.txt");
+        std::fs::write(&text, "not a shared object").unwrap();
+        assert!(load_plugin(&text).unwrap_err().starts_with(&format!("Cannot load plugin {}: ", text.display())));
+    }
+}
@@ -0,0 +1,237 @@
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::functions::processlib::read_pipe;
+
+// Programs that run longer than this are killed and reported as failures
+const SNAPSHOT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Changed lines shown in a snapshot diff before the rest is elided
+const MAX_DIFF_LINES: usize = 40;
+
+const STDOUT_MARKER: &str = "==== stdout ====\n";
+const STDERR_MARKER: &str = "\n==== stderr ====\n";
+
+/// Captured result of running a program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub exit_code: i32,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl Snapshot {
+    /// Render the snapshot in the `.expected` file format
+    pub fn render(&self) -> String {
+        format!("exit code: {}\n{}{}{}{}", self.exit_code, STDOUT_MARKER, self.stdout, STDERR_MARKER, self.stderr)
+    }
+
+    /// Parse the contents of an `.expected` file
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (header, rest) = text.split_once('\n')
+            .ok_or_else(|| "Snapshot file is missing the exit code line".to_string())?;
+        let exit_code = header.strip_prefix("exit code: ")
+            .and_then(|code| code.trim().parse::<i32>().ok())
+            .ok_or_else(|| format!("Invalid snapshot header: {}", header))?;
+        let rest = rest.strip_prefix(STDOUT_MARKER)
+            .ok_or_else(|| "Snapshot file is missing the stdout section".to_string())?;
+        let (stdout, stderr) = rest.rsplit_once(STDERR_MARKER)
+            .ok_or_else(|| "Snapshot file is missing the stderr section".to_string())?;
+
+        Ok(Snapshot {
+            exit_code,
+            stdout: stdout.to_string(),
+            stderr: stderr.to_string(),
+        })
+    }
+}
+
+/// Path of the `.expected` file for a program
+pub fn expected_path(file_path: &Path) -> PathBuf {
+    file_path.with_extension("expected")
+}
+
+/// Path of the `.stdin` file fed to a program
+pub fn stdin_path(file_path: &Path) -> PathBuf {
+    file_path.with_extension("stdin")
+}
+
+/// Run a program with `razen run` in a child process and capture its output
+pub fn capture(file_path: &Path) -> Result<Snapshot, String> {
+    let input = match fs::read(stdin_path(file_path)) {
+        Ok(input) => Some(input),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(format!("Failed to read {}: {}", stdin_path(file_path).display(), e)),
+    };
+
+    let razen = env::current_exe().map_err(|e| format!("Failed to locate the razen binary: {}", e))?;
+    let mut child = Command::new(razen)
+        .arg("run")
+        .arg(file_path)
+        .arg("--clean-output")
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to start {}: {}", file_path.display(), e))?;
+
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        // Write from a thread so a program that never reads cannot block us
+        thread::spawn(move || {
+            let _ = stdin.write_all(&input);
+        });
+    }
+
+    let stdout = read_pipe(child.stdout.take());
+    let stderr = read_pipe(child.stderr.take());
+
+    let exit_code = wait_with_timeout(&mut child, SNAPSHOT_TIMEOUT)?;
+
+    Ok(Snapshot {
+        exit_code,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+/// Compare a program against its `.expected` file, or write it when `update` is set.
+/// Returns the captured stdout and a failure message when the snapshot does not match.
+pub fn check(file_path: &Path, update: bool) -> (String, Option<String>) {
+    let actual = match capture(file_path) {
+        Ok(actual) => actual,
+        Err(e) => return (String::new(), Some(e)),
+    };
+
+    let expected_file = expected_path(file_path);
+    if update {
+        let failure = fs::write(&expected_file, actual.render()).err()
+            .map(|e| format!("Failed to write {}: {}", expected_file.display(), e));
+        return (actual.stdout, failure);
+    }
+
+    let expected = match fs::read_to_string(&expected_file) {
+        Ok(text) => match Snapshot::parse(&text) {
+            Ok(expected) => expected,
+            Err(e) => return (actual.stdout, Some(format!("{}: {}", expected_file.display(), e))),
+        },
+        Err(_) => {
+            return (actual.stdout, Some(format!(
+                "Missing snapshot {} (run with --update to create it)",
+                expected_file.display()
+            )));
+        }
+    };
+
+    let failure = compare(&expected, &actual);
+    (actual.stdout, failure)
+}
+
+// Describe every difference between two snapshots
+fn compare(expected: &Snapshot, actual: &Snapshot) -> Option<String> {
+    let mut problems = Vec::new();
+
+    if expected.exit_code != actual.exit_code {
+        problems.push(format!("exit code: expected {}, got {}", expected.exit_code, actual.exit_code));
+    }
+    if expected.stdout != actual.stdout {
+        problems.push(format!("stdout differs:\n{}", diff_lines(&expected.stdout, &actual.stdout)));
+    }
+    if expected.stderr != actual.stderr {
+        problems.push(format!("stderr differs:\n{}", diff_lines(&expected.stderr, &actual.stderr)));
+    }
+
+    if problems.is_empty() {
+        None
+    } else {
+        Some(format!("snapshot mismatch\n{}", problems.join("\n")))
+    }
+}
+
+// Line diff based on the longest common subsequence.
+// Removed lines are prefixed with '-', added lines with '+'.
+fn diff_lines(expected: &str, actual: &str) -> String {
+    let old: Vec<&str> = expected.lines().collect();
+    let new: Vec<&str> = actual.lines().collect();
+
+    // lcs[i][j] = length of the common subsequence of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut changes = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            changes.push(format!("- {}", old[i]));
+            i += 1;
+        } else {
+            changes.push(format!("+ {}", new[j]));
+            j += 1;
+        }
+    }
+
+    if changes.is_empty() {
+        // Only line endings differ
+        return "  (whitespace differences only)".to_string();
+    }
+
+    let hidden = changes.len().saturating_sub(MAX_DIFF_LINES);
+    let mut result: Vec<String> = changes.into_iter().take(MAX_DIFF_LINES).map(|line| format!("  {}", line)).collect();
+    if hidden > 0 {
+        result.push(format!("  ... {} more changed lines", hidden));
+    }
+    result.join("\n")
+}
+
+fn wait_with_timeout(child: &mut Child, timeout: Duration) -> Result<i32, String> {
+    let start = Instant::now();
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => return Ok(status.code().unwrap_or(-1)),
+            Ok(None) if start.elapsed() >= timeout => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("Timed out after {}s", timeout.as_secs()));
+            },
+            Ok(None) => thread::sleep(Duration::from_millis(10)),
+            Err(e) => return Err(format!("Failed to wait for program: {}", e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let snapshot = Snapshot {
+            exit_code: 1,
+            stdout: "hello\n==== stderr ====\nnot really\n".to_string(),
+            stderr: "Execution error: boom\n".to_string(),
+        };
+
+        assert_eq!(Snapshot::parse(&snapshot.render()), Ok(snapshot));
+    }
+
+    #[test]
+    fn test_diff_lines() {
+        let diff = diff_lines("a\nb\nc\n", "a\nx\nc\n");
+        assert_eq!(diff, "  - b\n  + x");
+    }
+}
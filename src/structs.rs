@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+
+use crate::ast::Expression;
+use crate::compiler::split_top_level;
+
+// Struct declarations: named records whose fields have a declared kind.
+//
+// The interpreter keeps values as text, so an instance is stored the way `show` prints it:
+// `Task { id: 1, text: "Write docs", done: false }`, with fields in declaration order.
+// Two instances are equal when their text is, which makes equality structural.
+
+/// Kind of value a struct field holds
+#[derive(Debug, Clone, PartialEq)]
+pub enum Kind {
+    Number,
+    String,
+    Bool,
+    Array,
+    Map,
+    Any,
+    Struct(String),
+}
+
+impl Kind {
+    /// Kind for a type name in a declaration; other names refer to structs
+    pub fn parse(name: &str) -> Kind {
+        match name {
+            "number" | "num" | "int" | "float" => Kind::Number,
+            "string" | "str" => Kind::String,
+            "bool" | "boolean" => Kind::Bool,
+            "array" | "list" => Kind::Array,
+            "map" => Kind::Map,
+            "any" => Kind::Any,
+            other => Kind::Struct(other.to_string()),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Kind::Number => "number",
+            Kind::String => "string",
+            Kind::Bool => "bool",
+            Kind::Array => "array",
+            Kind::Map => "map",
+            Kind::Any => "any",
+            Kind::Struct(name) => name,
+        }
+    }
+
+    /// Kind of an expression that can be told without running it
+    pub fn of_literal(expression: &Expression) -> Option<Kind> {
+        match expression {
+            Expression::NumberLiteral(_) => Some(Kind::Number),
+            Expression::StringLiteral(_) => Some(Kind::String),
+            Expression::BooleanLiteral(_) => Some(Kind::Bool),
+            Expression::ArrayLiteral { .. } => Some(Kind::Array),
+            Expression::MapLiteral { .. } => Some(Kind::Map),
+            Expression::StructLiteral { name, .. } => Some(Kind::Struct(name.clone())),
+            Expression::PrefixExpression { operator, right } if operator == "-" => {
+                Kind::of_literal(right).filter(|kind| *kind == Kind::Number)
+            },
+            Expression::PrefixExpression { operator, .. } if operator == "!" => Some(Kind::Bool),
+            _ => None,
+        }
+    }
+
+    // Whether an interpreter value can be stored in a field of this kind.
+    // Text can't be told apart from other values, so strings accept anything.
+    fn accepts(&self, value: &str) -> bool {
+        match self {
+            Kind::Number => value.parse::<f64>().is_ok(),
+            Kind::Bool => value == "true" || value == "false",
+            Kind::Array => value.starts_with('[') && value.ends_with(']'),
+            Kind::Map => value.starts_with('{') && value.ends_with('}'),
+            Kind::Struct(name) => parse(value).is_some_and(|(found, _)| found == name),
+            Kind::String | Kind::Any => true,
+        }
+    }
+
+    // Text of a value inside an instance; strings are quoted so commas in them are kept
+    fn encode(&self, value: &str) -> String {
+        let plain = match self {
+            Kind::String => false,
+            Kind::Any => value == "null" || [Kind::Number, Kind::Bool, Kind::Array, Kind::Map].iter().any(|kind| kind.accepts(value)) || parse(value).is_some(),
+            _ => true,
+        };
+        if plain {
+            value.to_string()
+        } else {
+            serde_json::to_string(value).unwrap()
+        }
+    }
+}
+
+/// A declared struct
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructType {
+    pub name: String,
+    pub fields: Vec<(String, Kind)>,
+}
+
+impl StructType {
+    /// Check a declaration against the structs declared before it
+    pub fn declare(name: &str, fields: &[(String, String)], known: &HashMap<String, StructType>) -> Result<StructType, String> {
+        let mut declared: Vec<(String, Kind)> = Vec::new();
+        for (field, type_name) in fields {
+            if declared.iter().any(|(existing, _)| existing == field) {
+                return Err(format!("Field '{}' is declared twice in struct {}", field, name));
+            }
+            let kind = Kind::parse(type_name);
+            if let Kind::Struct(other) = &kind {
+                if !known.contains_key(other) {
+                    return Err(format!("Unknown type '{}' for field {}.{}", other, name, field));
+                }
+            }
+            declared.push((field.clone(), kind));
+        }
+        Ok(StructType { name: name.to_string(), fields: declared })
+    }
+
+    /// Field values of a constructor in declaration order, checking names and the kinds `kind_of` knows
+    pub fn order<'a>(&self, fields: &'a [(String, Expression)], kind_of: impl Fn(&Expression) -> Option<Kind>) -> Result<Vec<&'a Expression>, String> {
+        for (index, (field, value)) in fields.iter().enumerate() {
+            let Some((_, kind)) = self.fields.iter().find(|(declared, _)| declared == field) else {
+                return Err(format!("{} has no field '{}'", self.name, field));
+            };
+            if fields[..index].iter().any(|(earlier, _)| earlier == field) {
+                return Err(format!("Field '{}' is given twice for {}", field, self.name));
+            }
+            if let Some(found) = kind_of(value) {
+                if *kind != Kind::Any && found != *kind {
+                    return Err(format!("Field {}.{} expects {}, got {}", self.name, field, kind.name(), found.name()));
+                }
+            }
+        }
+
+        self.fields.iter()
+            .map(|(field, _)| fields.iter()
+                .find(|(given, _)| given == field)
+                .map(|(_, value)| value)
+                .ok_or_else(|| format!("Missing field '{}' for {}", field, self.name)))
+            .collect()
+    }
+
+    /// Build an instance from interpreter values given in declaration order
+    pub fn create(&self, values: &[String]) -> Result<String, String> {
+        let mut fields = Vec::with_capacity(values.len());
+        for ((field, kind), value) in self.fields.iter().zip(values) {
+            self.check(field, kind, value)?;
+            fields.push(format!("{}: {}", field, kind.encode(value)));
+        }
+        Ok(format_instance(&self.name, &fields))
+    }
+
+    /// Read a field of an instance
+    pub fn get(&self, instance: &str, field: &str) -> Result<String, String> {
+        let (_, values) = parse(instance).ok_or_else(|| format!("Not a {} value: {}", self.name, instance))?;
+        let value = values.into_iter()
+            .find(|(name, _)| name == field)
+            .map(|(_, value)| value)
+            .ok_or_else(|| format!("{} has no field '{}'", self.name, field))?;
+        Ok(match value.starts_with('"') {
+            true => serde_json::from_str(&value).unwrap_or(value),
+            false => value,
+        })
+    }
+
+    /// An instance with one field changed
+    pub fn set(&self, instance: &str, field: &str, value: &str) -> Result<String, String> {
+        let (_, values) = parse(instance).ok_or_else(|| format!("Not a {} value: {}", self.name, instance))?;
+        let Some((_, kind)) = self.fields.iter().find(|(name, _)| name == field) else {
+            return Err(format!("{} has no field '{}'", self.name, field));
+        };
+        self.check(field, kind, value)?;
+        let fields: Vec<String> = values.into_iter()
+            .map(|(name, old)| match name == field {
+                true => format!("{}: {}", name, kind.encode(value)),
+                false => format!("{}: {}", name, old),
+            })
+            .collect();
+        Ok(format_instance(&self.name, &fields))
+    }
+
+    fn check(&self, field: &str, kind: &Kind, value: &str) -> Result<(), String> {
+        match kind.accepts(value) {
+            true => Ok(()),
+            false => Err(format!("Field {}.{} expects {}, got {}", self.name, field, kind.name(), value)),
+        }
+    }
+}
+
+fn format_instance(name: &str, fields: &[String]) -> String {
+    match fields.is_empty() {
+        true => format!("{} {{}}", name),
+        false => format!("{} {{ {} }}", name, fields.join(", ")),
+    }
+}
+
+/// Struct name of an interpreter value, if it is an instance
+pub fn type_name(value: &str) -> Option<&str> {
+    let (name, rest) = value.split_once(" {")?;
+    let is_name = name.starts_with(|c: char| c.is_alphabetic() || c == '_') && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+    (is_name && (rest == "}" || (rest.starts_with(' ') && rest.ends_with(" }")))).then_some(name)
+}
+
+// Name and fields of an instance, with field values as stored
+fn parse(value: &str) -> Option<(&str, Vec<(String, String)>)> {
+    let name = type_name(value)?;
+    let body = &value[name.len() + 2..value.len() - 1];
+    let mut fields = Vec::new();
+    for entry in split_top_level(body) {
+        let (field, value) = entry.split_once(": ")?;
+        fields.push((field.to_string(), value.to_string()));
+    }
+    Some((name, fields))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn declare(source: &[(&str, &str)], known: &HashMap<String, StructType>) -> StructType {
+        let fields: Vec<(String, String)> = source.iter().map(|(name, kind)| (name.to_string(), kind.to_string())).collect();
+        StructType::declare("Task", &fields, known).unwrap()
+    }
+
+    #[test]
+    fn test_instances_round_trip_through_text() {
+        let task = declare(&[("id", "number"), ("text", "string"), ("tags", "array"), ("extra", "any")], &HashMap::new());
+        let values = ["1", "Say \"hi\", then {leave}", "[a, b]", "x, y"].map(String::from);
+        let instance = task.create(&values).unwrap();
+        assert_eq!(instance, r#"Task { id: 1, text: "Say \"hi\", then {leave}", tags: [a, b], extra: "x, y" }"#);
+        assert_eq!(type_name(&instance), Some("Task"));
+
+        assert_eq!(task.get(&instance, "text").unwrap(), "Say \"hi\", then {leave}");
+        assert_eq!(task.get(&instance, "tags").unwrap(), "[a, b]");
+        assert_eq!(task.get(&instance, "extra").unwrap(), "x, y");
+        assert!(task.get(&instance, "done").unwrap_err().contains("no field 'done'"));
+
+        let changed = task.set(&instance, "id", "2").unwrap();
+        assert_eq!(task.get(&changed, "id").unwrap(), "2");
+        assert!(task.set(&instance, "id", "two").unwrap_err().contains("expects number"));
+        assert_eq!(task.create(&values).unwrap(), instance);
+    }
+
+    #[test]
+    fn test_declarations_and_constructors_are_checked() {
+        let mut known = HashMap::new();
+        known.insert("Point".to_string(), StructType { name: "Point".to_string(), fields: Vec::new() });
+        let task = declare(&[("id", "num"), ("at", "Point")], &known);
+        assert_eq!(task.fields[1].1, Kind::Struct("Point".to_string()));
+        assert!(StructType::declare("Task", &[("at".to_string(), "Place".to_string())], &known).unwrap_err().contains("Unknown type 'Place'"));
+
+        let point = Expression::StructLiteral { name: "Point".to_string(), fields: Vec::new() };
+        let given = vec![("at".to_string(), point.clone()), ("id".to_string(), Expression::NumberLiteral(1.0))];
+        let ordered = task.order(&given, Kind::of_literal).unwrap();
+        assert_eq!(ordered, vec![&Expression::NumberLiteral(1.0), &point]);
+
+        let wrong = vec![("id".to_string(), Expression::StringLiteral("1".to_string())), ("at".to_string(), point)];
+        assert_eq!(task.order(&wrong, Kind::of_literal).unwrap_err(), "Field Task.id expects number, got string");
+        assert!(task.order(&given[..1], Kind::of_literal).unwrap_err().contains("Missing field 'id'"));
+    }
+}
@@ -0,0 +1,508 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use serde_json::json;
+
+use crate::compiler::{Compiler, TestCase};
+use crate::snapshot;
+
+// Human-readable progress output, silenced when a report goes to stdout
+macro_rules! progress {
+    ($options:expr, $($arg:tt)*) => {
+        if !$options.quiet {
+            print!($($arg)*);
+        }
+    };
+}
+
+macro_rules! progressln {
+    ($options:expr, $($arg:tt)*) => {
+        if !$options.quiet {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// Report formats supported by `--format`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReportFormat {
+    #[default]
+    Text,
+    Junit,
+    Tap,
+    Json,
+}
+
+impl ReportFormat {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "text" => Ok(ReportFormat::Text),
+            "junit" => Ok(ReportFormat::Junit),
+            "tap" => Ok(ReportFormat::Tap),
+            "json" => Ok(ReportFormat::Json),
+            _ => Err(format!("Unknown test format '{}' (expected text, junit, tap or json)", name)),
+        }
+    }
+}
+
+/// Options for the `test` command
+#[derive(Debug, Clone, Default)]
+pub struct TestOptions {
+    pub debug_mode: bool,
+    pub fail_fast: bool,
+    pub filter: Option<String>, // Only run tests whose name contains this text
+    pub quiet: bool,            // Suppress progress output (report is written to stdout)
+    pub snapshot: bool,         // Compare program output against .expected files
+    pub update: bool,           // Write .expected files instead of comparing
+}
+
+/// Outcome of a single test case
+#[derive(Debug, Clone)]
+pub struct TestResult {
+    pub file: PathBuf,
+    pub line: Option<usize>,
+    pub name: String,
+    pub duration: f64,
+    pub failure: Option<String>,
+    pub output: String, // Captured program output
+}
+
+/// Results collected over a whole test run
+#[derive(Debug, Default)]
+pub struct TestSummary {
+    pub results: Vec<TestResult>,
+    pub stopped_early: bool, // Set when --fail-fast stopped the run
+}
+
+impl TestSummary {
+    pub fn passed(&self) -> usize {
+        self.results.iter().filter(|r| r.failure.is_none()).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.results.iter().filter(|r| r.failure.is_some()).count()
+    }
+
+    pub fn total_time(&self) -> f64 {
+        self.results.iter().map(|r| r.duration).sum()
+    }
+
+    fn record(&mut self, result: TestResult, options: &TestOptions) {
+        if result.failure.is_some() && options.fail_fast {
+            self.stopped_early = true;
+        }
+        self.results.push(result);
+    }
+}
+
+// Collects program output, optionally echoing it to the terminal
+struct CapturedOutput {
+    buffer: Vec<u8>,
+    echo: bool,
+}
+
+impl Write for CapturedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        if self.echo {
+            io::stdout().write_all(buf)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.echo {
+            io::stdout().flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Run a test file, or every .rzn file below a directory
+pub fn run_tests(path: &Path, options: &TestOptions) -> io::Result<TestSummary> {
+    let mut summary = TestSummary::default();
+    if path.is_dir() {
+        run_tests_in_directory(path, options, &mut summary)?;
+    } else {
+        run_test_file(path, options, &mut summary);
+    }
+    Ok(summary)
+}
+
+/// Print the totals for a finished run
+pub fn print_summary(summary: &TestSummary, test_path: &Path) {
+    // Nothing useful can be done if stdout is gone
+    let _ = write_summary(summary, test_path, &mut io::stdout());
+}
+
+/// Write the human-readable summary of a finished run, as printed after the tests
+pub fn write_summary(summary: &TestSummary, test_path: &Path, out: &mut dyn Write) -> io::Result<()> {
+    writeln!(out, "\nTest Summary for {}:", test_path.display())?;
+    writeln!(out, "  Passed: {}", summary.passed())?;
+    writeln!(out, "  Failed: {}", summary.failed())?;
+    writeln!(out, "  Total: {}", summary.results.len())?;
+    writeln!(out, "  Time: {:.2}s", summary.total_time())?;
+
+    if summary.failed() > 0 {
+        writeln!(out, "\nFailures:")?;
+        for result in summary.results.iter().filter(|r| r.failure.is_some()) {
+            writeln!(out, "  {} ({})", result.name, location(result))?;
+        }
+    }
+
+    if summary.stopped_early {
+        writeln!(out, "\nStopped after the first failure (--fail-fast)")?;
+    }
+    Ok(())
+}
+
+/// Write a machine-readable report of a finished run
+pub fn write_report(summary: &TestSummary, format: ReportFormat, out: &mut dyn Write) -> io::Result<()> {
+    match format {
+        ReportFormat::Text => Ok(()),
+        ReportFormat::Junit => write_junit(summary, out),
+        ReportFormat::Tap => write_tap(summary, out),
+        ReportFormat::Json => write_json(summary, out),
+    }
+}
+
+// Run all tests in a directory
+fn run_tests_in_directory(dir_path: &Path, options: &TestOptions, summary: &mut TestSummary) -> io::Result<()> {
+    let mut test_files = Vec::new();
+    let mut sub_dirs = Vec::new();
+
+    for entry in fs::read_dir(dir_path)? {
+        let path = entry?.path();
+
+        if path.is_file() && path.extension().is_some_and(|ext| ext == "rzn") {
+            test_files.push(path);
+        } else if path.is_dir() {
+            sub_dirs.push(path);
+        }
+    }
+
+    // Sort for consistent output
+    test_files.sort();
+    sub_dirs.sort();
+
+    progressln!(options, "Found {} test files in {}", test_files.len(), dir_path.display());
+
+    for test_file in test_files {
+        run_test_file(&test_file, options, summary);
+        if summary.stopped_early {
+            return Ok(());
+        }
+    }
+
+    // Recursively run tests in subdirectories
+    for sub_dir in sub_dirs {
+        if options.debug_mode {
+            progressln!(options, "Entering directory: {}", sub_dir.display());
+        }
+        run_tests_in_directory(&sub_dir, options, summary)?;
+        if summary.stopped_early {
+            return Ok(());
+        }
+    }
+
+    Ok(())
+}
+
+// Run the tests declared in a single file.
+// A file without `test` blocks or `test_` functions is run as one test.
+// In snapshot mode the whole program's output is checked instead.
+fn run_test_file(file_path: &Path, options: &TestOptions, summary: &mut TestSummary) {
+    let file_name = file_path.file_name().unwrap_or_default().to_string_lossy().to_string();
+    let start = Instant::now();
+
+    if options.snapshot {
+        if !matches_filter(&file_name, options) {
+            return;
+        }
+
+        let action = if options.update { "Updating" } else { "Snapshot" };
+        progress!(options, "{} {}... ", action, file_name);
+        let (output, failure) = snapshot::check(file_path, options.update);
+        report(TestResult {
+            file: file_path.to_path_buf(),
+            line: None,
+            name: file_name,
+            duration: start.elapsed().as_secs_f64(),
+            failure,
+            output,
+        }, options, summary);
+        return;
+    }
+
+    let compiler = match Compiler::from_file_with_clean_output(file_path, !options.debug_mode) {
+        Ok(compiler) => compiler,
+        Err(e) => {
+            progress!(options, "Testing {}... ", file_name);
+            report(TestResult {
+                file: file_path.to_path_buf(),
+                line: None,
+                name: file_name,
+                duration: start.elapsed().as_secs_f64(),
+                failure: Some(e),
+                output: String::new(),
+            }, options, summary);
+            return;
+        }
+    };
+
+    if compiler.tests.is_empty() {
+        if !matches_filter(&file_name, options) {
+            return;
+        }
+
+        progress!(options, "Testing {}... ", file_name);
+        let mut output = CapturedOutput { buffer: Vec::new(), echo: !options.quiet };
+        let result = compiler.execute_with_output(&mut output);
+        report(TestResult {
+            file: file_path.to_path_buf(),
+            line: None,
+            name: file_name,
+            duration: start.elapsed().as_secs_f64(),
+            failure: exit_failure(result),
+            output: String::from_utf8_lossy(&output.buffer).to_string(),
+        }, options, summary);
+        return;
+    }
+
+    let tests: Vec<&TestCase> = compiler.tests.iter()
+        .filter(|test| matches_filter(&test.name, options))
+        .collect();
+    if tests.is_empty() {
+        return;
+    }
+
+    let source = fs::read_to_string(file_path).unwrap_or_default();
+
+    progressln!(options, "Testing {} ({} tests)", file_name, tests.len());
+    for test in tests {
+        progress!(options, "  {} ... ", test.name);
+        let start = Instant::now();
+        let mut output = CapturedOutput { buffer: Vec::new(), echo: !options.quiet };
+        let result = compiler.execute_test(test, &mut output);
+        report(TestResult {
+            file: file_path.to_path_buf(),
+            line: find_test_line(&source, test),
+            name: test.name.clone(),
+            duration: start.elapsed().as_secs_f64(),
+            failure: exit_failure(result),
+            output: String::from_utf8_lossy(&output.buffer).to_string(),
+        }, options, summary);
+        if summary.stopped_early {
+            return;
+        }
+    }
+}
+
+// A program or test that exits with a non-zero status fails
+fn exit_failure(result: Result<i32, String>) -> Option<String> {
+    match result {
+        Ok(0) => None,
+        Ok(status) => Some(format!("Program exited with status {}", status)),
+        Err(e) => Some(e),
+    }
+}
+
+fn matches_filter(name: &str, options: &TestOptions) -> bool {
+    options.filter.as_ref().is_none_or(|filter| name.contains(filter.as_str()))
+}
+
+// Find the line a test is declared on: `test "name" {` or `fun test_name(`
+fn find_test_line(source: &str, test: &TestCase) -> Option<usize> {
+    let block = format!("test \"{}\"", test.name);
+    let function = format!("fun {}(", test.name);
+    source.lines()
+        .position(|line| {
+            let line = line.trim_start();
+            line.starts_with(&block) || line.starts_with(&function)
+        })
+        .map(|index| index + 1)
+}
+
+fn location(result: &TestResult) -> String {
+    match result.line {
+        Some(line) => format!("{}:{}", result.file.display(), line),
+        None => result.file.display().to_string(),
+    }
+}
+
+// Print the outcome of one test and add it to the summary
+fn report(result: TestResult, options: &TestOptions, summary: &mut TestSummary) {
+    if options.debug_mode {
+        progressln!(options, "");
+    }
+
+    match &result.failure {
+        None => progressln!(options, "PASS ({:.2}s)", result.duration),
+        Some(e) => {
+            progressln!(options, "FAIL ({:.2}s)", result.duration);
+            for line in e.lines() {
+                progressln!(options, "    {}", line);
+            }
+        }
+    }
+
+    summary.record(result, options);
+}
+
+// Group results by file, keeping the order the files were run in
+fn results_by_file(summary: &TestSummary) -> Vec<(&Path, Vec<&TestResult>)> {
+    let mut groups: Vec<(&Path, Vec<&TestResult>)> = Vec::new();
+    for result in &summary.results {
+        match groups.iter_mut().find(|(file, _)| *file == result.file.as_path()) {
+            Some((_, results)) => results.push(result),
+            None => groups.push((result.file.as_path(), vec![result])),
+        }
+    }
+    groups
+}
+
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Control characters are not allowed in XML 1.0
+            c if (c as u32) < 0x20 && !matches!(c, '\n' | '\r' | '\t') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn write_junit(summary: &TestSummary, out: &mut dyn Write) -> io::Result<()> {
+    writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(out, "<testsuites name=\"razen\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">",
+        summary.results.len(), summary.failed(), summary.total_time())?;
+
+    for (file, results) in results_by_file(summary) {
+        let failures = results.iter().filter(|r| r.failure.is_some()).count();
+        let time: f64 = results.iter().map(|r| r.duration).sum();
+        let suite = file.file_stem().unwrap_or_default().to_string_lossy();
+        writeln!(out, "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">",
+            xml_escape(&suite), results.len(), failures, time)?;
+
+        for result in results {
+            let line = result.line.map(|line| format!(" line=\"{}\"", line)).unwrap_or_default();
+            writeln!(out, "    <testcase name=\"{}\" classname=\"{}\" file=\"{}\"{} time=\"{:.3}\">",
+                xml_escape(&result.name), xml_escape(&suite), xml_escape(&file.display().to_string()), line, result.duration)?;
+            if let Some(failure) = &result.failure {
+                let message = failure.lines().next().unwrap_or_default();
+                writeln!(out, "      <failure message=\"{}\">{}</failure>", xml_escape(message), xml_escape(failure))?;
+            }
+            if !result.output.is_empty() {
+                writeln!(out, "      <system-out>{}</system-out>", xml_escape(&result.output))?;
+            }
+            writeln!(out, "    </testcase>")?;
+        }
+
+        writeln!(out, "  </testsuite>")?;
+    }
+
+    writeln!(out, "</testsuites>")
+}
+
+fn write_tap_block(out: &mut dyn Write, key: &str, text: &str) -> io::Result<()> {
+    writeln!(out, "  {}: |", key)?;
+    for line in text.lines() {
+        writeln!(out, "    {}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+fn write_tap(summary: &TestSummary, out: &mut dyn Write) -> io::Result<()> {
+    writeln!(out, "TAP version 13")?;
+    writeln!(out, "1..{}", summary.results.len())?;
+
+    for (index, result) in summary.results.iter().enumerate() {
+        let status = if result.failure.is_some() { "not ok" } else { "ok" };
+        // '#' starts a directive in TAP, so it cannot appear in the description
+        writeln!(out, "{} {} - {}", status, index + 1, result.name.replace('#', "\\#"))?;
+        writeln!(out, "  ---")?;
+        writeln!(out, "  file: {}", result.file.display())?;
+        if let Some(line) = result.line {
+            writeln!(out, "  line: {}", line)?;
+        }
+        writeln!(out, "  duration_ms: {:.3}", result.duration * 1000.0)?;
+        if let Some(failure) = &result.failure {
+            write_tap_block(out, "message", failure)?;
+        }
+        if !result.output.is_empty() {
+            write_tap_block(out, "output", &result.output)?;
+        }
+        writeln!(out, "  ...")?;
+    }
+
+    if summary.stopped_early {
+        writeln!(out, "Bail out! Stopped after the first failure (--fail-fast)")?;
+    }
+    Ok(())
+}
+
+fn write_json(summary: &TestSummary, out: &mut dyn Write) -> io::Result<()> {
+    let tests: Vec<serde_json::Value> = summary.results.iter().map(|result| json!({
+        "name": result.name,
+        "file": result.file.display().to_string(),
+        "line": result.line,
+        "status": if result.failure.is_some() { "failed" } else { "passed" },
+        "duration": result.duration,
+        "failure": result.failure,
+        "output": result.output,
+    })).collect();
+
+    let report = json!({
+        "passed": summary.passed(),
+        "failed": summary.failed(),
+        "total": summary.results.len(),
+        "duration": summary.total_time(),
+        "stopped_early": summary.stopped_early,
+        "tests": tests,
+    });
+
+    serde_json::to_writer_pretty(&mut *out, &report)?;
+    writeln!(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_junit_report_escapes_failures() {
+        let summary = TestSummary {
+            results: vec![TestResult {
+                file: PathBuf::from("math.rzn"),
+                line: Some(3),
+                name: "a < b".to_string(),
+                duration: 0.5,
+                failure: Some("expected \"x\" & \"y\"".to_string()),
+                output: "hi\n".to_string(),
+            }],
+            stopped_early: false,
+        };
+
+        let mut out = Vec::new();
+        write_report(&summary, ReportFormat::Junit, &mut out).unwrap();
+        let xml = String::from_utf8(out).unwrap();
+
+        assert!(xml.contains("<testcase name=\"a &lt; b\" classname=\"math\" file=\"math.rzn\" line=\"3\" time=\"0.500\">"));
+        assert!(xml.contains("<failure message=\"expected &quot;x&quot; &amp; &quot;y&quot;\">"));
+        assert!(xml.contains("<system-out>hi\n</system-out>"));
+
+        let mut out = Vec::new();
+        write_summary(&summary, Path::new("math.rzn"), &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("\nTest Summary for math.rzn:\n  Passed: 0\n  Failed: 1\n"));
+        assert!(text.contains("\nFailures:\n  a < b (math.rzn:3)\n"));
+    }
+}
@@ -222,6 +222,7 @@ pub enum TokenType {
     // 19 - Performance and Type Safety Keywords
     Const,          // For declaring constant values
     Enum,           // For defining enumerated types
+    Struct,         // For defining record types with typed fields
     Inline,         // For suggesting function inlining
     Final,          // For declaring classes that cannot be extended
     Volatile,       // For declaring variables that might change externally
@@ -451,6 +452,7 @@ impl fmt::Display for TokenType {
             // 19 - Performance and Type Safety Keywords
             TokenType::Const => write!(f, "CONST"),
             TokenType::Enum => write!(f, "ENUM"),
+            TokenType::Struct => write!(f, "STRUCT"),
             TokenType::Inline => write!(f, "INLINE"),
             TokenType::Final => write!(f, "FINAL"),
             TokenType::Volatile => write!(f, "VOLATILE"),
@@ -660,6 +662,7 @@ pub fn lookup_identifier(identifier: &str) -> TokenType {
         // 19 - Performance and Type Safety Keywords
         "const" => TokenType::Const,
         "enum" => TokenType::Enum,
+        "struct" => TokenType::Struct,
         "inline" => TokenType::Inline,
         "final" => TokenType::Final,
         "volatile" => TokenType::Volatile,
@@ -0,0 +1,604 @@
+use inkwell::attributes::AttributeLoc;
+use inkwell::builder::Builder;
+use inkwell::context::Context;
+use inkwell::module::{Linkage, Module as LlvmModule};
+use inkwell::types::{BasicMetadataTypeEnum, BasicType, BasicTypeEnum};
+use inkwell::values::{BasicMetadataValueEnum, FunctionValue, IntValue, PointerValue};
+use inkwell::{AddressSpace, FloatPredicate, IntPredicate};
+
+// The runtime compiled programs need on WebAssembly, built straight into the module so
+// it links without a C library. Strings are NUL-terminated and allocated from linear
+// memory past the end of what the linker laid out; nothing is ever freed. Strings are
+// the only values allocated at run time, since the backend has no runtime arrays or maps.
+
+const PAGE_SIZE: u64 = 65536;
+const LINE_CAPACITY: u64 = 1024;
+
+// Digits kept after the decimal point when printing floats
+const FLOAT_DIGITS: u32 = 6;
+
+/// Where a module gets its input and output from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Host {
+    /// Any WASI runtime, through fd_write, fd_read and proc_exit
+    Wasi,
+    /// The embedder, which provides `razen.write(ptr, len)`, `razen.read(ptr, capacity)` and `razen.exit(code)`
+    Embedder,
+}
+
+/// Runtime functions the LLVM backend calls
+pub struct Runtime<'ctx> {
+    pub print: FunctionValue<'ctx>,           // (i8* text)
+    pub read_line: FunctionValue<'ctx>,       // () -> i8*, without the line ending
+    pub concat: FunctionValue<'ctx>,          // (i8* a, i8* b) -> i8*
+    pub int_to_string: FunctionValue<'ctx>,   // (i64) -> i8*
+    pub float_to_string: FunctionValue<'ctx>, // (f64) -> i8*
+    pub bool_to_string: FunctionValue<'ctx>,  // (i1) -> i8*
+    pub exit: FunctionValue<'ctx>,            // (i32 status), does not return
+}
+
+/// Add the runtime to a module
+pub fn build<'ctx>(context: &'ctx Context, module: &LlvmModule<'ctx>, host: Host) -> Runtime<'ctx> {
+    let emitter = Emitter { context, module, builder: context.create_builder() };
+    let (write, read, exit) = emitter.host_io(host);
+    let alloc = emitter.alloc(write, exit);
+    let strlen = emitter.strlen();
+    let print = emitter.print(write, strlen);
+    let read_line = emitter.read_line(alloc, read);
+    let concat = emitter.concat(alloc, strlen);
+    let int_to_string = emitter.int_to_string(alloc);
+    let float_to_string = emitter.float_to_string(int_to_string, concat);
+    let bool_to_string = emitter.bool_to_string();
+    Runtime { print, read_line, concat, int_to_string, float_to_string, bool_to_string, exit }
+}
+
+/// Add `_start`, which WASI runtimes call: it runs `main` and exits with its result
+pub fn add_start<'ctx>(context: &'ctx Context, module: &LlvmModule<'ctx>, runtime: &Runtime<'ctx>, main: FunctionValue<'ctx>) {
+    let emitter = Emitter { context, module, builder: context.create_builder() };
+    let start = module.add_function("_start", context.void_type().fn_type(&[], false), None);
+    emitter.builder.position_at_end(context.append_basic_block(start, "entry"));
+    let status = emitter.call(main, &[]);
+    let status = match status {
+        Some(value) if value.is_int_value() => {
+            emitter.builder.build_int_truncate_or_bit_cast(value.into_int_value(), context.i32_type(), "status")
+        },
+        _ => context.i32_type().const_zero(),
+    };
+    emitter.call(runtime.exit, &[status.into()]);
+    emitter.builder.build_unreachable();
+}
+
+/// Export a function to the embedder under its own name
+pub fn export(context: &Context, function: FunctionValue) {
+    let name = function.get_name().to_string_lossy().into_owned();
+    function.add_attribute(AttributeLoc::Function, context.create_string_attribute("wasm-export-name", &name));
+}
+
+struct Emitter<'a, 'ctx> {
+    context: &'ctx Context,
+    module: &'a LlvmModule<'ctx>,
+    builder: Builder<'ctx>,
+}
+
+impl<'a, 'ctx> Emitter<'a, 'ctx> {
+    fn i32(&self, value: u64) -> IntValue<'ctx> {
+        self.context.i32_type().const_int(value, false)
+    }
+
+    fn i8_ptr(&self) -> inkwell::types::PointerType<'ctx> {
+        self.context.i8_type().ptr_type(AddressSpace::default())
+    }
+
+    // Start an internal runtime function, returning it with its entry block selected
+    fn function(&self, name: &str, params: &[BasicMetadataTypeEnum<'ctx>], returns: Option<BasicTypeEnum<'ctx>>) -> FunctionValue<'ctx> {
+        let fn_type = match returns {
+            Some(returns) => returns.fn_type(params, false),
+            None => self.context.void_type().fn_type(params, false),
+        };
+        let function = self.module.add_function(name, fn_type, Some(Linkage::Internal));
+        self.builder.position_at_end(self.context.append_basic_block(function, "entry"));
+        function
+    }
+
+    // Declare a function the host provides
+    fn import(&self, module: &str, name: &str, params: &[BasicMetadataTypeEnum<'ctx>], returns: Option<BasicTypeEnum<'ctx>>) -> FunctionValue<'ctx> {
+        let fn_type = match returns {
+            Some(returns) => returns.fn_type(params, false),
+            None => self.context.void_type().fn_type(params, false),
+        };
+        let function = self.module.add_function(&format!("__{}_{}", module, name), fn_type, None);
+        function.add_attribute(AttributeLoc::Function, self.context.create_string_attribute("wasm-import-module", module));
+        function.add_attribute(AttributeLoc::Function, self.context.create_string_attribute("wasm-import-name", name));
+        function
+    }
+
+    fn call(&self, function: FunctionValue<'ctx>, args: &[BasicMetadataValueEnum<'ctx>]) -> Option<inkwell::values::BasicValueEnum<'ctx>> {
+        self.builder.build_call(function, args, "call").try_as_basic_value().left()
+    }
+
+    fn call_int(&self, function: FunctionValue<'ctx>, args: &[BasicMetadataValueEnum<'ctx>]) -> IntValue<'ctx> {
+        self.call(function, args).unwrap().into_int_value()
+    }
+
+    fn call_ptr(&self, function: FunctionValue<'ctx>, args: &[BasicMetadataValueEnum<'ctx>]) -> PointerValue<'ctx> {
+        self.call(function, args).unwrap().into_pointer_value()
+    }
+
+    fn byte_at(&self, base: PointerValue<'ctx>, index: IntValue<'ctx>) -> PointerValue<'ctx> {
+        unsafe { self.builder.build_in_bounds_gep(self.context.i8_type(), base, &[index], "byte") }
+    }
+
+    fn load_i32(&self, pointer: PointerValue<'ctx>) -> IntValue<'ctx> {
+        self.builder.build_load(self.context.i32_type(), pointer, "value").into_int_value()
+    }
+
+    fn load_i64(&self, pointer: PointerValue<'ctx>) -> IntValue<'ctx> {
+        self.builder.build_load(self.context.i64_type(), pointer, "value").into_int_value()
+    }
+
+    fn load_byte(&self, pointer: PointerValue<'ctx>) -> IntValue<'ctx> {
+        self.builder.build_load(self.context.i8_type(), pointer, "byte").into_int_value()
+    }
+
+    // Bump allocator, 8-byte aligned, growing memory a page at a time as needed. When
+    // memory cannot grow the program stops with an error instead of writing past the end.
+    fn alloc(&self, write: FunctionValue<'ctx>, exit: FunctionValue<'ctx>) -> FunctionValue<'ctx> {
+        let i32_type = self.context.i32_type();
+        let memory_size = self.module.add_function("llvm.wasm.memory.size.i32", i32_type.fn_type(&[i32_type.into()], false), None);
+        let memory_grow = self.module.add_function("llvm.wasm.memory.grow.i32", i32_type.fn_type(&[i32_type.into(), i32_type.into()], false), None);
+        let next = self.module.add_global(i32_type, None, "razen_heap_next");
+        next.set_linkage(Linkage::Internal);
+        next.set_initializer(&i32_type.const_zero());
+        let next = next.as_pointer_value();
+
+        let function = self.function("razen_alloc", &[i32_type.into()], Some(self.i8_ptr().into()));
+        let size = function.get_nth_param(0).unwrap().into_int_value();
+        let start = self.context.append_basic_block(function, "start");
+        let allocate = self.context.append_basic_block(function, "allocate");
+        let check = self.context.append_basic_block(function, "check");
+        let grow = self.context.append_basic_block(function, "grow");
+        let out_of_memory = self.context.append_basic_block(function, "out_of_memory");
+        let done = self.context.append_basic_block(function, "done");
+
+        // The heap starts where the memory the module was given ends
+        let current = self.load_i32(next);
+        let empty = self.builder.build_int_compare(IntPredicate::EQ, current, i32_type.const_zero(), "empty");
+        self.builder.build_conditional_branch(empty, start, allocate);
+
+        self.builder.position_at_end(start);
+        let pages = self.call_int(memory_size, &[i32_type.const_zero().into()]);
+        let end_of_memory = self.builder.build_int_mul(pages, self.i32(PAGE_SIZE), "end_of_memory");
+        self.builder.build_store(next, end_of_memory);
+        self.builder.build_unconditional_branch(allocate);
+
+        self.builder.position_at_end(allocate);
+        let base = self.load_i32(next);
+        let padded = self.builder.build_int_add(size, self.i32(7), "padded");
+        let aligned = self.builder.build_and(padded, i32_type.const_int(!7u64, true), "aligned");
+        let end = self.builder.build_int_add(base, aligned, "end");
+        let wrapped = self.builder.build_int_compare(IntPredicate::ULT, end, base, "wrapped");
+        self.builder.build_conditional_branch(wrapped, out_of_memory, check);
+
+        self.builder.position_at_end(check);
+        self.builder.build_store(next, end);
+        let pages = self.call_int(memory_size, &[i32_type.const_zero().into()]);
+        let available = self.builder.build_int_mul(pages, self.i32(PAGE_SIZE), "available");
+        let full = self.builder.build_int_compare(IntPredicate::UGT, end, available, "full");
+        self.builder.build_conditional_branch(full, grow, done);
+
+        self.builder.position_at_end(grow);
+        let missing = self.builder.build_int_sub(end, available, "missing");
+        let missing = self.builder.build_int_add(missing, self.i32(PAGE_SIZE - 1), "missing");
+        let new_pages = self.builder.build_int_unsigned_div(missing, self.i32(PAGE_SIZE), "new_pages");
+        let grown = self.call_int(memory_grow, &[i32_type.const_zero().into(), new_pages.into()]);
+        let failed = self.builder.build_int_compare(IntPredicate::EQ, grown, i32_type.const_all_ones(), "failed");
+        self.builder.build_conditional_branch(failed, out_of_memory, done);
+
+        self.builder.position_at_end(out_of_memory);
+        let message = "Error: out of memory\n";
+        let text = self.builder.build_global_string_ptr(message, "out_of_memory").as_pointer_value();
+        self.call(write, &[text.into(), self.i32(message.len() as u64).into()]);
+        self.call(exit, &[self.i32(1).into()]);
+        self.builder.build_unreachable();
+
+        self.builder.position_at_end(done);
+        let pointer = self.builder.build_int_to_ptr(base, self.i8_ptr(), "pointer");
+        self.builder.build_return(Some(&pointer));
+        function
+    }
+
+    fn strlen(&self) -> FunctionValue<'ctx> {
+        let i32_type = self.context.i32_type();
+        let function = self.function("razen_strlen", &[self.i8_ptr().into()], Some(i32_type.into()));
+        let text = function.get_nth_param(0).unwrap().into_pointer_value();
+        let length = self.builder.build_alloca(i32_type, "length");
+        self.builder.build_store(length, i32_type.const_zero());
+        let check = self.context.append_basic_block(function, "check");
+        let next = self.context.append_basic_block(function, "next");
+        let done = self.context.append_basic_block(function, "done");
+        self.builder.build_unconditional_branch(check);
+
+        self.builder.position_at_end(check);
+        let index = self.load_i32(length);
+        let byte = self.load_byte(self.byte_at(text, index));
+        let end = self.builder.build_int_compare(IntPredicate::EQ, byte, self.context.i8_type().const_zero(), "end");
+        self.builder.build_conditional_branch(end, done, next);
+
+        self.builder.position_at_end(next);
+        let index = self.builder.build_int_add(index, self.i32(1), "index");
+        self.builder.build_store(length, index);
+        self.builder.build_unconditional_branch(check);
+
+        self.builder.position_at_end(done);
+        let length = self.load_i32(length);
+        self.builder.build_return(Some(&length));
+        function
+    }
+
+    // write(ptr, len), read(ptr, capacity) -> count and exit(status) for the host
+    fn host_io(&self, host: Host) -> (FunctionValue<'ctx>, FunctionValue<'ctx>, FunctionValue<'ctx>) {
+        let i32_type = self.context.i32_type();
+        let ptr = self.i8_ptr();
+        match host {
+            Host::Embedder => (
+                self.import("razen", "write", &[ptr.into(), i32_type.into()], None),
+                self.import("razen", "read", &[ptr.into(), i32_type.into()], Some(i32_type.into())),
+                self.import("razen", "exit", &[i32_type.into()], None),
+            ),
+            Host::Wasi => {
+                let module = "wasi_snapshot_preview1";
+                let io_params = [i32_type.into(), ptr.into(), i32_type.into(), ptr.into()];
+                let fd_write = self.import(module, "fd_write", &io_params, Some(i32_type.into()));
+                let fd_read = self.import(module, "fd_read", &io_params, Some(i32_type.into()));
+                let proc_exit = self.import(module, "proc_exit", &[i32_type.into()], None);
+
+                // One iovec (buffer, length) followed by the count the call reports
+                let iovec = self.module.add_global(i32_type.array_type(3), None, "razen_iovec");
+                iovec.set_linkage(Linkage::Internal);
+                iovec.set_initializer(&i32_type.array_type(3).const_zero());
+                let iovec = iovec.as_pointer_value();
+
+                let wrap = |name: &str, fd: u64, call: FunctionValue<'ctx>| {
+                    let function = self.function(name, &[ptr.into(), i32_type.into()], Some(i32_type.into()));
+                    let buffer = function.get_nth_param(0).unwrap().into_pointer_value();
+                    let length = function.get_nth_param(1).unwrap().into_int_value();
+                    let slot = |index: u64| unsafe {
+                        self.builder.build_in_bounds_gep(i32_type.array_type(3), iovec, &[self.i32(0), self.i32(index)], "slot")
+                    };
+                    let address = self.builder.build_ptr_to_int(buffer, i32_type, "address");
+                    self.builder.build_store(slot(0), address);
+                    self.builder.build_store(slot(1), length);
+                    let count = self.builder.build_pointer_cast(slot(2), ptr, "count");
+                    let iovs = self.builder.build_pointer_cast(iovec, ptr, "iovs");
+                    self.call(call, &[self.i32(fd).into(), iovs.into(), self.i32(1).into(), count.into()]);
+                    let done = self.load_i32(slot(2));
+                    self.builder.build_return(Some(&done));
+                    function
+                };
+                let write = wrap("razen_write", 1, fd_write);
+                let read = wrap("razen_read", 0, fd_read);
+                (write, read, proc_exit)
+            },
+        }
+    }
+
+    fn print(&self, write: FunctionValue<'ctx>, strlen: FunctionValue<'ctx>) -> FunctionValue<'ctx> {
+        let function = self.function("razen_print", &[self.i8_ptr().into()], None);
+        let text = function.get_nth_param(0).unwrap().into_pointer_value();
+        let length = self.call_int(strlen, &[text.into()]);
+        self.call(write, &[text.into(), length.into()]);
+        self.builder.build_return(None);
+        function
+    }
+
+    // Read a byte at a time up to a newline or the end of input
+    fn read_line(&self, alloc: FunctionValue<'ctx>, read: FunctionValue<'ctx>) -> FunctionValue<'ctx> {
+        let i32_type = self.context.i32_type();
+        let i8_type = self.context.i8_type();
+        let function = self.function("razen_read_line", &[], Some(self.i8_ptr().into()));
+        let length = self.builder.build_alloca(i32_type, "length");
+        self.builder.build_store(length, i32_type.const_zero());
+        let buffer = self.call_ptr(alloc, &[self.i32(LINE_CAPACITY).into()]);
+        let check = self.context.append_basic_block(function, "check");
+        let read_byte = self.context.append_basic_block(function, "read_byte");
+        let got_byte = self.context.append_basic_block(function, "got_byte");
+        let next = self.context.append_basic_block(function, "next");
+        let trim = self.context.append_basic_block(function, "trim");
+        let drop_return = self.context.append_basic_block(function, "drop_return");
+        let done = self.context.append_basic_block(function, "done");
+        self.builder.build_unconditional_branch(check);
+
+        self.builder.position_at_end(check);
+        let index = self.load_i32(length);
+        let full = self.builder.build_int_compare(IntPredicate::UGE, index, self.i32(LINE_CAPACITY - 1), "full");
+        self.builder.build_conditional_branch(full, trim, read_byte);
+
+        self.builder.position_at_end(read_byte);
+        let slot = self.byte_at(buffer, index);
+        let count = self.call_int(read, &[slot.into(), self.i32(1).into()]);
+        let ended = self.builder.build_int_compare(IntPredicate::EQ, count, i32_type.const_zero(), "ended");
+        self.builder.build_conditional_branch(ended, trim, got_byte);
+
+        self.builder.position_at_end(got_byte);
+        let byte = self.load_byte(slot);
+        let newline = self.builder.build_int_compare(IntPredicate::EQ, byte, i8_type.const_int(b'\n' as u64, false), "newline");
+        self.builder.build_conditional_branch(newline, trim, next);
+
+        self.builder.position_at_end(next);
+        let index = self.builder.build_int_add(index, self.i32(1), "index");
+        self.builder.build_store(length, index);
+        self.builder.build_unconditional_branch(check);
+
+        // Drop the \r of a Windows line ending
+        self.builder.position_at_end(trim);
+        let index = self.load_i32(length);
+        let empty = self.builder.build_int_compare(IntPredicate::EQ, index, i32_type.const_zero(), "empty");
+        let last = self.builder.build_int_sub(index, self.i32(1), "last");
+        let last_index = self.builder.build_select(empty, index, last, "last_index").into_int_value();
+        let last_byte = self.load_byte(self.byte_at(buffer, last_index));
+        let is_return = self.builder.build_int_compare(IntPredicate::EQ, last_byte, i8_type.const_int(b'\r' as u64, false), "is_return");
+        let ends_in_return = self.builder.build_and(is_return, self.builder.build_not(empty, "filled"), "ends_in_return");
+        self.builder.build_conditional_branch(ends_in_return, drop_return, done);
+
+        self.builder.position_at_end(drop_return);
+        self.builder.build_store(length, last);
+        self.builder.build_unconditional_branch(done);
+
+        self.builder.position_at_end(done);
+        let index = self.load_i32(length);
+        self.builder.build_store(self.byte_at(buffer, index), i8_type.const_zero());
+        self.builder.build_return(Some(&buffer));
+        function
+    }
+
+    fn concat(&self, alloc: FunctionValue<'ctx>, strlen: FunctionValue<'ctx>) -> FunctionValue<'ctx> {
+        let i32_type = self.context.i32_type();
+        let ptr = self.i8_ptr();
+        let copy = self.copy();
+        let function = self.function("razen_concat", &[ptr.into(), ptr.into()], Some(ptr.into()));
+        let left = function.get_nth_param(0).unwrap().into_pointer_value();
+        let right = function.get_nth_param(1).unwrap().into_pointer_value();
+        let left_length = self.call_int(strlen, &[left.into()]);
+        let right_length = self.call_int(strlen, &[right.into()]);
+        let length = self.builder.build_int_add(left_length, right_length, "length");
+        let size = self.builder.build_int_add(length, i32_type.const_int(1, false), "size");
+        let result = self.call_ptr(alloc, &[size.into()]);
+        self.call(copy, &[result.into(), left.into(), left_length.into()]);
+        let rest = self.byte_at(result, left_length);
+        self.call(copy, &[rest.into(), right.into(), right_length.into()]);
+        self.builder.build_store(self.byte_at(result, length), self.context.i8_type().const_zero());
+        self.builder.build_return(Some(&result));
+        function
+    }
+
+    // A plain byte loop; memcpy would need a C library
+    fn copy(&self) -> FunctionValue<'ctx> {
+        let i32_type = self.context.i32_type();
+        let ptr = self.i8_ptr();
+        let function = self.function("razen_copy", &[ptr.into(), ptr.into(), i32_type.into()], None);
+        let target = function.get_nth_param(0).unwrap().into_pointer_value();
+        let source = function.get_nth_param(1).unwrap().into_pointer_value();
+        let count = function.get_nth_param(2).unwrap().into_int_value();
+        let position = self.builder.build_alloca(i32_type, "position");
+        self.builder.build_store(position, i32_type.const_zero());
+        let check = self.context.append_basic_block(function, "check");
+        let body = self.context.append_basic_block(function, "body");
+        let done = self.context.append_basic_block(function, "done");
+        self.builder.build_unconditional_branch(check);
+
+        self.builder.position_at_end(check);
+        let index = self.load_i32(position);
+        let finished = self.builder.build_int_compare(IntPredicate::UGE, index, count, "finished");
+        self.builder.build_conditional_branch(finished, done, body);
+
+        self.builder.position_at_end(body);
+        let byte = self.load_byte(self.byte_at(source, index));
+        self.builder.build_store(self.byte_at(target, index), byte);
+        let index = self.builder.build_int_add(index, self.i32(1), "index");
+        self.builder.build_store(position, index);
+        self.builder.build_unconditional_branch(check);
+
+        self.builder.position_at_end(done);
+        self.builder.build_return(None);
+        function
+    }
+
+    // Decimal digits written backwards from the end of a 24-byte buffer
+    fn int_to_string(&self, alloc: FunctionValue<'ctx>) -> FunctionValue<'ctx> {
+        let i32_type = self.context.i32_type();
+        let i64_type = self.context.i64_type();
+        let i8_type = self.context.i8_type();
+        let function = self.function("razen_int_to_string", &[i64_type.into()], Some(self.i8_ptr().into()));
+        let value = function.get_nth_param(0).unwrap().into_int_value();
+        let position = self.builder.build_alloca(i32_type, "position");
+        let remaining = self.builder.build_alloca(i64_type, "remaining");
+        let buffer = self.call_ptr(alloc, &[self.i32(24).into()]);
+        self.builder.build_store(self.byte_at(buffer, self.i32(23)), i8_type.const_zero());
+        self.builder.build_store(position, self.i32(23));
+
+        // The magnitude is read as unsigned, so i64::MIN works too
+        let negative = self.builder.build_int_compare(IntPredicate::SLT, value, i64_type.const_zero(), "negative");
+        let negated = self.builder.build_int_neg(value, "negated");
+        let magnitude = self.builder.build_select(negative, negated, value, "magnitude").into_int_value();
+        self.builder.build_store(remaining, magnitude);
+        let digit = self.context.append_basic_block(function, "digit");
+        let sign = self.context.append_basic_block(function, "sign");
+        let minus = self.context.append_basic_block(function, "minus");
+        let done = self.context.append_basic_block(function, "done");
+        self.builder.build_unconditional_branch(digit);
+
+        self.builder.position_at_end(digit);
+        let ten = i64_type.const_int(10, false);
+        let current = self.load_i64(remaining);
+        let index = self.builder.build_int_sub(self.load_i32(position), self.i32(1), "index");
+        self.builder.build_store(position, index);
+        let digit_value = self.builder.build_int_unsigned_rem(current, ten, "digit_value");
+        let digit_value = self.builder.build_int_truncate(digit_value, i8_type, "digit_byte");
+        let character = self.builder.build_int_add(digit_value, i8_type.const_int(b'0' as u64, false), "character");
+        self.builder.build_store(self.byte_at(buffer, index), character);
+        let rest = self.builder.build_int_unsigned_div(current, ten, "rest");
+        self.builder.build_store(remaining, rest);
+        let more = self.builder.build_int_compare(IntPredicate::NE, rest, i64_type.const_zero(), "more");
+        self.builder.build_conditional_branch(more, digit, sign);
+
+        self.builder.position_at_end(sign);
+        let index = self.load_i32(position);
+        let with_sign = self.builder.build_int_sub(index, self.i32(1), "with_sign");
+        self.builder.build_conditional_branch(negative, minus, done);
+        self.builder.position_at_end(minus);
+        self.builder.build_store(self.byte_at(buffer, with_sign), i8_type.const_int(b'-' as u64, false));
+        self.builder.build_store(position, with_sign);
+        self.builder.build_unconditional_branch(done);
+
+        self.builder.position_at_end(done);
+        let start = self.byte_at(buffer, self.load_i32(position));
+        self.builder.build_return(Some(&start));
+        function
+    }
+
+    // Fixed-point with up to six decimals and trailing zeros removed, e.g. 2.5 and -0.125
+    fn float_to_string(&self, int_to_string: FunctionValue<'ctx>, concat: FunctionValue<'ctx>) -> FunctionValue<'ctx> {
+        let i32_type = self.context.i32_type();
+        let i64_type = self.context.i64_type();
+        let f64_type = self.context.f64_type();
+        let i8_type = self.context.i8_type();
+        let scale = 10u64.pow(FLOAT_DIGITS);
+        let function = self.function("razen_float_to_string", &[f64_type.into()], Some(self.i8_ptr().into()));
+        let value = function.get_nth_param(0).unwrap().into_float_value();
+        let end = self.builder.build_alloca(i32_type, "end");
+
+        let negative = self.builder.build_float_compare(FloatPredicate::OLT, value, f64_type.const_zero(), "negative");
+        let negated = self.builder.build_float_neg(value, "negated");
+        let magnitude = self.builder.build_select(negative, negated, value, "magnitude").into_float_value();
+        let scaled = self.builder.build_float_mul(magnitude, f64_type.const_float(scale as f64), "scaled");
+        let rounded = self.builder.build_float_add(scaled, f64_type.const_float(0.5), "rounded");
+        let fixed = self.builder.build_float_to_unsigned_int(rounded, i64_type, "fixed");
+        let whole = self.builder.build_int_unsigned_div(fixed, i64_type.const_int(scale, false), "whole");
+        let fraction = self.builder.build_int_unsigned_rem(fixed, i64_type.const_int(scale, false), "fraction");
+        let signed_whole = self.builder.build_int_neg(whole, "signed_whole");
+        let signed_whole = self.builder.build_select(negative, signed_whole, whole, "whole").into_int_value();
+        let whole_text = self.call_ptr(int_to_string, &[signed_whole.into()]);
+
+        // -0.5 has a whole part of 0, which loses its sign
+        let zero_whole = self.builder.build_int_compare(IntPredicate::EQ, whole, i64_type.const_zero(), "zero_whole");
+        let needs_minus = self.builder.build_and(negative, zero_whole, "needs_minus");
+        let minus = self.builder.build_global_string_ptr("-", "minus").as_pointer_value();
+        let nothing = self.builder.build_global_string_ptr("", "nothing").as_pointer_value();
+        let prefix = self.builder.build_select(needs_minus, minus, nothing, "prefix").into_pointer_value();
+        let whole_text = self.call_ptr(concat, &[prefix.into(), whole_text.into()]);
+
+        let has_fraction = self.builder.build_int_compare(IntPredicate::NE, fraction, i64_type.const_zero(), "has_fraction");
+        let decimals = self.context.append_basic_block(function, "decimals");
+        let trim = self.context.append_basic_block(function, "trim");
+        let shorten = self.context.append_basic_block(function, "shorten");
+        let joined = self.context.append_basic_block(function, "joined");
+        let integral = self.context.append_basic_block(function, "integral");
+        self.builder.build_conditional_branch(has_fraction, decimals, integral);
+
+        // Leading zeros come from printing fraction + 10^6 and skipping the 1
+        self.builder.position_at_end(decimals);
+        let padded = self.builder.build_int_add(fraction, i64_type.const_int(scale, false), "padded");
+        let digits = self.call_ptr(int_to_string, &[padded.into()]);
+        self.builder.build_store(digits, i8_type.const_int(b'.' as u64, false));
+        self.builder.build_store(end, self.i32(FLOAT_DIGITS as u64));
+        self.builder.build_unconditional_branch(trim);
+
+        self.builder.position_at_end(trim);
+        let last = self.load_i32(end);
+        let last_byte = self.load_byte(self.byte_at(digits, last));
+        let zero = self.builder.build_int_compare(IntPredicate::EQ, last_byte, i8_type.const_int(b'0' as u64, false), "zero");
+        self.builder.build_conditional_branch(zero, shorten, joined);
+
+        self.builder.position_at_end(shorten);
+        self.builder.build_store(self.byte_at(digits, last), i8_type.const_zero());
+        self.builder.build_store(end, self.builder.build_int_sub(last, self.i32(1), "last"));
+        self.builder.build_unconditional_branch(trim);
+
+        self.builder.position_at_end(joined);
+        let text = self.call_ptr(concat, &[whole_text.into(), digits.into()]);
+        self.builder.build_return(Some(&text));
+
+        self.builder.position_at_end(integral);
+        self.builder.build_return(Some(&whole_text));
+        function
+    }
+
+    fn bool_to_string(&self) -> FunctionValue<'ctx> {
+        let function = self.function("razen_bool_to_string", &[self.context.bool_type().into()], Some(self.i8_ptr().into()));
+        let value = function.get_nth_param(0).unwrap().into_int_value();
+        let yes = self.builder.build_global_string_ptr("true", "true").as_pointer_value();
+        let no = self.builder.build_global_string_ptr("false", "false").as_pointer_value();
+        let text = self.builder.build_select(value, yes, no, "text");
+        self.builder.build_return(Some(&text));
+        function
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::Compiler;
+    use crate::llvm::{self, CompileTarget, LlvmCompiler};
+    use crate::value::Value;
+    use inkwell::context::Context;
+    use std::fs;
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+    use std::process::{Command, Stdio};
+
+    const SOURCE: &str = "num x = 40;\nshow \"answer: \" + (x + 2);\nshow -2.5 + 0.125;\nshow -9223372036854775807 - 1;\nread name;\nshow \"hi \" + name;\nexit(3);\n";
+
+    // Compile SOURCE for a target into dir, returning the object file
+    fn compile(target: CompileTarget, dir: &Path) -> PathBuf {
+        fs::create_dir_all(dir).unwrap();
+        fs::write(dir.join("main.rzn"), SOURCE).unwrap();
+        let ir = Compiler::from_file_with_clean_output(dir.join("main.rzn"), true).unwrap().ir;
+
+        let context = Context::create();
+        let mut compiler = LlvmCompiler::with_target(&context, "main", true, target).unwrap();
+        compiler.compile_function("main", vec![], Value::Int(0), &ir).unwrap();
+        let object = dir.join("main.o");
+        compiler.write_object(&object).unwrap();
+        object
+    }
+
+    #[test]
+    fn test_wasi_object_imports_fd_calls() {
+        let dir = std::env::temp_dir().join(format!("razen_wasm_object_test_{}", std::process::id()));
+        let object = fs::read(compile(CompileTarget::Wasm32Wasi, &dir)).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(object.starts_with(b"\0asm"));
+        let text = String::from_utf8_lossy(&object);
+        for name in ["wasi_snapshot_preview1", "fd_write", "fd_read", "proc_exit", "_start"] {
+            assert!(text.contains(name), "{} missing", name);
+        }
+        assert!(!text.contains("memcpy"));
+        assert!(text.contains("Error: out of memory"));
+    }
+
+    #[test]
+    #[ignore = "needs node and wasm-ld (or RAZEN_WASM_LD); run with `cargo test -- --ignored`"]
+    fn test_wasi_module_runs_in_node() {
+        assert!(Command::new("node").arg("--version").output().is_ok(), "node is not installed");
+        let dir = std::env::temp_dir().join(format!("razen_wasm_run_test_{}", std::process::id()));
+        let object = compile(CompileTarget::Wasm32Wasi, &dir);
+        let module = dir.join("main.wasm");
+        let linked = llvm::link_wasm(&llvm::wasm_link_args(CompileTarget::Wasm32Wasi, &object, &module)).unwrap();
+        assert!(linked, "wasm-ld is not installed; set RAZEN_WASM_LD to a linker");
+
+        let script = "import { WASI } from \"node:wasi\";\nimport { readFileSync } from \"node:fs\";\nconst wasi = new WASI({ version: \"preview1\" });\nconst { instance } = await WebAssembly.instantiate(readFileSync(process.argv[2]), wasi.getImportObject());\nprocess.exitCode = wasi.start(instance);\n";
+        fs::write(dir.join("run.mjs"), script).unwrap();
+        let mut child = Command::new("node").arg("--no-warnings").arg(dir.join("run.mjs")).arg(&module)
+            .stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped())
+            .spawn().unwrap();
+        child.stdin.take().unwrap().write_all(b"Ada\r\n").unwrap();
+        let output = child.wait_with_output().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(output.status.code(), Some(3), "{}", String::from_utf8_lossy(&output.stderr));
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout).replace('\r', ""),
+            "answer: 42\n-2.375\n-9223372036854775808\nhi Ada\n"
+        );
+    }
+}